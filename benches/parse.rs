@@ -0,0 +1,13 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use timestamp_microservice::parse::parse_input;
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_input");
+    for input in ["1482624000", "2016-12-25", "2016-W51-7", "not-a-date"].iter() {
+        group.bench_function(*input, |b| b.iter(|| parse_input(black_box(input))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+//! Embeds the details reported by `/version` into the binary, and generates the protobuf
+//! types of `proto/`.
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let built_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("The build clock is before 1970")
+        .as_secs();
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(name, _)| name.strip_prefix("CARGO_FEATURE_").map(str::to_string))
+        .map(|feature| feature.to_lowercase().replace('_', "-"))
+        .collect();
+    features.sort();
+
+    println!("cargo:rustc-env=TIMESTAMP_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=TIMESTAMP_BUILT_AT={}", built_at);
+    println!("cargo:rustc-env=TIMESTAMP_FEATURES={}", features.join(","));
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-changed=proto/timestamp.proto");
+
+    prost_build::compile_protos(&["proto/timestamp.proto"], &["proto/"])
+        .expect("Invalid protobuf schema");
+}
@@ -0,0 +1,26 @@
+-- Dates are RFC 3339 strings and durations milliseconds.
+
+CREATE TABLE marks (
+    name TEXT PRIMARY KEY NOT NULL,
+    at TEXT NOT NULL,
+    expires_at TEXT
+);
+
+CREATE TABLE timers (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT,
+    started_at TEXT NOT NULL,
+    -- JSON array of lap times
+    laps_ms TEXT NOT NULL,
+    stopped_ms INTEGER
+);
+
+CREATE TABLE jobs (
+    id TEXT PRIMARY KEY NOT NULL,
+    at TEXT NOT NULL,
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    next_attempt TEXT
+);
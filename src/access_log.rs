@@ -0,0 +1,267 @@
+//! Access log of every request, for audits, apart from the tracing logs whose level and
+//! format change with debugging needs.
+//!
+//! Each request is a JSON line giving its time, client IP, method, path, status, latency
+//! and request id. Lines are written by a dedicated thread so that file I/O never blocks
+//! the runtime, to `path` suffixed with the current day or hour when rotating, such as
+//! `access.log.2024-05-01`. Only the last `max_files` files are kept when it's not 0.
+//! Lines are dropped, rather than held, if the writer can't open its file.
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::{Request, Response};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{BoxError, Layer, Service};
+
+use crate::config::AccessLogConfig;
+use crate::rate_limit;
+use crate::request_id::REQUEST_ID;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl FromStr for Rotation {
+    type Err = ();
+
+    fn from_str(rotation: &str) -> Result<Rotation, ()> {
+        match rotation {
+            "hourly" => Ok(Rotation::Hourly),
+            "daily" => Ok(Rotation::Daily),
+            "never" => Ok(Rotation::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Rotation {
+    /// The file lines logged at `now` go to.
+    fn file(self, path: &Path, now: DateTime<Utc>) -> PathBuf {
+        let suffix = match self {
+            Rotation::Hourly => now.format("%Y-%m-%d-%H").to_string(),
+            Rotation::Daily => now.format("%Y-%m-%d").to_string(),
+            Rotation::Never => return path.to_path_buf(),
+        };
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(suffix);
+        path.with_file_name(name)
+    }
+}
+
+/// Removes the oldest rotated files of `path` beyond `keep`. Their suffixes sort in
+/// chronological order.
+fn prune(path: &Path, keep: usize) -> std::io::Result<()> {
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    let prefix = format!(
+        "{}.",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    );
+    let mut rotated: Vec<PathBuf> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|file| {
+            file.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        .collect();
+    rotated.sort();
+    let excess = rotated.len().saturating_sub(keep);
+    for file in &rotated[..excess] {
+        fs::remove_file(file)?;
+    }
+    Ok(())
+}
+
+/// Writes the lines it receives until every sender is gone.
+fn write_lines(lines: Receiver<String>, path: PathBuf, rotation: Rotation, max_files: usize) {
+    let mut current: Option<(PathBuf, BufWriter<File>)> = None;
+    while let Ok(line) = lines.recv() {
+        let file = rotation.file(&path, Utc::now());
+        if current.as_ref().map(|(open, _)| open) != Some(&file) {
+            current = match OpenOptions::new().create(true).append(true).open(&file) {
+                Ok(opened) => Some((file, BufWriter::new(opened))),
+                Err(e) => {
+                    tracing::error!("Can't open the access log {}: {}", file.display(), e);
+                    None
+                }
+            };
+            if max_files > 0 && rotation != Rotation::Never {
+                if let Err(e) = prune(&path, max_files) {
+                    tracing::warn!("Can't remove old access logs: {}", e);
+                }
+            }
+        }
+        let writer = match &mut current {
+            Some((_, writer)) => writer,
+            None => continue,
+        };
+        let mut result = writeln!(writer, "{}", line);
+        // lines arriving together are flushed together
+        while let Ok(line) = lines.try_recv() {
+            result = result.and_then(|_| writeln!(writer, "{}", line));
+        }
+        if let Err(e) = result.and_then(|_| writer.flush()) {
+            tracing::error!("Can't write the access log: {}", e);
+        }
+    }
+}
+
+/// Logs requests when a path is configured, doing nothing otherwise.
+#[derive(Clone, Debug, Default)]
+pub struct AccessLogLayer {
+    lines: Option<Sender<String>>,
+}
+
+impl AccessLogLayer {
+    pub fn new(config: &AccessLogConfig) -> Result<AccessLogLayer, String> {
+        let path = match &config.path {
+            Some(path) => PathBuf::from(path),
+            None => return Ok(AccessLogLayer::default()),
+        };
+        let (sender, receiver) = mpsc::channel();
+        let (rotation, max_files) = (config.rotation, config.max_files);
+        std::thread::Builder::new()
+            .name("access-log".to_string())
+            .spawn(move || write_lines(receiver, path, rotation, max_files))
+            .map_err(|e| format!("Can't start the access log writer: {}", e))?;
+        Ok(AccessLogLayer {
+            lines: Some(sender),
+        })
+    }
+}
+
+impl<S> Layer<S> for AccessLogLayer {
+    type Service = AccessLog<S>;
+
+    fn layer(&self, inner: S) -> AccessLog<S> {
+        AccessLog {
+            inner,
+            lines: self.lines.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AccessLog<S> {
+    inner: S,
+    lines: Option<Sender<String>>,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for AccessLog<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let lines = match &self.lines {
+            Some(lines) => lines.clone(),
+            None => {
+                let response = self.inner.call(request);
+                return Box::pin(async move { Ok(response.await?.map(Body::new)) });
+            }
+        };
+        let time = Utc::now();
+        let started = Instant::now();
+        let client = rate_limit::client(&request);
+        let method = request.method().to_string();
+        let path = request.uri().path().to_string();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await?;
+            let request_id = response
+                .headers()
+                .get(REQUEST_ID)
+                .and_then(|id| id.to_str().ok());
+            let line = json!({
+                "time": time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                "client": client.map(|client| client.to_string()),
+                "method": method,
+                "path": path,
+                "status": response.status().as_u16(),
+                "latency_ms": started.elapsed().as_secs_f64() * 1000.0,
+                "request_id": request_id,
+            });
+            // the writer only goes away with the process
+            let _ = lines.send(line.to_string());
+            Ok(response.map(Body::new))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn rotated_files() {
+        let path = Path::new("/var/log/timestamp/access.log");
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 13, 30, 0).unwrap();
+        assert_eq!(
+            Rotation::Daily.file(path, now),
+            Path::new("/var/log/timestamp/access.log.2024-05-01")
+        );
+        assert_eq!(
+            Rotation::Hourly.file(path, now),
+            Path::new("/var/log/timestamp/access.log.2024-05-01-13")
+        );
+        assert_eq!(Rotation::Never.file(path, now), path);
+    }
+
+    #[test]
+    fn keeps_the_latest_files() {
+        let directory = std::env::temp_dir().join("timestamp-access-log-test");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for day in 1..=4 {
+            File::create(directory.join(format!("access.log.2024-05-0{}", day))).unwrap();
+        }
+        File::create(directory.join("other.log")).unwrap();
+
+        prune(&directory.join("access.log"), 2).unwrap();
+        let mut left: Vec<String> = fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            vec![
+                "access.log.2024-05-03",
+                "access.log.2024-05-04",
+                "other.log"
+            ]
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
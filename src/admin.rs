@@ -0,0 +1,141 @@
+//! Administration endpoints, under `/admin`.
+//!
+//! They are only served when `admin_token` is configured, and require it as a bearer token
+//! in the `Authorization` header.
+//!
+//! A log level set through `PUT /admin/log-level` lasts until the process exits or the
+//! configuration is reloaded, which applies the configured level again.
+//! `POST /admin/drain` shuts the instance down, see [`crate::drain`].
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::{Arc, OnceLock};
+use tracing_subscriber::EnvFilter;
+
+use crate::drain::Drain;
+use crate::error::AppError;
+use crate::extract::Json;
+
+type Reloader = Box<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+/// Replaces the filter of the subscriber, set by the binary that owns it.
+static LOG_RELOADER: OnceLock<Reloader> = OnceLock::new();
+
+/// Lets `/admin/log-level` change the filter of the global subscriber through `reload`,
+/// once at startup.
+pub fn install_log_reloader(reload: impl Fn(&str) -> Result<(), String> + Send + Sync + 'static) {
+    if LOG_RELOADER.set(Box::new(reload)).is_err() {
+        tracing::warn!("A log level reloader is already installed");
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AdminSettings {
+    token: Option<Arc<str>>,
+}
+
+impl AdminSettings {
+    pub fn new(token: Option<&str>) -> AdminSettings {
+        AdminSettings {
+            token: token.filter(|token| !token.is_empty()).map(Arc::from),
+        }
+    }
+
+    /// Checks the bearer token of a request, in constant time.
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), AppError> {
+        let expected = self
+            .token
+            .as_deref()
+            .ok_or_else(|| AppError::NotFound("Admin endpoints are disabled".to_string()))?;
+        let given = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or("");
+        let matches = given.len() == expected.len()
+            && given
+                .bytes()
+                .zip(expected.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0;
+        if matches {
+            Ok(())
+        } else {
+            Err(AppError::Unauthorized)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogLevelRequest {
+    /// A `RUST_LOG` style filter, such as `debug` or `timestamp_microservice=trace`.
+    level: String,
+}
+
+pub async fn log_level_handler(
+    State(settings): State<AdminSettings>,
+    headers: HeaderMap,
+    Json(request): Json<LogLevelRequest>,
+) -> Result<Json<Value>, AppError> {
+    settings.authorize(&headers)?;
+    EnvFilter::try_new(&request.level)
+        .map_err(|e| AppError::BadRequest(format!("Invalid log level {}: {}", request.level, e)))?;
+    let reload = LOG_RELOADER.get().ok_or_else(|| {
+        AppError::NotFound("The log level can't be changed in this process".to_string())
+    })?;
+    reload(&request.level).map_err(AppError::BadRequest)?;
+
+    tracing::info!("Log level changed to {}", request.level);
+    Ok(Json(json!({ "level": request.level })))
+}
+
+pub async fn drain_handler(
+    State(settings): State<AdminSettings>,
+    State(drain): State<Drain>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    settings.authorize(&headers)?;
+    if drain.start() {
+        tracing::info!(
+            "Draining, shutting down in {} seconds",
+            drain.grace().as_secs()
+        );
+    }
+    Ok(Json(json!({
+        "draining": true,
+        "grace_seconds": drain.grace().as_secs(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn bearer_tokens() {
+        let mut headers = HeaderMap::new();
+        assert!(matches!(
+            AdminSettings::new(None).authorize(&headers),
+            Err(AppError::NotFound(_))
+        ));
+
+        let settings = AdminSettings::new(Some("s3cret"));
+        assert!(matches!(
+            settings.authorize(&headers),
+            Err(AppError::Unauthorized)
+        ));
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer s3cre"),
+        );
+        assert!(settings.authorize(&headers).is_err());
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer s3cret"),
+        );
+        assert!(settings.authorize(&headers).is_ok());
+    }
+}
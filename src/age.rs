@@ -0,0 +1,132 @@
+use axum::Json;
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::anniversary::{anniversary, LeapDay};
+use crate::calendar::add_months;
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::query::Query;
+use crate::{parse_base, parse_date};
+
+#[derive(Debug, PartialEq)]
+pub struct Age {
+    pub years: i32,
+    pub months: i32,
+    pub days: i64,
+    pub total_days: i64,
+}
+
+/// Age on `at` of someone born on `birth`, in full years, then full months, then days.
+/// Feb 29 birthdays fall on Feb 28 or Mar 1 in common years, as chosen by `leap_day`.
+pub fn age(birth: NaiveDate, at: NaiveDate, leap_day: LeapDay) -> Option<Age> {
+    if at < birth {
+        return None;
+    }
+    let birthday = |year: i32| anniversary(birth, year, leap_day);
+
+    let mut years = at.year() - birth.year();
+    let mut last_birthday = birthday(at.year())?;
+    if last_birthday > at {
+        years -= 1;
+        last_birthday = birthday(at.year() - 1)?;
+    }
+
+    let mut months =
+        (at.year() - last_birthday.year()) * 12 + at.month() as i32 - last_birthday.month() as i32;
+    let mut month_start = add_months(last_birthday, months)?;
+    if month_start > at {
+        months -= 1;
+        month_start = add_months(last_birthday, months)?;
+    }
+
+    Some(Age {
+        years,
+        months,
+        days: (at - month_start).num_days(),
+        total_days: (at - birth).num_days(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AgeParams {
+    /// Date the age is computed at, today by default.
+    at: Option<String>,
+    /// Birthday of people born on Feb 29 in common years, Mar 1 by default.
+    leap_day: Option<LeapDay>,
+}
+
+pub async fn age_handler(
+    Path(birthdate): Path<String>,
+    Query(params): Query<AgeParams>,
+) -> Result<Json<Value>, AppError> {
+    let birth = parse_date(&birthdate)?.date_naive();
+    let at = parse_base(params.at.as_deref())?.date_naive();
+    let leap_day = match params.leap_day.unwrap_or(LeapDay::Mar1) {
+        LeapDay::Skip => {
+            return Err(AppError::BadRequest(
+                "Birthdays can't be skipped, use feb28 or mar1".to_string(),
+            ))
+        }
+        leap_day => leap_day,
+    };
+    let age = age(birth, at, leap_day).ok_or_else(|| {
+        AppError::Unprocessable("The birth date is after the reference date".to_string())
+    })?;
+
+    Ok(Json(json!({
+        "birthdate": birth.to_string(),
+        "at": at.to_string(),
+        "years": age.years,
+        "months": age.months,
+        "days": age.days,
+        "total_days": age.total_days,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn full_units() {
+        assert_eq!(
+            age(day(1990, 5, 15), day(2021, 8, 20), LeapDay::Mar1),
+            Some(Age {
+                years: 31,
+                months: 3,
+                days: 5,
+                total_days: 11420
+            })
+        );
+        let before_birthday = age(day(1990, 5, 15), day(2021, 5, 14), LeapDay::Mar1).unwrap();
+        assert_eq!((before_birthday.years, before_birthday.months), (30, 11));
+        assert_eq!(age(day(2021, 1, 2), day(2021, 1, 1), LeapDay::Mar1), None);
+    }
+
+    #[test]
+    fn leap_day_birthdays() {
+        let birth = day(2004, 2, 29);
+        assert_eq!(
+            age(birth, day(2022, 2, 28), LeapDay::Mar1).unwrap().years,
+            17
+        );
+        assert_eq!(
+            age(birth, day(2022, 2, 28), LeapDay::Feb28).unwrap().years,
+            18
+        );
+        assert_eq!(
+            age(birth, day(2022, 3, 1), LeapDay::Mar1).unwrap().years,
+            18
+        );
+        assert_eq!(
+            age(birth, day(2024, 2, 29), LeapDay::Mar1).unwrap().years,
+            20
+        );
+    }
+}
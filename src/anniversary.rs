@@ -0,0 +1,126 @@
+use axum::Json;
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::calendar::is_leap_year;
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+use crate::query::Query;
+use crate::timezone::{parse_tz, start_of_day};
+
+/// Upper bound on `?years=`, so that a single request stays cheap.
+const MAX_YEARS: u32 = 200;
+
+/// Where a Feb 29 anniversary falls in common years.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LeapDay {
+    Feb28,
+    Mar1,
+    /// Only leap years have an anniversary.
+    Skip,
+}
+
+impl LeapDay {
+    fn name(self) -> &'static str {
+        match self {
+            LeapDay::Feb28 => "feb28",
+            LeapDay::Mar1 => "mar1",
+            LeapDay::Skip => "skip",
+        }
+    }
+}
+
+/// The anniversary of `date` in `year`, or `None` when the policy skips that year.
+pub fn anniversary(date: NaiveDate, year: i32, leap_day: LeapDay) -> Option<NaiveDate> {
+    if date.month() == 2 && date.day() == 29 && !is_leap_year(year) {
+        return match leap_day {
+            LeapDay::Feb28 => NaiveDate::from_ymd_opt(year, 2, 28),
+            LeapDay::Mar1 => NaiveDate::from_ymd_opt(year, 3, 1),
+            LeapDay::Skip => None,
+        };
+    }
+    date.with_year(year)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnniversaryParams {
+    /// Number of following years, 10 by default.
+    years: Option<u32>,
+    leap_day: Option<LeapDay>,
+    tz: Option<String>,
+}
+
+/// The same calendar date in each of the years following the given date.
+pub async fn anniversary_handler(
+    Path(date): Path<String>,
+    Query(params): Query<AnniversaryParams>,
+) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?.date_naive();
+    let years = params.years.unwrap_or(10);
+    if years > MAX_YEARS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} years can be listed",
+            MAX_YEARS
+        )));
+    }
+    let leap_day = params.leap_day.unwrap_or(LeapDay::Feb28);
+    let tz = parse_tz(params.tz.as_deref())?;
+
+    let anniversaries: Vec<Value> = (1..=years as i32)
+        .filter_map(|n| {
+            let year = date.year().checked_add(n)?;
+            let day = anniversary(date, year, leap_day)?;
+            let start = start_of_day(tz, day);
+            Some(json!({
+                "years": n,
+                "date": day.to_string(),
+                "weekday": day.weekday().to_string(),
+                "unix": start.timestamp(),
+                "utc": start.to_rfc2822(),
+                "shifted": day.day() != date.day(),
+            }))
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "date": date.to_string(),
+        "tz": tz.name(),
+        "leap_day": leap_day.name(),
+        "anniversaries": anniversaries,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leap_day_policies() {
+        let date = NaiveDate::from_ymd_opt(2020, 2, 29).unwrap();
+        assert_eq!(
+            anniversary(date, 2021, LeapDay::Feb28),
+            Some(NaiveDate::from_ymd_opt(2021, 2, 28).unwrap())
+        );
+        assert_eq!(
+            anniversary(date, 2021, LeapDay::Mar1),
+            Some(NaiveDate::from_ymd_opt(2021, 3, 1).unwrap())
+        );
+        assert_eq!(anniversary(date, 2021, LeapDay::Skip), None);
+        assert_eq!(
+            anniversary(date, 2024, LeapDay::Skip),
+            Some(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap())
+        );
+    }
+
+    #[test]
+    fn regular_dates() {
+        let date = NaiveDate::from_ymd_opt(2016, 12, 25).unwrap();
+        assert_eq!(
+            anniversary(date, 2021, LeapDay::Skip),
+            Some(NaiveDate::from_ymd_opt(2021, 12, 25).unwrap())
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Static files of the converter page, served under `/static`.
+//!
+//! They're embedded in the binary, which stays deployable alone, rather than read from a
+//! directory at runtime. Browsers revalidate them with their `ETag`, see
+//! [`crate::cache`], so that a new release is picked up at once.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+
+use crate::error::AppError;
+use crate::extract::Path;
+
+struct Asset {
+    name: &'static str,
+    content_type: &'static str,
+    body: &'static [u8],
+}
+
+const ASSETS: [Asset; 3] = [
+    Asset {
+        name: "app.js",
+        content_type: "text/javascript; charset=utf-8",
+        body: include_bytes!("../static/app.js"),
+    },
+    Asset {
+        name: "style.css",
+        content_type: "text/css; charset=utf-8",
+        body: include_bytes!("../static/style.css"),
+    },
+    Asset {
+        name: "favicon.svg",
+        content_type: "image/svg+xml",
+        body: include_bytes!("../static/favicon.svg"),
+    },
+];
+
+fn find(name: &str) -> Option<&'static Asset> {
+    ASSETS.iter().find(|asset| asset.name == name)
+}
+
+pub async fn static_handler(
+    Path(name): Path<String>,
+) -> Result<(HeaderMap, &'static [u8]), AppError> {
+    let asset = find(&name).ok_or_else(|| AppError::NotFound(format!("No file {}", name)))?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(asset.content_type),
+    );
+    Ok((headers, asset.body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_files() {
+        assert_eq!(find("favicon.svg").unwrap().content_type, "image/svg+xml");
+        assert!(find("app.js").unwrap().body.starts_with(b"\"use strict\";"));
+        assert!(find("../Cargo.toml").is_none());
+    }
+}
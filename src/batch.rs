@@ -0,0 +1,194 @@
+//! Conversion of newline-delimited JSON streams of dates, for batches too large to send as
+//! a comma-separated list.
+//!
+//! Every line of the request is a date, either as a JSON string or as an object with a
+//! `date` field, and gets a line of the response holding what `/api/:date` returns for it,
+//! or an error entry. Lines are converted as they arrive and sent back straight away, so
+//! memory use doesn't grow with the size of the batch. Such requests are exempt from the
+//! body size limit and the request timeout.
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Extension, State};
+use axum::http::{header, HeaderMap, HeaderValue, Response};
+use chrono::{DateTime, Utc};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::error::AppError;
+use crate::hal;
+use crate::locale::{self, Locale};
+use crate::parse_cache::ParseCache;
+use crate::query::Query;
+use crate::version::ApiVersion;
+use crate::{convert_date, parse_base, DateParams};
+
+pub const NDJSON: &str = "application/x-ndjson";
+
+/// Longest accepted line, so that a stream without newlines can't take up memory.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+/// Converted lines waiting to be sent, before reading the request pauses.
+const PENDING_LINES: usize = 64;
+
+type Lines = mpsc::Sender<Result<Bytes, Infallible>>;
+
+/// Everything a line is converted with.
+struct Converter {
+    params: DateParams,
+    base: DateTime<Utc>,
+    cache: ParseCache,
+    locale: Option<&'static Locale>,
+    version: ApiVersion,
+    links: bool,
+}
+
+impl Converter {
+    /// The response line of an input line, newline included.
+    fn convert(&self, line: &[u8]) -> Bytes {
+        let date = serde_json::from_slice::<Value>(line)
+            .ok()
+            .and_then(|value| match value {
+                Value::String(date) => Some(date),
+                Value::Object(mut object) => match object.remove("date") {
+                    Some(Value::String(date)) => Some(date),
+                    _ => None,
+                },
+                _ => None,
+            });
+        let result = match date {
+            Some(date) => match convert_date(
+                &date,
+                &self.params,
+                self.base,
+                &self.cache,
+                self.locale,
+                self.version,
+                self.links,
+            ) {
+                Ok((body, _)) => body,
+                Err(error) => error_entry(Some(&date), &error),
+            },
+            None => error_entry(
+                None,
+                &AppError::BadRequest(
+                    "Lines must be JSON strings or objects with a date".to_string(),
+                ),
+            ),
+        };
+        line_of(&result)
+    }
+}
+
+fn error_entry(input: Option<&str>, error: &AppError) -> Value {
+    json!({
+        "input": input,
+        "error": error.to_string(),
+        "code": error.code(),
+    })
+}
+
+/// Splits the complete lines off the front of `buffer`, leaving the incomplete last one.
+fn complete_lines(buffer: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let end = match buffer.iter().rposition(|byte| *byte == b'\n') {
+        Some(end) => end,
+        None => return Vec::new(),
+    };
+    let rest = buffer.split_off(end + 1);
+    let complete = std::mem::replace(buffer, rest);
+    complete
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(<[u8]>::to_vec)
+        .collect()
+}
+
+/// Reads the request body line by line, sending back the converted lines as it goes.
+/// Stops early when the client goes away.
+async fn convert_stream(converter: Converter, mut body: Body, lines: Lines) {
+    let send = |line: Bytes| lines.send(Ok(line));
+    let mut buffer = Vec::new();
+    loop {
+        let chunk = match body.frame().await {
+            Some(Ok(frame)) => match frame.into_data() {
+                Ok(chunk) => chunk,
+                Err(_) => continue,
+            },
+            Some(Err(_)) => {
+                let error = AppError::BadRequest("Invalid request body".to_string());
+                let _ = send(line_of(&error_entry(None, &error))).await;
+                return;
+            }
+            None => break,
+        };
+        buffer.extend_from_slice(&chunk);
+        for line in complete_lines(&mut buffer) {
+            if send(converter.convert(&line)).await.is_err() {
+                return;
+            }
+        }
+        if buffer.len() > MAX_LINE_BYTES {
+            let error = AppError::PayloadTooLarge;
+            let _ = send(line_of(&error_entry(None, &error))).await;
+            return;
+        }
+    }
+    if !buffer.iter().all(u8::is_ascii_whitespace) {
+        let _ = send(converter.convert(&buffer)).await;
+    }
+}
+
+fn line_of(value: &Value) -> Bytes {
+    let mut line = serde_json::to_vec(value).unwrap_or_default();
+    line.push(b'\n');
+    line.into()
+}
+
+pub async fn stream_handler(
+    Query(params): Query<DateParams>,
+    State(cache): State<ParseCache>,
+    Extension(version): Extension<ApiVersion>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response<Body>, AppError> {
+    let locale = locale::negotiate(&headers, params.locale.as_deref())?;
+    let base = parse_base(params.base.as_deref())?;
+    let links = hal::wants(&headers, params.links);
+    let converter = Converter {
+        params,
+        base,
+        cache,
+        locale,
+        version,
+        links,
+    };
+
+    let (lines, pending) = mpsc::channel(PENDING_LINES);
+    tokio::spawn(convert_stream(converter, body, lines));
+
+    let mut response = Response::new(Body::from_stream(ReceiverStream::new(pending)));
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(NDJSON));
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_complete_lines() {
+        let mut buffer = b"\"2016-12-25\"\n\n{\"date\": \"1482624000\"}\n\"2016-".to_vec();
+        assert_eq!(
+            complete_lines(&mut buffer),
+            vec![
+                b"\"2016-12-25\"".to_vec(),
+                b"{\"date\": \"1482624000\"}".to_vec()
+            ]
+        );
+        assert_eq!(buffer, b"\"2016-".to_vec());
+        assert!(complete_lines(&mut buffer).is_empty());
+    }
+}
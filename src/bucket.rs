@@ -0,0 +1,135 @@
+//! Histograms of posted timestamps over fixed intervals, for lightweight analytics.
+//!
+//! Buckets are aligned on the wall clock of the requested timezone, counting from the
+//! local midnight of January 1st, 1970: hourly buckets start on the hour and daily ones at
+//! local midnight, even in zones offset by half an hour. Being wall-clock ranges, the hour
+//! repeated when daylight saving time ends is a single bucket. Empty buckets between the
+//! first and the last timestamp are listed too.
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::duration::IsoDuration;
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::sort::parse_entries;
+use crate::timezone::{parse_tz, resolve_local};
+
+/// Longer lists of timestamps are rejected.
+const MAX_TIMESTAMPS: usize = 100_000;
+/// Histograms with more buckets are rejected.
+const MAX_BUCKETS: i64 = 10_000;
+
+/// Counts per bucket, keyed by the local start of the bucket in seconds since 1970.
+fn count(instants: &[DateTime<Utc>], interval: i64, tz: Tz) -> BTreeMap<i64, u64> {
+    let mut buckets = BTreeMap::new();
+    for instant in instants {
+        let local = instant
+            .with_timezone(&tz)
+            .naive_local()
+            .and_utc()
+            .timestamp();
+        *buckets
+            .entry(local.div_euclid(interval) * interval)
+            .or_insert(0) += 1;
+    }
+    buckets
+}
+
+/// The instant a bucket starts at, an hour later when DST skips it.
+fn bucket_start(local: i64, tz: Tz) -> Option<DateTime<Utc>> {
+    let local = DateTime::from_timestamp(local, 0)?.naive_utc();
+    resolve_local(local, None, tz).or_else(|| resolve_local(local + Duration::hours(1), None, tz))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BucketRequest {
+    timestamps: Vec<Value>,
+    /// A duration without months, such as `5m`, `1h` or `PT30M`.
+    interval: String,
+    tz: Option<String>,
+}
+
+pub async fn bucket_handler(Json(request): Json<BucketRequest>) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(request.tz.as_deref())?;
+    let interval = IsoDuration::parse_shorthand(&request.interval)
+        .and_then(IsoDuration::wall_seconds)
+        .filter(|seconds| *seconds > 0)
+        .ok_or_else(|| {
+            AppError::BadRequest(
+                "The interval must be a positive duration without months".to_string(),
+            )
+        })?;
+    if request.timestamps.len() > MAX_TIMESTAMPS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} timestamps can be bucketed at once",
+            MAX_TIMESTAMPS
+        )));
+    }
+
+    let (dates, invalid) = parse_entries(&request.timestamps);
+    let instants: Vec<DateTime<Utc>> = dates.into_iter().map(|(date, _)| date).collect();
+    let counts = count(&instants, interval, tz);
+
+    let mut buckets = Vec::new();
+    if let (Some(&first), Some(&last)) = (counts.keys().next(), counts.keys().next_back()) {
+        if (last - first) / interval >= MAX_BUCKETS {
+            return Err(AppError::BadRequest(format!(
+                "The histogram would have more than {} buckets",
+                MAX_BUCKETS
+            )));
+        }
+        let mut local = first;
+        while local <= last {
+            let start = bucket_start(local, tz).ok_or(AppError::TimestampOutOfRange)?;
+            buckets.push(json!({
+                "start": start.timestamp(),
+                "utc": start.to_rfc2822(),
+                "local": start.with_timezone(&tz).to_rfc3339_opts(SecondsFormat::Secs, false),
+                "count": counts.get(&local).copied().unwrap_or(0),
+            }));
+            local += interval;
+        }
+    }
+
+    Ok(Json(json!({
+        "interval_seconds": interval,
+        "tz": tz.name(),
+        "total": instants.len(),
+        "buckets": buckets,
+        "invalid": invalid,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn local_buckets() {
+        let kolkata: Tz = "Asia/Kolkata".parse().unwrap();
+        // 10:20, 10:50 and 12:05 in Kolkata
+        let instants = [
+            Utc.with_ymd_and_hms(2024, 5, 1, 4, 50, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 5, 1, 5, 20, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 5, 1, 6, 35, 0).unwrap(),
+        ];
+        let counts = count(&instants, 3600, kolkata);
+        let starts: Vec<DateTime<Utc>> = counts
+            .keys()
+            .map(|local| bucket_start(*local, kolkata).unwrap())
+            .collect();
+        assert_eq!(counts.values().copied().collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(
+            starts,
+            vec![
+                Utc.with_ymd_and_hms(2024, 5, 1, 4, 30, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 5, 1, 6, 30, 0).unwrap(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! Details of the running build, embedded at compile time by `build.rs`.
+
+use axum::Json;
+use chrono::{TimeZone, Utc};
+use serde_json::{json, Value};
+
+const GIT_COMMIT: &str = env!("TIMESTAMP_GIT_COMMIT");
+const BUILT_AT: &str = env!("TIMESTAMP_BUILT_AT");
+const FEATURES: &str = env!("TIMESTAMP_FEATURES");
+
+fn features() -> Vec<&'static str> {
+    FEATURES
+        .split(',')
+        .filter(|feature| !feature.is_empty())
+        .collect()
+}
+
+pub async fn handler() -> Json<Value> {
+    let built_at = Utc
+        .timestamp_opt(BUILT_AT.parse().expect("Invalid build timestamp"), 0)
+        .unwrap();
+
+    Json(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_commit": GIT_COMMIT,
+        "built_at": {
+            "unix": built_at.timestamp(),
+            "utc": built_at.to_rfc2822(),
+        },
+        "features": features(),
+    }))
+}
@@ -0,0 +1,357 @@
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::calendar::days_in_month;
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::holidays::{self, is_holiday, Holiday};
+use crate::parse_date;
+use crate::query::Query;
+
+/// Upper bound on the days spanned by additions and counts, about a century, as they walk
+/// the calendar one day at a time.
+const MAX_DAYS: i64 = 100 * 366;
+
+/// Which days are not business days: a set of weekend days plus explicit holidays,
+/// optionally extended with a country's public holidays.
+pub struct BusinessCalendar {
+    weekend: Vec<Weekday>,
+    holidays: Vec<NaiveDate>,
+    country: Option<&'static [Holiday]>,
+}
+
+impl BusinessCalendar {
+    pub fn is_business_day(&self, date: NaiveDate) -> bool {
+        !self.weekend.contains(&date.weekday())
+            && !self.holidays.contains(&date)
+            && !self
+                .country
+                .is_some_and(|holidays| is_holiday(holidays, date))
+    }
+
+    /// Moves `date` by `days` business days, backwards when negative. The starting day
+    /// itself is never counted, so adding 2 to a trade date gives its T+2 settlement date.
+    pub fn add(&self, mut date: NaiveDate, days: i64) -> Option<NaiveDate> {
+        let weekdays = [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+            Weekday::Sat,
+            Weekday::Sun,
+        ];
+        if weekdays.iter().all(|day| self.weekend.contains(day)) {
+            return None;
+        }
+        let step = Duration::days(days.signum());
+        let mut remaining = days.unsigned_abs();
+        while remaining > 0 {
+            date = date.checked_add_signed(step)?;
+            if self.is_business_day(date) {
+                remaining -= 1;
+            }
+        }
+        Some(date)
+    }
+
+    /// Number of business days after `from` up to and including `to`, negative when `to`
+    /// comes first. This is the inverse of [`BusinessCalendar::add`].
+    pub fn count(&self, from: NaiveDate, to: NaiveDate) -> i64 {
+        let (start, end, sign) = if from <= to {
+            (from, to, 1)
+        } else {
+            (to, from, -1)
+        };
+        let mut count = 0;
+        let mut date = start;
+        while date < end {
+            date = date.succ_opt().unwrap();
+            if self.is_business_day(date) {
+                count += 1;
+            }
+        }
+        sign * count
+    }
+
+    /// The `n`-th business day of a month, counting from the end when `n` is negative.
+    pub fn nth_in_month(&self, year: i32, month: u32, n: i64) -> Option<NaiveDate> {
+        let mut days = (1..=days_in_month(year, month))
+            .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+            .filter(|date| self.is_business_day(*date));
+        let index = usize::try_from(n.unsigned_abs().checked_sub(1)?).ok()?;
+        if n > 0 {
+            days.nth(index)
+        } else {
+            days.rev().nth(index)
+        }
+    }
+}
+
+fn parse_weekday(day: &str) -> Result<Weekday, AppError> {
+    day.parse::<Weekday>()
+        .map_err(|_| AppError::BadRequest(format!("Invalid weekday {}", day)))
+}
+
+fn country(code: &str) -> Result<&'static [Holiday], AppError> {
+    holidays::country(code).ok_or_else(|| AppError::BadRequest(format!("Unknown country {}", code)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CalendarParams {
+    /// Weekend days such as `["fri", "sat"]`, Saturday and Sunday by default.
+    weekend: Option<Vec<String>>,
+    #[serde(default)]
+    holidays: Vec<String>,
+    /// Country whose public holidays are added to `holidays`, such as `US` or `IT`.
+    country: Option<String>,
+}
+
+impl CalendarParams {
+    fn calendar(&self) -> Result<BusinessCalendar, AppError> {
+        let weekend = match &self.weekend {
+            Some(days) => days
+                .iter()
+                .map(|day| parse_weekday(day))
+                .collect::<Result<_, _>>()?,
+            None => vec![Weekday::Sat, Weekday::Sun],
+        };
+        let holidays = self
+            .holidays
+            .iter()
+            .map(|holiday| parse_date(holiday).map(|date| date.naive_utc().date()))
+            .collect::<Result<_, _>>()?;
+
+        let country = match &self.country {
+            Some(code) => Some(country(code)?),
+            None => None,
+        };
+
+        Ok(BusinessCalendar {
+            weekend,
+            holidays,
+            country,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddRequest {
+    date: String,
+    days: i64,
+    #[serde(flatten)]
+    calendar: CalendarParams,
+}
+
+pub async fn add_handler(Json(request): Json<AddRequest>) -> Result<Json<Value>, AppError> {
+    if request.days.unsigned_abs() > MAX_DAYS as u64 {
+        return Err(AppError::BadRequest(format!(
+            "days must be between -{} and {}",
+            MAX_DAYS, MAX_DAYS
+        )));
+    }
+    let calendar = request.calendar.calendar()?;
+    let date = parse_date(&request.date)?.naive_utc().date();
+    let result = calendar
+        .add(date, request.days)
+        .ok_or_else(|| AppError::BadRequest("No business days in the calendar".to_string()))?;
+
+    Ok(Json(json!({
+        "date": result.to_string(),
+        "business_day": calendar.is_business_day(date),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountRequest {
+    from: String,
+    to: String,
+    #[serde(flatten)]
+    calendar: CalendarParams,
+}
+
+pub async fn count_handler(Json(request): Json<CountRequest>) -> Result<Json<Value>, AppError> {
+    let calendar = request.calendar.calendar()?;
+    let from = parse_date(&request.from)?.naive_utc().date();
+    let to = parse_date(&request.to)?.naive_utc().date();
+    if (to - from).num_days().abs() > MAX_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "from and to must be at most {} days apart",
+            MAX_DAYS
+        )));
+    }
+
+    Ok(Json(json!({
+        "from": from.to_string(),
+        "to": to.to_string(),
+        "business_days": calendar.count(from, to),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NthParams {
+    year: i32,
+    month: u32,
+    /// Which business day, counting from the end of the month when negative.
+    n: i64,
+    /// Country whose public holidays aren't business days, such as `US` or `IT`.
+    calendar: Option<String>,
+    /// Comma-separated weekend days, `sat,sun` by default.
+    weekend: Option<String>,
+}
+
+/// The `n`-th business day of a month, as payroll and billing schedules are often
+/// defined.
+pub async fn nth_handler(Query(params): Query<NthParams>) -> Result<Json<Value>, AppError> {
+    let weekend = match &params.weekend {
+        Some(days) => days
+            .split(',')
+            .map(|day| parse_weekday(day.trim()))
+            .collect::<Result<_, _>>()?,
+        None => vec![Weekday::Sat, Weekday::Sun],
+    };
+    let calendar = BusinessCalendar {
+        weekend,
+        holidays: Vec::new(),
+        country: params.calendar.as_deref().map(country).transpose()?,
+    };
+    if params.n == 0 {
+        return Err(AppError::BadRequest("n must not be 0".to_string()));
+    }
+    if NaiveDate::from_ymd_opt(params.year, params.month, 1).is_none() {
+        return Err(AppError::InvalidDate);
+    }
+    let date = calendar
+        .nth_in_month(params.year, params.month, params.n)
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "{}-{:02} has fewer than {} business days",
+                params.year,
+                params.month,
+                params.n.unsigned_abs()
+            ))
+        })?;
+
+    Ok(Json(json!({
+        "date": date.to_string(),
+        "weekday": date.weekday().to_string(),
+        "n": params.n,
+        "calendar": params.calendar,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar() -> BusinessCalendar {
+        BusinessCalendar {
+            weekend: vec![Weekday::Sat, Weekday::Sun],
+            holidays: vec![NaiveDate::from_ymd_opt(2021, 8, 23).unwrap()],
+            country: None,
+        }
+    }
+
+    #[test]
+    fn settlement_date() {
+        // Thursday, T+2 skips the weekend and Monday's holiday
+        let trade = NaiveDate::from_ymd_opt(2021, 8, 19).unwrap();
+        assert_eq!(
+            calendar().add(trade, 2),
+            Some(NaiveDate::from_ymd_opt(2021, 8, 24).unwrap())
+        );
+        assert_eq!(
+            calendar().add(trade, -4),
+            Some(NaiveDate::from_ymd_opt(2021, 8, 13).unwrap())
+        );
+    }
+
+    #[test]
+    fn count_is_inverse_of_add() {
+        let from = NaiveDate::from_ymd_opt(2021, 8, 19).unwrap();
+        for days in -10..10 {
+            let to = calendar().add(from, days).unwrap();
+            assert_eq!(calendar().count(from, to), days);
+        }
+    }
+
+    #[tokio::test]
+    async fn spans_are_capped() {
+        let request = serde_json::from_value(json!({
+            "date": "2021-08-19",
+            "days": i64::MIN,
+        }))
+        .unwrap();
+        assert!(add_handler(Json(request)).await.is_err());
+        let request = serde_json::from_value(json!({
+            "from": "0001-01-01",
+            "to": "9999-12-31",
+        }))
+        .unwrap();
+        assert!(count_handler(Json(request)).await.is_err());
+    }
+
+    #[test]
+    fn country_holidays() {
+        let calendar = BusinessCalendar {
+            weekend: vec![Weekday::Sat, Weekday::Sun],
+            holidays: Vec::new(),
+            country: holidays::country("IT"),
+        };
+        // Immacolata Concezione and Easter Monday
+        let date = NaiveDate::from_ymd_opt(2021, 12, 7).unwrap();
+        assert_eq!(
+            calendar.add(date, 1),
+            Some(NaiveDate::from_ymd_opt(2021, 12, 9).unwrap())
+        );
+        let date = NaiveDate::from_ymd_opt(2021, 4, 2).unwrap();
+        assert_eq!(
+            calendar.add(date, 1),
+            Some(NaiveDate::from_ymd_opt(2021, 4, 6).unwrap())
+        );
+    }
+
+    #[test]
+    fn everything_is_weekend() {
+        let calendar = BusinessCalendar {
+            weekend: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+                Weekday::Sat,
+                Weekday::Sun,
+            ],
+            holidays: Vec::new(),
+            country: None,
+        };
+        assert_eq!(
+            calendar.add(NaiveDate::from_ymd_opt(2021, 8, 19).unwrap(), 1),
+            None
+        );
+    }
+
+    #[test]
+    fn nth_business_day() {
+        let calendar = BusinessCalendar {
+            weekend: vec![Weekday::Sat, Weekday::Sun],
+            holidays: Vec::new(),
+            country: holidays::country("US"),
+        };
+        // July 4, 2024 is a Thursday
+        assert_eq!(
+            calendar.nth_in_month(2024, 7, 5),
+            Some(NaiveDate::from_ymd_opt(2024, 7, 8).unwrap())
+        );
+        assert_eq!(
+            calendar.nth_in_month(2024, 7, -1),
+            Some(NaiveDate::from_ymd_opt(2024, 7, 31).unwrap())
+        );
+        assert_eq!(calendar.nth_in_month(2024, 7, 23), None);
+        assert_eq!(calendar.nth_in_month(2024, 7, i64::MIN), None);
+        assert_eq!(calendar.nth_in_month(2024, 7, 0), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! HTTP caching headers for individual routes.
+//!
+//! Conversions of absolute dates never change, so their responses are cacheable forever
+//! and carry an `ETag` computed from the body, answering `If-None-Match` with a 304. The
+//! outer layers may still re-encode, indent, wrap or compress the body, so the tag also
+//! covers the parts of the request choosing among those representations.
+//! Responses depending on the current time must not be stored, and those only changing
+//! with a new release, such as static assets, are revalidated with their `ETag`.
+//! Handlers can opt out of their route's policy by setting `Cache-Control` themselves.
+//!
+//! Cacheable responses can also carry a `Last-Modified` date, usually the start of the
+//! release, answering `If-Modified-Since` with a 304 when the request has no
+//! `If-None-Match`, which takes precedence.
+
+use axum::body::{to_bytes, Body, Bytes, HttpBody};
+use axum::http::{header, HeaderValue, Method, Request, Response, StatusCode};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{BoxError, Layer, Service};
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const NO_STORE: &str = "no-store";
+const REVALIDATE: &str = "public, no-cache";
+/// The IMF-fixdate format of HTTP dates.
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Policy {
+    Immutable,
+    NoStore,
+    Revalidate,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CacheLayer {
+    policy: Policy,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl CacheLayer {
+    /// For responses that are a pure function of the request.
+    pub fn immutable() -> CacheLayer {
+        CacheLayer {
+            policy: Policy::Immutable,
+            last_modified: None,
+        }
+    }
+
+    /// For responses that depend on the current time.
+    pub fn no_store() -> CacheLayer {
+        CacheLayer {
+            policy: Policy::NoStore,
+            last_modified: None,
+        }
+    }
+
+    /// For responses that only change from one release to the next.
+    pub fn revalidate() -> CacheLayer {
+        CacheLayer {
+            policy: Policy::Revalidate,
+            last_modified: None,
+        }
+    }
+
+    /// Dates cacheable responses with `Last-Modified`, which must be no earlier than the
+    /// last change of any of them.
+    pub fn last_modified(self, last_modified: DateTime<Utc>) -> CacheLayer {
+        CacheLayer {
+            last_modified: Some(last_modified),
+            ..self
+        }
+    }
+}
+
+impl<S> Layer<S> for CacheLayer {
+    type Service = Cached<S>;
+
+    fn layer(&self, inner: S) -> Cached<S> {
+        Cached {
+            inner,
+            policy: self.policy,
+            last_modified: self.last_modified,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cached<S> {
+    inner: S,
+    policy: Policy,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+/// Identifies the representation the outer layers make of a body: the query string carries
+/// `?pretty`, JSONP callbacks and templates, `Accept` the binary encodings and
+/// `Accept-Encoding` the compression.
+fn representation<B>(request: &Request<B>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    request.uri().query().hash(&mut hasher);
+    for name in &[header::ACCEPT, header::ACCEPT_ENCODING] {
+        for value in request.headers().get_all(name) {
+            value.as_bytes().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// A strong validator derived from the body and its representation.
+fn etag(body: &[u8], representation: u64) -> HeaderValue {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    representation.hash(&mut hasher);
+    HeaderValue::from_str(&format!("\"{:016x}\"", hasher.finish())).unwrap()
+}
+
+/// Whether an `If-None-Match` header lists the given tag, or `*`.
+fn matches(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let etag = etag.to_str().unwrap_or_default();
+    if_none_match.to_str().is_ok_and(|tags| {
+        tags.split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag)
+    })
+}
+
+fn http_date(date: DateTime<Utc>) -> HeaderValue {
+    HeaderValue::from_str(&date.format(HTTP_DATE).to_string()).unwrap()
+}
+
+/// Reads an `If-Modified-Since` date. The obsolete formats are ignored like invalid dates,
+/// the response then being sent in full.
+fn parse_http_date(value: &HeaderValue) -> Option<DateTime<Utc>> {
+    let date = NaiveDateTime::parse_from_str(value.to_str().ok()?, HTTP_DATE).ok()?;
+    Some(DateTime::from_naive_utc_and_offset(date, Utc))
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for Cached<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+        let if_modified_since = match *request.method() {
+            Method::GET | Method::HEAD => request
+                .headers()
+                .get(header::IF_MODIFIED_SINCE)
+                .and_then(parse_http_date),
+            _ => None,
+        };
+        let representation = representation(&request);
+        let policy = self.policy;
+        let last_modified = self.last_modified;
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await?;
+            if response.status() != StatusCode::OK
+                || response.headers().contains_key(header::CACHE_CONTROL)
+            {
+                return Ok(response.map(Body::new));
+            }
+            if policy == Policy::NoStore {
+                let mut response = response.map(Body::new);
+                response
+                    .headers_mut()
+                    .insert(header::CACHE_CONTROL, HeaderValue::from_static(NO_STORE));
+                return Ok(response);
+            }
+
+            let (mut parts, body) = response.into_parts();
+            let body = match to_bytes(Body::new(body), usize::MAX).await {
+                Ok(body) => body,
+                Err(_) => {
+                    let mut response = Response::new(Body::empty());
+                    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                    return Ok(response);
+                }
+            };
+            let etag = etag(&body, representation);
+            let cache_control = match policy {
+                Policy::Revalidate => REVALIDATE,
+                _ => IMMUTABLE,
+            };
+            parts.headers.insert(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static(cache_control),
+            );
+            parts.headers.insert(header::ETAG, etag.clone());
+            if let Some(last_modified) = last_modified {
+                parts
+                    .headers
+                    .insert(header::LAST_MODIFIED, http_date(last_modified));
+            }
+
+            let not_modified = match (if_none_match, last_modified, if_modified_since) {
+                (Some(tags), _, _) => matches(&tags, &etag),
+                (None, Some(modified), Some(since)) => modified.timestamp() <= since.timestamp(),
+                _ => false,
+            };
+            if not_modified {
+                parts.status = StatusCode::NOT_MODIFIED;
+                parts.headers.remove(header::CONTENT_TYPE);
+                parts.headers.remove(header::CONTENT_LENGTH);
+                return Ok(Response::from_parts(parts, Body::empty()));
+            }
+            Ok(Response::from_parts(parts, Body::from(body)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn if_none_match() {
+        let tag = etag(b"{}", 0);
+        let header = |value: &str| HeaderValue::from_str(value).unwrap();
+        assert!(matches(&header("*"), &tag));
+        assert!(matches(&tag, &tag));
+        let listed = format!("\"other\", W/{}", tag.to_str().unwrap());
+        assert!(matches(&header(&listed), &tag));
+        assert!(!matches(&header("\"other\""), &tag));
+    }
+
+    #[test]
+    fn representations() {
+        let request = |accept: &'static str, uri: &str| {
+            Request::get(uri)
+                .header(header::ACCEPT, accept)
+                .header(header::ACCEPT_ENCODING, "gzip")
+                .body(())
+                .unwrap()
+        };
+        let json = representation(&request("application/json", "/api/0"));
+        assert_eq!(json, representation(&request("application/json", "/api/0")));
+        assert_ne!(
+            json,
+            representation(&request("application/msgpack", "/api/0"))
+        );
+        assert_ne!(
+            json,
+            representation(&request("application/json", "/api/0?pretty"))
+        );
+        let identity = Request::get("/api/0")
+            .header(header::ACCEPT, "application/json")
+            .body(())
+            .unwrap();
+        assert_ne!(json, representation(&identity));
+        assert_ne!(etag(b"{}", json), etag(b"{}", representation(&identity)));
+    }
+
+    #[test]
+    fn http_dates() {
+        let date = Utc.with_ymd_and_hms(1994, 11, 6, 8, 49, 37).unwrap();
+        assert_eq!(http_date(date), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_http_date(&http_date(date)), Some(date));
+        let obsolete = HeaderValue::from_static("Sunday, 06-Nov-94 08:49:37 GMT");
+        assert_eq!(parse_http_date(&obsolete), None);
+    }
+}
@@ -0,0 +1,187 @@
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+/// Returns whether `year` is a leap year in the proleptic Gregorian calendar.
+pub fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Number of days in the given month, `month` being 1-based.
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        _ if is_leap_year(year) => 29,
+        _ => 28,
+    }
+}
+
+/// `Duration::seconds`, returning `None` instead of panicking out of its range.
+pub fn checked_seconds(seconds: i64) -> Option<Duration> {
+    if seconds.checked_abs()? <= Duration::MAX.num_seconds() {
+        Some(Duration::seconds(seconds))
+    } else {
+        None
+    }
+}
+
+/// `Duration::days`, returning `None` instead of panicking out of its range.
+pub fn checked_days(days: i64) -> Option<Duration> {
+    checked_seconds(days.checked_mul(86_400)?)
+}
+
+/// Moves `date` by a number of calendar months, clamping the day to the end of the
+/// target month (Jan 31 + 1 month is Feb 28 or 29).
+pub fn add_months(date: NaiveDate, months: i32) -> Option<NaiveDate> {
+    let total = date
+        .year()
+        .checked_mul(12)?
+        .checked_add(date.month0() as i32)?
+        .checked_add(months)?;
+    let year = total.div_euclid(12);
+    let month = total.rem_euclid(12) as u32 + 1;
+    let day = date.day().min(days_in_month(year, month));
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// The `n`-th given weekday of a month, counting from the end when `n` is negative
+/// (`-1` is the last one). Returns `None` if the month doesn't have that many.
+pub fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: i32) -> Option<NaiveDate> {
+    if n > 0 {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let offset =
+            (7 + weekday.num_days_from_monday() - first.weekday().num_days_from_monday()) % 7;
+        let date = first + Duration::days(offset as i64 + 7 * (n as i64 - 1));
+        Some(date).filter(|date| date.month() == month)
+    } else if n < 0 {
+        let last = NaiveDate::from_ymd_opt(year, month, days_in_month(year, month))?;
+        let offset =
+            (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+        let date = last - Duration::days(offset as i64 + 7 * (-n as i64 - 1));
+        Some(date).filter(|date| date.month() == month)
+    } else {
+        None
+    }
+}
+
+/// Easter Sunday of the given year in the Gregorian calendar, using the anonymous
+/// Gregorian algorithm (Meeus/Jones/Butcher).
+pub fn easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+/// Parses an ISO 8601 week date such as `2016-W51-7`, the day being 1 for Monday.
+pub fn parse_week_date(input: &str) -> Option<NaiveDate> {
+    let (year, rest) = input.split_once("-W")?;
+    let (week, day) = rest.split_once('-')?;
+    if week.len() != 2 || day.len() != 1 {
+        return None;
+    }
+    let weekday = match day.parse::<u32>().ok()? {
+        1 => Weekday::Mon,
+        2 => Weekday::Tue,
+        3 => Weekday::Wed,
+        4 => Weekday::Thu,
+        5 => Weekday::Fri,
+        6 => Weekday::Sat,
+        7 => Weekday::Sun,
+        _ => return None,
+    };
+    NaiveDate::from_isoywd_opt(year.parse().ok()?, week.parse().ok()?, weekday)
+}
+
+/// Formats a date as an ISO 8601 week date, such as `2016-W51-7`.
+pub fn week_date(date: NaiveDate) -> String {
+    date.format("%G-W%V-%u").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn month_arithmetic_clamps_day() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        assert_eq!(
+            add_months(date, 1),
+            Some(NaiveDate::from_ymd_opt(2020, 2, 29).unwrap())
+        );
+        assert_eq!(
+            add_months(date, -2),
+            Some(NaiveDate::from_ymd_opt(2019, 11, 30).unwrap())
+        );
+        assert_eq!(
+            add_months(date, 12),
+            Some(NaiveDate::from_ymd_opt(2021, 1, 31).unwrap())
+        );
+        assert_eq!(add_months(date, i32::MAX), None);
+    }
+
+    #[test]
+    fn checked_durations() {
+        assert_eq!(checked_days(2), Some(Duration::hours(48)));
+        assert_eq!(checked_seconds(-1), Some(Duration::seconds(-1)));
+        assert_eq!(checked_days(i64::MAX / 86_400), None);
+        assert_eq!(checked_seconds(9_999_999_999_999_999), None);
+        assert_eq!(checked_seconds(i64::MIN), None);
+    }
+
+    #[test]
+    fn nth_weekdays() {
+        let thanksgiving = nth_weekday(2021, 11, Weekday::Thu, 4);
+        assert_eq!(
+            thanksgiving,
+            Some(NaiveDate::from_ymd_opt(2021, 11, 25).unwrap())
+        );
+        let memorial_day = nth_weekday(2021, 5, Weekday::Mon, -1);
+        assert_eq!(
+            memorial_day,
+            Some(NaiveDate::from_ymd_opt(2021, 5, 31).unwrap())
+        );
+        assert_eq!(nth_weekday(2021, 2, Weekday::Mon, 5), None);
+    }
+
+    #[test]
+    fn easter_sundays() {
+        assert_eq!(
+            easter(2021),
+            Some(NaiveDate::from_ymd_opt(2021, 4, 4).unwrap())
+        );
+        assert_eq!(
+            easter(2024),
+            Some(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap())
+        );
+        assert_eq!(
+            easter(2038),
+            Some(NaiveDate::from_ymd_opt(2038, 4, 25).unwrap())
+        );
+    }
+
+    #[test]
+    fn week_dates() {
+        let christmas = NaiveDate::from_ymd_opt(2016, 12, 25).unwrap();
+        assert_eq!(week_date(christmas), "2016-W51-7");
+        assert_eq!(parse_week_date("2016-W51-7"), Some(christmas));
+        // the ISO year of early January can be the previous one
+        assert_eq!(
+            parse_week_date("2020-W53-5"),
+            Some(NaiveDate::from_ymd_opt(2021, 1, 1).unwrap())
+        );
+        assert_eq!(parse_week_date("2021-W53-1"), None);
+        assert_eq!(parse_week_date("2016-W51-8"), None);
+        assert_eq!(parse_week_date("2016-12-25"), None);
+    }
+}
@@ -0,0 +1,275 @@
+//! The Chinese lunisolar calendar, from 1900 to 2100.
+//!
+//! Months start on new moons and have 29 or 30 days, and a leap month repeating the
+//! previous one is added in 7 years out of 19. Since they follow astronomical events as
+//! seen from China, month lengths and leap months are read from a table rather than
+//! computed.
+
+use axum::Json;
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+use crate::query::Query;
+
+const FIRST_YEAR: i32 = 1900;
+
+/// Each year from 1900, bits 4 to 15 telling which of months 12 down to 1 have 30 days,
+/// bits 0 to 3 the month followed by a leap month, if any, and bit 16 whether that leap
+/// month has 30 days.
+const YEARS: [u32; 201] = [
+    0x04bd8, 0x04ae0, 0x0a570, 0x054d5, 0x0d260, 0x0d950, 0x16554, 0x056a0, 0x09ad0, 0x055d2,
+    0x04ae0, 0x0a5b6, 0x0a4d0, 0x0d250, 0x1d255, 0x0b540, 0x0d6a0, 0x0ada2, 0x095b0, 0x14977,
+    0x04970, 0x0a4b0, 0x0b4b5, 0x06a50, 0x06d40, 0x1ab54, 0x02b60, 0x09570, 0x052f2, 0x04970,
+    0x06566, 0x0d4a0, 0x0ea50, 0x16a95, 0x05ad0, 0x02b60, 0x186e3, 0x092e0, 0x1c8d7, 0x0c950,
+    0x0d4a0, 0x1d8a6, 0x0b550, 0x056a0, 0x1a5b4, 0x025d0, 0x092d0, 0x0d2b2, 0x0a950, 0x0b557,
+    0x06ca0, 0x0b550, 0x15355, 0x04da0, 0x0a5b0, 0x14573, 0x052b0, 0x0a9a8, 0x0e950, 0x06aa0,
+    0x0aea6, 0x0ab50, 0x04b60, 0x0aae4, 0x0a570, 0x05260, 0x0f263, 0x0d950, 0x05b57, 0x056a0,
+    0x096d0, 0x04dd5, 0x04ad0, 0x0a4d0, 0x0d4d4, 0x0d250, 0x0d558, 0x0b540, 0x0b6a0, 0x195a6,
+    0x095b0, 0x049b0, 0x0a974, 0x0a4b0, 0x0b27a, 0x06a50, 0x06d40, 0x0af46, 0x0ab60, 0x09570,
+    0x04af5, 0x04970, 0x064b0, 0x074a3, 0x0ea50, 0x06b58, 0x05ac0, 0x0ab60, 0x096d5, 0x092e0,
+    0x0c960, 0x0d954, 0x0d4a0, 0x0da50, 0x07552, 0x056a0, 0x0abb7, 0x025d0, 0x092d0, 0x0cab5,
+    0x0a950, 0x0b4a0, 0x0baa4, 0x0ad50, 0x055d9, 0x04ba0, 0x0a5b0, 0x15176, 0x052b0, 0x0a930,
+    0x07954, 0x06aa0, 0x0ad50, 0x05b52, 0x04b60, 0x0a6e6, 0x0a4e0, 0x0d260, 0x0ea65, 0x0d530,
+    0x05aa0, 0x076a3, 0x096d0, 0x04afb, 0x04ad0, 0x0a4d0, 0x1d0b6, 0x0d250, 0x0d520, 0x0dd45,
+    0x0b5a0, 0x056d0, 0x055b2, 0x049b0, 0x0a577, 0x0a4b0, 0x0aa50, 0x1b255, 0x06d20, 0x0ada0,
+    0x14b63, 0x09370, 0x049f8, 0x04970, 0x064b0, 0x168a6, 0x0ea50, 0x06b20, 0x1a6c4, 0x0aae0,
+    0x092e0, 0x0d2e3, 0x0c960, 0x0d557, 0x0d4a0, 0x0da50, 0x05d55, 0x056a0, 0x0a6d0, 0x055d4,
+    0x052d0, 0x0a9b8, 0x0a950, 0x0b4a0, 0x0b6a6, 0x0ad50, 0x055a0, 0x0aba4, 0x0a5b0, 0x052b0,
+    0x0b273, 0x06930, 0x07337, 0x06aa0, 0x0ad50, 0x14b55, 0x04b60, 0x0a570, 0x054e4, 0x0d160,
+    0x0e968, 0x0d520, 0x0daa0, 0x16aa6, 0x056d0, 0x04ae0, 0x0a9d4, 0x0a2d0, 0x0d150, 0x0f252,
+    0x0d520,
+];
+
+const STEMS: [&str; 10] = [
+    "jia", "yi", "bing", "ding", "wu", "ji", "geng", "xin", "ren", "gui",
+];
+
+const BRANCHES: [&str; 12] = [
+    "zi", "chou", "yin", "mao", "chen", "si", "wu", "wei", "shen", "you", "xu", "hai",
+];
+
+const ANIMALS: [&str; 12] = [
+    "Rat", "Ox", "Tiger", "Rabbit", "Dragon", "Snake", "Horse", "Goat", "Monkey", "Rooster", "Dog",
+    "Pig",
+];
+
+/// The first day of 1900 in the Chinese calendar.
+fn first_new_year() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1900, 1, 31).unwrap()
+}
+
+fn year_bits(year: i32) -> Option<u32> {
+    let index = usize::try_from(year - FIRST_YEAR).ok()?;
+    YEARS.get(index).copied()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ChineseDate {
+    /// The Gregorian year most of the Chinese year falls in.
+    pub year: i32,
+    pub month: u32,
+    /// Whether the month is the leap month repeating `month`.
+    pub leap_month: bool,
+    pub day: u32,
+}
+
+/// The months of a known year in order, with their length.
+fn months(bits: u32) -> impl Iterator<Item = (u32, bool, i64)> {
+    let leap = bits & 0xf;
+    (1..=12).flat_map(move |month| {
+        let length = if bits & (0x10000 >> month) != 0 {
+            30
+        } else {
+            29
+        };
+        let leap_length = if bits & 0x10000 != 0 { 30 } else { 29 };
+        let repeated = Some((month, true, leap_length)).filter(|_| month == leap);
+        std::iter::once((month, false, length)).chain(repeated)
+    })
+}
+
+fn year_length(bits: u32) -> i64 {
+    months(bits).map(|(_, _, length)| length).sum()
+}
+
+/// The Chinese date of a day, `None` outside of the years of the table.
+pub fn to_chinese(date: NaiveDate) -> Option<ChineseDate> {
+    let mut days = (date - first_new_year()).num_days();
+    if days < 0 {
+        return None;
+    }
+    let mut year = FIRST_YEAR;
+    let mut bits = year_bits(year)?;
+    while days >= year_length(bits) {
+        days -= year_length(bits);
+        year += 1;
+        bits = year_bits(year)?;
+    }
+    for (month, leap_month, length) in months(bits) {
+        if days < length {
+            return Some(ChineseDate {
+                year,
+                month,
+                leap_month,
+                day: days as u32 + 1,
+            });
+        }
+        days -= length;
+    }
+    unreachable!("the days left are fewer than those of the year")
+}
+
+/// The Gregorian day of a Chinese date, `None` when it doesn't exist or is outside of the
+/// years of the table.
+pub fn from_chinese(date: &ChineseDate) -> Option<NaiveDate> {
+    let bits = year_bits(date.year)?;
+    let mut days: i64 = (FIRST_YEAR..date.year)
+        .map(|year| year_bits(year).map_or(0, year_length))
+        .sum();
+    for (month, leap_month, length) in months(bits) {
+        if (month, leap_month) == (date.month, date.leap_month) {
+            if date.day < 1 || i64::from(date.day) > length {
+                return None;
+            }
+            days += i64::from(date.day) - 1;
+            return first_new_year().checked_add_signed(Duration::days(days));
+        }
+        days += length;
+    }
+    None
+}
+
+fn describe(chinese: &ChineseDate, gregorian: NaiveDate) -> Value {
+    let cycle = (chinese.year - 4).rem_euclid(60) as usize;
+    json!({
+        "gregorian": gregorian.to_string(),
+        "weekday": gregorian.weekday().to_string(),
+        "chinese": {
+            "year": chinese.year,
+            "month": chinese.month,
+            "leap_month": chinese.leap_month,
+            "day": chinese.day,
+            "year_name": format!("{}-{}", STEMS[cycle % 10], BRANCHES[cycle % 12]),
+            "zodiac": ANIMALS[cycle % 12],
+        },
+    })
+}
+
+fn out_of_range() -> AppError {
+    AppError::BadRequest(format!(
+        "Chinese dates are only known from {} to {}",
+        FIRST_YEAR,
+        FIRST_YEAR + YEARS.len() as i32 - 1
+    ))
+}
+
+/// Converts a Gregorian date to the Chinese calendar.
+pub async fn to_chinese_handler(Path(date): Path<String>) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?.date_naive();
+    let chinese = to_chinese(date).ok_or_else(out_of_range)?;
+
+    Ok(Json(describe(&chinese, date)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChineseParams {
+    /// Whether the month is the leap one repeating the given month.
+    #[serde(default)]
+    leap: bool,
+}
+
+/// Converts a Chinese date, written `YYYY-MM-DD` with `?leap=true` for leap months, to the
+/// Gregorian calendar.
+pub async fn from_chinese_handler(
+    Path(date): Path<String>,
+    Query(params): Query<ChineseParams>,
+) -> Result<Json<Value>, AppError> {
+    let mut parts = date.splitn(3, '-');
+    let mut next = || parts.next().and_then(|part| part.parse::<u32>().ok());
+    let (year, month, day) = match (next(), next(), next()) {
+        (Some(year), Some(month), Some(day)) => (year, month, day),
+        _ => return Err(AppError::InvalidDate),
+    };
+    let chinese = ChineseDate {
+        year: i32::try_from(year).map_err(|_| AppError::InvalidDate)?,
+        month,
+        leap_month: params.leap,
+        day,
+    };
+    if year_bits(chinese.year).is_none() {
+        return Err(out_of_range());
+    }
+    let gregorian = from_chinese(&chinese).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(describe(&chinese, gregorian)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chinese(year: i32, month: u32, leap_month: bool, day: u32) -> ChineseDate {
+        ChineseDate {
+            year,
+            month,
+            leap_month,
+            day,
+        }
+    }
+
+    #[test]
+    fn new_years() {
+        for (year, month, day) in &[(1900, 1, 31), (1970, 2, 6), (2024, 2, 10), (2100, 2, 9)] {
+            let date = NaiveDate::from_ymd_opt(*year, *month, *day).unwrap();
+            assert_eq!(to_chinese(date), Some(chinese(*year, 1, false, 1)));
+            assert_eq!(
+                to_chinese(date.pred_opt().unwrap()).map(|previous| previous.year),
+                Some(*year - 1).filter(|_| *year > 1900)
+            );
+        }
+        assert_eq!(
+            to_chinese(NaiveDate::from_ymd_opt(2101, 1, 29).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn leap_months() {
+        // 2023 repeats its second month
+        assert_eq!(
+            to_chinese(NaiveDate::from_ymd_opt(2023, 3, 22).unwrap()),
+            Some(chinese(2023, 2, true, 1))
+        );
+        assert_eq!(
+            from_chinese(&chinese(2023, 2, true, 1)),
+            Some(NaiveDate::from_ymd_opt(2023, 3, 22).unwrap())
+        );
+        assert_eq!(from_chinese(&chinese(2024, 2, true, 1)), None);
+        assert_eq!(from_chinese(&chinese(2024, 1, false, 31)), None);
+    }
+
+    #[test]
+    fn round_trips() {
+        let start = first_new_year();
+        for days in (0..73_000).step_by(11) {
+            let date = start + Duration::days(days);
+            let chinese = to_chinese(date).unwrap();
+            assert_eq!(from_chinese(&chinese), Some(date));
+        }
+    }
+
+    #[test]
+    fn sexagenary_years() {
+        let dragon = describe(
+            &chinese(2024, 1, false, 1),
+            NaiveDate::from_ymd_opt(2024, 2, 10).unwrap(),
+        );
+        assert_eq!(dragon["chinese"]["year_name"], "jia-chen");
+        assert_eq!(dragon["chinese"]["zodiac"], "Dragon");
+    }
+}
@@ -0,0 +1,108 @@
+use axum::Json;
+use chrono::{Datelike, NaiveTime, Weekday};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::query::Query;
+use crate::timezone::{describe_local, parse_in_zone, parse_tz};
+
+/// Boundaries used to label an instant, all of them wall-clock times in the target zone.
+#[derive(Debug, Deserialize)]
+pub struct ClassifyParams {
+    tz: Option<String>,
+    /// Start of each part of the day, `05:00`, `12:00`, `17:00` and `21:00` by default.
+    morning: Option<String>,
+    afternoon: Option<String>,
+    evening: Option<String>,
+    night: Option<String>,
+    /// Business hours, `09:00` to `17:00` by default.
+    business_start: Option<String>,
+    business_end: Option<String>,
+    /// Comma-separated weekend days, `sat,sun` by default.
+    weekend: Option<String>,
+}
+
+fn time_param(value: &Option<String>, default: (u32, u32)) -> Result<NaiveTime, AppError> {
+    match value {
+        Some(value) => NaiveTime::parse_from_str(value, "%H:%M")
+            .map_err(|_| AppError::BadRequest(format!("Invalid time {}", value))),
+        None => Ok(NaiveTime::from_hms_opt(default.0, default.1, 0).unwrap()),
+    }
+}
+
+/// Whether `time` is in `[start, end)`, wrapping around midnight when `end < start`.
+fn within(time: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start <= end {
+        start <= time && time < end
+    } else {
+        start <= time || time < end
+    }
+}
+
+/// Labels an instant as a part of the day, business hours or not, weekday or weekend.
+pub async fn classify_handler(
+    Path(date): Path<String>,
+    Query(params): Query<ClassifyParams>,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(params.tz.as_deref())?;
+    let instant = parse_in_zone(&date, tz)?;
+    let local = instant.with_timezone(&tz);
+    let time = local.time();
+
+    let parts = [
+        ("morning", time_param(&params.morning, (5, 0))?),
+        ("afternoon", time_param(&params.afternoon, (12, 0))?),
+        ("evening", time_param(&params.evening, (17, 0))?),
+        ("night", time_param(&params.night, (21, 0))?),
+    ];
+    let part = (0..parts.len())
+        .find(|i| within(time, parts[*i].1, parts[(i + 1) % parts.len()].1))
+        .map_or("night", |i| parts[i].0);
+
+    let business_start = time_param(&params.business_start, (9, 0))?;
+    let business_end = time_param(&params.business_end, (17, 0))?;
+    let weekend = match &params.weekend {
+        Some(days) => days
+            .split(',')
+            .map(|day| {
+                day.trim()
+                    .parse::<Weekday>()
+                    .map_err(|_| AppError::BadRequest(format!("Invalid weekday {}", day)))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![Weekday::Sat, Weekday::Sun],
+    };
+    let is_weekend = weekend.contains(&local.weekday());
+    let business_hours = !is_weekend && within(time, business_start, business_end);
+
+    Ok(Json(json!({
+        "unix": instant.timestamp(),
+        "utc": instant.to_rfc2822(),
+        "local": describe_local(instant, tz),
+        "part_of_day": part,
+        "business_hours": business_hours,
+        "day_type": if is_weekend { "weekend" } else { "weekday" },
+        "labels": [
+            part,
+            if business_hours { "business-hours" } else { "off-hours" },
+            if is_weekend { "weekend" } else { "weekday" },
+        ],
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapping_ranges() {
+        let at = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        assert!(within(at(10), at(9), at(17)));
+        assert!(!within(at(17), at(9), at(17)));
+        assert!(within(at(23), at(21), at(5)));
+        assert!(within(at(2), at(21), at(5)));
+        assert!(!within(at(6), at(21), at(5)));
+    }
+}
@@ -0,0 +1,78 @@
+//! One-shot conversions from the command line, printing the same JSON as the latest API
+//! version:
+//!
+//! ```text
+//! timestamp now
+//! timestamp parse 2016-12-25
+//! timestamp diff 2016-12-25 "in 3 days"
+//! ```
+
+use chrono::Utc;
+use serde_json::{json, Value};
+
+use crate::relative::{humanize, Unit};
+use crate::version::ApiVersion;
+use crate::{parse_date_at, timestamp_body};
+
+const USAGE: &str = "Usage: timestamp now | parse <date> | diff <from> <to>";
+
+/// Runs the subcommand in `args`, if any. Returns `None` when the server should start
+/// instead.
+pub fn run(args: &[String]) -> Option<Result<Value, String>> {
+    let command = args.get(1)?;
+    let operands = &args[2..];
+    let now = Utc::now();
+    let parse = |date: &String| parse_date_at(date, now).map_err(|e| e.to_string());
+    let body = |date| timestamp_body(date, ApiVersion::LATEST);
+
+    let outcome = match (command.as_str(), operands) {
+        ("now", []) => Ok(body(now)),
+        ("parse", [date]) => parse(date).map(body),
+        ("diff", [from, to]) => parse(from).and_then(|from| {
+            let to = parse(to)?;
+            let seconds = (to - from).num_seconds();
+            Ok(json!({
+                "from": body(from),
+                "to": body(to),
+                "seconds": seconds,
+                "relative": humanize(seconds, Unit::Second),
+            }))
+        }),
+        ("now", _) | ("parse", _) | ("diff", _) => Err(USAGE.to_string()),
+        _ => return None,
+    };
+    Some(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Option<Result<Value, String>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        super::run(&args)
+    }
+
+    #[test]
+    fn subcommands() {
+        assert!(run(&["timestamp"]).is_none());
+        assert!(run(&["timestamp", "--uds", "/run/timestamp.sock"]).is_none());
+
+        let body = run(&["timestamp", "parse", "2016-12-25"]).unwrap().unwrap();
+        assert_eq!(body["unix"], 1482624000);
+
+        let body = run(&["timestamp", "diff", "2016-12-25", "2016-12-24"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(body["seconds"], -86400);
+        assert_eq!(body["relative"], "1 day ago");
+
+        assert_eq!(run(&["timestamp", "parse"]).unwrap().unwrap_err(), USAGE);
+        assert_eq!(
+            run(&["timestamp", "parse", "someday"])
+                .unwrap()
+                .unwrap_err(),
+            "Invalid Date"
+        );
+    }
+}
@@ -0,0 +1,764 @@
+//! Service configuration.
+//!
+//! Settings are read from the TOML file passed with `--config`, every one of them being
+//! overridable by an environment variable, which takes precedence:
+//!
+//! ```toml
+//! listen = ["0.0.0.0:3000", "[::]:3000"]  # TIMESTAMP_LISTEN, comma separated, see `Listener`
+//! log_level = "info"                      # RUST_LOG
+//! default_timezone = "Europe/Rome"        # TIMESTAMP_DEFAULT_TZ
+//! week_start = "Mon"                      # TIMESTAMP_WEEK_START
+//! cors_origins = ["https://example.com"]  # TIMESTAMP_CORS_ORIGINS, comma separated
+//! trusted_proxies = ["10.0.0.1"]          # TIMESTAMP_TRUSTED_PROXIES, comma separated
+//! parse_cache_size = 1024                 # TIMESTAMP_PARSE_CACHE_SIZE
+//! max_clock_skew_ms = 250                 # TIMESTAMP_MAX_CLOCK_SKEW_MS
+//! v1_sunset = "2022-06-30"                # TIMESTAMP_V1_SUNSET
+//! ntp_servers = ["pool.ntp.org"]          # TIMESTAMP_NTP_SERVERS, comma separated
+//! debug_endpoints = false                 # TIMESTAMP_DEBUG_ENDPOINTS
+//! tzdata_dir = "/usr/share/zoneinfo"      # TIMESTAMP_TZDATA_DIR
+//! tz_boundaries = "combined.json"         # TIMESTAMP_TZ_BOUNDARIES
+//! geoip_db = "GeoLite2-City.mmdb"         # TIMESTAMP_GEOIP_DB, unset disables /api/local
+//! admin_token = "..."                     # TIMESTAMP_ADMIN_TOKEN, unset disables /admin
+//! api_keys = ["..."]                      # TIMESTAMP_API_KEYS, comma separated
+//! redis_url = "redis://127.0.0.1/"        # TIMESTAMP_REDIS_URL, needs the redis feature
+//! database_url = "sqlite://timestamp.db"  # TIMESTAMP_DATABASE_URL, needs the sqlite feature
+//!
+//! [templates]                            # named response templates, see `crate::template`
+//! legacy = '{"epoch": "{unix}", "pretty": "{utc}"}'
+//!
+//! [security_headers]
+//! enabled = true                          # TIMESTAMP_SECURITY_HEADERS
+//! hsts_max_age_secs = 31536000            # TIMESTAMP_HSTS_MAX_AGE_SECS, 0 disables HSTS
+//! referrer_policy = "no-referrer"
+//! content_security_policy = "default-src 'self'; frame-ancestors 'none'"  # HTML only
+//!
+//! [access_log]                          # see `crate::access_log`
+//! path = "/var/log/timestamp/access.log"  # TIMESTAMP_ACCESS_LOG, unset disables it
+//! rotation = "daily"                      # TIMESTAMP_ACCESS_LOG_ROTATION, hourly, daily or never
+//! max_files = 30                          # 0 keeps every file
+//!
+//! [rate_limit]
+//! requests_per_minute = 600               # TIMESTAMP_RATE_LIMIT_PER_MINUTE, 0 disables it
+//! burst = 60                              # TIMESTAMP_RATE_LIMIT_BURST
+//!
+//! [server]
+//! worker_threads = 4                      # TIMESTAMP_WORKER_THREADS, --worker-threads
+//! max_blocking_threads = 16               # TIMESTAMP_MAX_BLOCKING_THREADS, --max-blocking-threads
+//! keep_alive = true                       # TIMESTAMP_KEEP_ALIVE, --keep-alive
+//! tcp_keepalive_secs = 60                 # TIMESTAMP_TCP_KEEPALIVE_SECS
+//! idle_timeout_secs = 75                  # TIMESTAMP_IDLE_TIMEOUT_SECS
+//! h2c = false                             # TIMESTAMP_H2C
+//! http2_keep_alive_interval_secs = 30     # TIMESTAMP_HTTP2_KEEP_ALIVE_INTERVAL_SECS
+//! http2_keep_alive_timeout_secs = 20      # TIMESTAMP_HTTP2_KEEP_ALIVE_TIMEOUT_SECS
+//! request_timeout_secs = 30               # TIMESTAMP_REQUEST_TIMEOUT_SECS, 0 disables it
+//! max_body_bytes = 1048576                # TIMESTAMP_MAX_BODY_BYTES
+//! max_concurrent_requests = 1024          # TIMESTAMP_MAX_CONCURRENT_REQUESTS, 0 disables it
+//! drain_grace_secs = 10                   # TIMESTAMP_DRAIN_GRACE_SECS
+//! ```
+//!
+//! The `[server]` settings can also be given on the command line, which takes precedence
+//! over both.
+//!
+//! On `SIGHUP` the file is read again and the [`Runtime`] settings, those that can change
+//! without a restart, are published to the layers reading them.
+
+use chrono::Weekday;
+use chrono_tz::Tz;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use tokio::sync::watch;
+
+use crate::access_log::Rotation;
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Addresses the same router is served on, a single one being accepted as a string.
+    #[serde(deserialize_with = "listeners")]
+    pub listen: Vec<Listener>,
+    /// A `RUST_LOG` style filter.
+    pub log_level: String,
+    /// Timezone of requests that don't name one.
+    pub default_timezone: String,
+    /// First day of the week of week numbers, calendar grids and alignment to weeks that
+    /// don't ask for one.
+    pub week_start: String,
+    /// Origins allowed to call the API from a browser, `*` allowing any. Empty disables CORS.
+    pub cors_origins: Vec<String>,
+    /// Proxies whose `X-Forwarded-For` tells the client address, see `crate::rate_limit`.
+    pub trusted_proxies: Vec<IpAddr>,
+    pub rate_limit: RateLimitConfig,
+    pub server: ServerConfig,
+    pub security_headers: SecurityHeadersConfig,
+    pub access_log: AccessLogConfig,
+    /// Capacity of the parse cache, 0 disabling it.
+    pub parse_cache_size: usize,
+    pub max_clock_skew_ms: i64,
+    /// Date the v1 API will be removed, announced in the `Sunset` header.
+    pub v1_sunset: Option<String>,
+    pub ntp_servers: Vec<String>,
+    pub debug_endpoints: bool,
+    /// Zoneinfo directory whose rules replace the built-in ones, see [`crate::tzdata`].
+    pub tzdata_dir: Option<String>,
+    /// GeoJSON file of zone boundaries used to locate coordinates, see [`crate::geo`].
+    pub tz_boundaries: Option<String>,
+    /// MaxMind City database locating callers by IP address, see [`crate::geo`].
+    pub geoip_db: Option<String>,
+    /// Bearer token of the `/admin` endpoints, which are disabled without one.
+    pub admin_token: Option<String>,
+    /// Keys clients identify themselves with to get their preferences applied, see
+    /// `crate::preferences`. Empty disables them.
+    pub api_keys: Vec<String>,
+    /// Redis server holding the state shared by replicas, see `crate::redis_store`. Only
+    /// supported when built with the `redis` feature.
+    pub redis_url: Option<String>,
+    /// SQLite database keeping timers, marks and scheduled webhooks across restarts, see
+    /// `crate::sqlite_store`. Marks stay in Redis when `redis_url` is set too. Only
+    /// supported when built with the `sqlite` feature.
+    pub database_url: Option<String>,
+    /// Response templates by name, see [`crate::template`].
+    pub templates: BTreeMap<String, String>,
+}
+
+/// Requests allowed per client. Buckets refill at `requests_per_minute`, and hold up to
+/// `burst` requests, which defaults to the per-minute rate.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub requests_per_minute: u32,
+    pub burst: u32,
+}
+
+/// Headers hardening responses, see [`crate::security_headers`]. Empty policies are
+/// left out.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityHeadersConfig {
+    pub enabled: bool,
+    /// `max-age` of `Strict-Transport-Security`, 0 leaving the header out.
+    pub hsts_max_age_secs: u64,
+    pub referrer_policy: String,
+    /// Policy of HTML responses.
+    pub content_security_policy: String,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        SecurityHeadersConfig {
+            enabled: true,
+            hsts_max_age_secs: 365 * 24 * 60 * 60,
+            referrer_policy: "no-referrer".to_string(),
+            content_security_policy: "default-src 'self'; frame-ancestors 'none'".to_string(),
+        }
+    }
+}
+
+/// Audit log of requests, see [`crate::access_log`].
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessLogConfig {
+    /// File the log is written to, suffixed with the period when rotating. `None`
+    /// disables the log.
+    pub path: Option<String>,
+    pub rotation: Rotation,
+    /// Rotated files kept, 0 keeping all of them.
+    pub max_files: usize,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        AccessLogConfig {
+            path: None,
+            rotation: Rotation::Daily,
+            max_files: 30,
+        }
+    }
+}
+
+/// An address the service listens on: a TCP socket address, IPv4 or IPv6 like
+/// `[::1]:3000`, or the path of a Unix socket written `unix:/run/timestamp.sock`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Listener {
+    Tcp(SocketAddr),
+    Unix(String),
+}
+
+impl FromStr for Listener {
+    type Err = ();
+
+    fn from_str(listener: &str) -> Result<Listener, ()> {
+        match listener.strip_prefix("unix:") {
+            Some("") => Err(()),
+            Some(path) => Ok(Listener::Unix(path.to_string())),
+            None => listener.parse().map(Listener::Tcp).map_err(|_| ()),
+        }
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Listener::Tcp(addr) => write!(f, "{}", addr),
+            Listener::Unix(path) => write!(f, "unix:{}", path),
+        }
+    }
+}
+
+fn listeners<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Listener>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Listen {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    let listen = match Listen::deserialize(deserializer)? {
+        Listen::One(listener) => vec![listener],
+        Listen::Many(listeners) => listeners,
+    };
+    listen
+        .iter()
+        .map(|listener| {
+            listener
+                .parse()
+                .map_err(|_| D::Error::custom(format!("Invalid listen address {}", listener)))
+        })
+        .collect()
+}
+
+/// Sizing of the Tokio runtime and connection handling. Unset thread counts use Tokio's
+/// defaults, one worker per core and 512 blocking threads.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub worker_threads: Option<usize>,
+    pub max_blocking_threads: Option<usize>,
+    /// Whether HTTP/1 connections are kept open between requests.
+    pub keep_alive: bool,
+    /// Interval of TCP keepalive probes on idle connections, none by default.
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Time a kept alive HTTP/1 connection may wait for the next request before being
+    /// closed, unlimited by default.
+    pub idle_timeout_secs: Option<u64>,
+    /// Whether TCP listeners without TLS also speak HTTP/2 to clients starting with its
+    /// preface, as meshes do. Over TLS, HTTP/2 is always offered.
+    pub h2c: bool,
+    /// Interval of the pings checking that HTTP/2 connections are alive, none by default.
+    pub http2_keep_alive_interval_secs: Option<u64>,
+    /// Time to wait for a ping to be acknowledged before closing the connection.
+    pub http2_keep_alive_timeout_secs: u64,
+    /// Time allowed to read a request and answer it, 0 for no limit.
+    pub request_timeout_secs: u64,
+    /// Largest request body accepted.
+    pub max_body_bytes: usize,
+    /// Requests served at once, those over it being refused with a 503. 0 for no limit.
+    pub max_concurrent_requests: usize,
+    /// Time between `/admin/drain` failing the readiness probe and the listener closing,
+    /// for load balancers to stop sending requests.
+    pub drain_grace_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            worker_threads: None,
+            max_blocking_threads: None,
+            keep_alive: true,
+            tcp_keepalive_secs: None,
+            idle_timeout_secs: None,
+            h2c: false,
+            http2_keep_alive_interval_secs: None,
+            http2_keep_alive_timeout_secs: 20,
+            request_timeout_secs: 30,
+            max_body_bytes: 1024 * 1024,
+            max_concurrent_requests: 1024,
+            drain_grace_secs: 10,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            listen: vec![Listener::Tcp(SocketAddr::from(([127, 0, 0, 1], 3000)))],
+            log_level: "timestamp_microservice=debug,tower_http=debug".to_string(),
+            default_timezone: "UTC".to_string(),
+            week_start: "Mon".to_string(),
+            cors_origins: Vec::new(),
+            trusted_proxies: Vec::new(),
+            rate_limit: RateLimitConfig::default(),
+            server: ServerConfig::default(),
+            security_headers: SecurityHeadersConfig::default(),
+            access_log: AccessLogConfig::default(),
+            parse_cache_size: crate::parse_cache::DEFAULT_CAPACITY,
+            max_clock_skew_ms: crate::hlc::DEFAULT_MAX_SKEW_MS,
+            v1_sunset: None,
+            ntp_servers: vec!["pool.ntp.org".to_string()],
+            debug_endpoints: false,
+            tzdata_dir: None,
+            tz_boundaries: None,
+            geoip_db: None,
+            admin_token: None,
+            api_keys: Vec::new(),
+            redis_url: None,
+            database_url: None,
+            templates: BTreeMap::new(),
+        }
+    }
+}
+
+/// The settings that can be changed while the service runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Runtime {
+    pub log_level: String,
+    pub cors_origins: Vec<String>,
+    pub rate_limit: RateLimitConfig,
+}
+
+/// Latest runtime settings, read by the layers on every request.
+pub type Settings = watch::Receiver<Runtime>;
+
+/// Parses the variable `var` into `target`, if it is set.
+fn override_with<T: FromStr>(
+    env: &impl Fn(&str) -> Option<String>,
+    var: &str,
+    target: &mut T,
+) -> Result<(), String> {
+    if let Some(value) = env(var) {
+        *target = value
+            .parse()
+            .map_err(|_| format!("Invalid {} {}", var, value))?;
+    }
+    Ok(())
+}
+
+/// Same as [`override_with`], for optional settings.
+fn override_option<T: FromStr>(
+    value: Option<String>,
+    name: &str,
+    target: &mut Option<T>,
+) -> Result<(), String> {
+    if let Some(value) = value {
+        *target = Some(
+            value
+                .parse()
+                .map_err(|_| format!("Invalid {} {}", name, value))?,
+        );
+    }
+    Ok(())
+}
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl Config {
+    /// Reads the file at `path`, if any, then applies the overrides found through `env`.
+    pub fn load(
+        path: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, String> {
+        let mut config = match path {
+            Some(path) => {
+                let toml = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+                toml::from_str(&toml).map_err(|e| format!("{}: {}", path, e))?
+            }
+            None => Config::default(),
+        };
+
+        if let Some(listen) = env("TIMESTAMP_LISTEN") {
+            config.listen = list(&listen)
+                .iter()
+                .map(|listener| {
+                    listener
+                        .parse()
+                        .map_err(|_| format!("Invalid TIMESTAMP_LISTEN {}", listen))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        override_with(&env, "RUST_LOG", &mut config.log_level)?;
+        override_with(&env, "TIMESTAMP_DEFAULT_TZ", &mut config.default_timezone)?;
+        override_with(&env, "TIMESTAMP_WEEK_START", &mut config.week_start)?;
+        if let Some(origins) = env("TIMESTAMP_CORS_ORIGINS") {
+            config.cors_origins = list(&origins);
+        }
+        if let Some(proxies) = env("TIMESTAMP_TRUSTED_PROXIES") {
+            config.trusted_proxies = list(&proxies)
+                .iter()
+                .map(|proxy| {
+                    proxy
+                        .parse()
+                        .map_err(|_| format!("Invalid TIMESTAMP_TRUSTED_PROXIES {}", proxies))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        override_with(
+            &env,
+            "TIMESTAMP_RATE_LIMIT_PER_MINUTE",
+            &mut config.rate_limit.requests_per_minute,
+        )?;
+        override_with(
+            &env,
+            "TIMESTAMP_RATE_LIMIT_BURST",
+            &mut config.rate_limit.burst,
+        )?;
+        override_with(
+            &env,
+            "TIMESTAMP_PARSE_CACHE_SIZE",
+            &mut config.parse_cache_size,
+        )?;
+        override_with(
+            &env,
+            "TIMESTAMP_MAX_CLOCK_SKEW_MS",
+            &mut config.max_clock_skew_ms,
+        )?;
+        if let Some(sunset) = env("TIMESTAMP_V1_SUNSET") {
+            config.v1_sunset = Some(sunset);
+        }
+        if let Some(servers) = env("TIMESTAMP_NTP_SERVERS") {
+            config.ntp_servers = list(&servers);
+        }
+        if let Some(enabled) = env("TIMESTAMP_DEBUG_ENDPOINTS") {
+            config.debug_endpoints = enabled == "true";
+        }
+        if let Some(dir) = env("TIMESTAMP_TZDATA_DIR") {
+            config.tzdata_dir = Some(dir);
+        }
+        if let Some(path) = env("TIMESTAMP_TZ_BOUNDARIES") {
+            config.tz_boundaries = Some(path);
+        }
+        if let Some(path) = env("TIMESTAMP_GEOIP_DB") {
+            config.geoip_db = Some(path);
+        }
+        if let Some(token) = env("TIMESTAMP_ADMIN_TOKEN") {
+            config.admin_token = Some(token);
+        }
+        if let Some(keys) = env("TIMESTAMP_API_KEYS") {
+            config.api_keys = list(&keys);
+        }
+        if let Some(url) = env("TIMESTAMP_REDIS_URL") {
+            config.redis_url = Some(url);
+        }
+        if let Some(url) = env("TIMESTAMP_DATABASE_URL") {
+            config.database_url = Some(url);
+        }
+        override_with(
+            &env,
+            "TIMESTAMP_SECURITY_HEADERS",
+            &mut config.security_headers.enabled,
+        )?;
+        override_with(
+            &env,
+            "TIMESTAMP_HSTS_MAX_AGE_SECS",
+            &mut config.security_headers.hsts_max_age_secs,
+        )?;
+        if let Some(path) = env("TIMESTAMP_ACCESS_LOG") {
+            config.access_log.path = Some(path);
+        }
+        override_with(
+            &env,
+            "TIMESTAMP_ACCESS_LOG_ROTATION",
+            &mut config.access_log.rotation,
+        )?;
+        let server = &mut config.server;
+        override_option(
+            env("TIMESTAMP_WORKER_THREADS"),
+            "TIMESTAMP_WORKER_THREADS",
+            &mut server.worker_threads,
+        )?;
+        override_option(
+            env("TIMESTAMP_MAX_BLOCKING_THREADS"),
+            "TIMESTAMP_MAX_BLOCKING_THREADS",
+            &mut server.max_blocking_threads,
+        )?;
+        override_with(&env, "TIMESTAMP_KEEP_ALIVE", &mut server.keep_alive)?;
+        override_option(
+            env("TIMESTAMP_TCP_KEEPALIVE_SECS"),
+            "TIMESTAMP_TCP_KEEPALIVE_SECS",
+            &mut server.tcp_keepalive_secs,
+        )?;
+        override_option(
+            env("TIMESTAMP_IDLE_TIMEOUT_SECS"),
+            "TIMESTAMP_IDLE_TIMEOUT_SECS",
+            &mut server.idle_timeout_secs,
+        )?;
+        override_with(&env, "TIMESTAMP_H2C", &mut server.h2c)?;
+        override_option(
+            env("TIMESTAMP_HTTP2_KEEP_ALIVE_INTERVAL_SECS"),
+            "TIMESTAMP_HTTP2_KEEP_ALIVE_INTERVAL_SECS",
+            &mut server.http2_keep_alive_interval_secs,
+        )?;
+        override_with(
+            &env,
+            "TIMESTAMP_HTTP2_KEEP_ALIVE_TIMEOUT_SECS",
+            &mut server.http2_keep_alive_timeout_secs,
+        )?;
+        override_with(
+            &env,
+            "TIMESTAMP_REQUEST_TIMEOUT_SECS",
+            &mut server.request_timeout_secs,
+        )?;
+        override_with(&env, "TIMESTAMP_MAX_BODY_BYTES", &mut server.max_body_bytes)?;
+        override_with(
+            &env,
+            "TIMESTAMP_MAX_CONCURRENT_REQUESTS",
+            &mut server.max_concurrent_requests,
+        )?;
+        override_with(
+            &env,
+            "TIMESTAMP_DRAIN_GRACE_SECS",
+            &mut server.drain_grace_secs,
+        )?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Applies the `[server]` settings given on the command line.
+    pub fn apply_arguments(&mut self, args: &[String]) -> Result<(), String> {
+        let server = &mut self.server;
+        override_option(
+            argument(args, "--worker-threads"),
+            "--worker-threads",
+            &mut server.worker_threads,
+        )?;
+        override_option(
+            argument(args, "--max-blocking-threads"),
+            "--max-blocking-threads",
+            &mut server.max_blocking_threads,
+        )?;
+        if let Some(keep_alive) = argument(args, "--keep-alive") {
+            server.keep_alive = keep_alive
+                .parse()
+                .map_err(|_| format!("Invalid --keep-alive {}", keep_alive))?;
+        }
+        self.validate()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.timezone()?;
+        self.week_start()?;
+        if self.listen.is_empty() {
+            return Err("listen needs at least one address".to_string());
+        }
+        if self.server.worker_threads == Some(0) || self.server.max_blocking_threads == Some(0) {
+            return Err("Thread counts must be positive".to_string());
+        }
+        let server = &self.server;
+        if server.idle_timeout_secs == Some(0)
+            || server.http2_keep_alive_interval_secs == Some(0)
+            || server.http2_keep_alive_timeout_secs == 0
+        {
+            return Err("Connection timeouts must be positive".to_string());
+        }
+        if self.max_clock_skew_ms < 0 {
+            return Err(format!(
+                "Invalid max_clock_skew_ms {}",
+                self.max_clock_skew_ms
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn runtime(&self) -> Runtime {
+        Runtime {
+            log_level: self.log_level.clone(),
+            cors_origins: self.cors_origins.clone(),
+            rate_limit: self.rate_limit,
+        }
+    }
+
+    pub fn timezone(&self) -> Result<Tz, String> {
+        self.default_timezone
+            .parse()
+            .map_err(|_| format!("Unknown default_timezone {}", self.default_timezone))
+    }
+
+    pub fn week_start(&self) -> Result<Weekday, String> {
+        self.week_start
+            .parse()
+            .map_err(|_| format!("Invalid week_start {}", self.week_start))
+    }
+}
+
+/// Reloads the configuration file whenever the process receives `SIGHUP`, publishing the
+/// new runtime settings. Settings that need a restart, such as the listen address, are
+/// ignored, and an invalid file is logged and the previous settings kept.
+#[cfg(unix)]
+pub fn reload_on_sighup(path: String, settings: watch::Sender<Runtime>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                tracing::error!(
+                    "Can't listen for SIGHUP, configuration reload disabled: {}",
+                    e
+                );
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            match Config::load(Some(&path), |var| std::env::var(var).ok()) {
+                Ok(config) => {
+                    tracing::info!("Reloaded the configuration from {}", path);
+                    if settings.send(config.runtime()).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => tracing::error!("Can't reload the configuration: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn reload_on_sighup(_path: String, _settings: watch::Sender<Runtime>) {}
+
+/// Reads the file named by the environment variable `var`, if it is set.
+pub fn read_env_file(var: &str) -> Result<Option<String>, String> {
+    let path = match std::env::var(var) {
+        Ok(path) => path,
+        Err(_) => return Ok(None),
+    };
+    std::fs::read_to_string(&path)
+        .map(Some)
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Value of `--name value` or `--name=value` in the command line arguments.
+pub fn argument(args: &[String], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    args.iter().enumerate().find_map(|(index, arg)| {
+        if arg == name {
+            args.get(index + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(str::to_string)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn command_line() {
+        let args = args(&[
+            "server",
+            "--tls-cert",
+            "cert.pem",
+            "--uds=/run/timestamp.sock",
+        ]);
+        assert_eq!(argument(&args, "--tls-cert").as_deref(), Some("cert.pem"));
+        assert_eq!(
+            argument(&args, "--uds").as_deref(),
+            Some("/run/timestamp.sock")
+        );
+        assert_eq!(argument(&args, "--port"), None);
+    }
+
+    #[test]
+    fn environment_overrides_file() {
+        let toml = r#"
+            listen = "0.0.0.0:8080"
+            default_timezone = "Europe/Rome"
+            cors_origins = ["https://example.com"]
+
+            [rate_limit]
+            requests_per_minute = 600
+        "#;
+        let path = std::env::temp_dir().join("timestamp-config-test.toml");
+        std::fs::write(&path, toml).unwrap();
+        let env = |var: &str| match var {
+            "TIMESTAMP_LISTEN" => Some("127.0.0.1:9000, [::1]:9000".to_string()),
+            "TIMESTAMP_CORS_ORIGINS" => Some("https://a.example, https://b.example".to_string()),
+            "TIMESTAMP_TRUSTED_PROXIES" => Some("10.0.0.1, ::1".to_string()),
+            _ => None,
+        };
+
+        let config = Config::load(path.to_str(), env).unwrap();
+        assert_eq!(
+            config.listen,
+            vec![
+                Listener::Tcp(SocketAddr::from(([127, 0, 0, 1], 9000))),
+                Listener::Tcp("[::1]:9000".parse().unwrap()),
+            ]
+        );
+        assert_eq!(config.timezone(), Ok(chrono_tz::Europe::Rome));
+        assert_eq!(
+            config.cors_origins,
+            vec!["https://a.example", "https://b.example"]
+        );
+        assert_eq!(
+            config.trusted_proxies,
+            vec![IpAddr::from([10, 0, 0, 1]), "::1".parse().unwrap()]
+        );
+        assert_eq!(config.rate_limit.requests_per_minute, 600);
+        assert_eq!(config.parse_cache_size, 1024);
+
+        let mut config = config;
+        config
+            .apply_arguments(&args(&["server", "--worker-threads", "2"]))
+            .unwrap();
+        assert_eq!(config.server.worker_threads, Some(2));
+        assert!(config
+            .apply_arguments(&args(&["server", "--worker-threads=0"]))
+            .is_err());
+
+        let env = |var: &str| match var {
+            "TIMESTAMP_DEFAULT_TZ" => Some("Mars/Olympus".to_string()),
+            _ => None,
+        };
+        assert!(Config::load(None, env).is_err());
+
+        let env = |var: &str| match var {
+            "TIMESTAMP_H2C" => Some("true".to_string()),
+            "TIMESTAMP_HTTP2_KEEP_ALIVE_INTERVAL_SECS" => Some("30".to_string()),
+            _ => None,
+        };
+        let server = Config::load(None, env).unwrap().server;
+        assert!(server.h2c);
+        assert_eq!(server.http2_keep_alive_interval_secs, Some(30));
+        let env = |var: &str| match var {
+            "TIMESTAMP_IDLE_TIMEOUT_SECS" => Some("0".to_string()),
+            _ => None,
+        };
+        assert!(Config::load(None, env).is_err());
+    }
+
+    #[test]
+    fn listeners() {
+        let config: Config = toml::from_str(r#"listen = "[::]:3000""#).unwrap();
+        assert_eq!(
+            config.listen,
+            vec![Listener::Tcp("[::]:3000".parse().unwrap())]
+        );
+        let config: Config =
+            toml::from_str(r#"listen = ["0.0.0.0:3000", "unix:/run/timestamp.sock"]"#).unwrap();
+        assert_eq!(
+            config.listen,
+            vec![
+                Listener::Tcp(SocketAddr::from(([0, 0, 0, 0], 3000))),
+                Listener::Unix("/run/timestamp.sock".to_string()),
+            ]
+        );
+        assert_eq!(config.listen[1].to_string(), "unix:/run/timestamp.sock");
+        assert!(toml::from_str::<Config>(r#"listen = ["localhost:3000"]"#).is_err());
+        assert!(toml::from_str::<Config>(r#"listen = "unix:""#).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//! Cross-origin requests from the configured browser origins.
+//!
+//! Responses to allowed origins carry `Access-Control-Allow-Origin`, and preflight
+//! `OPTIONS` requests are answered directly. Requests from other origins are served
+//! without CORS headers, leaving the browser to block them. The allowed origins are read
+//! from the runtime settings on every request, so that reloads apply immediately.
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::{header, HeaderValue, Method, Request, Response, StatusCode};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{BoxError, Layer, Service};
+
+use crate::config::Settings;
+
+const ALLOWED_METHODS: &str = "GET, POST, PUT, DELETE";
+/// How long browsers may cache a preflight response, in seconds.
+const MAX_AGE: &str = "86400";
+
+#[derive(Clone, Debug)]
+pub struct CorsLayer {
+    settings: Settings,
+}
+
+impl CorsLayer {
+    pub fn new(settings: Settings) -> CorsLayer {
+        CorsLayer { settings }
+    }
+}
+
+impl<S> Layer<S> for CorsLayer {
+    type Service = Cors<S>;
+
+    fn layer(&self, inner: S) -> Cors<S> {
+        Cors {
+            inner,
+            settings: self.settings.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cors<S> {
+    inner: S,
+    settings: Settings,
+}
+
+/// The `Access-Control-Allow-Origin` value for `origin`, if it is allowed.
+fn allow_origin(origins: &[String], origin: &HeaderValue) -> Option<HeaderValue> {
+    if origins.iter().any(|allowed| allowed == "*") {
+        return Some(HeaderValue::from_static("*"));
+    }
+    let origin_str = origin.to_str().ok()?;
+    origins
+        .iter()
+        .any(|allowed| allowed == origin_str)
+        .then(|| origin.clone())
+}
+
+impl<S, B, ResBody> Service<Request<B>> for Cors<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let (allowed, vary) = {
+            let origins = &self.settings.borrow().cors_origins;
+            let allowed = request
+                .headers()
+                .get(header::ORIGIN)
+                .and_then(|origin| allow_origin(origins, origin));
+            // responses differ by origin unless CORS is off or any origin is allowed
+            let vary = !origins.is_empty() && !origins.iter().any(|origin| origin == "*");
+            (allowed, vary)
+        };
+
+        let preflight = request.method() == Method::OPTIONS
+            && request
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+        if let (true, Some(origin)) = (preflight, &allowed) {
+            let mut response = Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_ALLOW_METHODS, ALLOWED_METHODS)
+                .header(header::ACCESS_CONTROL_MAX_AGE, MAX_AGE)
+                .header(header::VARY, "Origin")
+                .body(Body::empty())
+                .unwrap();
+            if let Some(headers) = request
+                .headers()
+                .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            {
+                response
+                    .headers_mut()
+                    .insert(header::ACCESS_CONTROL_ALLOW_HEADERS, headers.clone());
+            }
+            return Box::pin(async move { Ok(response) });
+        }
+
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = response.await?.map(Body::new);
+            let headers = response.headers_mut();
+            if let Some(origin) = allowed {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                headers.insert(
+                    header::ACCESS_CONTROL_EXPOSE_HEADERS,
+                    HeaderValue::from_static(
+                        "x-request-id, api-version, deprecation, sunset, memento-datetime",
+                    ),
+                );
+            }
+            if vary {
+                headers.append(header::VARY, HeaderValue::from_static("Origin"));
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_origins() {
+        let origins = vec!["https://example.com".to_string()];
+        let origin = HeaderValue::from_static("https://example.com");
+        assert_eq!(allow_origin(&origins, &origin), Some(origin));
+        assert_eq!(
+            allow_origin(&origins, &HeaderValue::from_static("https://evil.example")),
+            None
+        );
+        assert_eq!(
+            allow_origin(
+                &["*".to_string()],
+                &HeaderValue::from_static("https://a.example")
+            ),
+            Some(HeaderValue::from_static("*"))
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use axum::Json;
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::query::Query;
+use crate::timezone::{parse_in_zone, parse_tz};
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// How far in the future we look for a match before giving up, enough to cover
+/// Feb 29 schedules that only fire on leap years.
+const MAX_DAYS: i64 = 8 * 366;
+
+/// A standard five-field cron expression: minute, hour, day of month, month, day of week.
+///
+/// Each field is stored as a bitset of the values it matches. As in Vixie cron, when both
+/// day of month and day of week are restricted a day matches if either of them does.
+#[derive(Debug, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    pub fn parse(expr: &str) -> Option<Schedule> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return None;
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7, &WEEKDAYS)?;
+        // both 0 and 7 are Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Some(Schedule {
+            minutes: parse_field(fields[0], 0, 59, &[])?,
+            hours: parse_field(fields[1], 0, 23, &[])?,
+            days: parse_field(fields[2], 1, 31, &[])?,
+            months: parse_field(fields[3], 1, 12, &MONTHS)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDateTime) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        day && self.months & (1 << date.month()) != 0
+    }
+
+    /// Returns the first wall-clock time strictly after `after` matching the schedule.
+    pub fn next_local(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = after
+            .date()
+            .and_hms_opt(after.hour(), after.minute(), 0)
+            .unwrap()
+            + Duration::minutes(1);
+        let mut day = start.date().and_hms_opt(0, 0, 0).unwrap();
+
+        for _ in 0..MAX_DAYS {
+            if self.matches_day(day) {
+                for hour in 0..24 {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+                    for minute in 0..60 {
+                        let candidate = day.date().and_hms_opt(hour, minute, 0).unwrap();
+                        if self.minutes & (1 << minute) != 0 && candidate >= start {
+                            return Some(candidate);
+                        }
+                    }
+                }
+            }
+            day += Duration::days(1);
+        }
+
+        None
+    }
+
+    /// Returns the last wall-clock time strictly before `before` matching the schedule.
+    pub fn prev_local(&self, before: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut day = before.date().and_hms_opt(0, 0, 0).unwrap();
+
+        for _ in 0..MAX_DAYS {
+            if self.matches_day(day) {
+                for hour in (0..24).rev() {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+                    for minute in (0..60).rev() {
+                        let candidate = day.date().and_hms_opt(hour, minute, 0).unwrap();
+                        if self.minutes & (1 << minute) != 0 && candidate < before {
+                            return Some(candidate);
+                        }
+                    }
+                }
+            }
+            day -= Duration::days(1);
+        }
+
+        None
+    }
+
+    /// Returns the first instant strictly after `after` at which the schedule fires in `tz`.
+    ///
+    /// Wall-clock times skipped by a DST transition never fire, while repeated ones only
+    /// fire on their first occurrence.
+    pub fn next(&self, after: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let mut local = after.with_timezone(&tz).naive_local();
+        loop {
+            local = self.next_local(local)?;
+            if let Some(instant) = tz.from_local_datetime(&local).earliest() {
+                let instant = instant.with_timezone(&Utc);
+                if instant > after {
+                    return Some(instant);
+                }
+            }
+        }
+    }
+
+    /// Returns the last instant strictly before `before` at which the schedule fired in
+    /// `tz`, following the same DST rules as [`Schedule::next`].
+    pub fn prev(&self, before: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let mut local = before.with_timezone(&tz).naive_local();
+        loop {
+            local = self.prev_local(local)?;
+            if let Some(instant) = tz.from_local_datetime(&local).earliest() {
+                let instant = instant.with_timezone(&Utc);
+                if instant < before {
+                    return Some(instant);
+                }
+            }
+        }
+    }
+}
+
+/// Parses a single cron field into a bitset of the matching values.
+///
+/// Supports `*`, single values, `a-b` ranges, `/step` suffixes and comma-separated lists.
+/// `names` are case-insensitive aliases for the values starting from `min`.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Option<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    parse_value(start, min, names)?,
+                    parse_value(end, min, names)?,
+                ),
+                // `5/15` means every 15 starting from 5
+                None if step > 1 => (parse_value(range, min, names)?, max),
+                None => {
+                    let value = parse_value(range, min, names)?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+fn parse_value(value: &str, min: u32, names: &[&str]) -> Option<u32> {
+    match names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+    {
+        Some(index) => Some(min + index as u32),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_schedule(expr: &str) -> Result<Schedule, AppError> {
+    Schedule::parse(expr).ok_or_else(|| AppError::BadRequest("Invalid cron expression".to_string()))
+}
+
+fn describe(instant: DateTime<Utc>) -> Value {
+    json!({
+        "unix": instant.timestamp(),
+        "utc": instant.to_rfc2822(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CronParams {
+    expr: String,
+    count: Option<usize>,
+    tz: Option<String>,
+}
+
+pub async fn next_handler(Query(params): Query<CronParams>) -> Result<Json<Value>, AppError> {
+    let schedule = parse_schedule(&params.expr)?;
+    let tz = parse_tz(params.tz.as_deref())?;
+    let count = params.count.unwrap_or(5).min(100);
+
+    let mut after = Utc::now();
+    let mut next = Vec::with_capacity(count);
+    while next.len() < count {
+        match schedule.next(after, tz) {
+            Some(instant) => {
+                next.push(describe(instant));
+                after = instant;
+            }
+            None => break,
+        }
+    }
+
+    Ok(Json(json!({
+        "expr": params.expr,
+        "tz": tz.name(),
+        "next": next,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrevParams {
+    expr: String,
+    count: Option<usize>,
+    /// Instant to look back from, now by default.
+    before: Option<String>,
+    tz: Option<String>,
+}
+
+/// The last times a schedule fired, most recent first.
+pub async fn prev_handler(Query(params): Query<PrevParams>) -> Result<Json<Value>, AppError> {
+    let schedule = parse_schedule(&params.expr)?;
+    let tz = parse_tz(params.tz.as_deref())?;
+    let count = params.count.unwrap_or(5).min(100);
+    let mut before = match params.before.as_deref() {
+        Some(before) => parse_in_zone(before, tz)?,
+        None => Utc::now(),
+    };
+
+    let mut prev = Vec::with_capacity(count);
+    while prev.len() < count {
+        match schedule.prev(before, tz) {
+            Some(instant) => {
+                prev.push(describe(instant));
+                before = instant;
+            }
+            None => break,
+        }
+    }
+
+    Ok(Json(json!({
+        "expr": params.expr,
+        "tz": tz.name(),
+        "prev": prev,
+    })))
+}
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+/// The times a schedule fires in `[from, to]` after `after`, at most `limit` of them, and
+/// whether more follow.
+fn between(
+    schedule: &Schedule,
+    tz: Tz,
+    after: DateTime<Utc>,
+    to: DateTime<Utc>,
+    limit: usize,
+) -> (Vec<DateTime<Utc>>, bool) {
+    let mut instants = Vec::with_capacity(limit);
+    let mut after = after;
+    while let Some(instant) = schedule.next(after, tz).filter(|instant| *instant <= to) {
+        if instants.len() == limit {
+            return (instants, true);
+        }
+        instants.push(instant);
+        after = instant;
+    }
+    (instants, false)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BetweenParams {
+    expr: String,
+    from: String,
+    to: String,
+    tz: Option<String>,
+    /// Unix timestamp the page starts after, as returned in `next`.
+    cursor: Option<i64>,
+    limit: Option<usize>,
+}
+
+/// Every time a schedule fires in a window, bounds included, one page at a time.
+pub async fn between_handler(Query(params): Query<BetweenParams>) -> Result<Json<Value>, AppError> {
+    let schedule = parse_schedule(&params.expr)?;
+    let tz = parse_tz(params.tz.as_deref())?;
+    let from = parse_in_zone(&params.from, tz)?;
+    let to = parse_in_zone(&params.to, tz)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    // the schedule fires strictly after the starting instant, so `from` itself is included
+    let after = match params.cursor {
+        Some(cursor) => Utc
+            .timestamp_opt(cursor, 0)
+            .single()
+            .ok_or(AppError::TimestampOutOfRange)?,
+        None => from - Duration::nanoseconds(1),
+    };
+
+    let (instants, more) = between(&schedule, tz, after, to, limit);
+    let next = match instants.last() {
+        Some(last) if more => Some(last.timestamp()),
+        _ => None,
+    };
+
+    Ok(Json(json!({
+        "expr": params.expr,
+        "tz": tz.name(),
+        "occurrences": instants.into_iter().map(describe).collect::<Vec<_>>(),
+        "next": next,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(
+            parse_field("*/15", 0, 59, &[]),
+            Some(1 | 1 << 15 | 1 << 30 | 1 << 45)
+        );
+        assert_eq!(parse_field("1-3,5", 0, 59, &[]), Some(0b101110));
+        assert_eq!(parse_field("mon-wed", 0, 7, &WEEKDAYS), Some(0b1110));
+        assert_eq!(parse_field("60", 0, 59, &[]), None);
+        assert_eq!(parse_field("*/0", 0, 59, &[]), None);
+    }
+
+    #[test]
+    fn next_weekly() {
+        let schedule = Schedule::parse("0 0 * * MON").unwrap();
+        // Wednesday
+        let after = at(2021, 8, 18, 10, 30);
+        assert_eq!(schedule.next_local(after), Some(at(2021, 8, 23, 0, 0)));
+    }
+
+    #[test]
+    fn day_of_month_or_weekday() {
+        let schedule = Schedule::parse("30 9 1 * 5").unwrap();
+        let after = at(2021, 8, 18, 10, 30);
+        assert_eq!(schedule.next_local(after), Some(at(2021, 8, 20, 9, 30)));
+        assert_eq!(
+            schedule.next_local(at(2021, 8, 28, 0, 0)),
+            Some(at(2021, 9, 1, 9, 30))
+        );
+    }
+
+    #[test]
+    fn skips_dst_gap() {
+        let schedule = Schedule::parse("30 2 * * *").unwrap();
+        let tz: Tz = "Europe/Rome".parse().unwrap();
+        // 2:30 doesn't exist in Rome on March 28th 2021
+        let after = Utc.with_ymd_and_hms(2021, 3, 27, 12, 0, 0).unwrap();
+        let next = schedule.next(after, tz).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2021, 3, 29, 0, 30, 0).unwrap());
+    }
+
+    #[test]
+    fn previous_runs() {
+        let schedule = Schedule::parse("0 0 * * MON").unwrap();
+        assert_eq!(
+            schedule.prev_local(at(2021, 8, 18, 10, 30)),
+            Some(at(2021, 8, 16, 0, 0))
+        );
+        assert_eq!(
+            schedule.prev_local(at(2021, 8, 16, 0, 0)),
+            Some(at(2021, 8, 9, 0, 0))
+        );
+
+        let schedule = Schedule::parse("30 2 * * *").unwrap();
+        let tz: Tz = "Europe/Rome".parse().unwrap();
+        let before = Utc.with_ymd_and_hms(2021, 3, 28, 12, 0, 0).unwrap();
+        assert_eq!(
+            schedule.prev(before, tz),
+            Some(Utc.with_ymd_and_hms(2021, 3, 27, 1, 30, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn window_pages() {
+        let schedule = Schedule::parse("0 */6 * * *").unwrap();
+        let from = Utc.with_ymd_and_hms(2021, 8, 18, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2021, 8, 19, 0, 0, 0).unwrap();
+
+        let (instants, more) = between(&schedule, Tz::UTC, from - Duration::seconds(1), to, 3);
+        assert_eq!(instants.len(), 3);
+        assert_eq!(instants[0], from);
+        assert!(more);
+
+        let (instants, more) = between(&schedule, Tz::UTC, instants[2], to, 3);
+        assert_eq!(
+            instants,
+            vec![Utc.with_ymd_and_hms(2021, 8, 18, 18, 0, 0).unwrap(), to]
+        );
+        assert!(!more);
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        assert!(Schedule::parse("* * * *").is_none());
+        assert!(Schedule::parse("* 24 * * *").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+//! Normalization of a timestamp column in uploaded CSV files.
+//!
+//! The file is sent as the `file` part of a multipart form, and the column named by
+//! `?column=`, or at that zero-based position, is converted to the requested format and
+//! timezone. Every other cell is copied as is, and so are cells of the column that aren't
+//! dates, empty ones included, so that a few bad rows don't fail a whole file. The upload
+//! is bounded by the body size limit, the converted file is streamed back as it's written.
+
+use axum::body::{Body, Bytes};
+use axum::extract::Multipart;
+use axum::http::{header, HeaderValue, Response};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::error::AppError;
+use crate::query::Query;
+use crate::timezone::{offset_at, parse_in_zone, parse_tz};
+
+/// Rows written between two chunks of the response.
+const ROWS_PER_CHUNK: usize = 1000;
+
+/// Chunks converted ahead of the client reading them.
+const PENDING_CHUNKS: usize = 4;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Unix,
+    UnixMs,
+    Iso8601,
+    Rfc2822,
+}
+
+impl Format {
+    fn render(self, instant: DateTime<Utc>, tz: Tz) -> String {
+        let local = instant.with_timezone(&FixedOffset::east_opt(offset_at(tz, instant)).unwrap());
+        match self {
+            Format::Unix => instant.timestamp().to_string(),
+            Format::UnixMs => instant.timestamp_millis().to_string(),
+            Format::Iso8601 => local.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            Format::Rfc2822 => local.to_rfc2822(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvParams {
+    /// Header of the timestamp column, or its zero-based position.
+    column: String,
+    /// ISO 8601 by default.
+    format: Option<Format>,
+    /// Zone date-times without an offset are read in, the default timezone if missing.
+    from: Option<String>,
+    /// Zone the dates are written in, the default timezone if missing.
+    to: Option<String>,
+    /// Whether the first row holds the column names, true by default.
+    headers: Option<bool>,
+}
+
+/// How a column is converted.
+struct Conversion {
+    format: Format,
+    from: Tz,
+    to: Tz,
+}
+
+impl Conversion {
+    fn convert(&self, cell: &str) -> Option<String> {
+        if cell.trim().is_empty() {
+            return None;
+        }
+        let instant = parse_in_zone(cell.trim(), self.from).ok()?;
+        Some(self.format.render(instant, self.to))
+    }
+}
+
+/// Finds the column by header, falling back to a position.
+fn column_index(headers: Option<&csv::StringRecord>, column: &str) -> Option<usize> {
+    headers
+        .and_then(|headers| headers.iter().position(|name| name == column))
+        .or_else(|| column.parse().ok())
+}
+
+fn csv_writer() -> csv::Writer<Vec<u8>> {
+    csv::WriterBuilder::new()
+        .flexible(true)
+        .from_writer(Vec::new())
+}
+
+/// Converts `data` into chunks of CSV, handing each to `send`, which returns false to stop.
+fn convert_csv(
+    data: &[u8],
+    column: usize,
+    has_headers: bool,
+    conversion: &Conversion,
+    mut send: impl FnMut(Vec<u8>) -> bool,
+) -> Result<(), csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+    let mut writer = csv_writer();
+    for (row, record) in reader.records().enumerate() {
+        let record = record?;
+        if row == 0 && has_headers {
+            writer.write_record(&record)?;
+            continue;
+        }
+        let converted = record.get(column).and_then(|cell| conversion.convert(cell));
+        writer.write_record(
+            record
+                .iter()
+                .enumerate()
+                .map(|(index, cell)| match &converted {
+                    Some(converted) if index == column => converted.as_str(),
+                    _ => cell,
+                }),
+        )?;
+        if (row + 1) % ROWS_PER_CHUNK == 0 {
+            let full = std::mem::replace(&mut writer, csv_writer());
+            if !send(full.into_inner().map_err(|e| e.into_error())?) {
+                return Ok(());
+            }
+        }
+    }
+    send(writer.into_inner().map_err(|e| e.into_error())?);
+    Ok(())
+}
+
+pub async fn csv_handler(
+    Query(params): Query<CsvParams>,
+    mut multipart: Multipart,
+) -> Result<Response<Body>, AppError> {
+    let conversion = Conversion {
+        format: params.format.unwrap_or(Format::Iso8601),
+        from: parse_tz(params.from.as_deref())?,
+        to: parse_tz(params.to.as_deref())?,
+    };
+    let has_headers = params.headers.unwrap_or(true);
+
+    let invalid = |_| AppError::BadRequest("Invalid multipart body".to_string());
+    let mut data = None;
+    while let Some(field) = multipart.next_field().await.map_err(invalid)? {
+        if field.name() == Some("file") {
+            data = Some(field.bytes().await.map_err(invalid)?);
+            break;
+        }
+    }
+    let data: Bytes =
+        data.ok_or_else(|| AppError::BadRequest("A file part is required".to_string()))?;
+
+    let names = if has_headers {
+        let mut reader = csv::ReaderBuilder::new().from_reader(&data[..]);
+        Some(
+            reader
+                .headers()
+                .map_err(|e| AppError::BadRequest(format!("Invalid CSV: {}", e)))?
+                .clone(),
+        )
+    } else {
+        None
+    };
+    let column = column_index(names.as_ref(), &params.column)
+        .ok_or_else(|| AppError::BadRequest(format!("Unknown column {}", params.column)))?;
+
+    let (chunks, pending) = mpsc::channel(PENDING_CHUNKS);
+    tokio::task::spawn_blocking(move || {
+        let result = convert_csv(&data, column, has_headers, &conversion, |chunk| {
+            chunks.blocking_send(Ok(Bytes::from(chunk))).is_ok()
+        });
+        if let Err(e) = result {
+            tracing::warn!("Stopped converting an uploaded CSV: {}", e);
+            let _ = chunks.blocking_send(Err(e));
+        }
+    });
+
+    let mut response = Response::new(Body::from_stream(ReceiverStream::new(pending)));
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/csv; charset=utf-8"),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_static("attachment; filename=\"converted.csv\""),
+    );
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(data: &str, column: usize, has_headers: bool) -> String {
+        let conversion = Conversion {
+            format: Format::Iso8601,
+            from: Tz::UTC,
+            to: Tz::Europe__Rome,
+        };
+        let mut output = Vec::new();
+        convert_csv(data.as_bytes(), column, has_headers, &conversion, |chunk| {
+            output.extend(chunk);
+            true
+        })
+        .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn converts_the_column() {
+        let data = "id,created_at,note\n1,1482624000,\"a, b\"\n2,not a date,c\n3,,d\n";
+        assert_eq!(
+            convert(data, 1, true),
+            "id,created_at,note\n1,2016-12-25T01:00:00+01:00,\"a, b\"\n2,not a date,c\n3,,d\n"
+        );
+        assert_eq!(
+            convert("2016-12-25T00:00:00\n", 0, false),
+            "2016-12-25T01:00:00+01:00\n"
+        );
+    }
+
+    #[test]
+    fn finds_columns() {
+        let headers = csv::StringRecord::from(vec!["id", "created_at"]);
+        assert_eq!(column_index(Some(&headers), "created_at"), Some(1));
+        assert_eq!(column_index(Some(&headers), "0"), Some(0));
+        assert_eq!(column_index(None, "updated_at"), None);
+    }
+}
@@ -0,0 +1,129 @@
+//! Step-by-step trace of how `/api/:date` parses its input.
+//!
+//! Only served when `debug_endpoints` is enabled in the configuration, as traces reveal
+//! implementation details that are of no use to regular clients.
+
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::natural;
+use crate::parse;
+use crate::parse_base;
+use crate::profile::Profile;
+use crate::query::Query;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DebugSettings {
+    pub enabled: bool,
+}
+
+fn step(parser: &str, outcome: Result<Value, String>) -> Value {
+    match outcome {
+        Ok(value) => json!({ "parser": parser, "matched": true, "result": value }),
+        Err(reason) => json!({ "parser": parser, "matched": false, "reason": reason }),
+    }
+}
+
+fn describe(date: DateTime<Utc>) -> Value {
+    json!({ "unix": date.timestamp(), "utc": date.to_rfc2822() })
+}
+
+/// Tries natural-language parsing, then the parser the shape of the input calls for,
+/// recording why each one failed.
+pub fn trace(input: &str, base: DateTime<Utc>) -> (Vec<Value>, Option<DateTime<Utc>>) {
+    let mut steps = Vec::new();
+
+    match natural::parse(input, base) {
+        Some(date) => {
+            steps.push(step("natural", Ok(describe(date))));
+            return (steps, Some(date));
+        }
+        None => steps.push(step(
+            "natural",
+            Err("not a natural-language expression".to_string()),
+        )),
+    }
+
+    // the input's shape picks the only parser that can match it
+    let kind = parse::classify(input);
+    match parse::parse_input(input) {
+        Ok(parsed) => {
+            steps.push(step(kind.name(), Ok(describe(parsed.instant))));
+            (steps, Some(parsed.instant))
+        }
+        Err(error) => {
+            steps.push(step(kind.name(), Err(error.reason)));
+            (steps, None)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DebugParams {
+    base: Option<String>,
+}
+
+pub async fn parse_trace_handler(
+    State(settings): State<DebugSettings>,
+    Path(input): Path<String>,
+    Query(params): Query<DebugParams>,
+) -> Result<Json<Value>, AppError> {
+    if !settings.enabled {
+        return Err(AppError::NotFound(
+            "Debug endpoints are disabled".to_string(),
+        ));
+    }
+    let base = parse_base(params.base.as_deref())?;
+    let (steps, date) = trace(&input, base);
+    // strict profiles aren't part of the default chain, but explain many surprises
+    let profiles: Vec<&str> = Profile::ALL
+        .iter()
+        .filter(|profile| profile.parse(&input).is_some())
+        .map(|profile| profile.name())
+        .collect();
+
+    Ok(Json(json!({
+        "input": input,
+        "base": describe(base),
+        "steps": steps,
+        "result": date.map(describe),
+        "profiles": profiles,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn timestamp_trace() {
+        let base = Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap();
+        let (steps, date) = trace("1451001600", base);
+        assert_eq!(
+            date,
+            Some(Utc.with_ymd_and_hms(2015, 12, 25, 0, 0, 0).unwrap())
+        );
+        let parsers: Vec<&str> = steps
+            .iter()
+            .map(|step| step["parser"].as_str().unwrap())
+            .collect();
+        assert_eq!(parsers, vec!["natural", "unix"]);
+        assert_eq!(steps[1]["result"]["unix"], 1451001600);
+    }
+
+    #[test]
+    fn failing_trace() {
+        let base = Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap();
+        let (steps, date) = trace("2016-13-01", base);
+        assert_eq!(date, None);
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1]["parser"], "date");
+        assert_eq!(steps[1]["matched"], false);
+    }
+}
@@ -0,0 +1,94 @@
+//! Controlled shutdown, for orchestrators taking an instance out of rotation over HTTP.
+//!
+//! `POST /admin/drain` fails the readiness probe at once, so that load balancers stop
+//! sending requests, then closes the listener after the grace period. Requests in flight
+//! are answered before the process exits.
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Whether the instance is draining, shared by the readiness probe, `/admin/drain` and the
+/// server awaiting its shutdown.
+#[derive(Clone, Debug)]
+pub struct Drain {
+    draining: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+    grace: Duration,
+}
+
+impl Drain {
+    pub fn new(grace: Duration) -> Drain {
+        let (draining, receiver) = watch::channel(false);
+        Drain {
+            draining: Arc::new(draining),
+            receiver,
+            grace,
+        }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    pub fn grace(&self) -> Duration {
+        self.grace
+    }
+
+    /// Starts draining, returning whether it wasn't already.
+    pub fn start(&self) -> bool {
+        if self.is_draining() {
+            return false;
+        }
+        // the receiver held by `self` keeps the channel open
+        let _ = self.draining.send(true);
+        true
+    }
+
+    /// Resolves once the grace period of a drain is over, for the server to shut down
+    /// gracefully.
+    pub async fn shutdown(mut self) {
+        while !*self.receiver.borrow() {
+            if self.receiver.changed().await.is_err() {
+                return;
+            }
+        }
+        tokio::time::sleep(self.grace).await;
+        tracing::info!("Drained, closing the listener");
+    }
+}
+
+/// Readiness probe, failing once the instance is draining.
+pub async fn ready_handler(State(drain): State<Drain>) -> (StatusCode, Json<Value>) {
+    if drain.is_draining() {
+        let body = json!({ "ready": false, "reason": "draining" });
+        (StatusCode::SERVICE_UNAVAILABLE, Json(body))
+    } else {
+        (StatusCode::OK, Json(json!({ "ready": true })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn shuts_down_after_the_grace_period() {
+        let drain = Drain::new(Duration::from_millis(10));
+        let mut shutdown = tokio::spawn(drain.clone().shutdown());
+        let waiting = tokio::time::timeout(Duration::from_millis(20), &mut shutdown).await;
+        assert!(waiting.is_err());
+
+        assert!(drain.start());
+        assert!(!drain.start());
+        assert!(drain.is_draining());
+        tokio::time::timeout(Duration::from_secs(1), shutdown)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
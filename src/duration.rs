@@ -0,0 +1,467 @@
+//! Arithmetic on lists of ISO 8601 durations mixing calendar and exact units.
+//!
+//! Years, months, weeks and days are calendar units: they move the wall-clock date in the
+//! requested timezone and keep the time of day, clamping to the end of shorter months.
+//! Hours, minutes and seconds are exact and move the instant itself. Since the two kinds
+//! don't commute, `P1M` then `PT24H` can differ from `PT24H` then `P1M`, and the order in
+//! which parts are applied is chosen explicitly by the caller.
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::calendar::{add_months, checked_days, checked_seconds};
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::timezone::{parse_in_zone, parse_tz, resolve_local};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IsoDuration {
+    /// Calendar months, years included.
+    months: i32,
+    /// Calendar days, weeks included.
+    days: i64,
+    /// Exact seconds, hours and minutes included.
+    seconds: i64,
+}
+
+impl IsoDuration {
+    /// Parses `PnYnMnWnDTnHnMnS`, with an optional leading sign. Fractions aren't supported.
+    pub fn parse(input: &str) -> Option<IsoDuration> {
+        let (sign, rest) = match input.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, input.strip_prefix('+').unwrap_or(input)),
+        };
+        let rest = rest.strip_prefix('P').or_else(|| rest.strip_prefix('p'))?;
+        let (date, time) = match rest.find(['T', 't']) {
+            Some(index) => (&rest[..index], Some(&rest[index + 1..])),
+            None => (rest, None),
+        };
+
+        let mut duration = IsoDuration::default();
+        let mut empty = true;
+        for (value, designator) in components(date)? {
+            empty = false;
+            match designator {
+                'Y' => {
+                    let months = i32::try_from(value.checked_mul(12)?).ok()?;
+                    duration.months = duration.months.checked_add(months)?;
+                }
+                'M' => duration.months = duration.months.checked_add(i32::try_from(value).ok()?)?,
+                'W' => duration.days = duration.days.checked_add(value.checked_mul(7)?)?,
+                'D' => duration.days = duration.days.checked_add(value)?,
+                _ => return None,
+            }
+        }
+        if let Some(time) = time {
+            let parts = components(time)?;
+            if parts.is_empty() {
+                return None;
+            }
+            for (value, designator) in parts {
+                empty = false;
+                let seconds = match designator {
+                    'H' => value.checked_mul(3600)?,
+                    'M' => value.checked_mul(60)?,
+                    'S' => value,
+                    _ => return None,
+                };
+                duration.seconds = duration.seconds.checked_add(seconds)?;
+            }
+        }
+        if empty {
+            return None;
+        }
+
+        duration.months *= sign as i32;
+        duration.days *= sign;
+        duration.seconds *= sign;
+        Some(duration)
+    }
+
+    /// Parses an ISO 8601 duration, or a shorthand such as `15m`, `1w` or `3mo`.
+    pub fn parse_shorthand(input: &str) -> Option<IsoDuration> {
+        if input.starts_with(['P', 'p', '-', '+']) {
+            return IsoDuration::parse(input);
+        }
+        let split = input.find(|c: char| !c.is_ascii_digit())?;
+        let (count, unit) = input.split_at(split);
+        let iso = match unit {
+            "s" => format!("PT{}S", count),
+            "m" => format!("PT{}M", count),
+            "h" => format!("PT{}H", count),
+            "d" => format!("P{}D", count),
+            "w" => format!("P{}W", count),
+            "mo" => format!("P{}M", count),
+            "y" => format!("P{}Y", count),
+            _ => return None,
+        };
+        IsoDuration::parse(&iso)
+    }
+
+    /// An exact duration of `seconds`.
+    pub fn from_seconds(seconds: i64) -> IsoDuration {
+        IsoDuration {
+            seconds,
+            ..IsoDuration::default()
+        }
+    }
+
+    pub fn calendar(self) -> IsoDuration {
+        IsoDuration { seconds: 0, ..self }
+    }
+
+    pub fn exact(self) -> IsoDuration {
+        IsoDuration {
+            seconds: self.seconds,
+            ..IsoDuration::default()
+        }
+    }
+
+    /// Length in wall-clock seconds, days counting as 24 hours, unless there are months.
+    pub fn wall_seconds(self) -> Option<i64> {
+        if self.months != 0 {
+            return None;
+        }
+        self.days.checked_mul(86_400)?.checked_add(self.seconds)
+    }
+
+    /// The duration repeated `count` times, parts being scaled independently.
+    pub fn times(self, count: i64) -> Option<IsoDuration> {
+        Some(IsoDuration {
+            months: i32::try_from(count.checked_mul(self.months as i64)?).ok()?,
+            days: self.days.checked_mul(count)?,
+            seconds: self.seconds.checked_mul(count)?,
+        })
+    }
+
+    pub fn is_zero(self) -> bool {
+        self == IsoDuration::default()
+    }
+
+    /// Adds the duration to `instant`, calendar parts first as mandated by ISO 8601.
+    /// Returns `None` when the result overflows or lands in a wall-clock time skipped by DST.
+    pub fn add_to(self, instant: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let mut instant = instant;
+        if self.months != 0 || self.days != 0 {
+            let local = instant.with_timezone(&tz).naive_local();
+            let date = add_months(local.date(), self.months)?
+                .checked_add_signed(checked_days(self.days)?)?;
+            instant = resolve_local(date.and_time(local.time()), None, tz)?;
+        }
+        instant.checked_add_signed(checked_seconds(self.seconds)?)
+    }
+}
+
+/// Renders the duration the way `parse` reads it, seconds being split into hours and
+/// minutes but never into days, as `java.time.Duration` does: 26 hours are `PT26H`.
+/// Negative durations get a leading sign, and parts of mixed signs are signed individually.
+impl fmt::Display for IsoDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return f.write_str("PT0S");
+        }
+        let mut duration = *self;
+        if duration.months <= 0 && duration.days <= 0 && duration.seconds <= 0 {
+            f.write_str("-")?;
+            duration = IsoDuration {
+                months: -duration.months,
+                days: -duration.days,
+                seconds: -duration.seconds,
+            };
+        }
+        f.write_str("P")?;
+        let (years, months) = (duration.months / 12, duration.months % 12);
+        let date = [
+            (years as i64, 'Y'),
+            (months as i64, 'M'),
+            (duration.days, 'D'),
+        ];
+        for (value, designator) in date.iter() {
+            if *value != 0 {
+                write!(f, "{}{}", value, designator)?;
+            }
+        }
+        if duration.seconds != 0 {
+            f.write_str("T")?;
+            let seconds = duration.seconds;
+            let parts = [
+                (seconds / 3600, 'H'),
+                (seconds % 3600 / 60, 'M'),
+                (seconds % 60, 'S'),
+            ];
+            for (value, designator) in parts.iter() {
+                if *value != 0 {
+                    write!(f, "{}{}", value, designator)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits `3Y2M` into `[(3, 'Y'), (2, 'M')]`.
+fn components(input: &str) -> Option<Vec<(i64, char)>> {
+    let mut parts = Vec::new();
+    let mut number = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+        } else {
+            if number.is_empty() {
+                return None;
+            }
+            parts.push((number.parse().ok()?, c.to_ascii_uppercase()));
+            number.clear();
+        }
+    }
+    if number.is_empty() {
+        Some(parts)
+    } else {
+        None
+    }
+}
+
+/// How the parts of the listed durations are interleaved.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    /// Each duration is applied in turn, its calendar part before its exact part.
+    Sequential,
+    /// The calendar parts of all durations are applied before any exact part.
+    CalendarFirst,
+    /// The exact parts of all durations are applied before any calendar part.
+    ExactFirst,
+}
+
+impl Order {
+    const ALL: [Order; 3] = [Order::Sequential, Order::CalendarFirst, Order::ExactFirst];
+
+    fn name(self) -> &'static str {
+        match self {
+            Order::Sequential => "sequential",
+            Order::CalendarFirst => "calendar_first",
+            Order::ExactFirst => "exact_first",
+        }
+    }
+
+    /// The durations split and reordered as they should be applied, with the index of the
+    /// duration each part comes from.
+    fn plan(self, durations: &[IsoDuration]) -> Vec<(usize, IsoDuration)> {
+        let calendar = durations.iter().map(|d| d.calendar()).enumerate();
+        let exact = durations.iter().map(|d| d.exact()).enumerate();
+        let plan: Vec<(usize, IsoDuration)> = match self {
+            Order::Sequential => durations.iter().copied().enumerate().collect(),
+            Order::CalendarFirst => calendar.chain(exact).collect(),
+            Order::ExactFirst => exact.chain(calendar).collect(),
+        };
+        plan.into_iter()
+            .filter(|(_, part)| !part.is_zero())
+            .collect()
+    }
+}
+
+/// Applies the durations to `base` in the given order, returning every intermediate instant.
+pub fn combine(
+    base: DateTime<Utc>,
+    durations: &[IsoDuration],
+    order: Order,
+    tz: Tz,
+) -> Option<Vec<(usize, DateTime<Utc>)>> {
+    let mut instant = base;
+    order
+        .plan(durations)
+        .into_iter()
+        .map(|(index, part)| {
+            instant = part.add_to(instant, tz)?;
+            Some((index, instant))
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CombineRequest {
+    /// Starting instant, now by default.
+    base: Option<String>,
+    /// ISO 8601 durations such as `P1M` or `-PT24H`.
+    durations: Vec<String>,
+    order: Option<Order>,
+    /// Timezone in which calendar units are applied, UTC by default.
+    tz: Option<String>,
+}
+
+fn describe(instant: DateTime<Utc>) -> Value {
+    json!({
+        "unix": instant.timestamp(),
+        "utc": instant.to_rfc2822(),
+    })
+}
+
+pub async fn combine_handler(Json(request): Json<CombineRequest>) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(request.tz.as_deref())?;
+    let base = match request.base.as_deref() {
+        Some(base) => parse_in_zone(base, tz)?,
+        None => Utc::now(),
+    };
+    let durations = request
+        .durations
+        .iter()
+        .map(|duration| {
+            IsoDuration::parse(duration)
+                .ok_or_else(|| AppError::BadRequest(format!("Invalid duration {}", duration)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let order = request.order.unwrap_or(Order::Sequential);
+
+    let unreachable = || {
+        AppError::Unprocessable(
+            "The result overflows or falls in a time skipped by a DST transition".to_string(),
+        )
+    };
+    let steps = combine(base, &durations, order, tz).ok_or_else(unreachable)?;
+    let result = steps.last().map_or(base, |(_, instant)| *instant);
+
+    // the results of the other orders, so that callers can tell when the order matters
+    let mut alternatives = serde_json::Map::new();
+    for other in Order::ALL.iter().filter(|other| **other != order) {
+        let instant = combine(base, &durations, *other, tz)
+            .map(|steps| steps.last().map_or(base, |(_, instant)| *instant));
+        alternatives.insert(
+            other.name().to_string(),
+            instant.map_or(Value::Null, describe),
+        );
+    }
+
+    let steps: Vec<Value> = steps
+        .into_iter()
+        .map(|(index, instant)| {
+            let mut step = describe(instant);
+            step["duration"] = json!(request.durations[index]);
+            step
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "base": describe(base),
+        "tz": tz.name(),
+        "order": order.name(),
+        "steps": steps,
+        "result": describe(result),
+        "alternatives": alternatives,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn shorthands() {
+        let parse = IsoDuration::parse_shorthand;
+        assert_eq!(parse("1w"), IsoDuration::parse("P7D"));
+        assert_eq!(parse("90s"), IsoDuration::parse("PT90S"));
+        assert_eq!(parse("PT1H"), IsoDuration::parse("PT1H"));
+        assert_eq!(parse("1fortnight"), None);
+        assert_eq!(parse("w"), None);
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(
+            IsoDuration::parse("P1Y2M3W4DT5H6M7S"),
+            Some(IsoDuration {
+                months: 14,
+                days: 25,
+                seconds: 5 * 3600 + 6 * 60 + 7
+            })
+        );
+        assert_eq!(
+            IsoDuration::parse("-PT24H"),
+            Some(IsoDuration {
+                seconds: -86400,
+                ..IsoDuration::default()
+            })
+        );
+        assert!(IsoDuration::parse("P").is_none());
+        assert!(IsoDuration::parse("P1DT").is_none());
+        assert!(IsoDuration::parse("P1H").is_none());
+        assert!(IsoDuration::parse("1D").is_none());
+        // 2^32 + 1 months would wrap around to one
+        assert!(IsoDuration::parse("P4294967297M").is_none());
+        assert!(IsoDuration::parse("P9223372036854775807DT9223372036854775807S").is_some());
+        assert!(IsoDuration::parse("P9223372036854775807D1W").is_none());
+        assert!(IsoDuration::parse("PT9223372036854775807S1S").is_none());
+    }
+
+    #[test]
+    fn formatting() {
+        let format = |input| IsoDuration::parse(input).unwrap().to_string();
+        assert_eq!(format("P1Y2M3DT4H"), "P1Y2M3DT4H");
+        assert_eq!(format("P14M"), "P1Y2M");
+        assert_eq!(format("-PT90M"), "-PT1H30M");
+        assert_eq!(format("PT0S"), "PT0S");
+        assert_eq!(IsoDuration::from_seconds(93784).to_string(), "PT26H3M4S");
+        let mixed = IsoDuration {
+            days: 1,
+            seconds: -3600,
+            ..IsoDuration::default()
+        };
+        assert_eq!(mixed.to_string(), "P1DT-1H");
+    }
+
+    #[test]
+    fn order_matters_across_month_ends() {
+        let base = Utc.with_ymd_and_hms(2021, 1, 30, 12, 0, 0).unwrap();
+        let durations = [
+            IsoDuration::parse("P1M").unwrap(),
+            IsoDuration::parse("PT24H").unwrap(),
+        ];
+        let last = |order| {
+            combine(base, &durations, order, Tz::UTC)
+                .unwrap()
+                .last()
+                .unwrap()
+                .1
+        };
+        // Jan 30 + 1 month clamps to Feb 28, then a day later
+        assert_eq!(
+            last(Order::Sequential),
+            Utc.with_ymd_and_hms(2021, 3, 1, 12, 0, 0).unwrap()
+        );
+        // Jan 31 + 1 month clamps to Feb 28
+        assert_eq!(
+            last(Order::ExactFirst),
+            Utc.with_ymd_and_hms(2021, 2, 28, 12, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn calendar_days_keep_wall_clock_across_dst() {
+        let tz: Tz = "Europe/Rome".parse().unwrap();
+        // 2021-03-27 12:00 CET
+        let base = Utc.with_ymd_and_hms(2021, 3, 27, 11, 0, 0).unwrap();
+        let day = IsoDuration::parse("P1D").unwrap();
+        let hours = IsoDuration::parse("PT24H").unwrap();
+        assert_eq!(
+            day.add_to(base, tz),
+            Some(Utc.with_ymd_and_hms(2021, 3, 28, 10, 0, 0).unwrap())
+        );
+        assert_eq!(
+            hours.add_to(base, tz),
+            Some(Utc.with_ymd_and_hms(2021, 3, 28, 11, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn overflowing_additions() {
+        let base = Utc.with_ymd_and_hms(2021, 3, 27, 11, 0, 0).unwrap();
+        for duration in &["PT9999999999999999S", "P9999999999999999D", "-P99999999Y"] {
+            let duration = IsoDuration::parse(duration).unwrap();
+            assert_eq!(duration.add_to(base, Tz::UTC), None);
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Alternative encodings of JSON responses, applied to every route.
+//!
+//! `?pretty=true` indents the JSON body, for humans reading responses in a terminal.
+//! `?callback=name` wraps the JSON body of GET requests in a JSONP call, for legacy
+//! embedded widgets.
+//!
+//! Binary encodings are negotiated with the `Accept` header. `application/msgpack` and
+//! `application/cbor` apply to any JSON body, while `application/x-protobuf` only applies
+//! to the standard timestamp response, encoded with the schema of `proto/timestamp.proto`;
+//! other bodies stay JSON.
+
+use axum::body::{to_bytes, Body, Bytes, HttpBody};
+use axum::http::{header, HeaderMap, HeaderValue, Method, Request, Response, StatusCode};
+use axum::response::IntoResponse;
+use prost::Message;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{BoxError, Layer, Service};
+
+use crate::error::AppError;
+use crate::hal::HAL;
+use crate::locale::accepted;
+use crate::proto::Timestamp;
+
+/// Longer JSONP callback names are rejected.
+const MAX_CALLBACK_LENGTH: usize = 64;
+
+/// Value of the `name` query parameter, `true` when it has no value.
+fn param<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, "true"));
+        Some(value).filter(|_| key == name)
+    })
+}
+
+/// Whether a query string asks for `name`, as `name`, `name=true` or `name=1`.
+fn flag(query: Option<&str>, name: &str) -> bool {
+    param(query, name).is_some_and(|value| value == "true" || value == "1")
+}
+
+/// Only plain JavaScript identifiers and property paths, such as `widgets.render`, are
+/// accepted as callbacks, so that the response can't inject arbitrary script.
+fn is_valid_callback(callback: &str) -> bool {
+    !callback.is_empty()
+        && callback.len() <= MAX_CALLBACK_LENGTH
+        && callback.split('.').all(|part| {
+            part.chars().next().is_some_and(|c| !c.is_ascii_digit())
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        })
+}
+
+/// Wraps a JSON body in a call to `callback`. The leading comment guards against
+/// content sniffing attacks such as Rosetta Flash.
+fn jsonp(body: Bytes, callback: &str) -> Bytes {
+    let mut wrapped = Vec::with_capacity(body.len() + callback.len() + 8);
+    wrapped.extend_from_slice(b"/**/");
+    wrapped.extend_from_slice(callback.as_bytes());
+    wrapped.push(b'(');
+    wrapped.extend_from_slice(&body);
+    wrapped.extend_from_slice(b");");
+    Bytes::from(wrapped)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Binary {
+    Protobuf,
+    MessagePack,
+    Cbor,
+}
+
+impl Binary {
+    fn from_media_type(media_type: &str) -> Option<Binary> {
+        match media_type {
+            "application/x-protobuf" => Some(Binary::Protobuf),
+            "application/msgpack" | "application/x-msgpack" => Some(Binary::MessagePack),
+            "application/cbor" => Some(Binary::Cbor),
+            _ => None,
+        }
+    }
+
+    fn media_type(self) -> &'static str {
+        match self {
+            Binary::Protobuf => "application/x-protobuf",
+            Binary::MessagePack => "application/msgpack",
+            Binary::Cbor => "application/cbor",
+        }
+    }
+
+    /// Re-encodes a JSON body, `None` when this encoding can't represent it.
+    fn encode(self, body: &Bytes) -> Option<Bytes> {
+        let value = serde_json::from_slice::<Value>(body).ok()?;
+        let encoded = match self {
+            Binary::Protobuf => Timestamp::from_json(&value)?.encode_to_vec(),
+            Binary::MessagePack => rmp_serde::to_vec_named(&value).ok()?,
+            Binary::Cbor => serde_cbor::to_vec(&value).ok()?,
+        };
+        Some(Bytes::from(encoded))
+    }
+}
+
+/// The binary encoding preferred in the `Accept` header, unless JSON comes first.
+fn negotiate(headers: &HeaderMap) -> Option<Binary> {
+    let header = headers.get(header::ACCEPT)?.to_str().ok()?;
+    accepted(header)
+        .into_iter()
+        .find_map(|range| match range {
+            "application/json" | "application/*" | "*/*" => Some(None),
+            range => Binary::from_media_type(range).map(Some),
+        })
+        .flatten()
+}
+
+fn is_json<B>(response: &Response<B>) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|kind| {
+            let kind = kind.as_bytes();
+            kind.starts_with(b"application/json") || kind.starts_with(HAL.as_bytes())
+        })
+}
+
+/// Re-encodes a JSON body with indentation, leaving anything unparsable untouched.
+fn pretty(body: Bytes) -> Bytes {
+    match serde_json::from_slice::<Value>(&body) {
+        Ok(value) => Bytes::from(serde_json::to_vec_pretty(&value).unwrap()),
+        Err(_) => body,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EncodingLayer;
+
+impl<S> Layer<S> for EncodingLayer {
+    type Service = Encoding<S>;
+
+    fn layer(&self, inner: S) -> Encoding<S> {
+        Encoding { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Encoding<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for Encoding<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let query = request.uri().query();
+        let indent = flag(query, "pretty");
+        let callback = match param(query, "callback") {
+            Some(callback) if request.method() == Method::GET => {
+                if !is_valid_callback(callback) {
+                    let error = AppError::BadRequest("Invalid JSONP callback".to_string());
+                    let response = error.into_response();
+                    return Box::pin(async move { Ok(response) });
+                }
+                Some(callback.to_string())
+            }
+            _ => None,
+        };
+        let binary = negotiate(request.headers());
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = response.await?;
+            if !is_json(&response) {
+                return Ok(response.map(Body::new));
+            }
+            // the same URL may be served as JSON or a binary encoding
+            response
+                .headers_mut()
+                .append(header::VARY, HeaderValue::from_static("Accept"));
+            if !indent && callback.is_none() && binary.is_none() {
+                return Ok(response.map(Body::new));
+            }
+            let (mut parts, body) = response.into_parts();
+            let mut body = match to_bytes(Body::new(body), usize::MAX).await {
+                Ok(body) => body,
+                Err(_) => {
+                    let mut response = Response::new(Body::empty());
+                    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                    return Ok(response);
+                }
+            };
+            if let Some((binary, encoded)) =
+                binary.and_then(|binary| Some((binary, binary.encode(&body)?)))
+            {
+                parts.headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(binary.media_type()),
+                );
+                parts.headers.remove(header::CONTENT_LENGTH);
+                return Ok(Response::from_parts(parts, Body::from(encoded)));
+            }
+            if indent {
+                body = pretty(body);
+            }
+            if let Some(callback) = callback {
+                body = jsonp(body, &callback);
+                parts.headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/javascript"),
+                );
+                parts.headers.insert(
+                    header::X_CONTENT_TYPE_OPTIONS,
+                    HeaderValue::from_static("nosniff"),
+                );
+            }
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Ok(Response::from_parts(parts, Body::from(body)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_flags() {
+        assert!(flag(Some("pretty=true"), "pretty"));
+        assert!(flag(Some("base=2016-12-25&pretty"), "pretty"));
+        assert!(flag(Some("pretty=1"), "pretty"));
+        assert!(!flag(Some("pretty=false"), "pretty"));
+        assert!(!flag(Some("prettier=true"), "pretty"));
+        assert!(!flag(None, "pretty"));
+    }
+
+    #[test]
+    fn callbacks() {
+        assert_eq!(
+            param(Some("callback=render&pretty"), "callback"),
+            Some("render")
+        );
+        assert!(is_valid_callback("render"));
+        assert!(is_valid_callback("widgets.$render_2"));
+        assert!(!is_valid_callback("alert(1)//"));
+        assert!(!is_valid_callback("1render"));
+        assert!(!is_valid_callback("widgets..render"));
+        assert_eq!(
+            jsonp(Bytes::from(r#"{"unix":0}"#), "render"),
+            r#"/**/render({"unix":0});"#
+        );
+    }
+
+    #[test]
+    fn binary_negotiation() {
+        let accept = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+            negotiate(&headers)
+        };
+        assert_eq!(
+            accept("application/x-protobuf, application/json;q=0.5"),
+            Some(Binary::Protobuf)
+        );
+        assert_eq!(accept("application/x-protobuf;q=0"), None);
+        assert_eq!(
+            accept("application/json;q=0.5, application/cbor"),
+            Some(Binary::Cbor)
+        );
+        assert_eq!(accept("*/*, application/msgpack"), None);
+        assert_eq!(negotiate(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn binary_encodings() {
+        let error = Bytes::from(r#"{"error":"Invalid Date"}"#);
+        assert_eq!(Binary::Protobuf.encode(&error), None);
+
+        let body = Bytes::from(r#"{"unix":0}"#);
+        // a map of one entry, the "unix" string and the positive integer 0
+        assert_eq!(
+            Binary::MessagePack.encode(&body).unwrap(),
+            &b"\x81\xa4unix\x00"[..]
+        );
+        assert_eq!(
+            Binary::Cbor.encode(&body).unwrap(),
+            &b"\xa1\x64unix\x00"[..]
+        );
+    }
+
+    #[test]
+    fn indentation() {
+        let body = pretty(Bytes::from(r#"{"unix":0}"#));
+        assert_eq!(body, "{\n  \"unix\": 0\n}");
+    }
+}
@@ -0,0 +1,89 @@
+//! Offsets from a caller-defined epoch, such as J2000 or the epoch of a device clock.
+
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::error::AppError;
+use crate::parse::TimeUnit;
+use crate::query::Query;
+use crate::{parse_date, profile};
+
+#[derive(Debug, Deserialize)]
+pub struct EpochParams {
+    date: String,
+    /// The epoch, such as `2000-01-01T12:00:00Z` for J2000.
+    base: String,
+    /// Unit of the offset, seconds by default.
+    unit: Option<TimeUnit>,
+}
+
+/// Accepts RFC 3339 instants on top of the usual date inputs, since epochs rarely
+/// start at midnight.
+fn parse_instant(input: &str) -> Result<DateTime<Utc>, AppError> {
+    match profile::parse_rfc3339(input) {
+        Some(date) => Ok(date.with_timezone(&Utc)),
+        None => parse_date(input),
+    }
+}
+
+/// Whole units from `epoch` to `date`, rounded down like unix timestamps.
+pub fn offset(date: DateTime<Utc>, epoch: DateTime<Utc>, unit: TimeUnit) -> Option<i64> {
+    let nanos = |date: DateTime<Utc>| {
+        date.timestamp() as i128 * 1_000_000_000 + date.timestamp_subsec_nanos() as i128
+    };
+    let offset = (nanos(date) - nanos(epoch)).div_euclid(unit.nanos() as i128);
+    i64::try_from(offset).ok()
+}
+
+pub async fn epoch_handler(Query(params): Query<EpochParams>) -> Result<Json<Value>, AppError> {
+    let date = parse_instant(&params.date)?;
+    let epoch = parse_instant(&params.base)?;
+    let unit = params.unit.unwrap_or(TimeUnit::Seconds);
+    let offset = offset(date, epoch, unit).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(json!({
+        "date": date.to_rfc3339(),
+        "base": epoch.to_rfc3339(),
+        "unit": unit.name(),
+        "offset": offset,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn offsets() {
+        let j2000 = Utc.with_ymd_and_hms(2000, 1, 1, 12, 0, 0).unwrap();
+        let christmas = Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap();
+        assert_eq!(
+            offset(christmas, j2000, TimeUnit::Seconds),
+            Some(535_896_000)
+        );
+        assert_eq!(
+            offset(christmas, j2000, TimeUnit::Millis),
+            Some(535_896_000_000)
+        );
+        assert_eq!(
+            offset(
+                j2000 - chrono::Duration::milliseconds(1),
+                j2000,
+                TimeUnit::Seconds
+            ),
+            Some(-1)
+        );
+        assert_eq!(
+            offset(
+                Utc.with_ymd_and_hms(2300, 1, 1, 0, 0, 0).unwrap(),
+                j2000,
+                TimeUnit::Nanos
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::format::ParseError;
+use serde_json::json;
+use std::fmt;
+
+use crate::parse::Rejection;
+
+/// Errors returned by the API handlers, rendered as `{ "error": "...", "code": "..." }`,
+/// the code being stable for clients to match on.
+#[derive(Debug)]
+pub enum AppError {
+    /// The provided date could not be parsed.
+    InvalidDate,
+    /// The provided date has a valid format but doesn't exist, such as `2023-02-30`.
+    ImpossibleDate,
+    /// The provided timestamp is too far from the epoch to be represented.
+    TimestampOutOfRange,
+    /// The request refers to a resource the service doesn't know about.
+    NotFound(String),
+    /// The request parameters are malformed.
+    BadRequest(String),
+    /// The request lacks the credentials the endpoint requires.
+    Unauthorized,
+    /// The input is well-formed but can't be processed, such as a UUID without a timestamp.
+    Unprocessable(String),
+    /// The client sent more requests than the rate limit allows.
+    TooManyRequests,
+    /// The request took longer than the configured timeout.
+    RequestTimeout,
+    /// The request body is larger than the configured maximum.
+    PayloadTooLarge,
+    /// The service is serving as many requests as it is allowed to.
+    Overloaded,
+}
+
+impl From<ParseError> for AppError {
+    fn from(error: ParseError) -> Self {
+        tracing::error!("Error while parsing the date: {}", error);
+        AppError::InvalidDate
+    }
+}
+
+impl From<Rejection> for AppError {
+    fn from(rejection: Rejection) -> Self {
+        match rejection {
+            Rejection::Malformed => AppError::InvalidDate,
+            Rejection::Impossible => AppError::ImpossibleDate,
+            Rejection::OutOfRange => AppError::TimestampOutOfRange,
+        }
+    }
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::InvalidDate => "invalid_date",
+            AppError::ImpossibleDate => "impossible_date",
+            AppError::TimestampOutOfRange => "timestamp_out_of_range",
+            AppError::NotFound(_) => "not_found",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized => "unauthorized",
+            AppError::Unprocessable(_) => "unprocessable",
+            AppError::TooManyRequests => "too_many_requests",
+            AppError::RequestTimeout => "request_timeout",
+            AppError::PayloadTooLarge => "payload_too_large",
+            AppError::Overloaded => "overloaded",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::InvalidDate => f.write_str("Invalid Date"),
+            AppError::ImpossibleDate => f.write_str("Impossible Date"),
+            AppError::TimestampOutOfRange => f.write_str("Timestamp Out Of Range"),
+            AppError::Unauthorized => f.write_str("Unauthorized"),
+            AppError::TooManyRequests => f.write_str("Too Many Requests"),
+            AppError::RequestTimeout => f.write_str("Request Timeout"),
+            AppError::PayloadTooLarge => f.write_str("Payload Too Large"),
+            AppError::Overloaded => f.write_str("Service Unavailable"),
+            AppError::NotFound(message)
+            | AppError::BadRequest(message)
+            | AppError::Unprocessable(message) => f.write_str(message),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            AppError::InvalidDate => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::ImpossibleDate => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::TimestampOutOfRange => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            AppError::RequestTimeout => StatusCode::REQUEST_TIMEOUT,
+            AppError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        let body = Json(json!({
+            "error": self.to_string(),
+            "code": self.code(),
+        }));
+
+        (status, body).into_response()
+    }
+}
@@ -0,0 +1,153 @@
+//! The Ethiopian calendar: twelve months of 30 days followed by Pagume, of 5 days or 6 in
+//! leap years, which come every 4 years like in the Julian calendar. Years are counted
+//! from the Incarnation Era, 7 or 8 years behind the Gregorian ones.
+
+use axum::Json;
+use chrono::{Datelike, Duration, NaiveDate};
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+
+const MONTHS: [&str; 13] = [
+    "Meskerem", "Tikimt", "Hidar", "Tahsas", "Tir", "Yekatit", "Megabit", "Miyazya", "Ginbot",
+    "Sene", "Hamle", "Nehase", "Pagume",
+];
+
+/// Meskerem 1 of year 1, August 29, 8 in the Julian calendar.
+fn epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(8, 8, 27).unwrap()
+}
+
+pub fn is_leap_year(year: i64) -> bool {
+    year.rem_euclid(4) == 3
+}
+
+fn month_length(year: i64, month: u32) -> u32 {
+    match month {
+        13 if is_leap_year(year) => 6,
+        13 => 5,
+        _ => 30,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct EthiopianDate {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+/// Days from the epoch to the first day of `year`.
+fn year_start(year: i64) -> i64 {
+    365 * (year - 1) + year.div_euclid(4)
+}
+
+pub fn to_ethiopian(date: NaiveDate) -> EthiopianDate {
+    let days = (date - epoch()).num_days();
+    let year = (4 * days + 1463).div_euclid(1461);
+    let days = days - year_start(year);
+    EthiopianDate {
+        year,
+        month: (days / 30) as u32 + 1,
+        day: (days % 30) as u32 + 1,
+    }
+}
+
+pub fn from_ethiopian(date: &EthiopianDate) -> Option<NaiveDate> {
+    if !(1..=13).contains(&date.month)
+        || date.day < 1
+        || date.day > month_length(date.year, date.month)
+    {
+        return None;
+    }
+    let days = year_start(date.year)
+        .checked_add(30 * (i64::from(date.month) - 1) + i64::from(date.day) - 1)?;
+    epoch().checked_add_signed(Duration::days(days))
+}
+
+fn describe(ethiopian: &EthiopianDate, gregorian: NaiveDate) -> Value {
+    json!({
+        "gregorian": gregorian.to_string(),
+        "weekday": gregorian.weekday().to_string(),
+        "ethiopian": {
+            "year": ethiopian.year,
+            "month": ethiopian.month,
+            "day": ethiopian.day,
+            "month_name": MONTHS[ethiopian.month as usize - 1],
+            "leap_year": is_leap_year(ethiopian.year),
+            "date": format!("{}-{:02}-{:02}", ethiopian.year, ethiopian.month, ethiopian.day),
+        },
+    })
+}
+
+/// Converts a Gregorian date to the Ethiopian calendar.
+pub async fn to_ethiopian_handler(Path(date): Path<String>) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?.date_naive();
+    let ethiopian = to_ethiopian(date);
+
+    Ok(Json(describe(&ethiopian, date)))
+}
+
+/// Converts an Ethiopian date, written `YYYY-MM-DD`, to the Gregorian calendar.
+pub async fn from_ethiopian_handler(Path(date): Path<String>) -> Result<Json<Value>, AppError> {
+    let mut parts = date.splitn(3, '-');
+    let mut next = || parts.next().and_then(|part| part.parse::<i64>().ok());
+    let (year, month, day) = match (next(), next(), next()) {
+        (Some(year), Some(month), Some(day)) => (year, month, day),
+        _ => return Err(AppError::InvalidDate),
+    };
+    let ethiopian = EthiopianDate {
+        year,
+        month: u32::try_from(month).map_err(|_| AppError::InvalidDate)?,
+        day: u32::try_from(day).map_err(|_| AppError::InvalidDate)?,
+    };
+    let gregorian = from_ethiopian(&ethiopian).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(describe(&ethiopian, gregorian)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ethiopian(year: i64, month: u32, day: u32) -> EthiopianDate {
+        EthiopianDate { year, month, day }
+    }
+
+    #[test]
+    fn conversions() {
+        let new_year = NaiveDate::from_ymd_opt(2023, 9, 12).unwrap();
+        assert_eq!(to_ethiopian(new_year), ethiopian(2016, 1, 1));
+        assert_eq!(from_ethiopian(&ethiopian(2016, 1, 1)), Some(new_year));
+        // 2015 is a leap year, its Pagume has 6 days
+        assert_eq!(
+            to_ethiopian(new_year.pred_opt().unwrap()),
+            ethiopian(2015, 13, 6)
+        );
+        assert_eq!(
+            to_ethiopian(NaiveDate::from_ymd_opt(2024, 1, 7).unwrap()),
+            ethiopian(2016, 4, 28)
+        );
+        assert_eq!(to_ethiopian(epoch()), ethiopian(1, 1, 1));
+    }
+
+    #[test]
+    fn round_trips() {
+        let start = NaiveDate::from_ymd_opt(1900, 1, 1).unwrap();
+        for days in (0..80_000).step_by(7) {
+            let date = start + Duration::days(days);
+            assert_eq!(from_ethiopian(&to_ethiopian(date)), Some(date));
+        }
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert_eq!(from_ethiopian(&ethiopian(2016, 14, 1)), None);
+        assert_eq!(from_ethiopian(&ethiopian(2016, 1, 31)), None);
+        assert_eq!(from_ethiopian(&ethiopian(2016, 13, 6)), None);
+        assert!(from_ethiopian(&ethiopian(2015, 13, 6)).is_some());
+    }
+}
@@ -0,0 +1,169 @@
+//! Excel (and Lotus 1-2-3) serial dates: days since an epoch, with the time of day as
+//! the fractional part.
+//!
+//! The default 1900 date system counts 1900-01-01 as day 1 and, to stay compatible with
+//! Lotus, believes 1900 was a leap year: serial 60 is the non-existent 1900-02-29 and
+//! every later serial is off by one. The 1904 date system, used by older Mac versions,
+//! counts days from 1904-01-01 as day 0.
+
+use axum::Json;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+use crate::query::Query;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum DateSystem {
+    #[serde(rename = "1900")]
+    Excel1900,
+    #[serde(rename = "1904")]
+    Excel1904,
+}
+
+impl DateSystem {
+    fn name(self) -> &'static str {
+        match self {
+            DateSystem::Excel1900 => "1900",
+            DateSystem::Excel1904 => "1904",
+        }
+    }
+}
+
+const MS_PER_DAY: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// Day from which whole serials are counted.
+fn base(system: DateSystem, days: i64) -> NaiveDate {
+    match system {
+        DateSystem::Excel1900 if days < 60 => NaiveDate::from_ymd_opt(1899, 12, 31).unwrap(),
+        // one day earlier, to absorb the phantom 1900-02-29
+        DateSystem::Excel1900 => NaiveDate::from_ymd_opt(1899, 12, 30).unwrap(),
+        DateSystem::Excel1904 => NaiveDate::from_ymd_opt(1904, 1, 1).unwrap(),
+    }
+}
+
+pub fn from_serial(serial: f64, system: DateSystem) -> Option<DateTime<Utc>> {
+    if !serial.is_finite() || !(0.0..=2_958_466.0).contains(&serial) {
+        return None;
+    }
+    let days = serial.floor() as i64;
+    if system == DateSystem::Excel1900 && days == 60 {
+        return None;
+    }
+    let ms = ((serial - serial.floor()) * MS_PER_DAY).round() as i64;
+    let date = base(system, days).and_hms_opt(0, 0, 0).unwrap()
+        + Duration::days(days)
+        + Duration::milliseconds(ms);
+    Some(DateTime::from_naive_utc_and_offset(date, Utc))
+}
+
+pub fn to_serial(date: DateTime<Utc>, system: DateSystem) -> Option<f64> {
+    let naive = date.naive_utc();
+    let days = match system {
+        DateSystem::Excel1900 if naive.date() < NaiveDate::from_ymd_opt(1900, 3, 1).unwrap() => {
+            (naive.date() - base(system, 0)).num_days()
+        }
+        _ => (naive.date() - base(system, 60)).num_days(),
+    };
+    if days < 0 {
+        return None;
+    }
+    let ms = (naive - naive.date().and_hms_opt(0, 0, 0).unwrap()).num_milliseconds();
+    Some(days as f64 + ms as f64 / MS_PER_DAY)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExcelParams {
+    /// Date system, `1900` by default.
+    epoch: Option<DateSystem>,
+}
+
+pub async fn from_serial_handler(
+    Path(serial): Path<String>,
+    Query(params): Query<ExcelParams>,
+) -> Result<Json<Value>, AppError> {
+    let system = params.epoch.unwrap_or(DateSystem::Excel1900);
+    let date = serial
+        .parse()
+        .ok()
+        .and_then(|serial| from_serial(serial, system))
+        .ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "serial": serial.parse::<f64>().ok(),
+        "epoch": system.name(),
+    })))
+}
+
+pub async fn to_serial_handler(
+    Path(date): Path<String>,
+    Query(params): Query<ExcelParams>,
+) -> Result<Json<Value>, AppError> {
+    let system = params.epoch.unwrap_or(DateSystem::Excel1900);
+    let date = parse_date(&date)?;
+    let serial = to_serial(date, system).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "serial": serial,
+        "epoch": system.name(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn lotus_leap_year_bug() {
+        let system = DateSystem::Excel1900;
+        assert_eq!(
+            from_serial(1.0, system),
+            Some(Utc.with_ymd_and_hms(1900, 1, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            from_serial(59.0, system),
+            Some(Utc.with_ymd_and_hms(1900, 2, 28, 0, 0, 0).unwrap())
+        );
+        assert_eq!(from_serial(60.0, system), None);
+        assert_eq!(
+            from_serial(61.0, system),
+            Some(Utc.with_ymd_and_hms(1900, 3, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            from_serial(42729.5, system),
+            Some(Utc.with_ymd_and_hms(2016, 12, 25, 12, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn mac_epoch() {
+        let system = DateSystem::Excel1904;
+        assert_eq!(
+            from_serial(0.0, system),
+            Some(Utc.with_ymd_and_hms(1904, 1, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            from_serial(41267.0, system),
+            Some(Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for system in &[DateSystem::Excel1900, DateSystem::Excel1904] {
+            let date = Utc.with_ymd_and_hms(2016, 12, 25, 6, 0, 0).unwrap();
+            let serial = to_serial(date, *system).unwrap();
+            assert_eq!(from_serial(serial, *system), Some(date));
+        }
+        let early = Utc.with_ymd_and_hms(1900, 2, 1, 0, 0, 0).unwrap();
+        assert_eq!(to_serial(early, DateSystem::Excel1900), Some(32.0));
+    }
+}
@@ -0,0 +1,128 @@
+//! Body and path extraction answering malformed input with the standard JSON error.
+
+use axum::extract::rejection::{JsonRejection, PathRejection};
+use axum::extract::{FromRequest, FromRequestParts, OptionalFromRequest, Request};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::AppError;
+
+/// Same as `axum::Json`, rejecting requests with a `bad_request` or `unprocessable` error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+fn json_rejected(rejection: JsonRejection) -> AppError {
+    match rejection.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge,
+        StatusCode::UNPROCESSABLE_ENTITY => AppError::Unprocessable(rejection.body_text()),
+        _ => AppError::BadRequest(rejection.body_text()),
+    }
+}
+
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        <axum::Json<T> as FromRequest<S>>::from_request(request, state)
+            .await
+            .map(|axum::Json(value)| Json(value))
+            .map_err(json_rejected)
+    }
+}
+
+/// A missing body gives `None`, as with `axum::Json`.
+impl<T, S> OptionalFromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        <axum::Json<T> as OptionalFromRequest<S>>::from_request(request, state)
+            .await
+            .map(|json| json.map(|axum::Json(value)| Json(value)))
+            .map_err(json_rejected)
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Same as `axum::extract::Path`, rejecting requests with a 400 `bad_request` error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Path<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        axum::extract::Path::<T>::from_request_parts(parts, state)
+            .await
+            .map(|axum::extract::Path(value)| Path(value))
+            .map_err(|rejection: PathRejection| AppError::BadRequest(rejection.body_text()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::post;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    async fn echo(Path(n): Path<i64>, Json(body): Json<Vec<i64>>) -> Json<Vec<i64>> {
+        Json(body.into_iter().map(|x| x + n).collect())
+    }
+
+    async fn status(uri: &str, body: &'static str) -> (StatusCode, String) {
+        let app = Router::new().route("/{n}", post(echo));
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn rejections_are_json() {
+        assert_eq!(
+            status("/1", "[1, 2]").await,
+            (StatusCode::OK, "[2,3]".to_string())
+        );
+
+        let (code, body) = status("/one", "[1, 2]").await;
+        assert_eq!(code, StatusCode::BAD_REQUEST);
+        assert!(body.contains(r#""code":"bad_request""#));
+
+        let (code, body) = status("/1", "[1, 2").await;
+        assert_eq!(code, StatusCode::BAD_REQUEST);
+        assert!(body.contains(r#""code":"bad_request""#));
+
+        let (code, body) = status("/1", r#"["one"]"#).await;
+        assert_eq!(code, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body.contains(r#""code":"unprocessable""#));
+    }
+}
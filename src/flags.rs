@@ -0,0 +1,163 @@
+//! Feature flags switched on and off at configured instants.
+//!
+//! Flags are read at startup from the JSON file pointed to by the `TIMESTAMP_FLAGS`
+//! environment variable, as a list of
+//! `{ "name": "launch", "activate_at": "2021-09-01T09:00:00", "tz": "Europe/Rome" }`.
+//! Both bounds are optional ISO 8601 date-times; those without an offset are read in `tz`,
+//! or UTC if it is not given.
+
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::config::read_env_file;
+use crate::error::AppError;
+use crate::parse_date;
+use crate::profile::parse_iso8601_local;
+use crate::query::Query;
+use crate::timezone::{parse_tz, resolve_local};
+
+#[derive(Debug, Deserialize)]
+struct FlagConfig {
+    name: String,
+    activate_at: Option<String>,
+    deactivate_at: Option<String>,
+    tz: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Flag {
+    name: String,
+    activate_at: Option<DateTime<Utc>>,
+    deactivate_at: Option<DateTime<Utc>>,
+}
+
+impl Flag {
+    /// A flag is active from its activation instant included to its deactivation excluded.
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.activate_at.is_none_or(|start| start <= at)
+            && self.deactivate_at.is_none_or(|end| at < end)
+    }
+}
+
+pub type Flags = Arc<Vec<Flag>>;
+
+/// Loads the flags from the file named by `TIMESTAMP_FLAGS`, if set.
+pub fn load() -> Result<Flags, String> {
+    match read_env_file("TIMESTAMP_FLAGS")? {
+        Some(json) => parse_flags(&json).map(Arc::new),
+        None => Ok(Flags::default()),
+    }
+}
+
+fn parse_flags(json: &str) -> Result<Vec<Flag>, String> {
+    let configs: Vec<FlagConfig> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let mut flags = Vec::with_capacity(configs.len());
+
+    for config in configs {
+        let tz = parse_tz(config.tz.as_deref())
+            .map_err(|_| format!("flag {}: unknown timezone", config.name))?;
+        let instant = |value: &Option<String>| match value {
+            Some(value) => parse_iso8601_local(value)
+                .and_then(|(local, offset)| resolve_local(local, offset, tz))
+                .map(Some)
+                .ok_or_else(|| format!("flag {}: invalid date {}", config.name, value)),
+            None => Ok(None),
+        };
+
+        flags.push(Flag {
+            activate_at: instant(&config.activate_at)?,
+            deactivate_at: instant(&config.deactivate_at)?,
+            name: config.name,
+        });
+    }
+
+    Ok(flags)
+}
+
+fn render(instant: Option<DateTime<Utc>>) -> Value {
+    match instant {
+        Some(instant) => json!({
+            "unix": instant.timestamp(),
+            "utc": instant.to_rfc2822(),
+        }),
+        None => Value::Null,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FlagsParams {
+    /// Defaults to now.
+    at: Option<String>,
+}
+
+pub async fn flags_handler(
+    State(flags): State<Flags>,
+    Query(params): Query<FlagsParams>,
+) -> Result<Json<Value>, AppError> {
+    let at = match params.at {
+        Some(at) => parse_date(&at)?,
+        None => Utc::now(),
+    };
+
+    let active: Vec<&str> = flags
+        .iter()
+        .filter(|flag| flag.is_active(at))
+        .map(|flag| flag.name.as_str())
+        .collect();
+    let all: Vec<Value> = flags
+        .iter()
+        .map(|flag| {
+            json!({
+                "name": flag.name,
+                "active": flag.is_active(at),
+                "activate_at": render(flag.activate_at),
+                "deactivate_at": render(flag.deactivate_at),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "at": render(Some(at)),
+        "active": active,
+        "flags": all,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn activation_window() {
+        let flags = parse_flags(
+            r#"[
+                { "name": "launch", "activate_at": "2021-09-01T09:00:00", "tz": "Europe/Rome" },
+                { "name": "sale", "activate_at": "2021-11-26", "deactivate_at": "2021-11-29T00:00:00-05:00" }
+            ]"#,
+        )
+        .unwrap();
+
+        let launch = &flags[0];
+        assert_eq!(
+            launch.activate_at,
+            Some(Utc.with_ymd_and_hms(2021, 9, 1, 7, 0, 0).unwrap())
+        );
+        assert!(!launch.is_active(Utc.with_ymd_and_hms(2021, 9, 1, 6, 59, 59).unwrap()));
+        assert!(launch.is_active(Utc.with_ymd_and_hms(2021, 9, 1, 7, 0, 0).unwrap()));
+
+        let sale = &flags[1];
+        assert!(sale.is_active(Utc.with_ymd_and_hms(2021, 11, 29, 4, 59, 59).unwrap()));
+        assert!(!sale.is_active(Utc.with_ymd_and_hms(2021, 11, 29, 5, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn invalid_flags() {
+        assert!(parse_flags(r#"[{ "name": "a", "tz": "Mars/Olympus" }]"#).is_err());
+        assert!(parse_flags(r#"[{ "name": "a", "activate_at": "soon" }]"#).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! Timezone lookup by geographic coordinates.
+//!
+//! Zone boundaries are loaded at startup from the GeoJSON published by
+//! timezone-boundary-builder, configured with `tz_boundaries`. The dataset weighs tens of
+//! megabytes, too much to build into the binary. Points outside every boundary, such as
+//! those at sea, and all points when no dataset is loaded get the nautical zone of their
+//! longitude, such as `Etc/GMT-9`.
+//!
+//! Callers can also be located by IP address with a MaxMind GeoIP2 or GeoLite2 City
+//! database, configured with `geoip_db`. Without one, `/api/local` answers 404.
+
+use axum::http::{Extensions, HeaderMap};
+use axum::Json;
+use chrono::{FixedOffset, Utc};
+use chrono_tz::Tz;
+use maxminddb::{geoip2, Reader};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::error::AppError;
+use crate::query::Query;
+use crate::rate_limit::client_address;
+use crate::timezone::{describe_local, offset_at, parse_tz};
+
+static LOADED: OnceLock<Boundaries> = OnceLock::new();
+
+static GEOIP: OnceLock<Reader<Vec<u8>>> = OnceLock::new();
+
+/// A ring of `(lon, lat)` points, the last one joining the first.
+type Ring = Vec<(f64, f64)>;
+
+/// An outer ring followed by its holes.
+type Polygon = Vec<Ring>;
+
+struct Zone {
+    tz: Tz,
+    /// `[min_lon, min_lat, max_lon, max_lat]`, to skip most polygons cheaply.
+    bbox: [f64; 4],
+    polygons: Vec<Polygon>,
+}
+
+impl Zone {
+    fn contains(&self, lon: f64, lat: f64) -> bool {
+        let [min_lon, min_lat, max_lon, max_lat] = self.bbox;
+        if lon < min_lon || lon > max_lon || lat < min_lat || lat > max_lat {
+            return false;
+        }
+        self.polygons.iter().any(|polygon| {
+            let mut rings = polygon.iter();
+            rings.next().is_some_and(|outer| in_ring(outer, lon, lat))
+                && !rings.any(|hole| in_ring(hole, lon, lat))
+        })
+    }
+}
+
+pub struct Boundaries {
+    zones: Vec<Zone>,
+}
+
+impl Boundaries {
+    pub fn locate(&self, lon: f64, lat: f64) -> Option<Tz> {
+        self.zones
+            .iter()
+            .find(|zone| zone.contains(lon, lat))
+            .map(|zone| zone.tz)
+    }
+}
+
+/// Even-odd ray casting.
+fn in_ring(ring: &[(f64, f64)], lon: f64, lat: f64) -> bool {
+    let mut inside = false;
+    let mut previous = match ring.last() {
+        Some(point) => *point,
+        None => return false,
+    };
+    for &(x, y) in ring {
+        let (px, py) = previous;
+        if (y > lat) != (py > lat) && lon < (px - x) * (lat - y) / (py - y) + x {
+            inside = !inside;
+        }
+        previous = (x, y);
+    }
+    inside
+}
+
+fn ring(value: &Value) -> Option<Ring> {
+    value
+        .as_array()?
+        .iter()
+        .map(|point| Some((point.get(0)?.as_f64()?, point.get(1)?.as_f64()?)))
+        .collect()
+}
+
+fn polygon(value: &Value) -> Option<Polygon> {
+    value.as_array()?.iter().map(ring).collect()
+}
+
+/// Reads the zones of a GeoJSON feature collection whose features have a `tzid` property
+/// and a `Polygon` or `MultiPolygon` geometry. Features of zones unknown to chrono-tz are
+/// skipped.
+pub fn parse(geojson: &str) -> Result<Boundaries, String> {
+    let collection: Value = serde_json::from_str(geojson).map_err(|e| e.to_string())?;
+    let features = collection["features"]
+        .as_array()
+        .ok_or("not a GeoJSON feature collection")?;
+
+    let mut zones = Vec::new();
+    for feature in features {
+        let name = feature["properties"]["tzid"]
+            .as_str()
+            .ok_or("feature without a tzid")?;
+        let tz: Tz = match name.parse() {
+            Ok(tz) => tz,
+            Err(_) => {
+                tracing::warn!("Skipping the boundaries of unknown zone {}", name);
+                continue;
+            }
+        };
+        let geometry = &feature["geometry"];
+        let coordinates = &geometry["coordinates"];
+        let polygons = match geometry["type"].as_str() {
+            Some("Polygon") => polygon(coordinates).map(|polygon| vec![polygon]),
+            Some("MultiPolygon") => coordinates
+                .as_array()
+                .and_then(|polygons| polygons.iter().map(polygon).collect()),
+            _ => None,
+        }
+        .ok_or_else(|| format!("invalid geometry for {}", name))?;
+
+        let points = polygons.iter().flat_map(|polygon| polygon.iter().flatten());
+        let bbox = points.fold(
+            [f64::MAX, f64::MAX, f64::MIN, f64::MIN],
+            |[min_lon, min_lat, max_lon, max_lat], &(lon, lat)| {
+                [
+                    min_lon.min(lon),
+                    min_lat.min(lat),
+                    max_lon.max(lon),
+                    max_lat.max(lat),
+                ]
+            },
+        );
+        zones.push(Zone { tz, bbox, polygons });
+    }
+    Ok(Boundaries { zones })
+}
+
+pub fn load(path: &Path) -> Result<Boundaries, String> {
+    let geojson =
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse(&geojson).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Makes `boundaries` available to lookups, once at startup.
+pub fn install(boundaries: Boundaries) {
+    tracing::info!("Loaded the boundaries of {} zones", boundaries.zones.len());
+    if LOADED.set(boundaries).is_err() {
+        tracing::warn!("Zone boundaries are already loaded");
+    }
+}
+
+/// The nautical zone of a longitude, one hour wide every 15 degrees. `Etc` zones have
+/// inverted signs: `Etc/GMT-9` is nine hours ahead of UTC.
+pub fn nautical(lon: f64) -> Tz {
+    let hours = (lon / 15.0).round() as i32;
+    let name = match hours.clamp(-12, 12) {
+        0 => "Etc/GMT".to_string(),
+        hours => format!("Etc/GMT{:+}", -hours),
+    };
+    name.parse().unwrap_or(Tz::UTC)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LocateParams {
+    lat: f64,
+    lon: f64,
+}
+
+pub async fn locate_handler(Query(params): Query<LocateParams>) -> Result<Json<Value>, AppError> {
+    let LocateParams { lat, lon } = params;
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(AppError::BadRequest(
+            "Coordinates must be within ±90° of latitude and ±180° of longitude".to_string(),
+        ));
+    }
+    let (tz, source) = match LOADED.get().and_then(|loaded| loaded.locate(lon, lat)) {
+        Some(tz) => (tz, "boundaries"),
+        None => (nautical(lon), "nautical"),
+    };
+
+    Ok(Json(json!({
+        "lat": lat,
+        "lon": lon,
+        "tz": tz.name(),
+        "source": source,
+        "offset": FixedOffset::east_opt(offset_at(tz, Utc::now())).unwrap().to_string(),
+    })))
+}
+
+/// Opens a GeoIP City database and makes it available to `/api/local`, once at startup.
+pub fn install_geoip(path: &Path) -> Result<(), String> {
+    let reader = Reader::open_readfile(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    tracing::info!(
+        "Loaded the {} GeoIP database from {}",
+        reader.metadata.database_type,
+        path.display()
+    );
+    if GEOIP.set(reader).is_err() {
+        tracing::warn!("A GeoIP database is already loaded");
+    }
+    Ok(())
+}
+
+/// The timezone of an IP address, if the database knows it.
+fn locate_ip(reader: &Reader<Vec<u8>>, ip: IpAddr) -> Option<Tz> {
+    let city: geoip2::City = reader.lookup(ip).ok()?;
+    city.location?.time_zone?.parse().ok()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LocalParams {
+    /// Address to locate instead of the caller's.
+    ip: Option<IpAddr>,
+}
+
+/// The current time where the caller is, as told by their IP address. Addresses the
+/// database can't place get the default timezone.
+pub async fn local_handler(
+    Query(params): Query<LocalParams>,
+    headers: HeaderMap,
+    extensions: Extensions,
+) -> Result<Json<Value>, AppError> {
+    let reader = GEOIP
+        .get()
+        .ok_or_else(|| AppError::NotFound("GeoIP lookups are not configured".to_string()))?;
+    let ip = params
+        .ip
+        .or_else(|| client_address(&headers, &extensions))
+        .ok_or_else(|| AppError::BadRequest("The client address is unknown".to_string()))?;
+    let located = locate_ip(reader, ip);
+    let tz = match located {
+        Some(tz) => tz,
+        None => parse_tz(None)?,
+    };
+    let now = Utc::now();
+
+    Ok(Json(json!({
+        "ip": ip.to_string(),
+        "located": located.is_some(),
+        "unix": now.timestamp(),
+        "utc": now.to_rfc2822(),
+        "local": describe_local(now, tz),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GEOJSON: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [{
+            "type": "Feature",
+            "properties": {"tzid": "Europe/Rome"},
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [
+                    [[6, 36], [19, 36], [19, 47], [6, 47], [6, 36]],
+                    [[12.4, 43.9], [12.5, 43.9], [12.5, 44], [12.4, 44], [12.4, 43.9]]
+                ]
+            }
+        }, {
+            "type": "Feature",
+            "properties": {"tzid": "Europe/San_Marino"},
+            "geometry": {
+                "type": "MultiPolygon",
+                "coordinates": [
+                    [[[12.4, 43.9], [12.5, 43.9], [12.5, 44], [12.4, 44], [12.4, 43.9]]]
+                ]
+            }
+        }]
+    }"#;
+
+    #[test]
+    fn point_in_boundaries() {
+        let boundaries = parse(GEOJSON).unwrap();
+        assert_eq!(boundaries.locate(12.5, 41.9), Some(Tz::Europe__Rome));
+        // in the hole of the Italian polygon
+        assert_eq!(
+            boundaries.locate(12.45, 43.95),
+            Some(Tz::Europe__San_Marino)
+        );
+        assert_eq!(boundaries.locate(-74.0, 40.7), None);
+        assert!(parse(r#"{"type": "Feature"}"#).is_err());
+    }
+
+    #[test]
+    fn nautical_zones() {
+        assert_eq!(nautical(0.0), Tz::Etc__GMT);
+        assert_eq!(nautical(139.7), Tz::Etc__GMTMinus9);
+        assert_eq!(nautical(-74.0), Tz::Etc__GMTPlus5);
+        assert_eq!(nautical(180.0), Tz::Etc__GMTMinus12);
+    }
+}
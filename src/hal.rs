@@ -0,0 +1,79 @@
+//! Opt-in HAL (`application/hal+json`) responses for `/api/:date`, so that generic
+//! hypermedia clients can browse from a date to the operations on it.
+//!
+//! It's asked for with `Accept: application/hal+json` or `?links=true`, and adds a
+//! `_links` object to every converted date, pointing at the instant itself, its
+//! difference with another date, adding durations to it, its classification and its
+//! conversion to another timezone. Links take the date in RFC 3339 rather than as it was
+//! written, so that relative inputs such as `tomorrow` keep pointing at the same instant.
+//! Templated links follow RFC 6570.
+
+use axum::http::{header, HeaderMap};
+use serde_json::{json, Value};
+
+pub const HAL: &str = "application/hal+json";
+
+/// Whether the client asked for links, through `Accept` or the query string.
+pub fn wants(headers: &HeaderMap, links: bool) -> bool {
+    links
+        || headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|accept| accept.split(','))
+            .any(|media| media.split(';').next().unwrap_or_default().trim() == HAL)
+}
+
+/// Whether `Accept` asked for HAL itself, so that the response is labelled as such.
+pub fn negotiated(headers: &HeaderMap) -> bool {
+    wants(headers, false)
+}
+
+/// The links of a converted date, from its RFC 3339 form.
+pub fn links(iso8601: &str) -> Value {
+    json!({
+        "self": {"href": format!("/api/{}", iso8601)},
+        "diff": {
+            "href": format!("/api/diff?from={}{{&to,unit}}", iso8601),
+            "templated": true,
+        },
+        "add": {
+            "href": "/api/duration/combine",
+            "title": format!("POST {{\"base\": \"{}\", \"durations\": [...]}}", iso8601),
+        },
+        "info": {"href": format!("/api/classify/{}", iso8601)},
+        "convert": {
+            "href": format!("/api/convert?date={}{{&to}}", iso8601),
+            "templated": true,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn negotiation() {
+        let mut headers = HeaderMap::new();
+        assert!(!wants(&headers, false));
+        assert!(wants(&headers, true));
+
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/json, application/hal+json;q=0.9"),
+        );
+        assert!(negotiated(&headers));
+    }
+
+    #[test]
+    fn links_of_dates() {
+        let links = links("2016-12-25T00:00:00Z");
+        assert_eq!(links["self"]["href"], "/api/2016-12-25T00:00:00Z");
+        assert_eq!(
+            links["convert"]["href"],
+            "/api/convert?date=2016-12-25T00:00:00Z{&to}"
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! The tabular Islamic calendar: 30-year cycles of 11 leap years, months alternating
+//! between 30 and 29 days, the last month having 30 days in leap years.
+
+use axum::Json;
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+use crate::query::Query;
+
+const CYCLE_YEARS: i64 = 30;
+const CYCLE_DAYS: i64 = 10_631;
+
+const MONTHS: [&str; 12] = [
+    "Muharram",
+    "Safar",
+    "Rabi' al-awwal",
+    "Rabi' al-thani",
+    "Jumada al-awwal",
+    "Jumada al-thani",
+    "Rajab",
+    "Sha'ban",
+    "Ramadan",
+    "Shawwal",
+    "Dhu al-Qi'dah",
+    "Dhu al-Hijjah",
+];
+
+/// Day the calendar starts from.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Epoch {
+    /// Friday, July 16, 622 (Julian), the most widespread choice.
+    Civil,
+    /// Thursday, July 15, 622 (Julian).
+    Astronomical,
+}
+
+impl Epoch {
+    fn date(self) -> NaiveDate {
+        match self {
+            Epoch::Civil => NaiveDate::from_ymd_opt(622, 7, 19).unwrap(),
+            Epoch::Astronomical => NaiveDate::from_ymd_opt(622, 7, 18).unwrap(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Epoch::Civil => "civil",
+            Epoch::Astronomical => "astronomical",
+        }
+    }
+}
+
+/// Leap years within each 30-year cycle. The first pattern is by far the most common,
+/// the others are used by Microsoft's Kuwaiti algorithm, the Fatimids and Habash al-Hasib.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Leaps {
+    Standard,
+    Kuwaiti,
+    Fatimid,
+    Habash,
+}
+
+impl Leaps {
+    fn years(self) -> [i64; 11] {
+        match self {
+            Leaps::Standard => [2, 5, 7, 10, 13, 16, 18, 21, 24, 26, 29],
+            Leaps::Kuwaiti => [2, 5, 7, 10, 13, 15, 18, 21, 24, 26, 29],
+            Leaps::Fatimid => [2, 5, 8, 10, 13, 16, 19, 21, 24, 27, 29],
+            Leaps::Habash => [2, 5, 8, 11, 13, 16, 19, 21, 24, 27, 30],
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Leaps::Standard => "standard",
+            Leaps::Kuwaiti => "kuwaiti",
+            Leaps::Fatimid => "fatimid",
+            Leaps::Habash => "habash",
+        }
+    }
+
+    pub fn is_leap_year(self, year: i64) -> bool {
+        self.years()
+            .contains(&((year - 1).rem_euclid(CYCLE_YEARS) + 1))
+    }
+
+    fn year_length(self, year: i64) -> i64 {
+        if self.is_leap_year(year) {
+            355
+        } else {
+            354
+        }
+    }
+
+    fn month_length(self, year: i64, month: u32) -> i64 {
+        if month % 2 == 1 || (month == 12 && self.is_leap_year(year)) {
+            30
+        } else {
+            29
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct HijriDate {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+pub fn to_hijri(date: NaiveDate, epoch: Epoch, leaps: Leaps) -> HijriDate {
+    let days = (date - epoch.date()).num_days();
+    let mut year = days.div_euclid(CYCLE_DAYS) * CYCLE_YEARS + 1;
+    let mut days = days.rem_euclid(CYCLE_DAYS);
+    while days >= leaps.year_length(year) {
+        days -= leaps.year_length(year);
+        year += 1;
+    }
+    let mut month = 1;
+    while days >= leaps.month_length(year, month) {
+        days -= leaps.month_length(year, month);
+        month += 1;
+    }
+    HijriDate {
+        year,
+        month,
+        day: days as u32 + 1,
+    }
+}
+
+pub fn from_hijri(date: &HijriDate, epoch: Epoch, leaps: Leaps) -> Option<NaiveDate> {
+    if !(1..=12).contains(&date.month)
+        || date.day < 1
+        || date.day as i64 > leaps.month_length(date.year, date.month)
+    {
+        return None;
+    }
+    let cycles = (date.year - 1).div_euclid(CYCLE_YEARS);
+    let first_year = cycles * CYCLE_YEARS + 1;
+    let mut days = cycles.checked_mul(CYCLE_DAYS)?;
+    days += (first_year..date.year)
+        .map(|year| leaps.year_length(year))
+        .sum::<i64>();
+    days += (1..date.month)
+        .map(|month| leaps.month_length(date.year, month))
+        .sum::<i64>();
+    days += date.day as i64 - 1;
+    epoch.date().checked_add_signed(Duration::days(days))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HijriParams {
+    epoch: Option<Epoch>,
+    leaps: Option<Leaps>,
+}
+
+fn describe(hijri: &HijriDate, gregorian: NaiveDate, epoch: Epoch, leaps: Leaps) -> Value {
+    json!({
+        "gregorian": gregorian.to_string(),
+        "weekday": gregorian.weekday().to_string(),
+        "hijri": {
+            "year": hijri.year,
+            "month": hijri.month,
+            "day": hijri.day,
+            "month_name": MONTHS[hijri.month as usize - 1],
+            "leap_year": leaps.is_leap_year(hijri.year),
+            "date": format!("{}-{:02}-{:02}", hijri.year, hijri.month, hijri.day),
+        },
+        "epoch": epoch.name(),
+        "leaps": leaps.name(),
+    })
+}
+
+/// Converts a Gregorian date to the tabular Islamic calendar.
+pub async fn to_hijri_handler(
+    Path(date): Path<String>,
+    Query(params): Query<HijriParams>,
+) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?.date_naive();
+    let epoch = params.epoch.unwrap_or(Epoch::Civil);
+    let leaps = params.leaps.unwrap_or(Leaps::Standard);
+    let hijri = to_hijri(date, epoch, leaps);
+
+    Ok(Json(describe(&hijri, date, epoch, leaps)))
+}
+
+/// Converts a tabular Islamic date, written `YYYY-MM-DD`, to the Gregorian calendar.
+pub async fn from_hijri_handler(
+    Path(date): Path<String>,
+    Query(params): Query<HijriParams>,
+) -> Result<Json<Value>, AppError> {
+    let epoch = params.epoch.unwrap_or(Epoch::Civil);
+    let leaps = params.leaps.unwrap_or(Leaps::Standard);
+    let mut parts = date.splitn(3, '-');
+    let mut next = || parts.next().and_then(|part| part.parse::<i64>().ok());
+    let (year, month, day) = match (next(), next(), next()) {
+        (Some(year), Some(month), Some(day)) => (year, month, day),
+        _ => return Err(AppError::InvalidDate),
+    };
+    let hijri = HijriDate {
+        year,
+        month: u32::try_from(month).map_err(|_| AppError::InvalidDate)?,
+        day: u32::try_from(day).map_err(|_| AppError::InvalidDate)?,
+    };
+    let gregorian = from_hijri(&hijri, epoch, leaps).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(describe(&hijri, gregorian, epoch, leaps)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let ramadan = NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let hijri = HijriDate {
+            year: 1445,
+            month: 9,
+            day: 1,
+        };
+        assert_eq!(to_hijri(ramadan, Epoch::Civil, Leaps::Standard), hijri);
+        assert_eq!(
+            from_hijri(&hijri, Epoch::Civil, Leaps::Standard),
+            Some(ramadan)
+        );
+        assert_eq!(
+            from_hijri(&hijri, Epoch::Astronomical, Leaps::Standard),
+            Some(NaiveDate::from_ymd_opt(2024, 3, 10).unwrap())
+        );
+        assert_eq!(
+            to_hijri(
+                NaiveDate::from_ymd_opt(622, 7, 19).unwrap(),
+                Epoch::Civil,
+                Leaps::Standard
+            ),
+            HijriDate {
+                year: 1,
+                month: 1,
+                day: 1
+            }
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let start = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        for days in (0..12_000).step_by(7) {
+            let date = start + Duration::days(days);
+            for leaps in &[
+                Leaps::Standard,
+                Leaps::Kuwaiti,
+                Leaps::Fatimid,
+                Leaps::Habash,
+            ] {
+                let hijri = to_hijri(date, Epoch::Civil, *leaps);
+                assert_eq!(from_hijri(&hijri, Epoch::Civil, *leaps), Some(date));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_dates() {
+        let date = |year, month, day| HijriDate { year, month, day };
+        assert_eq!(
+            from_hijri(&date(1445, 13, 1), Epoch::Civil, Leaps::Standard),
+            None
+        );
+        assert_eq!(
+            from_hijri(&date(1445, 2, 30), Epoch::Civil, Leaps::Standard),
+            None
+        );
+        // 1446 is the 6th year of its cycle, a common year
+        assert_eq!(
+            from_hijri(&date(1446, 12, 30), Epoch::Civil, Leaps::Standard),
+            None
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Hybrid logical clock (Kulkarni et al., 2014).
+//!
+//! Each timestamp pairs the largest physical time seen so far, in milliseconds, with a
+//! logical counter ordering events that share it. Timestamps received from other nodes
+//! are merged in, so that anything happening here afterwards is ordered after them.
+
+use axum::extract::State;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::sync::{Arc, Mutex};
+
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::parse_date;
+use crate::sequence::pack;
+
+/// Remote timestamps further ahead of our physical clock are rejected, so that a single
+/// node with a broken clock can't drag everybody into the future.
+const MAX_DRIFT_MS: i64 = 60 * 1000;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Timestamp {
+    pub physical_ms: i64,
+    pub logical: u32,
+}
+
+impl Timestamp {
+    pub fn to_json(self) -> Value {
+        json!({
+            "physical_ms": self.physical_ms,
+            "logical": self.logical,
+            "packed": pack(self.physical_ms, self.logical),
+        })
+    }
+}
+
+/// Maximum clock skew assumed between nodes when comparing timestamps, unless set
+/// through `max_clock_skew_ms` in the configuration.
+pub const DEFAULT_MAX_SKEW_MS: i64 = 250;
+
+#[derive(Clone, Default)]
+pub struct Clock {
+    last: Arc<Mutex<Timestamp>>,
+    max_skew_ms: i64,
+}
+
+impl Clock {
+    pub fn new(max_skew_ms: i64) -> Clock {
+        Clock {
+            last: Arc::default(),
+            max_skew_ms,
+        }
+    }
+
+    /// Timestamp for a local or send event.
+    pub fn now(&self, physical_ms: i64) -> Timestamp {
+        let mut last = self.last.lock().unwrap();
+        *last = if physical_ms > last.physical_ms {
+            Timestamp {
+                physical_ms,
+                logical: 0,
+            }
+        } else {
+            Timestamp {
+                physical_ms: last.physical_ms,
+                logical: last.logical + 1,
+            }
+        };
+        *last
+    }
+
+    /// Timestamp for the receipt of a message stamped `remote`.
+    pub fn update(&self, remote: Timestamp, physical_ms: i64) -> Timestamp {
+        let mut last = self.last.lock().unwrap();
+        let physical = physical_ms.max(last.physical_ms).max(remote.physical_ms);
+        let logical = if physical == last.physical_ms && physical == remote.physical_ms {
+            last.logical.max(remote.logical) + 1
+        } else if physical == last.physical_ms {
+            last.logical + 1
+        } else if physical == remote.physical_ms {
+            remote.logical + 1
+        } else {
+            0
+        };
+        *last = Timestamp {
+            physical_ms: physical,
+            logical,
+        };
+        *last
+    }
+}
+
+pub async fn now_handler(State(clock): State<Clock>) -> Json<Value> {
+    Json(clock.now(Utc::now().timestamp_millis()).to_json())
+}
+
+pub async fn update_handler(
+    State(clock): State<Clock>,
+    Json(remote): Json<Timestamp>,
+) -> Result<Json<Value>, AppError> {
+    let physical_ms = Utc::now().timestamp_millis();
+    if remote.physical_ms - physical_ms > MAX_DRIFT_MS {
+        return Err(AppError::BadRequest(
+            "Remote timestamp is too far in the future".to_string(),
+        ));
+    }
+
+    Ok(Json(clock.update(remote, physical_ms).to_json()))
+}
+
+/// Either a hybrid logical clock timestamp or a plain date, whose logical part is zero.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Stamp {
+    Hlc(Timestamp),
+    Date(String),
+}
+
+impl Stamp {
+    fn resolve(&self) -> Result<Timestamp, AppError> {
+        match self {
+            Stamp::Hlc(timestamp) => Ok(*timestamp),
+            Stamp::Date(date) => Ok(Timestamp {
+                physical_ms: parse_date(date)?.timestamp_millis(),
+                logical: 0,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareRequest {
+    a: Stamp,
+    b: Stamp,
+    /// Overrides the configured maximum clock skew.
+    max_skew_ms: Option<i64>,
+}
+
+/// How two timestamps relate once clock skew between their nodes is accounted for.
+#[derive(Debug, PartialEq)]
+pub enum Causality {
+    Equal,
+    Before,
+    After,
+    /// Ordered by the clock, but too close to tell which happened first in real time.
+    Concurrent,
+}
+
+pub fn compare(a: Timestamp, b: Timestamp, max_skew_ms: i64) -> Causality {
+    if a == b {
+        Causality::Equal
+    } else if (a.physical_ms - b.physical_ms).abs() < max_skew_ms {
+        Causality::Concurrent
+    } else if a < b {
+        Causality::Before
+    } else {
+        Causality::After
+    }
+}
+
+pub async fn compare_handler(
+    State(clock): State<Clock>,
+    Json(request): Json<CompareRequest>,
+) -> Result<Json<Value>, AppError> {
+    let a = request.a.resolve()?;
+    let b = request.b.resolve()?;
+    let max_skew_ms = request.max_skew_ms.unwrap_or(clock.max_skew_ms);
+    if max_skew_ms < 0 {
+        return Err(AppError::BadRequest(
+            "max_skew_ms must not be negative".to_string(),
+        ));
+    }
+
+    let causality = match compare(a, b, max_skew_ms) {
+        Causality::Equal => "equal",
+        Causality::Before => "before",
+        Causality::After => "after",
+        Causality::Concurrent => "concurrent",
+    };
+    let order = match a.cmp(&b) {
+        Ordering::Less => "before",
+        Ordering::Equal => "equal",
+        Ordering::Greater => "after",
+    };
+
+    Ok(Json(json!({
+        "a": a.to_json(),
+        "b": b.to_json(),
+        "order": order,
+        "causality": causality,
+        "delta_ms": b.physical_ms - a.physical_ms,
+        "uncertainty_ms": max_skew_ms,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(physical_ms: i64, logical: u32) -> Timestamp {
+        Timestamp {
+            physical_ms,
+            logical,
+        }
+    }
+
+    #[test]
+    fn local_events() {
+        let clock = Clock::default();
+        assert_eq!(clock.now(100), ts(100, 0));
+        assert_eq!(clock.now(100), ts(100, 1));
+        assert_eq!(clock.now(90), ts(100, 2));
+        assert_eq!(clock.now(101), ts(101, 0));
+    }
+
+    #[test]
+    fn receive_events() {
+        let clock = Clock::default();
+        clock.now(100);
+        // remote ahead of both clocks
+        assert_eq!(clock.update(ts(120, 5), 110), ts(120, 6));
+        // same physical time on both sides
+        assert_eq!(clock.update(ts(120, 9), 110), ts(120, 10));
+        // remote behind
+        assert_eq!(clock.update(ts(50, 3), 110), ts(120, 11));
+        // physical clock ahead of everything
+        assert_eq!(clock.update(ts(50, 3), 130), ts(130, 0));
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(compare(ts(100, 1), ts(100, 1), 250), Causality::Equal);
+        assert_eq!(compare(ts(100, 1), ts(100, 2), 250), Causality::Concurrent);
+        assert_eq!(compare(ts(100, 1), ts(100, 2), 0), Causality::Before);
+        assert_eq!(compare(ts(1000, 0), ts(100, 9), 250), Causality::After);
+    }
+}
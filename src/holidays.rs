@@ -0,0 +1,200 @@
+//! Embedded public holiday rules for a few countries.
+//!
+//! Only nationwide holidays are listed, on their nominal dates: days observed in lieu
+//! when a holiday falls on a weekend are not included.
+
+use axum::Json;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde_json::{json, Value};
+
+use crate::calendar::{easter, nth_weekday};
+use crate::error::AppError;
+use crate::extract::Path;
+
+enum Rule {
+    /// Same month and day every year.
+    Fixed(u32, u32),
+    /// The n-th weekday of a month, negative counting from the end.
+    Nth(u32, Weekday, i32),
+    /// Days from Easter Sunday.
+    Easter(i64),
+}
+
+pub struct Holiday {
+    pub name: &'static str,
+    rule: Rule,
+    /// First year the holiday has been observed.
+    since: Option<i32>,
+}
+
+impl Holiday {
+    pub fn date(&self, year: i32) -> Option<NaiveDate> {
+        if self.since.is_some_and(|since| year < since) {
+            return None;
+        }
+        match self.rule {
+            Rule::Fixed(month, day) => NaiveDate::from_ymd_opt(year, month, day),
+            Rule::Nth(month, weekday, n) => nth_weekday(year, month, weekday, n),
+            Rule::Easter(days) => easter(year).map(|easter| easter + Duration::days(days)),
+        }
+    }
+}
+
+const fn holiday(name: &'static str, rule: Rule) -> Holiday {
+    Holiday {
+        name,
+        rule,
+        since: None,
+    }
+}
+
+const US: &[Holiday] = &[
+    holiday("New Year's Day", Rule::Fixed(1, 1)),
+    holiday("Martin Luther King Jr. Day", Rule::Nth(1, Weekday::Mon, 3)),
+    holiday("Washington's Birthday", Rule::Nth(2, Weekday::Mon, 3)),
+    holiday("Memorial Day", Rule::Nth(5, Weekday::Mon, -1)),
+    Holiday {
+        name: "Juneteenth National Independence Day",
+        rule: Rule::Fixed(6, 19),
+        since: Some(2021),
+    },
+    holiday("Independence Day", Rule::Fixed(7, 4)),
+    holiday("Labor Day", Rule::Nth(9, Weekday::Mon, 1)),
+    holiday("Columbus Day", Rule::Nth(10, Weekday::Mon, 2)),
+    holiday("Veterans Day", Rule::Fixed(11, 11)),
+    holiday("Thanksgiving Day", Rule::Nth(11, Weekday::Thu, 4)),
+    holiday("Christmas Day", Rule::Fixed(12, 25)),
+];
+
+const IT: &[Holiday] = &[
+    holiday("Capodanno", Rule::Fixed(1, 1)),
+    holiday("Epifania", Rule::Fixed(1, 6)),
+    holiday("Pasqua", Rule::Easter(0)),
+    holiday("Lunedì dell'Angelo", Rule::Easter(1)),
+    holiday("Festa della Liberazione", Rule::Fixed(4, 25)),
+    holiday("Festa del Lavoro", Rule::Fixed(5, 1)),
+    holiday("Festa della Repubblica", Rule::Fixed(6, 2)),
+    holiday("Ferragosto", Rule::Fixed(8, 15)),
+    holiday("Ognissanti", Rule::Fixed(11, 1)),
+    holiday("Immacolata Concezione", Rule::Fixed(12, 8)),
+    holiday("Natale", Rule::Fixed(12, 25)),
+    holiday("Santo Stefano", Rule::Fixed(12, 26)),
+];
+
+const DE: &[Holiday] = &[
+    holiday("Neujahr", Rule::Fixed(1, 1)),
+    holiday("Karfreitag", Rule::Easter(-2)),
+    holiday("Ostermontag", Rule::Easter(1)),
+    holiday("Tag der Arbeit", Rule::Fixed(5, 1)),
+    holiday("Christi Himmelfahrt", Rule::Easter(39)),
+    holiday("Pfingstmontag", Rule::Easter(50)),
+    holiday("Tag der Deutschen Einheit", Rule::Fixed(10, 3)),
+    holiday("1. Weihnachtstag", Rule::Fixed(12, 25)),
+    holiday("2. Weihnachtstag", Rule::Fixed(12, 26)),
+];
+
+const FR: &[Holiday] = &[
+    holiday("Jour de l'an", Rule::Fixed(1, 1)),
+    holiday("Lundi de Pâques", Rule::Easter(1)),
+    holiday("Fête du Travail", Rule::Fixed(5, 1)),
+    holiday("Victoire 1945", Rule::Fixed(5, 8)),
+    holiday("Ascension", Rule::Easter(39)),
+    holiday("Lundi de Pentecôte", Rule::Easter(50)),
+    holiday("Fête nationale", Rule::Fixed(7, 14)),
+    holiday("Assomption", Rule::Fixed(8, 15)),
+    holiday("Toussaint", Rule::Fixed(11, 1)),
+    holiday("Armistice 1918", Rule::Fixed(11, 11)),
+    holiday("Noël", Rule::Fixed(12, 25)),
+];
+
+/// England and Wales bank holidays.
+const GB: &[Holiday] = &[
+    holiday("New Year's Day", Rule::Fixed(1, 1)),
+    holiday("Good Friday", Rule::Easter(-2)),
+    holiday("Easter Monday", Rule::Easter(1)),
+    holiday("Early May bank holiday", Rule::Nth(5, Weekday::Mon, 1)),
+    holiday("Spring bank holiday", Rule::Nth(5, Weekday::Mon, -1)),
+    holiday("Summer bank holiday", Rule::Nth(8, Weekday::Mon, -1)),
+    holiday("Christmas Day", Rule::Fixed(12, 25)),
+    holiday("Boxing Day", Rule::Fixed(12, 26)),
+];
+
+/// Holiday rules of a country, by ISO 3166-1 alpha-2 code.
+pub fn country(code: &str) -> Option<&'static [Holiday]> {
+    match code.to_ascii_uppercase().as_str() {
+        "US" => Some(US),
+        "IT" => Some(IT),
+        "DE" => Some(DE),
+        "FR" => Some(FR),
+        "GB" | "UK" => Some(GB),
+        _ => None,
+    }
+}
+
+pub fn is_holiday(holidays: &[Holiday], date: NaiveDate) -> bool {
+    holidays
+        .iter()
+        .any(|holiday| holiday.date(date.year()) == Some(date))
+}
+
+pub async fn holidays_handler(
+    Path((code, year)): Path<(String, i32)>,
+) -> Result<Json<Value>, AppError> {
+    let holidays =
+        country(&code).ok_or_else(|| AppError::NotFound("Unknown country".to_string()))?;
+
+    let mut dates: Vec<(NaiveDate, &str)> = holidays
+        .iter()
+        .filter_map(|holiday| Some((holiday.date(year)?, holiday.name)))
+        .collect();
+    dates.sort();
+
+    let dates: Vec<Value> = dates
+        .iter()
+        .map(|(date, name)| {
+            json!({
+                "date": date.to_string(),
+                "name": name,
+                "weekday": date.weekday().to_string(),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "country": code.to_ascii_uppercase(),
+        "year": year,
+        "holidays": dates,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn movable_holidays() {
+        let de = country("de").unwrap();
+        assert!(is_holiday(de, NaiveDate::from_ymd_opt(2021, 4, 2).unwrap()));
+        assert!(is_holiday(
+            de,
+            NaiveDate::from_ymd_opt(2021, 5, 24).unwrap()
+        ));
+        assert!(!is_holiday(
+            de,
+            NaiveDate::from_ymd_opt(2021, 5, 25).unwrap()
+        ));
+    }
+
+    #[test]
+    fn observed_since() {
+        let us = country("US").unwrap();
+        assert!(!is_holiday(
+            us,
+            NaiveDate::from_ymd_opt(2020, 6, 19).unwrap()
+        ));
+        assert!(is_holiday(
+            us,
+            NaiveDate::from_ymd_opt(2021, 6, 19).unwrap()
+        ));
+    }
+}
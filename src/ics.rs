@@ -0,0 +1,165 @@
+//! iCalendar (RFC 5545) events, for "Add to calendar" links.
+//!
+//! Times are written in UTC, which every client understands without a `VTIMEZONE`
+//! definition; the requested timezone only applies to the local times given as input.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::duration::IsoDuration;
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::timezone::{parse_in_zone, parse_tz};
+
+/// Lines are folded at this many octets, as the RFC requires.
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Makes the identifiers of events created within the same nanosecond unique.
+static EVENTS: AtomicU64 = AtomicU64::new(0);
+
+fn basic_format(instant: DateTime<Utc>) -> String {
+    instant.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes a `TEXT` value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Folds a content line into lines of at most 75 octets, continuation lines starting with
+/// a space, without splitting UTF-8 characters.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / MAX_LINE_LENGTH * 3);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+pub fn event(
+    uid: &str,
+    stamp: DateTime<Utc>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    summary: &str,
+) -> String {
+    [
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//timestamp-microservice//EN".to_string(),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", uid),
+        format!("DTSTAMP:{}", basic_format(stamp)),
+        format!("DTSTART:{}", basic_format(start)),
+        format!("DTEND:{}", basic_format(end)),
+        format!("SUMMARY:{}", escape(summary)),
+        "END:VEVENT".to_string(),
+        "END:VCALENDAR".to_string(),
+    ]
+    .iter()
+    .map(|line| fold(line))
+    .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IcsRequest {
+    start: String,
+    /// Either `end` or an ISO 8601 `duration` such as `PT1H`.
+    end: Option<String>,
+    duration: Option<String>,
+    summary: String,
+    /// Timezone of local start and end times, the default timezone otherwise.
+    tz: Option<String>,
+}
+
+pub async fn ics_handler(Json(request): Json<IcsRequest>) -> Result<(HeaderMap, String), AppError> {
+    let tz = parse_tz(request.tz.as_deref())?;
+    let start = parse_in_zone(&request.start, tz)?;
+    let end = match (&request.end, &request.duration) {
+        (Some(end), None) => parse_in_zone(end, tz)?,
+        (None, Some(duration)) => IsoDuration::parse(duration)
+            .ok_or_else(|| AppError::BadRequest(format!("Invalid duration {}", duration)))?
+            .add_to(start, tz)
+            .ok_or(AppError::InvalidDate)?,
+        _ => {
+            return Err(AppError::BadRequest(
+                "Exactly one of end and duration is required".to_string(),
+            ))
+        }
+    };
+    if end < start {
+        return Err(AppError::BadRequest(
+            "The event must not end before it starts".to_string(),
+        ));
+    }
+
+    let now = Utc::now();
+    let uid = format!(
+        "{}-{}@timestamp-microservice",
+        now.timestamp_nanos_opt().unwrap(),
+        EVENTS.fetch_add(1, Ordering::Relaxed)
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/calendar; charset=utf-8"),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_static("attachment; filename=\"event.ics\""),
+    );
+    Ok((headers, event(&uid, now, start, end, &request.summary)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn vevent() {
+        let start = Utc.with_ymd_and_hms(2016, 12, 25, 18, 0, 0).unwrap();
+        let ics = event(
+            "1@example",
+            start,
+            start,
+            Utc.with_ymd_and_hms(2016, 12, 25, 21, 0, 0).unwrap(),
+            "Dinner; family, friends",
+        );
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.contains("\r\nDTSTART:20161225T180000Z\r\nDTEND:20161225T210000Z\r\n"));
+        assert!(ics.contains("\r\nSUMMARY:Dinner\\; family\\, friends\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let folded = fold(&format!("SUMMARY:{}", "é".repeat(40)));
+        let lines: Vec<&str> = folded.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(lines[1].starts_with(' '));
+    }
+}
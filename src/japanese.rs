@@ -0,0 +1,117 @@
+//! Japanese era (wareki) dates, from the Meiji era onwards.
+
+use axum::Json;
+use chrono::{Datelike, NaiveDate};
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+
+pub struct Era {
+    pub name: &'static str,
+    pub kanji: &'static str,
+    /// First day of the era, in the Gregorian calendar.
+    pub start: (i32, u32, u32),
+}
+
+/// Eras by start date. The first year of an era runs until the end of its Gregorian year.
+const ERAS: [Era; 5] = [
+    Era {
+        name: "Meiji",
+        kanji: "明治",
+        start: (1868, 10, 23),
+    },
+    Era {
+        name: "Taisho",
+        kanji: "大正",
+        start: (1912, 7, 30),
+    },
+    Era {
+        name: "Showa",
+        kanji: "昭和",
+        start: (1926, 12, 25),
+    },
+    Era {
+        name: "Heisei",
+        kanji: "平成",
+        start: (1989, 1, 8),
+    },
+    Era {
+        name: "Reiwa",
+        kanji: "令和",
+        start: (2019, 5, 1),
+    },
+];
+
+/// Era of a date and the year within it, `None` before the Meiji era.
+pub fn era(date: NaiveDate) -> Option<(&'static Era, i32)> {
+    let era = ERAS.iter().rev().find(|era| {
+        let (year, month, day) = era.start;
+        date >= NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    })?;
+    Some((era, date.year() - era.start.0 + 1))
+}
+
+/// The era of a date as reported in responses.
+pub fn describe(date: NaiveDate) -> Option<Value> {
+    let (era, year) = era(date)?;
+    // the first year of an era is written 元年 rather than 1年
+    let kanji_year = if year == 1 {
+        "元".to_string()
+    } else {
+        year.to_string()
+    };
+    Some(json!({
+        "name": era.name,
+        "kanji": era.kanji,
+        "year": year,
+        "label": format!("{} {}", era.name, year),
+        "japanese": format!(
+            "{}{}年{}月{}日",
+            era.kanji,
+            kanji_year,
+            date.month(),
+            date.day()
+        ),
+    }))
+}
+
+pub async fn japanese_handler(Path(date): Path<String>) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?;
+    let era = describe(date.date_naive()).ok_or_else(|| {
+        AppError::Unprocessable("Dates before the Meiji era are not supported".to_string())
+    })?;
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "era": era,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn era_of(y: i32, m: u32, d: u32) -> Option<(&'static str, i32)> {
+        era(NaiveDate::from_ymd_opt(y, m, d).unwrap()).map(|(era, year)| (era.name, year))
+    }
+
+    #[test]
+    fn boundaries() {
+        assert_eq!(era_of(2019, 4, 30), Some(("Heisei", 31)));
+        assert_eq!(era_of(2019, 5, 1), Some(("Reiwa", 1)));
+        assert_eq!(era_of(2024, 12, 25), Some(("Reiwa", 6)));
+        assert_eq!(era_of(1989, 1, 7), Some(("Showa", 64)));
+        assert_eq!(era_of(1989, 1, 8), Some(("Heisei", 1)));
+        assert_eq!(era_of(1868, 1, 1), None);
+    }
+
+    #[test]
+    fn first_year() {
+        let era = describe(NaiveDate::from_ymd_opt(2019, 5, 1).unwrap()).unwrap();
+        assert_eq!(era["japanese"], "令和元年5月1日");
+        assert_eq!(era["label"], "Reiwa 1");
+    }
+}
@@ -0,0 +1,238 @@
+//! The Julian calendar and the switch to the Gregorian one, for historical dates.
+//!
+//! chrono reads every date in the proleptic Gregorian calendar, which is wrong for most
+//! dates written before 1582. With `?calendar=julian`, dates of `/api/:date` starting with
+//! `YYYY-MM-DD` are read in the Julian calendar instead, and with `?calendar=hybrid` in
+//! the Julian calendar before `?cutover=`, the first Gregorian day, and in the Gregorian
+//! one from then on. The days skipped by the switch, October 5 to 14, 1582 with the
+//! default cutover, don't exist. Timestamps are instants whatever the calendar, only their
+//! description in it changes.
+
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+use crate::error::AppError;
+
+/// Day difference between Julian day numbers and chrono's days from the common era.
+const JDN_OF_CE: i64 = 1_721_425;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Calendar {
+    Gregorian,
+    Julian,
+    /// Julian before the cutover, Gregorian after.
+    Hybrid,
+}
+
+/// How dates are reckoned: a calendar, and the first Gregorian day of a hybrid one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reckoning {
+    pub calendar: Calendar,
+    pub cutover: NaiveDate,
+}
+
+impl Reckoning {
+    /// The reckoning asked for in a query string, if any. The cutover defaults to the
+    /// papal one of October 15, 1582.
+    pub fn new(
+        calendar: Option<Calendar>,
+        cutover: Option<&str>,
+    ) -> Result<Option<Reckoning>, AppError> {
+        let cutover = match cutover {
+            Some(cutover) => NaiveDate::parse_from_str(cutover, "%Y-%m-%d")
+                .map_err(|_| AppError::BadRequest(format!("Invalid cutover {}", cutover)))?,
+            None => NaiveDate::from_ymd_opt(1582, 10, 15).unwrap(),
+        };
+        Ok(calendar.map(|calendar| Reckoning { calendar, cutover }))
+    }
+
+    /// The proleptic Gregorian date of a date written in this reckoning.
+    pub fn to_gregorian(self, year: i32, month: u32, day: u32) -> Result<NaiveDate, AppError> {
+        let gregorian = || NaiveDate::from_ymd_opt(year, month, day);
+        let date = match self.calendar {
+            Calendar::Gregorian => gregorian(),
+            Calendar::Julian => from_julian(year, month, day),
+            Calendar::Hybrid => {
+                let cutover = &self.cutover;
+                if (year, month, day) >= (cutover.year(), cutover.month(), cutover.day()) {
+                    gregorian()
+                } else {
+                    // Julian dates from the cutover on were skipped
+                    from_julian(year, month, day).filter(|date| date < cutover)
+                }
+            }
+        };
+        date.ok_or(AppError::ImpossibleDate)
+    }
+
+    /// The calendar a Gregorian date is written in, with its year, month and day there.
+    pub fn written(self, date: NaiveDate) -> (Calendar, i32, u32, u32) {
+        let julian = match self.calendar {
+            Calendar::Gregorian => false,
+            Calendar::Julian => true,
+            Calendar::Hybrid => date < self.cutover,
+        };
+        if julian {
+            let (year, month, day) = to_julian(date);
+            (Calendar::Julian, year, month, day)
+        } else {
+            (Calendar::Gregorian, date.year(), date.month(), date.day())
+        }
+    }
+
+    /// Describes a date in this reckoning, for responses.
+    pub fn describe(self, date: NaiveDate) -> Value {
+        let (calendar, year, month, day) = self.written(date);
+        let mut description = json!({
+            "calendar": match calendar {
+                Calendar::Julian => "julian",
+                _ => "gregorian",
+            },
+            "date": format!("{:04}-{:02}-{:02}", year, month, day),
+        });
+        if self.calendar == Calendar::Hybrid {
+            description["cutover"] = json!(self.cutover.to_string());
+        }
+        description
+    }
+}
+
+pub fn is_julian_leap_year(year: i32) -> bool {
+    year.rem_euclid(4) == 0
+}
+
+/// The Gregorian date of a Julian one, if it exists.
+pub fn from_julian(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    let length = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_julian_leap_year(year) => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if !(1..=length).contains(&day) {
+        return None;
+    }
+    // days counted from March, so that leap days end the year
+    let shift = i64::from(month <= 2);
+    let year = i64::from(year) + 4800 - shift;
+    let month = i64::from(month) + 12 * shift - 3;
+    let jdn = i64::from(day) + (153 * month + 2) / 5 + 365 * year + year.div_euclid(4) - 32_083;
+    NaiveDate::from_num_days_from_ce_opt(i32::try_from(jdn - JDN_OF_CE).ok()?)
+}
+
+/// The Julian year, month and day of a Gregorian date.
+pub fn to_julian(date: NaiveDate) -> (i32, u32, u32) {
+    let c = i64::from(date.num_days_from_ce()) + JDN_OF_CE + 32_082;
+    let d = (4 * c + 3).div_euclid(1461);
+    let e = c - 1461 * d / 4;
+    let m = (5 * e + 2) / 153;
+    let day = e - (153 * m + 2) / 5 + 1;
+    let month = m + 3 - 12 * (m / 10);
+    let year = d - 4800 + m / 10;
+    (year as i32, month as u32, day as u32)
+}
+
+/// Rewrites the leading `YYYY-MM-DD` of an input written in `reckoning` to the Gregorian
+/// calendar, keeping whatever follows it. Other inputs are left as they are.
+pub fn to_gregorian_input(input: &str, reckoning: Reckoning) -> Result<Cow<'_, str>, AppError> {
+    let bytes = input.as_bytes();
+    let digits = |range: std::ops::Range<usize>| {
+        bytes
+            .get(range.clone())
+            .filter(|part| part.iter().all(u8::is_ascii_digit))?;
+        input[range].parse::<u32>().ok()
+    };
+    let is_date = bytes.get(4) == Some(&b'-')
+        && bytes.get(7) == Some(&b'-')
+        && matches!(bytes.get(10), None | Some(b'T') | Some(b't') | Some(b' '));
+    let (year, month, day) = match (digits(0..4), digits(5..7), digits(8..10)) {
+        (Some(year), Some(month), Some(day)) if is_date => (year, month, day),
+        _ => return Ok(Cow::Borrowed(input)),
+    };
+    let date = reckoning.to_gregorian(year as i32, month, day)?;
+    Ok(Cow::Owned(format!(
+        "{:04}-{:02}-{:02}{}",
+        date.year(),
+        date.month(),
+        date.day(),
+        &input[10..]
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hybrid(cutover: &str) -> Reckoning {
+        Reckoning::new(Some(Calendar::Hybrid), Some(cutover))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn julian_dates() {
+        assert_eq!(
+            from_julian(1582, 10, 5),
+            Some(NaiveDate::from_ymd_opt(1582, 10, 15).unwrap())
+        );
+        assert_eq!(
+            from_julian(1500, 2, 29),
+            Some(NaiveDate::from_ymd_opt(1500, 3, 10).unwrap())
+        );
+        assert_eq!(
+            from_julian(2000, 1, 1),
+            Some(NaiveDate::from_ymd_opt(2000, 1, 14).unwrap())
+        );
+        assert_eq!(from_julian(1501, 2, 29), None);
+        assert_eq!(
+            to_julian(NaiveDate::from_ymd_opt(1582, 10, 14).unwrap()),
+            (1582, 10, 4)
+        );
+        assert_eq!(
+            to_julian(NaiveDate::from_ymd_opt(-44, 3, 13).unwrap()),
+            (-44, 3, 15)
+        );
+    }
+
+    #[test]
+    fn cutovers() {
+        let papal = hybrid("1582-10-15");
+        assert_eq!(
+            papal.to_gregorian(1582, 10, 4).unwrap(),
+            NaiveDate::from_ymd_opt(1582, 10, 14).unwrap()
+        );
+        assert_eq!(
+            papal.to_gregorian(1582, 10, 15).unwrap(),
+            NaiveDate::from_ymd_opt(1582, 10, 15).unwrap()
+        );
+        assert!(papal.to_gregorian(1582, 10, 10).is_err());
+
+        let british = hybrid("1752-09-14");
+        assert!(british.to_gregorian(1752, 9, 3).is_err());
+        assert_eq!(
+            british.describe(NaiveDate::from_ymd_opt(1752, 9, 13).unwrap()),
+            json!({"calendar": "julian", "date": "1752-09-02", "cutover": "1752-09-14"})
+        );
+    }
+
+    #[test]
+    fn rewrites_inputs() {
+        let julian = Reckoning::new(Some(Calendar::Julian), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            to_gregorian_input("1500-02-29T12:00:00Z", julian).unwrap(),
+            "1500-03-10T12:00:00Z"
+        );
+        assert_eq!(
+            to_gregorian_input("1482624000", julian).unwrap(),
+            "1482624000"
+        );
+        assert!(to_gregorian_input("1501-02-29", julian).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! Leap years of the proleptic Gregorian calendar in a range of years.
+
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::calendar::is_leap_year;
+use crate::error::AppError;
+use crate::query::Query;
+
+/// Longest range of years listed at once.
+const MAX_YEARS: i32 = 10_000;
+
+/// Leap years from `from` to `to`, both included.
+pub fn leap_years(from: i32, to: i32) -> Vec<i32> {
+    (from..=to).filter(|year| is_leap_year(*year)).collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeapParams {
+    from: i32,
+    to: i32,
+}
+
+pub async fn leap_handler(Query(params): Query<LeapParams>) -> Result<Json<Value>, AppError> {
+    let (from, to) = (params.from, params.to);
+    if from > to {
+        return Err(AppError::BadRequest(
+            "The range must not end before it starts".to_string(),
+        ));
+    }
+    if to.checked_sub(from).is_none_or(|span| span >= MAX_YEARS) {
+        return Err(AppError::BadRequest(format!(
+            "At most {} years can be listed at once",
+            MAX_YEARS
+        )));
+    }
+
+    let years = leap_years(from, to);
+    Ok(Json(json!({
+        "from": from,
+        "to": to,
+        "count": years.len(),
+        "leap_years": years,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn centuries() {
+        assert_eq!(leap_years(1896, 1912), vec![1896, 1904, 1908, 1912]);
+        assert_eq!(leap_years(1990, 2050).len(), 15);
+        assert_eq!(leap_years(2000, 2000), vec![2000]);
+        assert!(leap_years(2001, 2003).is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+//! Conversions between UTC, TAI and GPS time using the table of leap seconds.
+//!
+//! TAI is reported on the unix scale: the number of SI seconds since 1970-01-01 TAI, which
+//! runs ahead of the unix timestamp by the current TAI-UTC offset. GPS time counts from its
+//! epoch, 1980-01-06T00:00:00 UTC, and runs 19 seconds behind TAI. Instants before 1972,
+//! when UTC was not yet kept within whole seconds of TAI, are not supported.
+//!
+//! The table has to be updated whenever the IERS announces a new leap second.
+
+use axum::Json;
+use chrono::DateTime;
+use chrono_tz::Tz;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::timezone::parse_in_zone;
+
+/// Unix timestamps from which each TAI-UTC offset applies, in seconds.
+const LEAP_SECONDS: [(i64, i64); 28] = [
+    (63072000, 10),   // 1972-01-01
+    (78796800, 11),   // 1972-07-01
+    (94694400, 12),   // 1973-01-01
+    (126230400, 13),  // 1974-01-01
+    (157766400, 14),  // 1975-01-01
+    (189302400, 15),  // 1976-01-01
+    (220924800, 16),  // 1977-01-01
+    (252460800, 17),  // 1978-01-01
+    (283996800, 18),  // 1979-01-01
+    (315532800, 19),  // 1980-01-01
+    (362793600, 20),  // 1981-07-01
+    (394329600, 21),  // 1982-07-01
+    (425865600, 22),  // 1983-07-01
+    (489024000, 23),  // 1985-07-01
+    (567993600, 24),  // 1988-01-01
+    (631152000, 25),  // 1990-01-01
+    (662688000, 26),  // 1991-01-01
+    (709948800, 27),  // 1992-07-01
+    (741484800, 28),  // 1993-07-01
+    (773020800, 29),  // 1994-07-01
+    (820454400, 30),  // 1996-01-01
+    (867715200, 31),  // 1997-07-01
+    (915148800, 32),  // 1999-01-01
+    (1136073600, 33), // 2006-01-01
+    (1230768000, 34), // 2009-01-01
+    (1341100800, 35), // 2012-07-01
+    (1435708800, 36), // 2015-07-01
+    (1483228800, 37), // 2017-01-01
+];
+
+/// The GPS epoch, 1980-01-06T00:00:00 UTC, as a unix timestamp.
+const GPS_EPOCH: i64 = 315964800;
+/// GPS time is a constant 19 seconds behind TAI.
+const TAI_GPS_OFFSET: i64 = 19;
+const SECONDS_PER_WEEK: i64 = 7 * 24 * 60 * 60;
+
+/// TAI-UTC offset in effect at the given unix timestamp.
+pub fn tai_offset(unix: i64) -> Option<i64> {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(since, _)| unix >= *since)
+        .map(|(_, offset)| *offset)
+}
+
+pub fn to_tai(unix: i64) -> Option<i64> {
+    unix.checked_add(tai_offset(unix)?)
+}
+
+/// Converts TAI seconds back to a unix timestamp, also telling whether the TAI second is
+/// an inserted leap second (`23:59:60` UTC), which unix time can't represent: it is then
+/// mapped onto the following second.
+pub fn from_tai(tai: i64) -> Option<(i64, bool)> {
+    let index = LEAP_SECONDS
+        .iter()
+        .rposition(|(since, offset)| tai >= since + offset)?;
+    let (_, offset) = LEAP_SECONDS[index];
+    let leap = LEAP_SECONDS
+        .get(index + 1)
+        .is_some_and(|(next, _)| tai == next + offset);
+    Some((tai - offset, leap))
+}
+
+pub fn to_gps(unix: i64) -> Option<i64> {
+    if unix < GPS_EPOCH {
+        return None;
+    }
+    Some(to_tai(unix)? - TAI_GPS_OFFSET - GPS_EPOCH)
+}
+
+pub fn from_gps(gps: i64) -> Option<(i64, bool)> {
+    if gps < 0 {
+        return None;
+    }
+    from_tai(gps.checked_add(GPS_EPOCH + TAI_GPS_OFFSET)?)
+}
+
+fn describe(unix: i64, leap: bool) -> Result<Value, AppError> {
+    let date = DateTime::from_timestamp(unix, 0).ok_or(AppError::InvalidDate)?;
+    let offset = tai_offset(unix).ok_or(AppError::InvalidDate)?;
+    let gps = to_gps(unix);
+
+    Ok(json!({
+        "unix": unix,
+        "utc": date.to_rfc2822(),
+        "leap_second": leap,
+        "tai": to_tai(unix),
+        "tai_utc_offset": offset,
+        "gps": gps,
+        "gps_week": gps.map(|gps| gps / SECONDS_PER_WEEK),
+        "gps_time_of_week": gps.map(|gps| gps % SECONDS_PER_WEEK),
+        "gps_utc_offset": offset - TAI_GPS_OFFSET,
+    }))
+}
+
+/// Converts a UTC date to TAI and GPS time.
+pub async fn of_handler(Path(date): Path<String>) -> Result<Json<Value>, AppError> {
+    let date = parse_in_zone(&date, Tz::UTC)?;
+    Ok(Json(describe(date.timestamp(), false)?))
+}
+
+pub async fn from_tai_handler(Path(tai): Path<String>) -> Result<Json<Value>, AppError> {
+    let tai: i64 = tai.parse().map_err(|_| AppError::InvalidDate)?;
+    let (unix, leap) = from_tai(tai).ok_or(AppError::InvalidDate)?;
+    Ok(Json(describe(unix, leap)?))
+}
+
+pub async fn from_gps_handler(Path(gps): Path<String>) -> Result<Json<Value>, AppError> {
+    let gps: i64 = gps.parse().map_err(|_| AppError::InvalidDate)?;
+    let (unix, leap) = from_gps(gps).ok_or(AppError::InvalidDate)?;
+    Ok(Json(describe(unix, leap)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets() {
+        assert_eq!(tai_offset(0), None);
+        assert_eq!(tai_offset(63072000), Some(10));
+        assert_eq!(tai_offset(1483228799), Some(36));
+        assert_eq!(tai_offset(1483228800), Some(37));
+    }
+
+    #[test]
+    fn gps() {
+        assert_eq!(to_gps(GPS_EPOCH), Some(0));
+        assert_eq!(to_gps(GPS_EPOCH - 1), None);
+        // 2017-01-01, 18 leap seconds after the GPS epoch
+        assert_eq!(to_gps(1483228800), Some(1483228800 - GPS_EPOCH + 18));
+        assert_eq!(
+            from_gps(1483228800 - GPS_EPOCH + 18),
+            Some((1483228800, false))
+        );
+    }
+
+    #[test]
+    fn round_trips_around_leap_second() {
+        for unix in 1483228790..1483228810 {
+            assert_eq!(from_tai(to_tai(unix).unwrap()), Some((unix, false)));
+        }
+        // 2016-12-31T23:59:60 UTC
+        assert_eq!(from_tai(1483228800 + 36), Some((1483228800, true)));
+        assert_eq!(from_tai(1483228800 + 37), Some((1483228800, false)));
+    }
+}
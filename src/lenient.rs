@@ -0,0 +1,146 @@
+//! Lenient reading of dates pasted from emails and documents, with `?lenient=true`.
+//!
+//! Inputs are normalized before parsing: surrounding whitespace is trimmed, runs of
+//! whitespace collapse to a single space and ordinal suffixes are dropped, so that
+//! ` 25th  Dec 2016 ` reads as `25 Dec 2016`. Dates spelling their month out in English,
+//! abbreviated or not and in any case, are then read in any order of their fields, such as
+//! `25 Dec 2016`, `December 25, 2016` or `Sun, Dec. 25th 2016`.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::error::AppError;
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// A number without its ordinal suffix, such as `25` for `25th`.
+fn strip_ordinal(token: &str) -> &str {
+    let lower = token.to_ascii_lowercase();
+    for suffix in ["st", "nd", "rd", "th"].iter() {
+        if lower.ends_with(suffix) {
+            let number = &token[..token.len() - suffix.len()];
+            if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) {
+                return number;
+            }
+        }
+    }
+    token
+}
+
+/// Trims the input, collapses its whitespace and drops ordinal suffixes.
+pub fn normalize(input: &str) -> String {
+    input
+        .split_whitespace()
+        .map(|token| match token.strip_suffix(',') {
+            Some(token) => format!("{},", strip_ordinal(token)),
+            None => strip_ordinal(token).to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds a name in `names` that `token` spells out or abbreviates to 3 letters or more,
+/// ignoring case.
+fn find_name(names: &[&str], token: &str) -> Option<usize> {
+    let token = token.to_ascii_lowercase();
+    if token.len() < 3 {
+        return None;
+    }
+    names.iter().position(|name| name.starts_with(&token))
+}
+
+/// Reads a normalized date with a spelled-out month, `None` when the input isn't one.
+pub fn parse_textual(input: &str) -> Result<Option<DateTime<Utc>>, AppError> {
+    let tokens: Vec<&str> = input
+        .split([' ', ','])
+        .map(|token| token.trim_end_matches('.'))
+        .filter(|token| !token.is_empty())
+        .collect();
+    let tokens = match tokens.split_first() {
+        Some((first, rest)) if find_name(&WEEKDAYS, first).is_some() => rest,
+        _ => &tokens[..],
+    };
+    if tokens.len() != 3 {
+        return Ok(None);
+    }
+
+    let (mut month, mut year, mut day) = (None, None, None);
+    for token in tokens {
+        if token.bytes().all(|b| b.is_ascii_digit()) {
+            match token.len() {
+                4 if year.is_none() => year = token.parse::<i32>().ok(),
+                1 | 2 if day.is_none() => day = token.parse::<u32>().ok(),
+                _ => return Ok(None),
+            }
+        } else {
+            match find_name(&MONTHS, token) {
+                Some(index) if month.is_none() => month = Some(index as u32 + 1),
+                _ => return Ok(None),
+            }
+        }
+    }
+    let (year, month, day) = match (year, month, day) {
+        (Some(year), Some(month), Some(day)) => (year, month, day),
+        _ => return Ok(None),
+    };
+    let date = NaiveDate::from_ymd_opt(year, month, day).ok_or(AppError::ImpossibleDate)?;
+    Ok(Some(DateTime::from_naive_utc_and_offset(
+        date.and_hms_opt(0, 0, 0).unwrap(),
+        Utc,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn lenient(input: &str) -> Option<DateTime<Utc>> {
+        parse_textual(&normalize(input)).unwrap()
+    }
+
+    #[test]
+    fn normalization() {
+        assert_eq!(normalize("  25th  Dec\t2016 "), "25 Dec 2016");
+        assert_eq!(normalize("December 1st, 2016"), "December 1, 2016");
+        assert_eq!(normalize("2016-12-25"), "2016-12-25");
+        assert_eq!(normalize("the 4th"), "the 4");
+    }
+
+    #[test]
+    fn spelled_out_months() {
+        let christmas = Some(Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap());
+        assert_eq!(lenient("25th Dec 2016"), christmas);
+        assert_eq!(lenient("DECEMBER 25, 2016"), christmas);
+        assert_eq!(lenient("Sun, Dec. 25th 2016"), christmas);
+        assert_eq!(lenient("2016 dec 25"), christmas);
+        assert_eq!(lenient("25 Dec"), None);
+        assert_eq!(lenient("25 De 2016"), None);
+        assert!(matches!(
+            parse_textual("31 Feb 2016"),
+            Err(AppError::ImpossibleDate)
+        ));
+    }
+}
@@ -0,0 +1,1895 @@
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, FromRef, State},
+    handler::Handler,
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode, Uri},
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
+use cache::CacheLayer;
+use chrono::{DateTime, SecondsFormat, Utc};
+use config::{Config, Settings};
+use error::AppError;
+use extract::Path;
+use parse::{Hints, InputError, InputKind, ParsedInstant, Rejection, TimeUnit};
+use parse_cache::ParseCache;
+use profile::Profile;
+use query::Query;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::sync::Arc;
+use tower::Layer;
+use tower_http::compression::CompressionLayer;
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use version::ApiVersion;
+
+pub mod access_log;
+pub mod admin;
+mod age;
+mod anniversary;
+mod assets;
+mod batch;
+mod bucket;
+mod build_info;
+mod business;
+mod cache;
+mod calendar;
+mod chinese;
+mod classify;
+pub mod cli;
+pub mod config;
+mod cors;
+mod cron;
+mod csv_upload;
+mod debug;
+pub mod drain;
+mod duration;
+mod encoding;
+mod epoch;
+mod error;
+mod ethiopian;
+mod excel;
+mod extract;
+mod flags;
+pub mod geo;
+mod hal;
+mod hijri;
+mod hlc;
+mod holidays;
+mod ics;
+mod japanese;
+mod julian;
+mod leap;
+mod leapseconds;
+mod lenient;
+mod limits;
+mod load_shed;
+mod locale;
+mod maintenance;
+mod marks;
+mod memento;
+mod metrics;
+mod mono;
+mod month;
+mod moon;
+mod natural;
+mod notes;
+mod ntp;
+mod overflow;
+mod overlap;
+pub mod parse;
+mod parse_cache;
+mod preferences;
+mod profile;
+mod proto;
+mod quarter;
+mod query;
+mod range;
+pub mod rate_limit;
+#[cfg(feature = "redis")]
+mod redis_store;
+mod relative;
+mod request_id;
+mod rrule;
+mod scheduler;
+mod security_headers;
+mod sequence;
+mod skew;
+mod snowflake;
+mod sort;
+#[cfg(feature = "sqlite")]
+mod sqlite_store;
+mod stats;
+mod sun;
+pub mod telemetry;
+mod template;
+mod ticks;
+mod timers;
+pub mod timezone;
+pub mod tls;
+mod totp;
+mod truncate;
+pub mod tzdata;
+mod ui;
+mod uncertainty;
+pub mod uptime;
+mod uuid;
+mod validate;
+mod version;
+mod week;
+
+/// Stores and settings shared by the handlers, each of which extracts the parts it needs
+/// with `State`.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    notes: notes::NoteStore,
+    timers: timers::TimerStore,
+    marks: marks::Marks,
+    scheduler: scheduler::Scheduler,
+    flags: flags::Flags,
+    windows: maintenance::Windows,
+    sequencer: sequence::Sequencer,
+    clock: hlc::Clock,
+    debug: debug::DebugSettings,
+    ntp: ntp::NtpSettings,
+    weeks: week::WeekSettings,
+    admin: admin::AdminSettings,
+    preferences: preferences::PreferenceStore,
+    started: uptime::Started,
+    drain: drain::Drain,
+    parse_cache: ParseCache,
+    route_metrics: metrics::RouteMetrics,
+}
+
+/// The stores whose state replicas of the service share, in Redis when `redis_url` is set
+/// and in memory otherwise.
+fn shared_stores(
+    config: &Config,
+    settings: Settings,
+) -> (marks::Marks, ParseCache, rate_limit::RateLimitLayer) {
+    let marks: marks::Marks = Arc::new(marks::MemoryStorage::default());
+    let parse_cache = ParseCache::new(config.parse_cache_size);
+    let rate_limit = rate_limit::RateLimitLayer::new(settings);
+    let url = match &config.redis_url {
+        Some(url) => url,
+        None => return (marks, parse_cache, rate_limit),
+    };
+    #[cfg(not(feature = "redis"))]
+    panic!(
+        "redis_url {} needs the service to be built with the redis feature",
+        url
+    );
+    #[cfg(feature = "redis")]
+    {
+        let redis =
+            Arc::new(redis_store::Redis::connect(url).expect("Invalid Redis configuration"));
+        (
+            redis.clone(),
+            parse_cache.with_shared(redis.clone()),
+            rate_limit.with_buckets(redis),
+        )
+    }
+}
+
+/// The stores kept across restarts, in SQLite when `database_url` is set and in memory
+/// otherwise. Marks shared in Redis stay there.
+fn persistent_stores(
+    config: &Config,
+    marks: marks::Marks,
+) -> (marks::Marks, timers::TimerStore, scheduler::Scheduler) {
+    let url = match &config.database_url {
+        Some(url) => url,
+        None => {
+            return (
+                marks,
+                timers::TimerStore::default(),
+                scheduler::Scheduler::default(),
+            )
+        }
+    };
+    #[cfg(not(feature = "sqlite"))]
+    panic!(
+        "database_url {} needs the service to be built with the sqlite feature",
+        url
+    );
+    #[cfg(feature = "sqlite")]
+    {
+        let mut database =
+            sqlite_store::Database::open(url).expect("Invalid database configuration");
+        let marks = match config.redis_url {
+            Some(_) => marks,
+            None => database.marks(),
+        };
+        (
+            marks,
+            timers::TimerStore::new(database.timers()),
+            scheduler::Scheduler::new(scheduler::RetryPolicy::default(), database.jobs()),
+        )
+    }
+}
+
+/// Builds the router with all the routes and middleware, from the startup configuration
+/// and the runtime settings.
+pub fn app(
+    config: &Config,
+    settings: Settings,
+    started: uptime::Started,
+    drain: drain::Drain,
+) -> Router {
+    let notes = notes::NoteStore::default();
+    notes.spawn_collector();
+    let (marks, parse_cache, rate_limit) = shared_stores(config, settings.clone());
+    let (marks, timers, scheduler) = persistent_stores(config, marks);
+    scheduler.spawn_worker();
+    let flags = flags::load().expect("Invalid feature flags configuration");
+    let windows = maintenance::load().expect("Invalid maintenance windows configuration");
+    let sequencer = sequence::Sequencer::default();
+    let clock = hlc::Clock::new(config.max_clock_skew_ms);
+    let debug = debug::DebugSettings {
+        enabled: config.debug_endpoints,
+    };
+    let ntp = ntp::NtpSettings::new(&config.ntp_servers);
+    let weeks = week::WeekSettings {
+        week_start: config.week_start().expect("Invalid week_start"),
+    };
+    let admin = admin::AdminSettings::new(config.admin_token.as_deref());
+    let preferences = preferences::PreferenceStore::new(&config.api_keys);
+    let route_metrics = metrics::RouteMetrics::default();
+    let versions = version::VersionLayer::new(config.v1_sunset.as_deref())
+        .expect("Invalid API version configuration");
+    let security_headers = security_headers::SecurityHeadersLayer::new(&config.security_headers)
+        .expect("Invalid security headers configuration");
+    let access_log = access_log::AccessLogLayer::new(&config.access_log)
+        .expect("Invalid access log configuration");
+    let templates =
+        template::TemplateLayer::new(&config.templates).expect("Invalid response templates");
+
+    let state = AppState {
+        notes,
+        timers,
+        marks,
+        scheduler,
+        flags,
+        windows,
+        sequencer,
+        clock,
+        debug,
+        ntp,
+        weeks,
+        admin,
+        preferences: preferences.clone(),
+        started,
+        drain,
+        parse_cache,
+        route_metrics: route_metrics.clone(),
+    };
+
+    // Cacheable responses only change with a release, the tz catalogue aside
+    let immutable = CacheLayer::immutable().last_modified(started.at);
+    let revalidate = CacheLayer::revalidate().last_modified(started.at);
+    let routes = Router::new()
+        .route("/", get(ui::page_handler.layer(revalidate)))
+        .route(
+            "/static/{file}",
+            get(assets::static_handler.layer(revalidate)),
+        )
+        .route("/api", get(now_handler.layer(CacheLayer::no_store())))
+        .route("/api/{date}", get(date_handler.layer(immutable)))
+        .route("/api/relative/{date}", get(relative::relative_handler))
+        .route("/api/i18n/{locale}", get(locale::i18n_handler))
+        .route("/api/until/{date}", get(relative::until_handler))
+        .route("/api/since/{date}", get(relative::since_handler))
+        .route("/api/cron/next", get(cron::next_handler))
+        .route(
+            "/api/month/{year}/{month}/epochs",
+            get(month::epochs_handler),
+        )
+        .route(
+            "/api/tz/{zone}/safe-times",
+            get(timezone::safe_times_handler),
+        )
+        .route("/api/rrule", post(rrule::rrule_handler))
+        .route("/api/business-days/add", post(business::add_handler))
+        .route("/api/business-days/count", post(business::count_handler))
+        .route("/api/offset/{date}/{offset}", get(timezone::offset_handler))
+        .route(
+            "/api/holidays/{country}/{year}",
+            get(holidays::holidays_handler),
+        )
+        .route("/api/detect/{input}", get(profile::detect_handler))
+        .route(
+            "/api/tz/{zone}/transitions",
+            get(timezone::transitions_handler),
+        )
+        .route(
+            "/api/expiring-notes",
+            get(notes::list_handler).post(notes::create_handler),
+        )
+        .route(
+            "/api/expiring-notes/{id}",
+            get(notes::get_handler)
+                .put(notes::put_handler)
+                .delete(notes::delete_handler),
+        )
+        .route("/api/flags", get(flags::flags_handler))
+        // current offsets change with daylight saving time, only the ETag can tell
+        .route(
+            "/api/tz",
+            get(timezone::catalogue_handler.layer(CacheLayer::revalidate())),
+        )
+        .route("/api/maintenance", get(maintenance::maintenance_handler))
+        .route("/api/tz/abbrev/{abbr}", get(timezone::abbreviation_handler))
+        .route("/api/convert", get(timezone::convert_handler))
+        .route("/api/classify/{date}", get(classify::classify_handler))
+        .route("/api/now/monotonic-id", get(sequence::monotonic_id_handler))
+        .route("/api/excel/{serial}", get(excel::from_serial_handler))
+        .route("/api/excel/serial/{date}", get(excel::to_serial_handler))
+        .route("/api/hlc/now", get(hlc::now_handler))
+        .route("/api/hlc/update", post(hlc::update_handler))
+        .route("/api/ticks/{value}", get(ticks::from_ticks_handler))
+        .route("/api/ticks/of/{date}", get(ticks::to_ticks_handler))
+        .route("/api/hlc/compare", post(hlc::compare_handler))
+        .route("/api/grid/{year}/{month}", get(month::grid_handler))
+        .route("/api/uuid/{uuid}", get(uuid::uuid_handler))
+        .route("/api/snowflake/{id}", get(snowflake::snowflake_handler))
+        .route("/api/debug/parse/{value}", get(debug::parse_trace_handler))
+        .route("/api/duration/combine", post(duration::combine_handler))
+        .route("/api/gps/{seconds}", get(leapseconds::from_gps_handler))
+        .route("/api/gps/of/{date}", get(leapseconds::of_handler))
+        .route("/api/tai/{seconds}", get(leapseconds::from_tai_handler))
+        .route("/api/tai/of/{date}", get(leapseconds::of_handler))
+        .route(
+            "/api/anniversary/{date}",
+            get(anniversary::anniversary_handler),
+        )
+        .route("/api/quarter/{date}", get(quarter::quarter_handler))
+        .route("/api/age/{birthdate}", get(age::age_handler))
+        .route("/api/sun", get(sun::sun_handler))
+        .route("/api/moon/{date}", get(moon::moon_handler))
+        .route("/api/calendar/hijri/{date}", get(hijri::to_hijri_handler))
+        .route(
+            "/api/calendar/hijri/gregorian/{date}",
+            get(hijri::from_hijri_handler),
+        )
+        .route(
+            "/api/calendar/chinese/{date}",
+            get(chinese::to_chinese_handler),
+        )
+        .route(
+            "/api/calendar/chinese/gregorian/{date}",
+            get(chinese::from_chinese_handler),
+        )
+        .route(
+            "/api/calendar/ethiopian/{date}",
+            get(ethiopian::to_ethiopian_handler),
+        )
+        .route(
+            "/api/calendar/ethiopian/gregorian/{date}",
+            get(ethiopian::from_ethiopian_handler),
+        )
+        .route(
+            "/api/calendar/japanese/{date}",
+            get(japanese::japanese_handler),
+        )
+        .route("/metrics", get(metrics::metrics_handler))
+        .route("/api/timers", post(timers::start_handler))
+        .route(
+            "/api/timers/{id}",
+            get(timers::get_handler).delete(timers::delete_handler),
+        )
+        .route("/api/timers/{id}/lap", post(timers::lap_handler))
+        .route("/api/timers/{id}/stop", post(timers::stop_handler))
+        .route("/api/marks", get(marks::list_handler))
+        .route(
+            "/api/marks/{name}",
+            get(marks::get_handler)
+                .put(marks::put_handler)
+                .delete(marks::delete_handler),
+        )
+        .route(
+            "/api/schedule",
+            get(scheduler::list_handler).post(scheduler::create_handler),
+        )
+        .route(
+            "/api/schedule/{id}",
+            get(scheduler::get_handler).delete(scheduler::cancel_handler),
+        )
+        .route("/api/ntp", get(ntp::handler.layer(CacheLayer::no_store())))
+        .route(
+            "/api/uptime",
+            get(uptime::handler.layer(CacheLayer::no_store())),
+        )
+        .route("/version", get(build_info::handler))
+        .route("/api/epoch", get(epoch::epoch_handler))
+        .route("/api/range", get(range::range_handler))
+        .route("/api/truncate/{date}", get(truncate::truncate_handler))
+        .route("/api/tz/{zone}/offset", get(timezone::zone_offset_handler))
+        .route("/api/validate/{date}", get(validate::validate_handler))
+        .route("/api/tz/version", get(tzdata::version_handler))
+        .route("/api/leap", get(leap::leap_handler))
+        .route("/api/calendar/{year}/{month}", get(month::grid_handler))
+        .route("/api/ics", post(ics::ics_handler))
+        .route("/api/skew", get(skew::skew_handler))
+        .route("/api/totp/counter", get(totp::counter_handler))
+        .route("/admin/log-level", put(admin::log_level_handler))
+        .route("/admin/drain", post(admin::drain_handler))
+        .route("/ready", get(drain::ready_handler))
+        .route("/api/diff", get(relative::diff_handler))
+        .route("/api/diff/{from}/{to}", get(relative::diff_path_handler))
+        .route("/api/humanize/{seconds}", get(relative::humanize_handler))
+        .route("/api/overlap", post(overlap::overlap_handler))
+        .route(
+            "/api/worldclock",
+            get(timezone::world_clock_handler.layer(CacheLayer::no_store())),
+        )
+        .route("/api/tz/locate", get(geo::locate_handler))
+        .route(
+            "/api/local",
+            get(geo::local_handler.layer(CacheLayer::no_store())),
+        )
+        .route(
+            "/api/mono",
+            get(mono::handler.layer(CacheLayer::no_store())),
+        )
+        .route("/api/sync", post(ntp::exchange_handler))
+        .route("/api/batch/stream", post(batch::stream_handler))
+        .route("/api/csv", post(csv_upload::csv_handler))
+        .route("/api/nth-weekday", get(month::nth_weekday_handler))
+        .route("/api/business-day", get(business::nth_handler))
+        .route("/api/cron/prev", get(cron::prev_handler))
+        .route("/api/cron/between", get(cron::between_handler))
+        .route("/api/boundary/{date}", get(truncate::boundary_handler))
+        .route("/api/week/{date}", get(week::week_handler))
+        .route("/api/sort", post(sort::sort_handler))
+        .route("/api/bucket", post(bucket::bucket_handler))
+        .route("/api/stats", post(stats::stats_handler))
+        .route("/api/check/{date}", get(overflow::check_handler))
+        .route(
+            "/api/preferences",
+            get(preferences::get_handler).put(preferences::put_handler),
+        )
+        .fallback(not_found_handler)
+        .with_state(state)
+        // bodies are limited by `limits`, streaming routes excepted
+        .layer(DefaultBodyLimit::disable())
+        .layer(limits::LimitsLayer::new(&config.server))
+        .layer(rate_limit)
+        .layer(load_shed::LoadShedLayer::new(
+            config.server.max_concurrent_requests,
+        ))
+        .layer(metrics::MetricsLayer::new(route_metrics))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                // status and latency of every request, the fields JSON logs are queried by
+                .on_response(DefaultOnResponse::new().level(tracing::Level::INFO)),
+        )
+        .layer(request_id::RequestIdLayer::default())
+        .layer(templates)
+        .layer(encoding::EncodingLayer)
+        // before templates, which may come from the preferences
+        .layer(preferences::PreferencesLayer::new(preferences));
+
+    // layers of a router run once a route is matched, so the version prefix has to be
+    // stripped by a service around it
+    Router::new()
+        .fallback_service(versions.layer(routes))
+        .layer(CompressionLayer::new())
+        .layer(cors::CorsLayer::new(settings))
+        .layer(security_headers)
+        .layer(access_log)
+}
+
+/// Span of a request, carrying its id so that every event logged while serving it
+/// can be correlated.
+fn request_span(request: &Request<Body>) -> tracing::Span {
+    let id = request
+        .headers()
+        .get(request_id::REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route = %request.uri().path(),
+        uri = %request.uri(),
+        request_id = %id,
+    );
+    telemetry::link_parent(&span, request.headers());
+    span
+}
+
+/// Fallback for unknown routes, so that every error has a JSON body.
+async fn not_found_handler(uri: Uri) -> (StatusCode, Json<Value>) {
+    let path = uri.path();
+    let hint = if path.starts_with("/api/") {
+        "Dates are converted by /api/:date, such as /api/2016-12-25".to_string()
+    } else {
+        format!("API routes start with /api, such as /api{}", path)
+    };
+
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "error": "Not Found",
+            "code": "not_found",
+            "path": path,
+            "hint": hint,
+        })),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct DateParams {
+    /// Instant natural-language dates are resolved against, defaults to now.
+    base: Option<String>,
+    /// Only accept inputs following this grammar.
+    profile: Option<Profile>,
+    /// Language of the localized date, overriding `Accept-Language`.
+    locale: Option<String>,
+    /// Include the Japanese era of the date.
+    #[serde(default)]
+    era: bool,
+    /// Unit of a timestamp input, guessed from its magnitude by default.
+    unit: Option<TimeUnit>,
+    /// Whether a regional date puts the day first, defaulting to the convention of
+    /// `locale` when given.
+    dayfirst: Option<bool>,
+    /// Read eight digits as a basic-format date, `20161225`, rather than as a timestamp.
+    #[serde(default)]
+    basic: bool,
+    /// Add hypermedia links to related operations, as `Accept: application/hal+json` does.
+    #[serde(default)]
+    links: bool,
+    /// Calendar `YYYY-MM-DD` dates are written in, proleptic Gregorian by default.
+    calendar: Option<julian::Calendar>,
+    /// First Gregorian day of the hybrid calendar, `1582-10-15` by default.
+    cutover: Option<String>,
+    /// Tolerate stray whitespace, ordinal suffixes and spelled-out months, see
+    /// [`lenient`].
+    #[serde(default)]
+    lenient: bool,
+    /// Clock of the localized time, defaulting to the one of the locale.
+    clock: Option<locale::Clock>,
+}
+
+/// Longer comma-separated lists of dates are rejected.
+const MAX_DATES: usize = 100;
+
+/// Converts a date, or a comma-separated list of dates into an array of results in input
+/// order. Dates of a list that can't be parsed get an error entry instead of failing the
+/// whole request.
+async fn date_handler(
+    Path(date): Path<String>,
+    Query(params): Query<DateParams>,
+    State(cache): State<ParseCache>,
+    Extension(version): Extension<ApiVersion>,
+    headers: HeaderMap,
+) -> Result<(HeaderMap, Json<Value>), AppError> {
+    let locale = locale::negotiate(&headers, params.locale.as_deref())?;
+    tracing::info!("Provided date is {}", date);
+    let base = parse_base(params.base.as_deref())?;
+    let links = hal::wants(&headers, params.links);
+    let convert = |date: &str| convert_date(date, &params, base, &cache, locale, version, links);
+    // commas are split on only when the whole input isn't a date, in case a format has some
+    let (body, relative) = match convert(&date) {
+        Err(AppError::InvalidDate) if date.contains(',') => {
+            let dates: Vec<&str> = date.split(',').map(str::trim).collect();
+            if dates.len() > MAX_DATES {
+                return Err(AppError::BadRequest(format!(
+                    "At most {} dates can be converted at once",
+                    MAX_DATES
+                )));
+            }
+            let mut relative = false;
+            let results: Vec<Value> = dates
+                .into_iter()
+                .map(|date| match convert(date) {
+                    Ok((body, date_relative)) => {
+                        relative |= date_relative;
+                        body
+                    }
+                    Err(error) => json!({
+                        "input": date,
+                        "error": error.to_string(),
+                        "code": error.code(),
+                    }),
+                })
+                .collect();
+            (json!(results), relative)
+        }
+        converted => converted?,
+    };
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        header::VARY,
+        HeaderValue::from_static("Accept, Accept-Language, Accept-Version"),
+    );
+    if hal::negotiated(&headers) {
+        response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(hal::HAL));
+    }
+    if relative {
+        response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    }
+    Ok((response_headers, Json(body)))
+}
+
+/// Converts a single date of `/api/:date`, also telling whether the result depends on
+/// the current time.
+fn convert_date(
+    date: &str,
+    params: &DateParams,
+    base: DateTime<Utc>,
+    cache: &ParseCache,
+    locale: Option<&'static locale::Locale>,
+    version: ApiVersion,
+    links: bool,
+) -> Result<(Value, bool), AppError> {
+    let reckoning = julian::Reckoning::new(params.calendar, params.cutover.as_deref())?;
+    let date: &str = &if params.lenient {
+        Cow::Owned(lenient::normalize(date))
+    } else {
+        Cow::Borrowed(date)
+    };
+    let date: &str = &match reckoning {
+        Some(reckoning) => julian::to_gregorian_input(date, reckoning)?,
+        None => Cow::Borrowed(date),
+    };
+    let textual = if params.lenient {
+        lenient::parse_textual(date)?
+    } else {
+        None
+    };
+    let offset = profile::offset_of(date);
+    // natural-language dates resolved against the current time change from one call to the next
+    let mut ambiguous = false;
+    let (date, relative) = match (params.profile, textual) {
+        (Some(profile), _) => {
+            let parsed = cache.get_or_parse(date, Some(profile), || {
+                profile.parse(date).ok_or(Rejection::Malformed)
+            });
+            (parsed?, false)
+        }
+        (None, Some(textual)) => (textual, false),
+        (None, None) => match natural::parse(date, base) {
+            Some(date) => (date, params.base.is_none()),
+            // date-times with an offset, which the default parsers don't read
+            None if offset.is_some() => {
+                let parsed = profile::parse_rfc3339(date).or_else(|| profile::parse_iso8601(date));
+                (
+                    parsed.ok_or(AppError::InvalidDate)?.with_timezone(&Utc),
+                    false,
+                )
+            }
+            None => {
+                let hints = Hints {
+                    unit: params.unit,
+                    day_first: params
+                        .dayfirst
+                        .or_else(|| params.locale.as_deref().map(parse::day_first_in)),
+                    basic: params.basic,
+                };
+                // the cache holds neither hinted readings nor the ambiguity of regional dates
+                if hints == Hints::default() && parse::classify(date) != InputKind::Regional {
+                    let parsed = cache.get_or_parse(date, None, || {
+                        parse::parse_input(date)
+                            .map(|parsed| parsed.instant)
+                            .map_err(|error| rejected(date, error))
+                    });
+                    (parsed?, false)
+                } else {
+                    let parsed = parse_date_with(date, hints)?;
+                    ambiguous = parsed.ambiguous;
+                    (parsed.instant, false)
+                }
+            }
+        },
+    };
+
+    tracing::debug!("Converted date is {}", date);
+    let mut body = timestamp_body(date, version);
+    if links {
+        body["_links"] = hal::links(&date.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+    }
+    if ambiguous {
+        body["ambiguous"] = json!(true);
+    }
+    if let Some(offset) = offset {
+        body["offset"] = json!(offset.to_string());
+        body["local"] = json!(date
+            .with_timezone(&offset)
+            .to_rfc3339_opts(SecondsFormat::AutoSi, false));
+    }
+    if let Some(reckoning) = reckoning {
+        let day = match offset {
+            Some(offset) => date.with_timezone(&offset).date_naive(),
+            None => date.date_naive(),
+        };
+        body["calendar"] = reckoning.describe(day);
+    }
+    if params.era {
+        body["era"] = json!(japanese::describe(date.date_naive()));
+    }
+    // a clock alone asks for an English rendering
+    if let Some(locale) = locale.or_else(|| params.clock.map(|_| &locale::EN)) {
+        let day = date.date_naive();
+        let clock = params.clock.unwrap_or(locale.clock);
+        body["localized"] = json!({
+            "locale": locale.code,
+            "date": locale.format_date(day),
+            "time": clock.format_time(date.time()),
+            "month": locale.month_name(day),
+            "weekday": locale.weekday_name(day),
+        });
+    }
+    Ok((body, relative))
+}
+
+/// The standard response body: the unix timestamp and the UTC date in RFC 2822 format,
+/// v2 adding the timestamp in finer units, the RFC 3339 date and the ISO week date.
+/// Finer units that don't fit in 64 bits, such as nanoseconds after 2262, are `null`.
+fn timestamp_body(date: DateTime<Utc>, version: ApiVersion) -> Value {
+    let seconds = date.timestamp();
+    if version == ApiVersion::V1 {
+        return json!({
+            "unix": seconds,
+            "utc": date.to_rfc2822(),
+        });
+    }
+    let nanos = date.timestamp_subsec_nanos() as i64;
+    let unit = |per_second: i64| {
+        seconds
+            .checked_mul(per_second)
+            .and_then(|value| value.checked_add(nanos / (1_000_000_000 / per_second)))
+    };
+
+    json!({
+        "unix": seconds,
+        "unix_ms": unit(1_000),
+        "unix_us": unit(1_000_000),
+        "unix_ns": unit(1_000_000_000),
+        "utc": date.to_rfc2822(),
+        "iso8601": date.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        "iso_week_date": calendar::week_date(date.date_naive()),
+    })
+}
+
+/// Parses the `?base=` reference instant of relative inputs, defaulting to now.
+fn parse_base(base: Option<&str>) -> Result<DateTime<Utc>, AppError> {
+    match base {
+        Some(base) => parse_date(base),
+        None => Ok(Utc::now()),
+    }
+}
+
+/// Same as [`parse_date`], also accepting natural-language dates resolved against `base`.
+fn parse_date_at(date: &str, base: DateTime<Utc>) -> Result<DateTime<Utc>, AppError> {
+    match natural::parse(date, base) {
+        Some(date) => Ok(date),
+        None => parse_date(date),
+    }
+}
+
+/// Parses a date as accepted by the `/api/:date` family of routes:
+/// either a unix timestamp, possibly fractional, a `YYYY-MM-DD` date, a `YYYY-Www-D`
+/// week date, a regional date such as `12/25/2016` or an iCalendar basic-format date
+/// such as `20161225T000000Z`.
+fn parse_date(date: &str) -> Result<DateTime<Utc>, AppError> {
+    parse_date_with(date, Hints::default()).map(|parsed| parsed.instant)
+}
+
+/// Same as [`parse_date`], reading inputs the way `hints` say.
+fn parse_date_with(date: &str, hints: Hints) -> Result<ParsedInstant, AppError> {
+    parse::parse_input_with(date, hints).map_err(|error| rejected(date, error).into())
+}
+
+/// Logs and counts an input the parser rejected.
+fn rejected(date: &str, error: InputError) -> Rejection {
+    tracing::error!("Error while parsing the date: {}", error);
+    metrics::record_parse_failure(parse::classify(date));
+    error.rejection
+}
+
+#[derive(Debug, Deserialize)]
+struct NowParams {
+    /// Include the estimated error of the server clock.
+    #[serde(default)]
+    uncertainty: bool,
+}
+
+/// The current time, or the one asked for in `Accept-Datetime`.
+async fn now_handler(
+    Query(params): Query<NowParams>,
+    Extension(version): Extension<ApiVersion>,
+    headers: HeaderMap,
+) -> Result<(HeaderMap, Json<Value>), AppError> {
+    let memento = memento::accept_datetime(&headers)?;
+    let utc: DateTime<Utc> = memento.unwrap_or_else(Utc::now);
+    let mut body = timestamp_body(utc, version);
+
+    // the uncertainty is the one of the server clock, meaningless for another instant
+    if params.uncertainty && memento.is_none() {
+        // like TrueTime, the actual time is somewhere in [earliest, latest]
+        let uncertainty = uncertainty::clock_uncertainty_ms();
+        let now_ms = utc.timestamp_millis() as f64;
+        body["uncertainty_ms"] = json!(uncertainty);
+        body["earliest_ms"] = json!(uncertainty.map(|ms| (now_ms - ms).floor() as i64));
+        body["latest_ms"] = json!(uncertainty.map(|ms| (now_ms + ms).ceil() as i64));
+    }
+
+    Ok((memento::headers(memento), Json(body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use chrono::TimeZone;
+    use serde_json::{json, Value};
+    use tokio::sync::watch;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn drain_handle() -> drain::Drain {
+        drain::Drain::new(std::time::Duration::from_secs(10))
+    }
+
+    fn test_app() -> Router {
+        let config = Config::default();
+        let (_, settings) = watch::channel(config.runtime());
+        app(&config, settings, uptime::Started::now(), drain_handle())
+    }
+
+    #[tokio::test]
+    async fn converter_page() {
+        let app = test_app();
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .contains_key(header::CONTENT_SECURITY_POLICY));
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let page = std::str::from_utf8(&body).unwrap();
+        assert!(page.contains(r#"<script src="/static/app.js" defer></script>"#));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/static/app.js")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, no-cache"
+        );
+        assert!(response.headers().contains_key(header::ETAG));
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/not-found")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["error"], "Not Found");
+        assert_eq!(body["path"], "/not-found");
+        assert_eq!(
+            body["hint"],
+            "API routes start with /api, such as /api/not-found"
+        );
+    }
+
+    #[tokio::test]
+    async fn uptime() {
+        let started = uptime::Started {
+            at: Utc.timestamp_opt(1482624000, 0).unwrap(),
+            instant: std::time::Instant::now(),
+        };
+        let config = Config::default();
+        let (_, settings) = watch::channel(config.runtime());
+        let response = app(&config, settings, started, drain_handle())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/uptime")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["started_at"]["unix"], 1482624000);
+        assert_eq!(body["uptime_seconds"], 0);
+        assert_eq!(body["humanized"], "0 seconds");
+    }
+
+    #[tokio::test]
+    async fn draining_fails_readiness() {
+        let config = Config {
+            admin_token: Some("s3cret".to_string()),
+            ..Config::default()
+        };
+        let (_, settings) = watch::channel(config.runtime());
+        let drain = drain_handle();
+        let app = app(&config, settings, uptime::Started::now(), drain.clone());
+        let ready = || {
+            Request::builder()
+                .uri("/ready")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(ready()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/admin/drain")
+                    .header("authorization", "Bearer s3cret")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(drain.is_draining());
+
+        let response = app.oneshot(ready()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn api_key_preferences() {
+        let config = Config {
+            api_keys: vec!["k1".to_string()],
+            ..Config::default()
+        };
+        let (_, settings) = watch::channel(config.runtime());
+        let app = app(&config, settings, uptime::Started::now(), drain_handle());
+        let convert = |key: &str| {
+            Request::builder()
+                .uri("/api/2016-12-25")
+                .header("x-api-key", key)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/api/preferences")
+                    .header("x-api-key", "k1")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"locale": "it"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(convert("k1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .get_all(header::VARY)
+            .iter()
+            .any(|vary| vary == "x-api-key"));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["localized"]["locale"], "it");
+
+        let response = app.oneshot(convert("k2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn reloaded_cors_origins() {
+        let config = Config::default();
+        let (updates, settings) = watch::channel(config.runtime());
+        let app = app(&config, settings, uptime::Started::now(), drain_handle());
+        let request = || {
+            Request::builder()
+                .uri("/api/2016-12-25")
+                .header("origin", "https://example.com")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert!(response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none());
+
+        let mut runtime = config.runtime();
+        runtime.cors_origins = vec!["https://example.com".to_string()];
+        updates.send(runtime).unwrap();
+
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(
+            response.headers()["access-control-allow-origin"],
+            "https://example.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn valid_date_string() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "unix": 1482624000,
+                "utc": "Sun, 25 Dec 2016 00:00:00 +0000"
+            })
+        );
+    }
+    // A request to /api/1451001600 should return { unix: 1451001600000, utc: "Fri, 25 Dec 2015 00:00:00 GMT" }
+    #[tokio::test]
+    async fn timestamp() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/1451001600")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "unix": 1451001600,
+                "utc": "Fri, 25 Dec 2015 00:00:00 +0000"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn eight_digits() {
+        let body = |uri: &'static str| async move {
+            let response = test_app()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<Value>(&body).unwrap()
+        };
+
+        assert_eq!(
+            body("/api/20161225").await,
+            json!({
+                "unix": 20161225,
+                "utc": "Sat, 22 Aug 1970 08:20:25 +0000"
+            })
+        );
+        assert_eq!(
+            body("/api/20161225?basic=true").await,
+            json!({
+                "unix": 1482624000,
+                "utc": "Sun, 25 Dec 2016 00:00:00 +0000"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn timestamp_unit() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/86400000?unit=ms")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["unix"], 86400);
+    }
+
+    #[tokio::test]
+    async fn regional_date() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/01.02.2023")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["unix"], 1675209600);
+        assert_eq!(body["ambiguous"], true);
+    }
+
+    #[tokio::test]
+    async fn input_offset() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25T10:00:00+05:30")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["unix"], 1482640200);
+        assert_eq!(body["utc"], "Sun, 25 Dec 2016 04:30:00 +0000");
+        assert_eq!(body["offset"], "+05:30");
+        assert_eq!(body["local"], "2016-12-25T10:00:00+05:30");
+    }
+
+    #[tokio::test]
+    async fn date_list() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25,1451001600,2019-02-29")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body[0]["unix"], 1482624000);
+        assert_eq!(body[1]["unix"], 1451001600);
+        assert_eq!(
+            body[2],
+            json!({
+                "input": "2019-02-29",
+                "error": "Impossible Date",
+                "code": "impossible_date",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn error_codes() {
+        for (uri, status, code) in &[
+            (
+                "/api/2023-02-30",
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "impossible_date",
+            ),
+            (
+                "/api/99999999999999999999999",
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "timestamp_out_of_range",
+            ),
+            (
+                "/api/2016-12-25?unit=weeks",
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+            ),
+        ] {
+            let response = test_app()
+                .oneshot(Request::builder().uri(*uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), *status, "{}", uri);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["code"], *code, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn natural_language_date() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/tomorrow?base=2016-12-25")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "unix": 1482710400,
+                "utc": "Mon, 26 Dec 2016 00:00:00 +0000"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn relative_to_base() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/relative/next+friday?base=2016-12-25")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "unix": 1483056000,
+                "utc": "Fri, 30 Dec 2016 00:00:00 +0000",
+                "relative": "in 5 days"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn diff_in_query() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/diff?from=2016-12-25T00:00:00%2B05:30&to=2016-12-27T12:00:00Z&unit=days")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "from": 1482604200,
+                "to": 1482840000,
+                "unit": "days",
+                "difference": 2,
+                "delta": {"seconds": 235800, "minutes": 3930, "hours": 65, "days": 2},
+                "iso8601": "PT65H30M"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn localized_date() {
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25")
+                    .header("accept-language", "it-IT, en;q=0.8")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["localized"]["date"], "domenica 25 dicembre 2016");
+        assert_eq!(body["localized"]["month"], "dicembre");
+        assert_eq!(body["localized"]["time"], "00:00:00");
+    }
+
+    #[tokio::test]
+    async fn twelve_hour_clock() {
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25T15:04:05Z?clock=12")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["localized"]["locale"], "en");
+        assert_eq!(body["localized"]["time"], "3:04:05 PM");
+    }
+
+    #[tokio::test]
+    async fn unknown_locales() {
+        let status = |uri: &'static str| async move {
+            test_app()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+                .status()
+        };
+
+        assert_eq!(
+            status("/api/2016-12-25?locale=xx").await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(status("/api/i18n/xx").await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn negative_durations() {
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/humanize/-7200?locale=it")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn cache_headers() {
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()["cache-control"]
+            .to_str()
+            .unwrap()
+            .contains("immutable"));
+        let etag = response.headers()["etag"].clone();
+
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25")
+                    .header("if-none-match", etag)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let modified_since = |since: &'static str| {
+            test_app().oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25")
+                    .header("if-modified-since", since)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+        let response = modified_since("Fri, 31 Dec 9999 23:59:59 GMT")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.headers().contains_key(header::LAST_MODIFIED));
+        let response = modified_since("Sat, 01 Jan 2000 00:00:00 GMT")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/tomorrow")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["cache-control"], "no-store");
+
+        let response = test_app()
+            .oneshot(Request::builder().uri("/api").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["cache-control"], "no-store");
+    }
+
+    #[tokio::test]
+    async fn compressed_responses() {
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/tz")
+                    .header("accept-encoding", "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+    }
+
+    #[tokio::test]
+    async fn versioned_routes() {
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/v2/api/2016-12-25")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["api-version"], "2");
+        assert!(response.headers().get("deprecation").is_none());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "unix": 1482624000,
+                "unix_ms": 1482624000000i64,
+                "unix_us": 1482624000000000i64,
+                "unix_ns": 1482624000000000000i64,
+                "utc": "Sun, 25 Dec 2016 00:00:00 +0000",
+                "iso8601": "2016-12-25T00:00:00Z",
+                "iso_week_date": "2016-W51-7"
+            })
+        );
+
+        let response = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["api-version"], "1");
+        assert_eq!(response.headers()["deprecation"], "true");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "unix": 1482624000,
+                "utc": "Sun, 25 Dec 2016 00:00:00 +0000"
+            })
+        );
+    }
+
+    // If the input date string is invalid, the api returns an object having the structure { error : "Invalid Date" }
+    #[tokio::test]
+    async fn invalid_date() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/this-is-not-a-date")
+                    .header("x-request-id", "req-42")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.headers()["x-request-id"], "req-42");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "error": "Invalid Date",
+                "code": "invalid_date",
+                "request_id": "req-42"
+            })
+        );
+    }
+
+    // An empty date parameter should return the current time in a JSON object with a unix key
+    // Note: this test is fragile as it's comparing equally the two responses.
+    // A more sound way would be to assert approximately as, due to latecy, the times may differ.
+    #[tokio::test]
+    async fn empty_param() {
+        let app = test_app();
+        let now: DateTime<Utc> = Utc::now();
+        let response = app
+            .oneshot(Request::builder().uri("/api").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "unix": now.timestamp(),
+                "utc": now.to_rfc2822(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn accept_datetime() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api")
+                    .header("accept-datetime", "Sun, 25 Dec 2016 00:00:00 GMT")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["memento-datetime"],
+            "Sun, 25 Dec 2016 00:00:00 GMT"
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["unix"], 1482624000);
+    }
+
+    #[tokio::test]
+    async fn sync_exchange() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/sync")
+                    .body(Body::from(r#"{"originate_ns": 1482624000000000000}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["originate_ns"], 1482624000000000000i64);
+        assert!(body["receive_ns"].as_i64() <= body["transmit_ns"].as_i64());
+    }
+
+    #[tokio::test]
+    async fn ndjson_stream() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/batch/stream")
+                    .header("content-type", "application/x-ndjson")
+                    .body(Body::from(
+                        "\"2016-12-25\"\n{\"date\": \"2019-02-29\"}\n42\n",
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let lines: Vec<Value> = body
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["unix"], 1482624000);
+        assert_eq!(lines[1]["code"], "impossible_date");
+        assert_eq!(lines[2]["code"], "bad_request");
+    }
+
+    #[tokio::test]
+    async fn response_template() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/2016-12-25?template=%7B%22epoch%22%3A%22%7Bunix%7D%22%7D")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body, json!({"epoch": 1482624000}));
+    }
+
+    #[tokio::test]
+    async fn hal_links() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/1482624000")
+                    .header("accept", "application/hal+json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/hal+json");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body["_links"]["info"]["href"],
+            "/api/classify/2016-12-25T00:00:00Z"
+        );
+    }
+
+    #[tokio::test]
+    async fn julian_calendar() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/1582-10-04?calendar=hybrid")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["unix"], -12219379200i64);
+        assert_eq!(
+            body["calendar"],
+            json!({"calendar": "julian", "date": "1582-10-04", "cutover": "1582-10-15"})
+        );
+    }
+
+    #[tokio::test]
+    async fn nth_weekday() {
+        let app = test_app();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/nth-weekday?year=2024&month=11&weekday=mon&n=3&tz=UTC")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["date"], "2024-11-18");
+        assert_eq!(body["unix"], 1731888000);
+    }
+
+    #[tokio::test]
+    async fn lenient_input() {
+        let app = test_app();
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app
+            .clone()
+            .oneshot(request("/api/%2025th%20%20Dec%202016%20?lenient=true"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["unix"], 1482624000);
+
+        let response = app
+            .oneshot(request("/api/%2025th%20%20Dec%202016%20"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[test]
+    fn epoch_units() {
+        let date = Utc.timestamp_opt(1451001600, 123_456_789).unwrap();
+        let body = timestamp_body(date, ApiVersion::V2);
+        assert_eq!(body["unix_ms"], 1451001600123i64);
+        assert_eq!(body["unix_us"], 1451001600123456i64);
+        assert_eq!(body["unix_ns"], 1451001600123456789i64);
+        assert_eq!(body["iso8601"], "2015-12-25T00:00:00.123456789Z");
+
+        // nanoseconds overflow 64 bits in 2262
+        let body = timestamp_body(
+            Utc.with_ymd_and_hms(2300, 1, 1, 0, 0, 0).unwrap(),
+            ApiVersion::V2,
+        );
+        assert!(body["unix_us"].is_i64());
+        assert!(body["unix_ns"].is_null());
+    }
+}
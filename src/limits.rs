@@ -0,0 +1,166 @@
+//! Bounds on the time and memory a single request can take.
+//!
+//! Requests not answered within the configured timeout get a 408, reading the body
+//! included. Bodies declaring a length over the maximum are refused with a 413 before being
+//! read. Bodies of unknown length, sent chunked, are read up to the maximum and handed on
+//! buffered, so that handlers never collect an unbounded body.
+//!
+//! Streaming routes, which read their body incrementally and may legitimately run for
+//! long, are exempt from both limits.
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::{header, Request, Response};
+use axum::response::IntoResponse;
+use http_body_util::BodyExt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{BoxError, Layer, Service};
+
+use crate::config::ServerConfig;
+use crate::error::AppError;
+
+/// Routes exempt from the limits.
+const STREAMING_ROUTES: &[&str] = &["/api/batch/stream"];
+
+#[derive(Clone, Copy, Debug)]
+pub struct LimitsLayer {
+    timeout: Option<Duration>,
+    max_body_bytes: usize,
+}
+
+impl LimitsLayer {
+    pub fn new(config: &ServerConfig) -> LimitsLayer {
+        LimitsLayer {
+            timeout: Some(config.request_timeout_secs)
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            max_body_bytes: config.max_body_bytes,
+        }
+    }
+}
+
+impl<S> Layer<S> for LimitsLayer {
+    type Service = Limits<S>;
+
+    fn layer(&self, inner: S) -> Limits<S> {
+        Limits {
+            inner,
+            timeout: self.timeout,
+            max_body_bytes: self.max_body_bytes,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Limits<S> {
+    inner: S,
+    timeout: Option<Duration>,
+    max_body_bytes: usize,
+}
+
+/// Checks the size of the body, buffering it when its length isn't declared.
+async fn limit_body(request: Request<Body>, max: usize) -> Result<Request<Body>, AppError> {
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<u64>().ok());
+    match declared {
+        Some(length) if length > max as u64 => return Err(AppError::PayloadTooLarge),
+        // hyper makes sure the body is as long as declared
+        Some(_) => return Ok(request),
+        None if request.body().size_hint().exact() == Some(0) => return Ok(request),
+        None => {}
+    }
+
+    let (parts, mut body) = request.into_parts();
+    let mut buffered = Vec::new();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|_| AppError::BadRequest("Invalid request body".to_string()))?;
+        // trailers don't count
+        let chunk = match frame.into_data() {
+            Ok(chunk) => chunk,
+            Err(_) => continue,
+        };
+        if buffered.len() + chunk.len() > max {
+            return Err(AppError::PayloadTooLarge);
+        }
+        buffered.extend_from_slice(&chunk);
+    }
+    Ok(Request::from_parts(parts, Body::from(buffered)))
+}
+
+impl<S, ResBody> Service<Request<Body>> for Limits<S>
+where
+    S: Service<Request<Body>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // the service polled ready is the one to call, a clone takes its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        if STREAMING_ROUTES.contains(&request.uri().path()) {
+            return Box::pin(async move { Ok(inner.call(request).await?.map(Body::new)) });
+        }
+        let max_body_bytes = self.max_body_bytes;
+        let serve = async move {
+            let request = match limit_body(request, max_body_bytes).await {
+                Ok(request) => request,
+                Err(error) => return Ok(error.into_response()),
+            };
+            Ok(inner.call(request).await?.map(Body::new))
+        };
+
+        match self.timeout {
+            Some(timeout) => Box::pin(async move {
+                match tokio::time::timeout(timeout, serve).await {
+                    Ok(response) => response,
+                    Err(_) => Ok(AppError::RequestTimeout.into_response()),
+                }
+            }),
+            None => Box::pin(serve),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn body_limits() {
+        let declared = Request::builder()
+            .header(header::CONTENT_LENGTH, "5")
+            .body(Body::from("hello"))
+            .unwrap();
+        assert!(matches!(
+            limit_body(declared, 4).await,
+            Err(AppError::PayloadTooLarge)
+        ));
+
+        // without a declared length, as when sent chunked
+        let chunked = || Request::new(Body::from("hello"));
+        assert!(matches!(
+            limit_body(chunked(), 4).await,
+            Err(AppError::PayloadTooLarge)
+        ));
+        let request = limit_body(chunked(), 5).await.unwrap();
+        let body = axum::body::to_bytes(request.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"hello");
+    }
+}
@@ -0,0 +1,127 @@
+//! Load shedding: a ceiling on the requests served at once.
+//!
+//! Requests over the ceiling are refused straight away with a 503 and a `Retry-After`
+//! header rather than queued, so that the latency of those accepted stays bounded during
+//! spikes and clients retry against a less busy instance.
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::{header, HeaderValue, Request, Response};
+use axum::response::IntoResponse;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::Semaphore;
+use tower::{BoxError, Layer, Service};
+
+use crate::error::AppError;
+
+/// Seconds clients are told to wait before retrying, requests being short-lived.
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// Sheds requests over `max` concurrent ones, or lets everything through when it is 0.
+#[derive(Clone, Debug)]
+pub struct LoadShedLayer {
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl LoadShedLayer {
+    pub fn new(max: usize) -> LoadShedLayer {
+        LoadShedLayer {
+            permits: Some(max)
+                .filter(|max| *max > 0)
+                .map(|max| Arc::new(Semaphore::new(max))),
+        }
+    }
+}
+
+impl<S> Layer<S> for LoadShedLayer {
+    type Service = LoadShed<S>;
+
+    fn layer(&self, inner: S) -> LoadShed<S> {
+        LoadShed {
+            inner,
+            permits: self.permits.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LoadShed<S> {
+    inner: S,
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for LoadShed<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let permit = match &self.permits {
+            Some(permits) => match permits.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    tracing::warn!("Shedding a request, the concurrency limit is reached");
+                    let mut response = AppError::Overloaded.into_response();
+                    response
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+                    return Box::pin(async move { Ok(response) });
+                }
+            },
+            None => None,
+        };
+
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?.map(Body::new);
+            drop(permit);
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use std::convert::Infallible;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn sheds_over_the_limit() {
+        let layer = LoadShedLayer::new(1);
+        let service = tower::service_fn(|_: Request<Body>| async {
+            Ok::<_, Infallible>(Response::new(Body::empty()))
+        });
+        let permit = layer.permits.clone().unwrap().try_acquire_owned().unwrap();
+
+        let response = layer
+            .layer(service)
+            .oneshot(Request::new(Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+
+        drop(permit);
+        let response = layer
+            .layer(service)
+            .oneshot(Request::new(Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,412 @@
+use axum::http::HeaderMap;
+use axum::Json;
+use chrono::{Datelike, NaiveDate, NaiveTime};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::relative::Unit;
+
+/// How times of day are written, picked with `?clock=12` or `?clock=24`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum Clock {
+    /// `3:04:05 PM`, with midnight at `12:00:00 AM`.
+    #[serde(rename = "12")]
+    H12,
+    /// `15:04:05`.
+    #[serde(rename = "24")]
+    H24,
+}
+
+impl Clock {
+    pub fn format_time(self, time: NaiveTime) -> String {
+        match self {
+            Clock::H12 => time.format("%-I:%M:%S %p").to_string(),
+            Clock::H24 => time.format("%H:%M:%S").to_string(),
+        }
+    }
+}
+
+/// Vocabulary used when rendering dates and relative times for a given language.
+pub struct Locale {
+    pub code: &'static str,
+    pub months: [&'static str; 12],
+    /// Weekday names, starting from Monday.
+    pub weekdays: [&'static str; 7],
+    /// Singular and plural name of each [`Unit`], in the same order as the enum.
+    pub units: [(&'static str, &'static str); 7],
+    pub just_now: &'static str,
+    /// Templates for past and future distances, `{}` being replaced by e.g. "3 days".
+    pub past: &'static str,
+    pub future: &'static str,
+    /// Template of a long date, with `{weekday}`, `{day}`, `{month}` and `{year}` placeholders.
+    pub date_format: &'static str,
+    /// Clock used unless one is requested.
+    pub clock: Clock,
+}
+
+pub const EN: Locale = Locale {
+    code: "en",
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    weekdays: [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ],
+    units: [
+        ("second", "seconds"),
+        ("minute", "minutes"),
+        ("hour", "hours"),
+        ("day", "days"),
+        ("week", "weeks"),
+        ("month", "months"),
+        ("year", "years"),
+    ],
+    just_now: "just now",
+    past: "{} ago",
+    future: "in {}",
+    date_format: "{weekday}, {month} {day}, {year}",
+    clock: Clock::H12,
+};
+
+pub const IT: Locale = Locale {
+    code: "it",
+    months: [
+        "gennaio",
+        "febbraio",
+        "marzo",
+        "aprile",
+        "maggio",
+        "giugno",
+        "luglio",
+        "agosto",
+        "settembre",
+        "ottobre",
+        "novembre",
+        "dicembre",
+    ],
+    weekdays: [
+        "lunedì",
+        "martedì",
+        "mercoledì",
+        "giovedì",
+        "venerdì",
+        "sabato",
+        "domenica",
+    ],
+    units: [
+        ("secondo", "secondi"),
+        ("minuto", "minuti"),
+        ("ora", "ore"),
+        ("giorno", "giorni"),
+        ("settimana", "settimane"),
+        ("mese", "mesi"),
+        ("anno", "anni"),
+    ],
+    just_now: "proprio ora",
+    past: "{} fa",
+    future: "tra {}",
+    date_format: "{weekday} {day} {month} {year}",
+    clock: Clock::H24,
+};
+
+pub const ES: Locale = Locale {
+    code: "es",
+    months: [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    weekdays: [
+        "lunes",
+        "martes",
+        "miércoles",
+        "jueves",
+        "viernes",
+        "sábado",
+        "domingo",
+    ],
+    units: [
+        ("segundo", "segundos"),
+        ("minuto", "minutos"),
+        ("hora", "horas"),
+        ("día", "días"),
+        ("semana", "semanas"),
+        ("mes", "meses"),
+        ("año", "años"),
+    ],
+    just_now: "ahora mismo",
+    past: "hace {}",
+    future: "dentro de {}",
+    date_format: "{weekday}, {day} de {month} de {year}",
+    clock: Clock::H24,
+};
+
+pub const FR: Locale = Locale {
+    code: "fr",
+    months: [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ],
+    weekdays: [
+        "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+    ],
+    units: [
+        ("seconde", "secondes"),
+        ("minute", "minutes"),
+        ("heure", "heures"),
+        ("jour", "jours"),
+        ("semaine", "semaines"),
+        ("mois", "mois"),
+        ("an", "ans"),
+    ],
+    just_now: "à l'instant",
+    past: "il y a {}",
+    future: "dans {}",
+    date_format: "{weekday} {day} {month} {year}",
+    clock: Clock::H24,
+};
+
+pub const DE: Locale = Locale {
+    code: "de",
+    months: [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+    weekdays: [
+        "Montag",
+        "Dienstag",
+        "Mittwoch",
+        "Donnerstag",
+        "Freitag",
+        "Samstag",
+        "Sonntag",
+    ],
+    // Plurals are in the dative case, as required by both "vor" and "in".
+    units: [
+        ("Sekunde", "Sekunden"),
+        ("Minute", "Minuten"),
+        ("Stunde", "Stunden"),
+        ("Tag", "Tagen"),
+        ("Woche", "Wochen"),
+        ("Monat", "Monaten"),
+        ("Jahr", "Jahren"),
+    ],
+    just_now: "gerade eben",
+    past: "vor {}",
+    future: "in {}",
+    date_format: "{weekday}, {day}. {month} {year}",
+    clock: Clock::H24,
+};
+
+pub const ALL: [&Locale; 5] = [&EN, &IT, &ES, &FR, &DE];
+
+/// Looks up a locale by its language code, ignoring any region subtag (`en-GB` → `en`).
+pub fn find(code: &str) -> Option<&'static Locale> {
+    let language = code.split(&['-', '_'][..]).next()?;
+    ALL.iter()
+        .find(|locale| locale.code.eq_ignore_ascii_case(language))
+        .copied()
+}
+
+/// Picks the locale of a request: the `?locale=` override if given, otherwise the preferred
+/// supported language of the `Accept-Language` header. Returns `None` when the client
+/// expressed no supported preference, and a 400 for an unknown `?locale=`.
+pub fn negotiate(
+    headers: &HeaderMap,
+    requested: Option<&str>,
+) -> Result<Option<&'static Locale>, AppError> {
+    if let Some(code) = requested {
+        return find(code)
+            .map(Some)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown locale {}", code)));
+    }
+    let header = match headers.get("accept-language").and_then(|v| v.to_str().ok()) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    Ok(accepted(header).into_iter().find_map(find))
+}
+
+/// Ranges of an `Accept-Language` or `Accept` header, by decreasing preference. The `*`
+/// language wildcard is left out.
+pub fn accepted(header: &str) -> Vec<&str> {
+    let mut ranges: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            Some((tag, quality)).filter(|(_, quality)| *quality > 0.0)
+        })
+        .collect();
+    // the sort is stable, so equally preferred ranges keep their order
+    ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    ranges.into_iter().map(|(tag, _)| tag).collect()
+}
+
+impl Locale {
+    pub fn month_name(&self, date: NaiveDate) -> &'static str {
+        self.months[date.month0() as usize]
+    }
+
+    pub fn weekday_name(&self, date: NaiveDate) -> &'static str {
+        self.weekdays[date.weekday().num_days_from_monday() as usize]
+    }
+
+    /// Renders a long date such as "domenica 25 dicembre 2016".
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        self.date_format
+            .replace("{weekday}", self.weekday_name(date))
+            .replace("{day}", &date.day().to_string())
+            .replace("{month}", self.month_name(date))
+            .replace("{year}", &date.year().to_string())
+    }
+
+    pub fn unit_name(&self, unit: Unit, count: i64) -> &'static str {
+        let (singular, plural) = self.units[unit as usize];
+        if count == 1 {
+            singular
+        } else {
+            plural
+        }
+    }
+}
+
+pub async fn i18n_handler(Path(code): Path<String>) -> Result<Json<Value>, AppError> {
+    let locale = find(&code).ok_or_else(|| AppError::NotFound("Unknown locale".to_string()))?;
+
+    let units: serde_json::Map<String, Value> = Unit::ALL
+        .iter()
+        .map(|unit| {
+            let (one, other) = locale.units[*unit as usize];
+            (
+                unit.name().to_string(),
+                json!({ "one": one, "other": other }),
+            )
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "locale": locale.code,
+        "months": locale.months,
+        "weekdays": locale.weekdays,
+        "date_format": locale.date_format,
+        "clock": match locale.clock {
+            Clock::H12 => 12,
+            Clock::H24 => 24,
+        },
+        "relative": {
+            "just_now": locale.just_now,
+            "past": locale.past,
+            "future": locale.future,
+            "units": units,
+        },
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_ignores_region() {
+        assert_eq!(find("en-GB").map(|l| l.code), Some("en"));
+        assert_eq!(find("IT").map(|l| l.code), Some("it"));
+        assert!(find("xx").is_none());
+    }
+
+    #[test]
+    fn accept_language() {
+        assert_eq!(
+            accepted("fr-CH, fr;q=0.9, en;q=0.8, *;q=0.5"),
+            vec!["fr-CH", "fr", "en"]
+        );
+        assert_eq!(accepted("en;q=0.5, de"), vec!["de", "en"]);
+        assert_eq!(accepted("it;q=0, es"), vec!["es"]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-language", "xx, de-AT;q=0.7".parse().unwrap());
+        assert_eq!(
+            negotiate(&headers, None).unwrap().map(|l| l.code),
+            Some("de")
+        );
+        assert_eq!(
+            negotiate(&headers, Some("it")).unwrap().map(|l| l.code),
+            Some("it")
+        );
+        assert!(negotiate(&HeaderMap::new(), None).unwrap().is_none());
+        assert!(matches!(
+            negotiate(&headers, Some("xx")),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn long_dates() {
+        let christmas = NaiveDate::from_ymd_opt(2016, 12, 25).unwrap();
+        assert_eq!(IT.format_date(christmas), "domenica 25 dicembre 2016");
+        assert_eq!(DE.format_date(christmas), "Sonntag, 25. Dezember 2016");
+        assert_eq!(EN.format_date(christmas), "Sunday, December 25, 2016");
+    }
+
+    #[test]
+    fn clocks() {
+        let time = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 5).unwrap();
+        assert_eq!(Clock::H12.format_time(time(15, 4)), "3:04:05 PM");
+        assert_eq!(Clock::H12.format_time(time(0, 30)), "12:30:05 AM");
+        assert_eq!(Clock::H12.format_time(time(12, 0)), "12:00:05 PM");
+        assert_eq!(Clock::H24.format_time(time(9, 4)), "09:04:05");
+        assert_eq!(EN.clock, Clock::H12);
+        assert_eq!(DE.clock, Clock::H24);
+    }
+}
@@ -1,248 +1,273 @@
-use axum::body::{Bytes, Full};
-use axum::response::IntoResponse;
-use axum::{extract::Path, handler::get, response::Html, routing::BoxRoute, Json, Router};
-use chrono::format::ParseError;
-use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
-use hyper::StatusCode;
-use serde_json::{json, Value};
-use std::convert::Infallible;
-use std::net::SocketAddr;
-use tower_http::trace::TraceLayer;
-
-#[tokio::main]
-async fn main() {
-    // Set the RUST_LOG, if it hasn't been explicitly defined
-    if std::env::var("RUST_LOG").is_err() {
-        std::env::set_var("RUST_LOG", "timestamp_microservice=debug,tower_http=debug")
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use hyper_util::server::conn::auto;
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::time::Duration;
+use timestamp_microservice::config::{self, Config, Listener, ServerConfig, Settings};
+use timestamp_microservice::drain::Drain;
+use timestamp_microservice::{
+    admin, app, cli, geo, rate_limit, telemetry, timezone, tls, tzdata, uptime,
+};
+use tokio::sync::watch;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    // Conversions from the command line print JSON to stdout, without any log
+    if let Some(outcome) = cli::run(&args) {
+        match outcome {
+            Ok(body) => println!("{}", serde_json::to_string_pretty(&body).unwrap()),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
     }
-    tracing_subscriber::fmt::init();
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    tracing::info!("listening on {}", addr);
-
-    axum::Server::bind(&addr)
-        .serve(app().into_make_service())
-        .await
-        .unwrap();
-}
-
-/// Having an app function makes it easy to call it from test
-fn app() -> Router<BoxRoute> {
-    Router::new()
-        .route("/", get(hello_handler))
-        .route("/api", get(now_handler))
-        .route("/api/:date", get(date_handler))
-        .layer(TraceLayer::new_for_http())
-        .boxed()
-}
-
-async fn hello_handler() -> Html<&'static str> {
-    Html("<h1>Hello World!</h1>")
-}
-
-async fn date_handler(Path(mut date): Path<String>) -> Result<Json<Value>, AppError> {
-    tracing::info!("Provided date is {}", date);
-    let timestamp = date.parse::<i64>();
-    if timestamp.is_ok() {
-        let timestamp = timestamp.unwrap();
-        let ndt = NaiveDateTime::from_timestamp(timestamp, 0);
-        date = ndt.format("%Y-%m-%d").to_string();
-        tracing::debug!(
-            "We converted from the original timestamp {} to the following date {}",
-            timestamp,
-            date
-        );
+    let mut config = Config::load(config::argument(&args, "--config").as_deref(), |var| {
+        std::env::var(var).ok()
+    })
+    .expect("Invalid configuration");
+    config
+        .apply_arguments(&args)
+        .expect("Invalid command line arguments");
+
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    runtime.enable_all();
+    if let Some(threads) = config.server.worker_threads {
+        runtime.worker_threads(threads);
     }
-
-    let date: NaiveDate = date.parse()?;
-    let date = DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc);
-
-    tracing::debug!("Converted date is {}", date);
-    Ok(Json(json!({
-        "unix": date.timestamp(),
-        "utc": date.to_rfc2822(),
-    })))
-}
-
-async fn now_handler() -> Result<Json<Value>, AppError> {
-    let utc: DateTime<Utc> = Utc::now();
-    Ok(Json(json!({
-        "unix": utc.timestamp(),
-        "utc": utc.to_rfc2822(),
-    })))
+    if let Some(threads) = config.server.max_blocking_threads {
+        runtime.max_blocking_threads(threads);
+    }
+    runtime
+        .build()
+        .expect("Can't start the Tokio runtime")
+        .block_on(serve(args, config));
 }
 
-struct AppError;
+async fn serve(args: Vec<String>, config: Config) {
+    let log_handle = init_logging(&config.log_level);
+    let admin_handle = log_handle.clone();
+    admin::install_log_reloader(move |level| {
+        admin_handle
+            .reload(EnvFilter::new(level))
+            .map_err(|e| e.to_string())
+    });
+    timezone::set_default(config.timezone().unwrap());
+    rate_limit::trust_proxies(config.trusted_proxies.clone());
+    if let Some(dir) = &config.tzdata_dir {
+        tzdata::install(tzdata::load(Path::new(dir)).expect("Invalid zoneinfo directory"));
+    }
+    if let Some(path) = &config.tz_boundaries {
+        geo::install(geo::load(Path::new(path)).expect("Invalid zone boundaries"));
+    }
+    if let Some(path) = &config.geoip_db {
+        geo::install_geoip(Path::new(path)).expect("Invalid GeoIP database");
+    }
 
-impl From<ParseError> for AppError {
-    fn from(error: ParseError) -> Self {
-        tracing::error!("Error while parsing the date: {}", error);
-        AppError
+    let (updates, settings) = watch::channel(config.runtime());
+    if let Some(path) = config::argument(&args, "--config") {
+        config::reload_on_sighup(path, updates);
+    }
+    reload_log_level(settings.clone(), log_handle);
+
+    let tls = tls::paths(&args).expect("Invalid TLS configuration");
+    // A Unix socket given on the command line replaces the configured listeners
+    let listen =
+        match config::argument(&args, "--uds").or_else(|| std::env::var("TIMESTAMP_UDS").ok()) {
+            Some(path) => vec![Listener::Unix(path)],
+            None => config.listen.clone(),
+        };
+    let server = config.server;
+    let tcp_keepalive = server.tcp_keepalive_secs.map(Duration::from_secs);
+    let drain = Drain::new(Duration::from_secs(server.drain_grace_secs));
+    let app = app(&config, settings, uptime::Started::now(), drain.clone());
+
+    let tls_config = match tls {
+        Some(paths) => {
+            let tls_config = tls::load(&paths)
+                .await
+                .expect("Invalid TLS certificate or key");
+            tls::reload_on_sighup(tls_config.clone(), paths);
+            Some(tls_config)
+        }
+        None => None,
+    };
+    let handle = Handle::new();
+    let shutdown = handle.clone();
+    let draining = drain.clone();
+    tokio::spawn(async move {
+        draining.shutdown().await;
+        shutdown.graceful_shutdown(None);
+    });
+
+    // An address that can't be bound fails the startup
+    let mut servers = Vec::new();
+    for listener in listen {
+        let app = app.clone();
+        let server = match listener {
+            Listener::Tcp(addr) => {
+                let listener = bind(addr, tcp_keepalive)
+                    .unwrap_or_else(|e| panic!("Can't bind {}: {}", addr, e));
+                tokio::spawn(serve_tcp(
+                    listener,
+                    tls_config.clone(),
+                    server,
+                    app,
+                    handle.clone(),
+                ))
+            }
+            Listener::Unix(path) => {
+                assert!(
+                    tls_config.is_none(),
+                    "TLS is not supported over a Unix socket"
+                );
+                tokio::spawn(serve_unix(path, app, drain.clone()))
+            }
+        };
+        servers.push(server);
+    }
+    for server in servers {
+        server.await.expect("A listener failed");
     }
+    telemetry::shutdown();
 }
 
-impl IntoResponse for AppError {
-    type Body = Full<Bytes>;
-    type BodyError = Infallible;
-
-    fn into_response(self) -> hyper::Response<Self::Body> {
-        let status = StatusCode::UNPROCESSABLE_ENTITY;
-        let body = Json(json!({
-            "error": "Invalid Date"
-        }));
-
-        (status, body).into_response()
+/// Binds a listening socket, with TCP keepalive probes that accepted connections inherit.
+/// IPv6 sockets only accept IPv6 connections, so that `[::]` and `0.0.0.0` can be bound
+/// side by side on dual-stack hosts.
+fn bind(addr: SocketAddr, tcp_keepalive: Option<Duration>) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(true)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    if let Some(time) = tcp_keepalive {
+        socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(time))?;
     }
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
 }
 
-#[cfg(test)]
-mod tests {
-    use axum::body::Body;
-    use axum::http::{Request, StatusCode};
-    use serde_json::{json, Value};
-    use tower::ServiceExt;
-
-    use super::*;
-
-    #[tokio::test]
-    async fn hello_world() {
-        let app = app();
-
-        let response = app
-            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
-            .await
-            .unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-
-        assert_eq!(&body[..], b"<h1>Hello World!</h1>");
+/// Applies the connection settings of `[server]`. HTTP/2 is negotiated over TLS, and
+/// without it only served to clients starting with its preface when `h2c` is set.
+fn configure(builder: &mut auto::Builder<TokioExecutor>, server: ServerConfig, tls: bool) {
+    if !tls && !server.h2c {
+        *builder = builder.clone().http1_only();
     }
-
-    #[tokio::test]
-    async fn not_found() {
-        let app = app();
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .uri("/not-found")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
-
-        assert_eq!(response.status(), StatusCode::NOT_FOUND);
-
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-
-        assert!(body.is_empty());
+    let mut http1 = builder.http1();
+    http1.keep_alive(server.keep_alive);
+    if let Some(secs) = server.idle_timeout_secs {
+        http1
+            .timer(TokioTimer::new())
+            .header_read_timeout(Duration::from_secs(secs));
     }
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .keep_alive_interval(
+            server
+                .http2_keep_alive_interval_secs
+                .map(Duration::from_secs),
+        )
+        .keep_alive_timeout(Duration::from_secs(server.http2_keep_alive_timeout_secs));
+}
 
-    #[tokio::test]
-    async fn valid_date_string() {
-        let app = app();
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .uri("/api/2016-12-25")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
-
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body: Value = serde_json::from_slice(&body).unwrap();
-
-        assert_eq!(
-            body,
-            json!({
-                "unix": 1482624000,
-                "utc": "Sun, 25 Dec 2016 00:00:00 +0000"
-            })
-        );
+/// Serves the app on a bound TCP socket, over TLS when configured. Listeners share the
+/// handle stopping them once drained.
+async fn serve_tcp(
+    listener: TcpListener,
+    tls_config: Option<RustlsConfig>,
+    server: ServerConfig,
+    app: Router,
+    handle: Handle,
+) {
+    let addr = listener.local_addr().expect("Unbound listener");
+    // peer addresses tell clients apart for rate limiting
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let served = match tls_config {
+        Some(tls_config) => {
+            tracing::info!("listening on https://{}", addr);
+            let mut https = axum_server::from_tcp_rustls(listener, tls_config).handle(handle);
+            configure(https.http_builder(), server, true);
+            https.serve(service).await
+        }
+        None => {
+            tracing::info!("listening on {}", addr);
+            let mut http = axum_server::from_tcp(listener).handle(handle);
+            configure(http.http_builder(), server, false);
+            http.serve(service).await
+        }
+    };
+    if let Err(e) = served {
+        tracing::error!("Can't serve on {}: {}", addr, e);
     }
-    // A request to /api/1451001600 should return { unix: 1451001600000, utc: "Fri, 25 Dec 2015 00:00:00 GMT" }
-    #[tokio::test]
-    async fn timestamp() {
-        let app = app();
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .uri("/api/1451001600")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
+}
 
-        assert_eq!(response.status(), StatusCode::OK);
+/// Serves the app on a Unix domain socket, replacing any stale socket file. Connections
+/// are always kept alive there and speak HTTP/1, `axum::serve` having no settings for it.
+#[cfg(unix)]
+async fn serve_unix(path: String, app: Router, drain: Drain) {
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path).expect("Can't bind the Unix socket");
+    tracing::info!("listening on unix:{}", path);
 
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body: Value = serde_json::from_slice(&body).unwrap();
+    axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(drain.shutdown())
+        .await
+        .unwrap();
+}
 
-        assert_eq!(
-            body,
-            json!({
-                "unix": 1451001600,
-                "utc": "Fri, 25 Dec 2015 00:00:00 +0000"
-            })
-        );
-    }
+#[cfg(not(unix))]
+async fn serve_unix(_path: String, _app: Router, _drain: Drain) {
+    panic!("Unix sockets are not supported on this platform");
+}
 
-    // If the input date string is invalid, the api returns an object having the structure { error : "Invalid Date" }
-    #[tokio::test]
-    async fn invalid_date() {
-        let app = app();
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .uri("/api/this-is-not-a-date")
-                    .body(Body::empty())
-                    .unwrap(),
+/// Logs human-readable lines, or one JSON object per line with `LOG_FORMAT=json`. JSON
+/// events are flattened and carry the fields of the request span they were logged in.
+/// Spans are also exported to OpenTelemetry when configured, see [`telemetry`].
+///
+/// Returns the handle changing the filter at runtime.
+fn init_logging(level: &str) -> reload::Handle<EnvFilter, Registry> {
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(level));
+    let registry = tracing_subscriber::registry().with(filter);
+    match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => registry
+            .with(
+                fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(true)
+                    .with_span_list(false),
             )
-            .await
-            .unwrap();
-
-        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
-
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body: Value = serde_json::from_slice(&body).unwrap();
-
-        assert_eq!(
-            body,
-            json!({
-                "error": "Invalid Date"
-            })
-        );
+            .with(telemetry::layer().expect("Invalid OpenTelemetry configuration"))
+            .init(),
+        _ => registry
+            .with(fmt::layer())
+            .with(telemetry::layer().expect("Invalid OpenTelemetry configuration"))
+            .init(),
     }
+    handle
+}
 
-    // An empty date parameter should return the current time in a JSON object with a unix key
-    // Note: this test is fragile as it's comparing equally the two responses.
-    // A more sound way would be to assert approximately as, due to latecy, the times may differ.
-    #[tokio::test]
-    async fn empty_param() {
-        let app = app();
-        let now: DateTime<Utc> = Utc::now();
-        let response = app
-            .oneshot(Request::builder().uri("/api").body(Body::empty()).unwrap())
-            .await
-            .unwrap();
-
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body: Value = serde_json::from_slice(&body).unwrap();
-
-        assert_eq!(
-            body,
-            json!({
-                "unix": now.timestamp(),
-                "utc": now.to_rfc2822(),
-            })
-        );
-    }
+/// Applies the log level of reloaded settings.
+fn reload_log_level(mut settings: Settings, handle: reload::Handle<EnvFilter, Registry>) {
+    tokio::spawn(async move {
+        while settings.changed().await.is_ok() {
+            let level = settings.borrow().log_level.clone();
+            if let Err(e) = handle.reload(EnvFilter::new(&level)) {
+                tracing::error!("Can't change the log level: {}", e);
+            }
+        }
+    });
 }
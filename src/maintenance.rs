@@ -0,0 +1,240 @@
+//! Recurring maintenance windows.
+//!
+//! Windows are read at startup from the JSON file pointed to by the
+//! `TIMESTAMP_MAINTENANCE` environment variable. Each one either starts on a cron schedule
+//! and lasts `duration` seconds, or spans a weekly range of wall-clock times:
+//!
+//! ```json
+//! [
+//!     { "name": "backups", "cron": "0 2 * * *", "duration": 3600, "tz": "Europe/Rome" },
+//!     { "name": "weekend", "weekly": { "start": "sat 22:00", "end": "mon 06:00" } }
+//! ]
+//! ```
+//!
+//! Weekly ranges are turned into a cron schedule plus a duration, so across a DST
+//! transition they end an hour early or late in wall-clock terms.
+
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Duration, NaiveTime, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::config::read_env_file;
+use crate::cron::Schedule;
+use crate::error::AppError;
+use crate::parse_date;
+use crate::query::Query;
+use crate::timezone::parse_tz;
+
+#[derive(Debug, Deserialize)]
+struct WeeklyRange {
+    start: String,
+    end: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WindowConfig {
+    name: String,
+    cron: Option<String>,
+    duration: Option<i64>,
+    weekly: Option<WeeklyRange>,
+    tz: Option<String>,
+}
+
+pub struct Window {
+    name: String,
+    schedule: Schedule,
+    duration: Duration,
+    tz: Tz,
+}
+
+impl Window {
+    /// The occurrence of the window containing `at`, if any.
+    pub fn current(&self, at: DateTime<Utc>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let start = self.schedule.next(at - self.duration, self.tz)?;
+        Some((start, start + self.duration)).filter(|_| start <= at)
+    }
+
+    /// The first occurrence of the window starting after `at`.
+    pub fn next(&self, at: DateTime<Utc>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let start = self.schedule.next(at, self.tz)?;
+        Some((start, start + self.duration))
+    }
+}
+
+pub type Windows = Arc<Vec<Window>>;
+
+/// Loads the windows from the file named by `TIMESTAMP_MAINTENANCE`, if set.
+pub fn load() -> Result<Windows, String> {
+    match read_env_file("TIMESTAMP_MAINTENANCE")? {
+        Some(json) => parse_windows(&json).map(Arc::new),
+        None => Ok(Windows::default()),
+    }
+}
+
+/// Parses `sat 22:00` into a weekday and a time.
+fn parse_weekly_time(value: &str) -> Option<(Weekday, NaiveTime)> {
+    let (day, time) = value.trim().split_once(' ')?;
+    let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()?;
+    Some((day.parse().ok()?, time))
+}
+
+fn parse_window(config: WindowConfig) -> Result<Window, String> {
+    let name = config.name;
+    let tz =
+        parse_tz(config.tz.as_deref()).map_err(|_| format!("window {}: unknown timezone", name))?;
+
+    let (expr, duration) = match (config.cron, config.duration, config.weekly) {
+        (Some(expr), Some(duration), None) if duration > 0 => (expr, Duration::seconds(duration)),
+        (None, None, Some(weekly)) => {
+            let (start_day, start) = parse_weekly_time(&weekly.start)
+                .ok_or_else(|| format!("window {}: invalid start", name))?;
+            let (end_day, end) = parse_weekly_time(&weekly.end)
+                .ok_or_else(|| format!("window {}: invalid end", name))?;
+            let minute_of_week = |day: Weekday, time: NaiveTime| {
+                (day.num_days_from_monday() * 24 * 60 + time.hour() * 60 + time.minute()) as i64
+            };
+            let mut minutes = minute_of_week(end_day, end) - minute_of_week(start_day, start);
+            if minutes <= 0 {
+                minutes += 7 * 24 * 60;
+            }
+            let expr = format!(
+                "{} {} * * {}",
+                start.minute(),
+                start.hour(),
+                start_day.num_days_from_sunday()
+            );
+            (expr, Duration::minutes(minutes))
+        }
+        _ => {
+            return Err(format!(
+                "window {}: either cron and a positive duration or weekly is required",
+                name
+            ))
+        }
+    };
+    let schedule = Schedule::parse(&expr)
+        .ok_or_else(|| format!("window {}: invalid cron expression", name))?;
+
+    Ok(Window {
+        name,
+        schedule,
+        duration,
+        tz,
+    })
+}
+
+fn parse_windows(json: &str) -> Result<Vec<Window>, String> {
+    let configs: Vec<WindowConfig> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    configs.into_iter().map(parse_window).collect()
+}
+
+fn render(window: Option<(DateTime<Utc>, DateTime<Utc>)>) -> Value {
+    match window {
+        Some((start, end)) => json!({
+            "start": { "unix": start.timestamp(), "utc": start.to_rfc2822() },
+            "end": { "unix": end.timestamp(), "utc": end.to_rfc2822() },
+        }),
+        None => Value::Null,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceParams {
+    /// Defaults to now.
+    at: Option<String>,
+}
+
+/// Tells whether an instant falls in a maintenance window, with the next boundaries.
+pub async fn maintenance_handler(
+    State(windows): State<Windows>,
+    Query(params): Query<MaintenanceParams>,
+) -> Result<Json<Value>, AppError> {
+    let at = match params.at {
+        Some(at) => parse_date(&at)?,
+        None => Utc::now(),
+    };
+
+    let mut in_maintenance = false;
+    let windows: Vec<Value> = windows
+        .iter()
+        .map(|window| {
+            let current = window.current(at);
+            in_maintenance |= current.is_some();
+            // while inside a window, the next one starts after it ends
+            let next = window.next(current.map_or(at, |(_, end)| end));
+            json!({
+                "name": window.name,
+                "tz": window.tz.name(),
+                "active": current.is_some(),
+                "current": render(current),
+                "next": render(next),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "at": { "unix": at.timestamp(), "utc": at.to_rfc2822() },
+        "in_maintenance": in_maintenance,
+        "windows": windows,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn cron_window() {
+        let windows = parse_windows(
+            r#"[{ "name": "backups", "cron": "0 2 * * *", "duration": 3600, "tz": "Europe/Rome" }]"#,
+        )
+        .unwrap();
+        let window = &windows[0];
+
+        let inside = Utc.with_ymd_and_hms(2021, 8, 18, 0, 30, 0).unwrap();
+        let start = Utc.with_ymd_and_hms(2021, 8, 18, 0, 0, 0).unwrap();
+        assert_eq!(
+            window.current(inside),
+            Some((start, start + Duration::hours(1)))
+        );
+        assert_eq!(window.current(start + Duration::hours(1)), None);
+        assert_eq!(
+            window.next(inside).map(|(start, _)| start),
+            Some(start + Duration::days(1))
+        );
+    }
+
+    #[test]
+    fn weekly_window() {
+        let windows = parse_windows(
+            r#"[{ "name": "weekend", "weekly": { "start": "sat 22:00", "end": "mon 06:00" } }]"#,
+        )
+        .unwrap();
+        let window = &windows[0];
+
+        // Sunday
+        assert!(window
+            .current(Utc.with_ymd_and_hms(2021, 8, 22, 12, 0, 0).unwrap())
+            .is_some());
+        assert!(window
+            .current(Utc.with_ymd_and_hms(2021, 8, 23, 6, 0, 0).unwrap())
+            .is_none());
+        assert!(window
+            .current(Utc.with_ymd_and_hms(2021, 8, 21, 21, 59, 0).unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_windows() {
+        assert!(parse_windows(r#"[{ "name": "a", "cron": "0 2 * * *" }]"#).is_err());
+        assert!(parse_windows(
+            r#"[{ "name": "a", "weekly": { "start": "sat", "end": "sun 01:00" } }]"#
+        )
+        .is_err());
+    }
+}
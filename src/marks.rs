@@ -0,0 +1,187 @@
+//! Named timestamps, recording "the moment X happened" for later lookups.
+//!
+//! Marks are kept behind the [`MarkStorage`] trait so that a persistent backend can
+//! replace the in-memory one without touching the handlers.
+
+use axum::extract::State;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::error::AppError;
+use crate::extract::{Json, Path};
+use crate::parse_date;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mark {
+    pub at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Mark {
+    pub fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// Storage backend for marks. Expired marks must never be returned.
+pub trait MarkStorage: Send + Sync {
+    fn put(&self, name: String, mark: Mark);
+    fn get(&self, name: &str, now: DateTime<Utc>) -> Option<Mark>;
+    fn remove(&self, name: &str, now: DateTime<Utc>) -> Option<Mark>;
+    /// Live marks, in no particular order.
+    fn list(&self, now: DateTime<Utc>) -> Vec<(String, Mark)>;
+}
+
+/// Shared handle to the storage, injected into the handlers as an extension.
+pub type Marks = Arc<dyn MarkStorage>;
+
+/// Keeps marks in memory; expired ones are dropped whenever a mark is written.
+#[derive(Default)]
+pub struct MemoryStorage {
+    marks: Mutex<HashMap<String, Mark>>,
+}
+
+impl MarkStorage for MemoryStorage {
+    fn put(&self, name: String, mark: Mark) {
+        let now = Utc::now();
+        let mut marks = self.marks.lock().unwrap();
+        marks.retain(|_, mark| mark.is_live(now));
+        marks.insert(name, mark);
+    }
+
+    fn get(&self, name: &str, now: DateTime<Utc>) -> Option<Mark> {
+        let marks = self.marks.lock().unwrap();
+        marks.get(name).filter(|mark| mark.is_live(now)).cloned()
+    }
+
+    fn remove(&self, name: &str, now: DateTime<Utc>) -> Option<Mark> {
+        let mut marks = self.marks.lock().unwrap();
+        marks.remove(name).filter(|mark| mark.is_live(now))
+    }
+
+    fn list(&self, now: DateTime<Utc>) -> Vec<(String, Mark)> {
+        let marks = self.marks.lock().unwrap();
+        marks
+            .iter()
+            .filter(|(_, mark)| mark.is_live(now))
+            .map(|(name, mark)| (name.clone(), mark.clone()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MarkRequest {
+    /// The marked moment, defaults to now.
+    at: Option<String>,
+    /// Time to live in seconds; marks are kept forever without it.
+    ttl: Option<i64>,
+}
+
+impl MarkRequest {
+    fn into_mark(self, now: DateTime<Utc>) -> Result<Mark, AppError> {
+        let at = match self.at {
+            Some(date) => parse_date(&date)?,
+            None => now,
+        };
+        let expires_at = match self.ttl {
+            Some(ttl) if ttl > 0 => Some(now + Duration::seconds(ttl)),
+            Some(_) => return Err(AppError::BadRequest("ttl must be positive".to_string())),
+            None => None,
+        };
+        Ok(Mark { at, expires_at })
+    }
+}
+
+fn render(name: &str, mark: &Mark, now: DateTime<Utc>) -> Value {
+    json!({
+        "name": name,
+        "unix": mark.at.timestamp(),
+        "utc": mark.at.to_rfc2822(),
+        "seconds_ago": (now - mark.at).num_seconds(),
+        "expires_at": mark.expires_at.map(|expires_at| json!({
+            "unix": expires_at.timestamp(),
+            "utc": expires_at.to_rfc2822(),
+        })),
+    })
+}
+
+pub async fn put_handler(
+    Path(name): Path<String>,
+    State(marks): State<Marks>,
+    request: Option<Json<MarkRequest>>,
+) -> Result<Json<Value>, AppError> {
+    let now = Utc::now();
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let mark = request.into_mark(now)?;
+    let body = render(&name, &mark, now);
+    marks.put(name, mark);
+
+    Ok(Json(body))
+}
+
+pub async fn get_handler(
+    Path(name): Path<String>,
+    State(marks): State<Marks>,
+) -> Result<Json<Value>, AppError> {
+    let now = Utc::now();
+    let mark = marks
+        .get(&name, now)
+        .ok_or_else(|| AppError::NotFound("Unknown or expired mark".to_string()))?;
+
+    Ok(Json(render(&name, &mark, now)))
+}
+
+pub async fn delete_handler(
+    Path(name): Path<String>,
+    State(marks): State<Marks>,
+) -> Result<Json<Value>, AppError> {
+    let now = Utc::now();
+    let mark = marks
+        .remove(&name, now)
+        .ok_or_else(|| AppError::NotFound("Unknown or expired mark".to_string()))?;
+
+    Ok(Json(render(&name, &mark, now)))
+}
+
+/// All live marks, the most recent first.
+pub async fn list_handler(State(marks): State<Marks>) -> Json<Value> {
+    let now = Utc::now();
+    let mut marks = marks.list(now);
+    marks.sort_by_key(|(_, mark)| std::cmp::Reverse(mark.at));
+    let marks: Vec<Value> = marks
+        .iter()
+        .map(|(name, mark)| render(name, mark, now))
+        .collect();
+
+    Json(json!({ "marks": marks }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_marks_are_hidden() {
+        let storage = MemoryStorage::default();
+        let now = Utc::now();
+        let request = MarkRequest {
+            at: Some("2016-12-25".to_string()),
+            ttl: Some(10),
+        };
+        storage.put("release".to_string(), request.into_mark(now).unwrap());
+        storage.put(
+            "deploy".to_string(),
+            MarkRequest::default().into_mark(now).unwrap(),
+        );
+
+        let mark = storage.get("release", now).unwrap();
+        assert_eq!(mark.at.timestamp(), 1482624000);
+        assert!(storage
+            .get("release", now + Duration::seconds(10))
+            .is_none());
+        assert_eq!(storage.list(now + Duration::days(365)).len(), 1);
+    }
+}
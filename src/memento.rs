@@ -0,0 +1,68 @@
+//! RFC 7089 datetime negotiation, letting web-archiving tools ask `/api` for the state of
+//! the clock at another instant with `Accept-Datetime`.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use chrono::{DateTime, Utc};
+
+use crate::error::AppError;
+
+pub const ACCEPT_DATETIME: &str = "accept-datetime";
+pub const MEMENTO_DATETIME: &str = "memento-datetime";
+
+/// Reads `Accept-Datetime`, an HTTP date such as `Thu, 01 Apr 2010 00:00:00 GMT`.
+pub fn accept_datetime(headers: &HeaderMap) -> Result<Option<DateTime<Utc>>, AppError> {
+    let value = match headers.get(ACCEPT_DATETIME) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        .map(|date| Some(date.with_timezone(&Utc)))
+        .ok_or_else(|| AppError::BadRequest("Invalid Accept-Datetime header".to_string()))
+}
+
+/// Headers of a response negotiated on `Accept-Datetime`, carrying the instant it
+/// describes in `Memento-Datetime` when one was asked for.
+pub fn headers(memento: Option<DateTime<Utc>>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::VARY, HeaderValue::from_static(ACCEPT_DATETIME));
+    if let Some(date) = memento {
+        let date = date.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        if let Ok(value) = HeaderValue::from_str(&date) {
+            headers.insert(MEMENTO_DATETIME, value);
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn negotiation() {
+        let mut request = HeaderMap::new();
+        assert_eq!(accept_datetime(&request).unwrap(), None);
+
+        request.insert(
+            ACCEPT_DATETIME,
+            HeaderValue::from_static("Thu, 01 Apr 2010 00:00:00 GMT"),
+        );
+        let date = accept_datetime(&request).unwrap();
+        assert_eq!(
+            date,
+            Some(Utc.with_ymd_and_hms(2010, 4, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            headers(date)[MEMENTO_DATETIME],
+            "Thu, 01 Apr 2010 00:00:00 GMT"
+        );
+        assert!(!headers(None).contains_key(MEMENTO_DATETIME));
+
+        request.insert(ACCEPT_DATETIME, HeaderValue::from_static("yesterday"));
+        assert!(accept_datetime(&request).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! Service metrics in the Prometheus text exposition format, served on `/metrics`.
+//!
+//! Requests are measured by route, a label built from their path by replacing every
+//! segment that isn't a lowercase word with `:param`, since the router doesn't tell which
+//! route matched. Paths made of words only, such as unknown routes, would still add a
+//! label each, so past `MAX_ROUTES` labels new ones are counted as `other`.
+//!
+//! Parse failures are counted when the parser runs: an invalid input repeated and answered
+//! from the parse cache counts once.
+//!
+//! Scrapers preferring `application/openmetrics-text` get the OpenMetrics format instead,
+//! where each latency bucket carries an exemplar: the trace id of the last request it
+//! counted, when traces are exported, so that a slow bucket leads to a slow trace.
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, Request};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{Layer, Service};
+
+use crate::locale::accepted;
+use crate::parse::InputKind;
+use crate::parse_cache::ParseCache;
+use crate::telemetry;
+
+/// Upper bounds of the latency buckets, in seconds.
+const BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// Number of route labels past which requests are counted as `other`.
+const MAX_ROUTES: usize = 100;
+
+const OPENMETRICS: &str = "application/openmetrics-text";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Prometheus,
+    OpenMetrics,
+}
+
+/// The format preferred in the `Accept` header of a scrape.
+fn negotiate(headers: &HeaderMap) -> Format {
+    let preferred = headers
+        .get(header::ACCEPT)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| accepted(header).into_iter().next());
+    match preferred {
+        Some(OPENMETRICS) => Format::OpenMetrics,
+        _ => Format::Prometheus,
+    }
+}
+
+/// Inputs rejected by the parser, by the format they looked like.
+static PARSE_FAILURES: Mutex<BTreeMap<&'static str, u64>> = Mutex::new(BTreeMap::new());
+
+/// Counts an input of the given kind that failed to parse.
+pub fn record_parse_failure(kind: InputKind) {
+    *PARSE_FAILURES
+        .lock()
+        .unwrap()
+        .entry(kind.name())
+        .or_default() += 1;
+}
+
+/// A traced request counted in a bucket.
+#[derive(Debug)]
+struct Exemplar {
+    trace_id: String,
+    seconds: f64,
+}
+
+#[derive(Debug, Default)]
+struct RouteStats {
+    in_flight: u64,
+    /// Requests that took at most each bound of `BUCKETS`, not cumulated.
+    buckets: [u64; BUCKETS.len()],
+    /// The last traced request of each bucket, then of those over every bound.
+    exemplars: [Option<Exemplar>; BUCKETS.len() + 1],
+    count: u64,
+    sum: f64,
+}
+
+/// Latencies and requests in flight, by route.
+#[derive(Clone, Debug, Default)]
+pub struct RouteMetrics {
+    routes: Arc<Mutex<BTreeMap<String, RouteStats>>>,
+}
+
+/// The route label of a path, such as `/api/tz/:param/offset`.
+fn route_label(path: &str) -> String {
+    let is_word = |segment: &str| {
+        segment
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b == b'_' || b == b'-')
+    };
+    let segments: Vec<&str> = path
+        .split('/')
+        .skip(1)
+        .map(|segment| if is_word(segment) { segment } else { ":param" })
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+impl RouteMetrics {
+    /// Counts a request as in flight, returning the label it is counted under.
+    fn start(&self, path: &str) -> String {
+        let mut routes = self.routes.lock().unwrap();
+        let mut label = route_label(path);
+        if routes.len() >= MAX_ROUTES && !routes.contains_key(&label) {
+            label = "other".to_string();
+        }
+        routes.entry(label.clone()).or_default().in_flight += 1;
+        label
+    }
+
+    fn finish(&self, label: &str, seconds: f64, trace_id: Option<String>) {
+        let mut routes = self.routes.lock().unwrap();
+        let stats = routes.entry(label.to_string()).or_default();
+        stats.in_flight -= 1;
+        stats.count += 1;
+        stats.sum += seconds;
+        let bucket = BUCKETS.iter().position(|bound| seconds <= *bound);
+        if let Some(bucket) = bucket {
+            stats.buckets[bucket] += 1;
+        }
+        if let Some(trace_id) = trace_id {
+            stats.exemplars[bucket.unwrap_or(BUCKETS.len())] = Some(Exemplar { trace_id, seconds });
+        }
+    }
+
+    fn write(&self, out: &mut String, format: Format) {
+        let routes = self.routes.lock().unwrap();
+
+        let name = "timestamp_http_request_duration_seconds";
+        header_lines(
+            out,
+            format,
+            name,
+            "histogram",
+            "Latency of requests, by route.",
+        );
+        for (route, stats) in routes.iter() {
+            let exemplar = |bucket: usize| match &stats.exemplars[bucket] {
+                Some(traced) if format == Format::OpenMetrics => {
+                    format!(" # {{trace_id=\"{}\"}} {}", traced.trace_id, traced.seconds)
+                }
+                _ => String::new(),
+            };
+            let mut cumulated = 0;
+            for (i, (bound, count)) in BUCKETS.iter().zip(&stats.buckets).enumerate() {
+                cumulated += count;
+                writeln!(
+                    out,
+                    "{}_bucket{{route=\"{}\",le=\"{}\"}} {}{}",
+                    name,
+                    route,
+                    bound,
+                    cumulated,
+                    exemplar(i)
+                )
+                .unwrap();
+            }
+            writeln!(
+                out,
+                "{}_bucket{{route=\"{}\",le=\"+Inf\"}} {}{}",
+                name,
+                route,
+                stats.count,
+                exemplar(BUCKETS.len())
+            )
+            .unwrap();
+            writeln!(out, "{}_sum{{route=\"{}\"}} {}", name, route, stats.sum).unwrap();
+            writeln!(out, "{}_count{{route=\"{}\"}} {}", name, route, stats.count).unwrap();
+        }
+
+        let name = "timestamp_http_requests_in_flight";
+        header_lines(
+            out,
+            format,
+            name,
+            "gauge",
+            "Requests being served, by route.",
+        );
+        for (route, stats) in routes.iter() {
+            writeln!(out, "{}{{route=\"{}\"}} {}", name, route, stats.in_flight).unwrap();
+        }
+    }
+}
+
+/// Ends the measure of a request when its response is ready, or when it is dropped.
+struct InFlight {
+    metrics: RouteMetrics,
+    label: String,
+    started: Instant,
+    trace_id: Option<String>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.metrics.finish(
+            &self.label,
+            self.started.elapsed().as_secs_f64(),
+            self.trace_id.take(),
+        );
+    }
+}
+
+/// Records the latency of every request in a [`RouteMetrics`].
+#[derive(Clone, Debug)]
+pub struct MetricsLayer {
+    metrics: RouteMetrics,
+}
+
+impl MetricsLayer {
+    pub fn new(metrics: RouteMetrics) -> MetricsLayer {
+        MetricsLayer { metrics }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = Metrics<S>;
+
+    fn layer(&self, inner: S) -> Metrics<S> {
+        Metrics {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Metrics<S> {
+    inner: S,
+    metrics: RouteMetrics,
+}
+
+impl<S, B> Service<Request<B>> for Metrics<S>
+where
+    S: Service<Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let in_flight = InFlight {
+            label: self.metrics.start(request.uri().path()),
+            metrics: self.metrics.clone(),
+            started: Instant::now(),
+            // the request span is entered by the trace layer around this one
+            trace_id: telemetry::trace_id(),
+        };
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await;
+            drop(in_flight);
+            response
+        })
+    }
+}
+
+/// OpenMetrics names counter families without their `_total` suffix.
+fn header_lines(out: &mut String, format: Format, name: &str, kind: &str, help: &str) {
+    let family = match format {
+        Format::OpenMetrics if kind == "counter" => name.trim_end_matches("_total"),
+        _ => name,
+    };
+    writeln!(out, "# HELP {} {}", family, help).unwrap();
+    writeln!(out, "# TYPE {} {}", family, kind).unwrap();
+}
+
+fn metric(out: &mut String, format: Format, name: &str, kind: &str, help: &str, value: u64) {
+    header_lines(out, format, name, kind, help);
+    writeln!(out, "{} {}", name, value).unwrap();
+}
+
+pub async fn metrics_handler(
+    State(cache): State<ParseCache>,
+    State(routes): State<RouteMetrics>,
+    request_headers: HeaderMap,
+) -> (HeaderMap, String) {
+    let format = negotiate(&request_headers);
+    let mut out = String::new();
+    metric(
+        &mut out,
+        format,
+        "timestamp_parse_cache_hits_total",
+        "counter",
+        "Parses answered from the cache.",
+        cache.hits(),
+    );
+    metric(
+        &mut out,
+        format,
+        "timestamp_parse_cache_misses_total",
+        "counter",
+        "Parses not found in the cache.",
+        cache.misses(),
+    );
+    metric(
+        &mut out,
+        format,
+        "timestamp_parse_cache_entries",
+        "gauge",
+        "Inputs currently cached.",
+        cache.len() as u64,
+    );
+
+    let name = "timestamp_parse_failures_total";
+    header_lines(
+        &mut out,
+        format,
+        name,
+        "counter",
+        "Inputs the parser rejected, by the format they looked like.",
+    );
+    for (kind, count) in PARSE_FAILURES.lock().unwrap().iter() {
+        writeln!(out, "{}{{kind=\"{}\"}} {}", name, kind, count).unwrap();
+    }
+
+    routes.write(&mut out, format);
+
+    let content_type = match format {
+        Format::Prometheus => "text/plain; version=0.0.4",
+        Format::OpenMetrics => {
+            out.push_str("# EOF\n");
+            "application/openmetrics-text; version=1.0.0; charset=utf-8"
+        }
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    (headers, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_labels() {
+        assert_eq!(route_label("/api/2016-12-25"), "/api/:param");
+        assert_eq!(
+            route_label("/api/tz/Europe%2FRome/offset"),
+            "/api/tz/:param/offset"
+        );
+        assert_eq!(
+            route_label("/api/business-days/add"),
+            "/api/business-days/add"
+        );
+        assert_eq!(route_label("/"), "/");
+    }
+
+    #[test]
+    fn histograms() {
+        let metrics = RouteMetrics::default();
+        let label = metrics.start("/api/1482624000");
+        let mut out = String::new();
+        metrics.write(&mut out, Format::Prometheus);
+        assert!(out.contains("timestamp_http_requests_in_flight{route=\"/api/:param\"} 1\n"));
+
+        metrics.finish(&label, 0.003, None);
+        let mut out = String::new();
+        metrics.write(&mut out, Format::Prometheus);
+        assert!(out.contains("{route=\"/api/:param\",le=\"0.0025\"} 0\n"));
+        assert!(out.contains("{route=\"/api/:param\",le=\"0.005\"} 1\n"));
+        assert!(out.contains("{route=\"/api/:param\",le=\"+Inf\"} 1\n"));
+        assert!(out.contains("timestamp_http_requests_in_flight{route=\"/api/:param\"} 0\n"));
+    }
+
+    #[test]
+    fn exemplars() {
+        let metrics = RouteMetrics::default();
+        let label = metrics.start("/api/1482624000");
+        metrics.finish(
+            &label,
+            0.003,
+            Some("0af7651916cd43dd8448eb211c80319c".to_string()),
+        );
+        let label = metrics.start("/api/1482624000");
+        metrics.finish(
+            &label,
+            7.0,
+            Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+        );
+
+        let mut out = String::new();
+        metrics.write(&mut out, Format::OpenMetrics);
+        assert!(out.contains(
+            "{route=\"/api/:param\",le=\"0.005\"} 1 # {trace_id=\"0af7651916cd43dd8448eb211c80319c\"} 0.003\n"
+        ));
+        assert!(out.contains("{route=\"/api/:param\",le=\"0.01\"} 1\n"));
+        assert!(out.contains(
+            "{route=\"/api/:param\",le=\"+Inf\"} 2 # {trace_id=\"4bf92f3577b34da6a3ce929d0e0e4736\"} 7\n"
+        ));
+
+        let mut out = String::new();
+        metrics.write(&mut out, Format::Prometheus);
+        assert!(!out.contains("trace_id"));
+    }
+
+    #[test]
+    fn formats() {
+        let accept = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+            negotiate(&headers)
+        };
+        assert_eq!(
+            accept("application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5"),
+            Format::OpenMetrics
+        );
+        assert_eq!(accept("text/plain"), Format::Prometheus);
+        assert_eq!(negotiate(&HeaderMap::new()), Format::Prometheus);
+
+        let mut out = String::new();
+        header_lines(
+            &mut out,
+            Format::OpenMetrics,
+            "timestamp_parse_failures_total",
+            "counter",
+            "Failures.",
+        );
+        assert_eq!(
+            out,
+            "# HELP timestamp_parse_failures Failures.\n# TYPE timestamp_parse_failures counter\n"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! A monotonic nanosecond counter for latency measurements, unaffected by NTP steps.
+//!
+//! The counter starts with the process, so it goes back to zero on restarts: clients
+//! comparing readings should check that `started_at_ns` didn't change in between.
+
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+use crate::uptime::Started;
+
+/// A reading of the monotonic counter, with the wall clock read within `window`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reading {
+    /// Time since the process started.
+    pub monotonic: Duration,
+    pub wall: DateTime<Utc>,
+    /// Time it took to read both clocks, bounding how far apart the two readings are.
+    pub window: Duration,
+}
+
+/// Reads the wall clock between two monotonic readings, reporting their midpoint.
+pub fn read(started: Instant) -> Reading {
+    let before = Instant::now();
+    let wall = Utc::now();
+    let after = Instant::now();
+    let window = after - before;
+    Reading {
+        monotonic: before - started + window / 2,
+        wall,
+        window,
+    }
+}
+
+pub async fn handler(State(started): State<Started>) -> Json<Value> {
+    let reading = read(started.instant);
+
+    Json(json!({
+        "monotonic_ns": reading.monotonic.as_nanos() as u64,
+        "unix_ns": reading.wall.timestamp_nanos_opt().unwrap(),
+        "utc": reading.wall.to_rfc3339_opts(SecondsFormat::Nanos, true),
+        "capture_window_ns": reading.window.as_nanos() as u64,
+        "started_at_ns": started.at.timestamp_nanos_opt().unwrap(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readings_increase() {
+        let started = Instant::now();
+        let first = read(started);
+        let second = read(started);
+        assert!(second.monotonic >= first.monotonic);
+        assert!(first.window < Duration::from_secs(1));
+    }
+}
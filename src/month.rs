@@ -0,0 +1,196 @@
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::Json;
+use chrono::{Datelike, Duration, NaiveDate, Utc, Weekday};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::calendar::{days_in_month, nth_weekday};
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::locale;
+use crate::query::Query;
+use crate::timezone::{parse_tz, start_of_day};
+use crate::week::WeekSettings;
+
+#[derive(Debug, Deserialize)]
+pub struct MonthParams {
+    tz: Option<String>,
+}
+
+/// Unix timestamp of the local midnight of every day in the given month.
+pub async fn epochs_handler(
+    Path((year, month)): Path<(i32, u32)>,
+    Query(params): Query<MonthParams>,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(params.tz.as_deref())?;
+    if NaiveDate::from_ymd_opt(year, month, 1).is_none() {
+        return Err(AppError::InvalidDate);
+    }
+
+    let days: Vec<Value> = (1..=days_in_month(year, month))
+        .map(|day| {
+            let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+            let start = start_of_day(tz, date);
+            json!({
+                "date": date.to_string(),
+                "unix": start.timestamp(),
+                "utc": start.to_rfc2822(),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "year": year,
+        "month": month,
+        "tz": tz.name(),
+        "days": days,
+    })))
+}
+
+/// Days shown in a month grid: whole weeks starting on `week_start`, covering the month.
+pub fn grid_days(year: i32, month: u32, week_start: Weekday) -> Option<Vec<NaiveDate>> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let last = NaiveDate::from_ymd_opt(year, month, days_in_month(year, month)).unwrap();
+    let lead = (7 + first.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+    let start = first - Duration::days(lead as i64);
+    let days = (last - start).num_days() + 1;
+    let weeks = (days + 6) / 7;
+    Some(
+        (0..weeks * 7)
+            .map(|day| start + Duration::days(day))
+            .collect(),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GridParams {
+    tz: Option<String>,
+    /// First day of the week, `week_start` of the configuration by default.
+    week_start: Option<String>,
+    /// Language of the weekday names, overriding `Accept-Language`.
+    locale: Option<String>,
+}
+
+/// Lays out a month as weeks of days, as needed to render a calendar. Days carry their ISO
+/// week number, since weeks not starting on Monday can span two ISO weeks.
+pub async fn grid_handler(
+    Path((year, month)): Path<(i32, u32)>,
+    Query(params): Query<GridParams>,
+    State(settings): State<WeekSettings>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(params.tz.as_deref())?;
+    let week_start = settings.week_start(params.week_start.as_deref())?;
+    let locale = locale::negotiate(&headers, params.locale.as_deref())?.unwrap_or(&locale::EN);
+    let days = grid_days(year, month, week_start).ok_or(AppError::InvalidDate)?;
+    let today = Utc::now().with_timezone(&tz).date_naive();
+    let weekday_name =
+        |date: &NaiveDate| locale.weekdays[date.weekday().num_days_from_monday() as usize];
+    let weekdays: Vec<&str> = days[..7].iter().map(weekday_name).collect();
+
+    let weeks: Vec<Value> = days
+        .chunks(7)
+        .map(|week| {
+            let days: Vec<Value> = week
+                .iter()
+                .map(|date| {
+                    json!({
+                        "date": date.to_string(),
+                        "unix": start_of_day(tz, *date).timestamp(),
+                        "weekday": weekday_name(date),
+                        "iso_week": date.iso_week().week(),
+                        "in_month": date.month() == month,
+                        "is_today": *date == today,
+                    })
+                })
+                .collect();
+            Value::from(days)
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "year": year,
+        "month": month,
+        "tz": tz.name(),
+        "week_start": week_start.to_string(),
+        "locale": locale.code,
+        "weekdays": weekdays,
+        "weeks": weeks,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NthWeekdayParams {
+    year: i32,
+    month: u32,
+    /// A weekday name such as `mon` or `monday`.
+    weekday: String,
+    /// Which of them, counting from the end of the month when negative.
+    n: i32,
+    tz: Option<String>,
+}
+
+/// The `n`-th given weekday of a month, such as the third Monday of November or, with
+/// `n=-1`, the last Friday of May.
+pub async fn nth_weekday_handler(
+    Query(params): Query<NthWeekdayParams>,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(params.tz.as_deref())?;
+    let weekday = params
+        .weekday
+        .parse::<Weekday>()
+        .map_err(|_| AppError::BadRequest(format!("Invalid weekday {}", params.weekday)))?;
+    if params.n == 0 {
+        return Err(AppError::BadRequest("n must not be 0".to_string()));
+    }
+    if NaiveDate::from_ymd_opt(params.year, params.month, 1).is_none() {
+        return Err(AppError::InvalidDate);
+    }
+    let date = nth_weekday(params.year, params.month, weekday, params.n).ok_or_else(|| {
+        AppError::NotFound(format!(
+            "{}-{:02} has fewer than {} {}",
+            params.year,
+            params.month,
+            params.n.abs(),
+            weekday
+        ))
+    })?;
+    let start = start_of_day(tz, date);
+
+    Ok(Json(json!({
+        "date": date.to_string(),
+        "weekday": weekday.to_string(),
+        "n": params.n,
+        "tz": tz.name(),
+        "unix": start.timestamp(),
+        "utc": start.to_rfc2822(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Tz;
+
+    #[test]
+    fn midnight_across_dst() {
+        let tz: Tz = "Europe/Rome".parse().unwrap();
+        let before = start_of_day(tz, NaiveDate::from_ymd_opt(2021, 3, 28).unwrap());
+        let after = start_of_day(tz, NaiveDate::from_ymd_opt(2021, 3, 29).unwrap());
+        assert_eq!(after.timestamp() - before.timestamp(), 23 * 60 * 60);
+    }
+
+    #[test]
+    fn grid_covers_whole_weeks() {
+        // August 2021 starts on a Sunday and ends on a Tuesday
+        let days = grid_days(2021, 8, Weekday::Mon).unwrap();
+        assert_eq!(days.len(), 6 * 7);
+        assert_eq!(days[0], NaiveDate::from_ymd_opt(2021, 7, 26).unwrap());
+        assert_eq!(days[41], NaiveDate::from_ymd_opt(2021, 9, 5).unwrap());
+
+        let days = grid_days(2021, 8, Weekday::Sun).unwrap();
+        assert_eq!(days.len(), 5 * 7);
+        assert_eq!(days[0], NaiveDate::from_ymd_opt(2021, 8, 1).unwrap());
+    }
+}
@@ -0,0 +1,81 @@
+//! Moon phases from the mean synodic month. The actual lunation varies by several hours
+//! around its mean, so phases are accurate to about half a day.
+
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::f64::consts::PI;
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+
+/// Mean length of a lunation, in days.
+const SYNODIC_MONTH: f64 = 29.530_588_853;
+/// A new moon, 2000-01-06T18:14:00 UTC, as a unix timestamp.
+const REFERENCE_NEW_MOON: i64 = 947_182_440;
+
+const PHASES: [&str; 8] = [
+    "new moon",
+    "waxing crescent",
+    "first quarter",
+    "waxing gibbous",
+    "full moon",
+    "waning gibbous",
+    "last quarter",
+    "waning crescent",
+];
+
+/// Days elapsed since the last new moon.
+pub fn age(instant: DateTime<Utc>) -> f64 {
+    let days = (instant.timestamp() - REFERENCE_NEW_MOON) as f64 / 86400.0;
+    days.rem_euclid(SYNODIC_MONTH)
+}
+
+/// Fraction of the visible disc that is lit, from 0 at new moon to 1 at full moon.
+pub fn illumination(age: f64) -> f64 {
+    (1.0 - (2.0 * PI * age / SYNODIC_MONTH).cos()) / 2.0
+}
+
+/// Name of the phase, principal phases covering the eighth of the lunation around them.
+pub fn phase(age: f64) -> &'static str {
+    let index = (age / SYNODIC_MONTH * 8.0 + 0.5).floor() as usize % 8;
+    PHASES[index]
+}
+
+pub async fn moon_handler(Path(date): Path<String>) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?;
+    let age = age(date);
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "phase": phase(age),
+        "illumination": illumination(age),
+        "days_since_new_moon": age,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn phases() {
+        // full moon of 2024-06-22T01:08Z
+        let full = age(Utc.with_ymd_and_hms(2024, 6, 22, 1, 8, 0).unwrap());
+        assert_eq!(phase(full), "full moon");
+        assert!(illumination(full) > 0.99);
+
+        // new moon of 2024-07-05T22:57Z
+        let new = age(Utc.with_ymd_and_hms(2024, 7, 5, 22, 57, 0).unwrap());
+        assert_eq!(phase(new), "new moon");
+        assert!(illumination(new) < 0.01);
+
+        assert_eq!(
+            phase(age(Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap())),
+            "waning crescent"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Parser for natural-language dates such as `tomorrow`, `next friday` or `in 3 weeks`.
+//!
+//! Inputs are case-insensitive and whitespace, `+` or `_` separated, following this grammar:
+//!
+//! ```text
+//! input    := day [time] | "now" ("+" | "-") duration
+//! day      := "now" | "today" | "tomorrow" | "yesterday"
+//!           | ["next" | "last"] weekday
+//!           | "in" amount | amount "ago"
+//! amount   := integer unit
+//! unit     := "second" | "minute" | "hour" | "day" | "week" | "month" | "year"  (optionally plural)
+//! weekday  := "monday" | "mon" | ... | "sunday" | "sun"
+//! time     := HH:MM[:SS]
+//! duration := an ISO 8601 duration or a shorthand such as "90m" or "2d", see [`IsoDuration`]
+//! ```
+//!
+//! Everything is resolved against a base instant: `now` and offsets keep its time of day,
+//! while named days start at midnight. A bare weekday is the next one on or after the base
+//! day, `next`/`last` are strictly after/before it. A trailing time replaces the time of day.
+//! Durations added to `now` are case-sensitive, and their calendar parts follow UTC dates.
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use std::convert::TryFrom;
+
+use crate::calendar::{add_months, checked_seconds};
+use crate::duration::IsoDuration;
+use crate::relative::Unit;
+
+/// Resolves `input` against `base`, returning `None` if it doesn't match the grammar.
+pub fn parse(input: &str, base: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if let Some(date) = from_now(input, base) {
+        return Some(date);
+    }
+    let input = input.to_lowercase();
+    let mut tokens: Vec<&str> = input
+        .split(|c: char| c.is_whitespace() || c == '+' || c == '_')
+        .filter(|token| !token.is_empty())
+        .collect();
+
+    let time = match tokens.last() {
+        Some(token) if token.contains(':') => {
+            let time = parse_time(token)?;
+            tokens.pop();
+            Some(time)
+        }
+        _ => None,
+    };
+
+    let date = parse_day(&tokens, base)?;
+    match time {
+        Some(time) => Some(DateTime::from_naive_utc_and_offset(
+            date.date_naive().and_time(time),
+            Utc,
+        )),
+        None => Some(date),
+    }
+}
+
+/// Resolves `now+2d` or `now-PT90M`.
+fn from_now(input: &str, base: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let rest = input
+        .get(..3)
+        .filter(|now| now.eq_ignore_ascii_case("now"))
+        .map(|_| &input[3..])?;
+    let (sign, duration) = match rest.as_bytes().first()? {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    IsoDuration::parse_shorthand(duration)?
+        .times(sign)?
+        .add_to(base, Tz::UTC)
+}
+
+fn parse_day(tokens: &[&str], base: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let midnight = DateTime::<Utc>::from_naive_utc_and_offset(
+        base.date_naive().and_hms_opt(0, 0, 0).unwrap(),
+        Utc,
+    );
+    // near the ends of the supported range, neighbouring days may not exist
+    let days = |days: i64| midnight.checked_add_signed(Duration::days(days));
+
+    match tokens {
+        ["now"] => Some(base),
+        ["today"] => Some(midnight),
+        ["tomorrow"] => days(1),
+        ["yesterday"] => days(-1),
+        ["in", count, unit] => offset(base, count.parse().ok()?, parse_unit(unit)?),
+        [count, unit, "ago"] => offset(
+            base,
+            count.parse::<i64>().ok()?.checked_neg()?,
+            parse_unit(unit)?,
+        ),
+        [weekday] => days(days_until(base.weekday(), parse_weekday(weekday)?)),
+        ["next", weekday] => {
+            let ahead = days_until(base.weekday(), parse_weekday(weekday)?);
+            days(if ahead == 0 { 7 } else { ahead })
+        }
+        ["last", weekday] => {
+            let behind = days_until(parse_weekday(weekday)?, base.weekday());
+            days(if behind == 0 { -7 } else { -behind })
+        }
+        _ => None,
+    }
+}
+
+/// Days to walk forward from `from` to reach `to`, between 0 and 6.
+fn days_until(from: Weekday, to: Weekday) -> i64 {
+    (7 + to.num_days_from_monday() as i64 - from.num_days_from_monday() as i64) % 7
+}
+
+fn offset(base: DateTime<Utc>, count: i64, unit: Unit) -> Option<DateTime<Utc>> {
+    let months = match unit {
+        Unit::Month => count,
+        Unit::Year => count.checked_mul(12)?,
+        _ => return base.checked_add_signed(checked_seconds(count.checked_mul(unit.seconds())?)?),
+    };
+    let date = add_months(base.date_naive(), i32::try_from(months).ok()?)?;
+    Some(DateTime::from_naive_utc_and_offset(
+        date.and_time(base.time()),
+        Utc,
+    ))
+}
+
+fn parse_unit(token: &str) -> Option<Unit> {
+    let token = token.strip_suffix('s').unwrap_or(token);
+    Unit::ALL.iter().find(|unit| unit.name() == token).copied()
+}
+
+/// Accepts full names and three-letter abbreviations.
+fn parse_weekday(token: &str) -> Option<Weekday> {
+    token.parse().ok()
+}
+
+fn parse_time(token: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(token, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(token, "%H:%M"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    // Wednesday
+    fn base() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 8, 18, 10, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn named_days() {
+        assert_eq!(parse("now", base()), Some(base()));
+        assert_eq!(
+            parse("tomorrow", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 19, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            parse("Yesterday 14:00", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 17, 14, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn weekdays() {
+        assert_eq!(
+            parse("wednesday", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 18, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            parse("next wed", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 25, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            parse("next friday", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 20, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            parse("last friday", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 13, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(
+            parse("in 3 weeks", base()),
+            Some(Utc.with_ymd_and_hms(2021, 9, 8, 10, 30, 0).unwrap())
+        );
+        assert_eq!(
+            parse("2+hours+ago", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 18, 8, 30, 0).unwrap())
+        );
+        assert_eq!(
+            parse("in 1 month", base()),
+            Some(Utc.with_ymd_and_hms(2021, 9, 18, 10, 30, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn durations_from_now() {
+        assert_eq!(
+            parse("now+2d", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 20, 10, 30, 0).unwrap())
+        );
+        assert_eq!(
+            parse("now-90m", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 18, 9, 0, 0).unwrap())
+        );
+        assert_eq!(
+            parse("NOW+P1MT1H", base()),
+            Some(Utc.with_ymd_and_hms(2021, 9, 18, 11, 30, 0).unwrap())
+        );
+        // a time of day still replaces the one of now
+        assert_eq!(
+            parse("now+14:00", base()),
+            Some(Utc.with_ymd_and_hms(2021, 8, 18, 14, 0, 0).unwrap())
+        );
+        assert_eq!(parse("now+2fortnights", base()), None);
+    }
+
+    #[test]
+    fn rejects_unknown_input() {
+        assert_eq!(parse("next", base()), None);
+        assert_eq!(parse("in 3 fortnights", base()), None);
+        assert_eq!(parse("tomorrow 25:00", base()), None);
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(parse("in 9999999999999999 seconds", base()), None);
+        assert_eq!(parse("4294967297 months ago", base()), None);
+        assert_eq!(parse("9223372036854775808 days ago", base()), None);
+        let last_day = DateTime::<Utc>::from_naive_utc_and_offset(
+            NaiveDate::MAX.and_hms_opt(12, 0, 0).unwrap(),
+            Utc,
+        );
+        assert_eq!(parse("tomorrow", last_day), None);
+        assert_eq!(parse("next monday", last_day), None);
+    }
+}
@@ -0,0 +1,217 @@
+//! A small in-memory key-value store whose entries expire at a given instant.
+//!
+//! Expired notes are never returned and are periodically removed by a background task.
+//! Re-writing a note before it expires pushes its expiry forward, which makes it usable
+//! as a dead man's switch.
+
+use axum::extract::State;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::error::AppError;
+use crate::extract::{Json, Path};
+use crate::parse_date;
+
+/// How often expired notes are garbage collected.
+const COLLECT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+struct Note {
+    value: Value,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Default)]
+pub struct NoteStore {
+    notes: Arc<Mutex<HashMap<String, Note>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl NoteStore {
+    /// Periodically removes expired notes for as long as the store is alive.
+    pub fn spawn_collector(&self) {
+        let notes = Arc::downgrade(&self.notes);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(COLLECT_INTERVAL);
+            loop {
+                interval.tick().await;
+                let notes = match notes.upgrade() {
+                    Some(notes) => notes,
+                    None => break,
+                };
+                let now = Utc::now();
+                let mut notes = notes.lock().unwrap();
+                let before = notes.len();
+                notes.retain(|_, note| note.expires_at > now);
+                tracing::debug!("Collected {} expired notes", before - notes.len());
+            }
+        });
+    }
+
+    fn insert(&self, id: String, note: Note) {
+        self.notes.lock().unwrap().insert(id, note);
+    }
+
+    fn get(&self, id: &str, now: DateTime<Utc>) -> Option<Note> {
+        let notes = self.notes.lock().unwrap();
+        notes.get(id).filter(|note| note.expires_at > now).cloned()
+    }
+
+    fn remove(&self, id: &str, now: DateTime<Utc>) -> Option<Note> {
+        let mut notes = self.notes.lock().unwrap();
+        notes.remove(id).filter(|note| note.expires_at > now)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NoteRequest {
+    value: Value,
+    /// Either an absolute expiry date or a time to live in seconds.
+    expires_at: Option<String>,
+    ttl: Option<i64>,
+}
+
+impl NoteRequest {
+    fn into_note(self, now: DateTime<Utc>) -> Result<Note, AppError> {
+        let expires_at = match (self.expires_at, self.ttl) {
+            (Some(date), None) => parse_date(&date)?,
+            (None, Some(ttl)) if ttl > 0 => now + Duration::seconds(ttl),
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Either expires_at or a positive ttl is required".to_string(),
+                ))
+            }
+        };
+        if expires_at <= now {
+            return Err(AppError::BadRequest("Expiry is in the past".to_string()));
+        }
+        Ok(Note {
+            value: self.value,
+            expires_at,
+        })
+    }
+}
+
+fn render(id: &str, note: &Note, now: DateTime<Utc>) -> Value {
+    json!({
+        "id": id,
+        "value": note.value,
+        "expires_at": {
+            "unix": note.expires_at.timestamp(),
+            "utc": note.expires_at.to_rfc2822(),
+        },
+        "expires_in": (note.expires_at - now).num_seconds(),
+    })
+}
+
+pub async fn create_handler(
+    State(store): State<NoteStore>,
+    Json(request): Json<NoteRequest>,
+) -> Result<Json<Value>, AppError> {
+    let now = Utc::now();
+    let note = request.into_note(now)?;
+    let id = store.next_id.fetch_add(1, Ordering::Relaxed).to_string();
+    let body = render(&id, &note, now);
+    store.insert(id, note);
+
+    Ok(Json(body))
+}
+
+pub async fn put_handler(
+    Path(id): Path<String>,
+    State(store): State<NoteStore>,
+    Json(request): Json<NoteRequest>,
+) -> Result<Json<Value>, AppError> {
+    let now = Utc::now();
+    let note = request.into_note(now)?;
+    let body = render(&id, &note, now);
+    store.insert(id, note);
+
+    Ok(Json(body))
+}
+
+pub async fn get_handler(
+    Path(id): Path<String>,
+    State(store): State<NoteStore>,
+) -> Result<Json<Value>, AppError> {
+    let now = Utc::now();
+    let note = store
+        .get(&id, now)
+        .ok_or_else(|| AppError::NotFound("Unknown or expired note".to_string()))?;
+
+    Ok(Json(render(&id, &note, now)))
+}
+
+pub async fn delete_handler(
+    Path(id): Path<String>,
+    State(store): State<NoteStore>,
+) -> Result<Json<Value>, AppError> {
+    let now = Utc::now();
+    let note = store
+        .remove(&id, now)
+        .ok_or_else(|| AppError::NotFound("Unknown or expired note".to_string()))?;
+
+    Ok(Json(render(&id, &note, now)))
+}
+
+pub async fn list_handler(State(store): State<NoteStore>) -> Json<Value> {
+    let now = Utc::now();
+    let notes = store.notes.lock().unwrap();
+    let mut notes: Vec<(&String, &Note)> = notes
+        .iter()
+        .filter(|(_, note)| note.expires_at > now)
+        .collect();
+    notes.sort_by_key(|(_, note)| note.expires_at);
+    let notes: Vec<Value> = notes
+        .iter()
+        .map(|(id, note)| render(id, note, now))
+        .collect();
+
+    Json(json!({ "notes": notes }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_notes_are_hidden() {
+        let store = NoteStore::default();
+        let now = Utc::now();
+        let note = Note {
+            value: json!("hello"),
+            expires_at: now + Duration::seconds(10),
+        };
+        store.insert("greeting".to_string(), note);
+
+        assert!(store.get("greeting", now).is_some());
+        assert!(store.get("greeting", now + Duration::seconds(10)).is_none());
+        assert!(store
+            .remove("greeting", now + Duration::seconds(11))
+            .is_none());
+    }
+
+    #[test]
+    fn expiry_must_be_in_the_future() {
+        let now = Utc::now();
+        let request = NoteRequest {
+            value: json!(1),
+            expires_at: Some("2016-12-25".to_string()),
+            ttl: None,
+        };
+        assert!(request.into_note(now).is_err());
+        let request = NoteRequest {
+            value: json!(1),
+            expires_at: None,
+            ttl: Some(60),
+        };
+        assert_eq!(
+            request.into_note(now).unwrap().expires_at,
+            now + Duration::seconds(60)
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Clock offset of this host against NTP servers, measured with SNTP (RFC 4330).
+//!
+//! The servers are configured with `ntp_servers`, as `host[:port]`.
+//!
+//! The service can also act as a time source itself, through a similar exchange over HTTP.
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::UdpSocket;
+
+use crate::error::AppError;
+
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch.
+const NTP_EPOCH_OFFSET: i64 = 2_208_988_800;
+const PACKET_SIZE: usize = 48;
+const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Clone, Debug)]
+pub struct NtpSettings {
+    pub servers: Vec<String>,
+}
+
+impl NtpSettings {
+    pub fn new(servers: &[String]) -> NtpSettings {
+        NtpSettings {
+            servers: servers
+                .iter()
+                .map(|server| {
+                    if server.contains(':') {
+                        server.to_string()
+                    } else {
+                        format!("{}:123", server)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Measurement {
+    offset: Duration,
+    delay: Duration,
+    stratum: u8,
+}
+
+fn to_ntp(date: DateTime<Utc>) -> [u8; 8] {
+    let seconds = (date.timestamp() + NTP_EPOCH_OFFSET) as u32;
+    let fraction = ((date.timestamp_subsec_nanos() as u64) << 32) / 1_000_000_000;
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+    bytes[4..].copy_from_slice(&(fraction as u32).to_be_bytes());
+    bytes
+}
+
+fn from_ntp(bytes: &[u8]) -> DateTime<Utc> {
+    let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64;
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as u64;
+    let nanos = (fraction * 1_000_000_000 + (1 << 31)) >> 32;
+    Utc.timestamp_opt(seconds - NTP_EPOCH_OFFSET, nanos as u32)
+        .unwrap()
+}
+
+fn request(sent: DateTime<Utc>) -> [u8; PACKET_SIZE] {
+    let mut packet = [0; PACKET_SIZE];
+    // No leap indicator, version 4, client mode
+    packet[0] = 0b00_100_011;
+    packet[40..].copy_from_slice(&to_ntp(sent));
+    packet
+}
+
+/// Computes the offset and round-trip delay from a server reply, given the instants the
+/// request was sent and the reply received.
+fn measure(
+    reply: &[u8],
+    sent: DateTime<Utc>,
+    received: DateTime<Utc>,
+) -> Result<Measurement, String> {
+    if reply.len() < PACKET_SIZE {
+        return Err("Truncated reply".to_string());
+    }
+    if reply[0] & 0b111 != 4 {
+        return Err("The reply isn't from a server".to_string());
+    }
+    let stratum = reply[1];
+    if stratum == 0 {
+        return Err("The server refused the request".to_string());
+    }
+    // Our transmit timestamp is echoed back, which rules out stray packets
+    if reply[24..32] != to_ntp(sent) {
+        return Err("The reply doesn't match the request".to_string());
+    }
+
+    let server_received = from_ntp(&reply[32..40]);
+    let server_sent = from_ntp(&reply[40..48]);
+    Ok(Measurement {
+        offset: ((server_received - sent) + (server_sent - received)) / 2,
+        delay: (received - sent) - (server_sent - server_received),
+        stratum,
+    })
+}
+
+async fn query(server: &str) -> Result<Measurement, String> {
+    let exchange = async {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket.connect(server).await?;
+        let sent = Utc::now();
+        socket.send(&request(sent)).await?;
+        let mut reply = [0; PACKET_SIZE];
+        let size = socket.recv(&mut reply).await?;
+        Ok::<_, std::io::Error>((reply, size, sent, Utc::now()))
+    };
+    let (reply, size, sent, received) = tokio::time::timeout(QUERY_TIMEOUT, exchange)
+        .await
+        .map_err(|_| "Timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+
+    measure(&reply[..size], sent, received)
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.num_microseconds().unwrap_or(i64::MAX) as f64 / 1000.0
+}
+
+/// Queries every configured server concurrently. The offset is positive when this host's
+/// clock is behind the server's.
+pub async fn handler(State(settings): State<NtpSettings>) -> Json<Value> {
+    let queries: Vec<_> = settings
+        .servers
+        .iter()
+        .cloned()
+        .map(|server| tokio::spawn(async move { (query(&server).await, server) }))
+        .collect();
+
+    let mut servers = Vec::new();
+    for query in queries {
+        let (outcome, server) = query.await.expect("The NTP query panicked");
+        servers.push(match outcome {
+            Ok(measurement) => json!({
+                "server": server,
+                "offset_ms": milliseconds(measurement.offset),
+                "delay_ms": milliseconds(measurement.delay),
+                "stratum": measurement.stratum,
+            }),
+            Err(error) => {
+                tracing::warn!("NTP query to {} failed: {}", server, error);
+                json!({ "server": server, "error": error })
+            }
+        });
+    }
+
+    Json(json!({ "servers": servers }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExchangeRequest {
+    /// When the client sent the request, in Unix nanoseconds by its own clock.
+    originate_ns: i64,
+}
+
+/// The server side of a Cristian/NTP exchange. With the originate time echoed back, and
+/// the destination time the client reads when the reply arrives, the client has the four
+/// timestamps of an NTP exchange: its offset from this clock is
+/// `((receive - originate) + (transmit - destination)) / 2` and the round-trip delay
+/// `(destination - originate) - (transmit - receive)`.
+pub async fn exchange_handler(body: Bytes) -> Result<Json<Value>, AppError> {
+    // read before the body is parsed, to leave the parsing out of the delay
+    let received = Utc::now();
+    let request: ExchangeRequest = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid exchange request: {}", e)))?;
+
+    Ok(Json(json!({
+        "originate_ns": request.originate_ns,
+        "receive_ns": received.timestamp_nanos_opt().unwrap(),
+        "transmit_ns": Utc::now().timestamp_nanos_opt().unwrap(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ntp_timestamps() {
+        let date = Utc.timestamp_opt(1482624000, 500_000_000).unwrap();
+        let bytes = to_ntp(date);
+        assert_eq!(bytes[4], 0x80);
+        assert_eq!(from_ntp(&bytes), date);
+    }
+
+    #[test]
+    fn offset_and_delay() {
+        let sent = Utc.timestamp_opt(1482624000, 0).unwrap();
+        let received = sent + Duration::milliseconds(100);
+
+        // The server clock is 1s ahead and takes 20ms to answer
+        let mut reply = [0; PACKET_SIZE];
+        reply[0] = 0b00_100_100;
+        reply[1] = 2;
+        reply[24..32].copy_from_slice(&to_ntp(sent));
+        reply[32..40].copy_from_slice(&to_ntp(sent + Duration::milliseconds(1040)));
+        reply[40..48].copy_from_slice(&to_ntp(sent + Duration::milliseconds(1060)));
+
+        let measurement = measure(&reply, sent, received).unwrap();
+        assert_eq!(measurement.offset, Duration::seconds(1));
+        assert_eq!(measurement.delay, Duration::milliseconds(80));
+
+        reply[1] = 0;
+        assert!(measure(&reply, sent, received).is_err());
+    }
+}
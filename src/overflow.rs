@@ -0,0 +1,213 @@
+//! Whether an instant fits in the timestamp representations of common systems, for audits
+//! of legacy code ahead of the year 2038 and similar rollovers.
+//!
+//! Limits are given with the last instant each representation can hold before it
+//! overflows, and the first one before it underflows. Some of them lie outside the years
+//! chrono can represent, in which case their date is `null` and only the unix timestamp
+//! is given.
+
+use axum::Json;
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+
+const NANOS: i128 = 1_000_000_000;
+
+/// Seconds from 1900-01-01, the NTP epoch, to the unix epoch.
+const NTP_OFFSET: i128 = 2_208_988_800;
+/// Seconds from 1601-01-01, the Windows epoch, to the unix epoch.
+const FILETIME_OFFSET: i128 = 11_644_473_600;
+
+/// A timestamp representation: a count of `unit` nanoseconds from `epoch` seconds before
+/// the unix epoch, in an integer type ranging from `min` to `max`.
+struct Representation {
+    name: &'static str,
+    description: &'static str,
+    epoch: i128,
+    unit: i128,
+    min: i128,
+    max: i128,
+}
+
+impl Representation {
+    /// Nanoseconds since the unix epoch of a count.
+    fn instant(&self, count: i128) -> i128 {
+        count * self.unit - self.epoch * NANOS
+    }
+
+    /// Whether the instant, truncated to the unit, can be stored.
+    fn holds(&self, nanos: i128) -> bool {
+        let count = (nanos + self.epoch * NANOS).div_euclid(self.unit);
+        (self.min..=self.max).contains(&count)
+    }
+}
+
+const REPRESENTATIONS: [Representation; 8] = [
+    Representation {
+        name: "i32_seconds",
+        description: "signed 32-bit unix seconds, time_t on 32-bit systems",
+        epoch: 0,
+        unit: NANOS,
+        min: i32::MIN as i128,
+        max: i32::MAX as i128,
+    },
+    Representation {
+        name: "u32_seconds",
+        description: "unsigned 32-bit unix seconds",
+        epoch: 0,
+        unit: NANOS,
+        min: 0,
+        max: u32::MAX as i128,
+    },
+    Representation {
+        name: "ntp_era_0",
+        description: "unsigned 32-bit seconds since 1900, as in NTP timestamps",
+        epoch: NTP_OFFSET,
+        unit: NANOS,
+        min: 0,
+        max: u32::MAX as i128,
+    },
+    Representation {
+        name: "i64_seconds",
+        description: "signed 64-bit unix seconds",
+        epoch: 0,
+        unit: NANOS,
+        min: i64::MIN as i128,
+        max: i64::MAX as i128,
+    },
+    Representation {
+        name: "i64_milliseconds",
+        description: "signed 64-bit unix milliseconds, as in Java",
+        epoch: 0,
+        unit: 1_000_000,
+        min: i64::MIN as i128,
+        max: i64::MAX as i128,
+    },
+    Representation {
+        name: "i64_microseconds",
+        description: "signed 64-bit unix microseconds, as in PostgreSQL",
+        epoch: 0,
+        unit: 1_000,
+        min: i64::MIN as i128,
+        max: i64::MAX as i128,
+    },
+    Representation {
+        name: "i64_nanoseconds",
+        description: "signed 64-bit unix nanoseconds, as in Go and Linux ktime",
+        epoch: 0,
+        unit: 1,
+        min: i64::MIN as i128,
+        max: i64::MAX as i128,
+    },
+    Representation {
+        name: "filetime",
+        description: "unsigned 64-bit 100-nanosecond intervals since 1601, as in Windows FILETIME",
+        epoch: FILETIME_OFFSET,
+        unit: 100,
+        min: 0,
+        max: u64::MAX as i128,
+    },
+];
+
+fn nanos(instant: DateTime<Utc>) -> i128 {
+    i128::from(instant.timestamp()) * NANOS + i128::from(instant.timestamp_subsec_nanos())
+}
+
+/// A limit, as a unix timestamp and an RFC 3339 date when chrono can represent it.
+fn describe_limit(nanos: i128) -> Value {
+    let seconds = i64::try_from(nanos.div_euclid(NANOS)).ok();
+    let date = seconds.and_then(|seconds| {
+        Utc.timestamp_opt(seconds, nanos.rem_euclid(NANOS) as u32)
+            .single()
+    });
+    json!({
+        "unix": seconds,
+        "utc": date.map(|date| date.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+    })
+}
+
+/// Checks `instant` against every representation.
+fn check(instant: DateTime<Utc>) -> Vec<Value> {
+    let nanos = nanos(instant);
+    REPRESENTATIONS
+        .iter()
+        .map(|representation| {
+            json!({
+                "name": representation.name,
+                "description": representation.description,
+                "fits": representation.holds(nanos),
+                "min": describe_limit(representation.instant(representation.min)),
+                "max": describe_limit(representation.instant(representation.max)),
+            })
+        })
+        .collect()
+}
+
+pub async fn check_handler(Path(date): Path<String>) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?;
+    let checks = check(date);
+    let fits_all = checks.iter().all(|check| check["fits"] == true);
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "fits_all": fits_all,
+        "representations": checks,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn fits(instant: DateTime<Utc>, name: &str) -> bool {
+        check(instant)
+            .into_iter()
+            .find(|check| check["name"] == name)
+            .unwrap()["fits"]
+            == true
+    }
+
+    #[test]
+    fn rollovers() {
+        let y2038 = Utc.with_ymd_and_hms(2038, 1, 19, 3, 14, 7).unwrap();
+        assert!(fits(y2038, "i32_seconds"));
+        assert!(!fits(y2038 + Duration::seconds(1), "i32_seconds"));
+        assert!(fits(y2038 + Duration::seconds(1), "u32_seconds"));
+        assert!(!fits(
+            Utc.with_ymd_and_hms(2036, 2, 8, 0, 0, 0).unwrap(),
+            "ntp_era_0"
+        ));
+        assert!(!fits(
+            Utc.with_ymd_and_hms(1969, 12, 31, 0, 0, 0).unwrap(),
+            "u32_seconds"
+        ));
+        assert!(!fits(
+            Utc.with_ymd_and_hms(2263, 1, 1, 0, 0, 0).unwrap(),
+            "i64_nanoseconds"
+        ));
+    }
+
+    #[test]
+    fn limits() {
+        let limit = |index: usize, max: bool| {
+            let representation = &REPRESENTATIONS[index];
+            let count = if max {
+                representation.max
+            } else {
+                representation.min
+            };
+            describe_limit(representation.instant(count))
+        };
+        assert_eq!(limit(0, true)["utc"], "2038-01-19T03:14:07Z");
+        assert_eq!(limit(2, true)["utc"], "2036-02-07T06:28:15Z");
+        assert_eq!(limit(6, false)["utc"], "1677-09-21T00:12:43.145224192Z");
+        assert_eq!(limit(7, false)["utc"], "1601-01-01T00:00:00Z");
+        assert_eq!(limit(3, true)["utc"], Value::Null);
+    }
+}
@@ -0,0 +1,227 @@
+//! Meeting planning: the times of a day when the working hours of people in different
+//! timezones overlap.
+//!
+//! Working hours are wall-clock times repeating every local day, so on a given UTC day a
+//! participant can be at work during the end of one local day and the start of the next.
+//! Windows ending before they start, such as `22:00` to `06:00`, run past midnight.
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::parse_date;
+use crate::timezone::{offset_at, parse_tz, resolve_local};
+
+type Interval = (DateTime<Utc>, DateTime<Utc>);
+
+/// Working hours of a participant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hours {
+    pub tz: Tz,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl Hours {
+    /// The instant `time` happens on the local `date`, moved past the end of a DST gap
+    /// when it falls in one.
+    fn at(&self, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+        let mut local = date.and_time(time);
+        for _ in 0..8 {
+            if let Some(instant) = resolve_local(local, None, self.tz) {
+                return Some(instant);
+            }
+            local += Duration::minutes(15);
+        }
+        None
+    }
+
+    /// The working intervals overlapping the interval `day`, clipped to it.
+    fn intervals(&self, day: Interval) -> Vec<Interval> {
+        let (from, to) = day;
+        let local = from.with_timezone(&self.tz).date_naive();
+        [local.pred_opt().unwrap(), local, local.succ_opt().unwrap()]
+            .iter()
+            .filter_map(|date| {
+                let start = self.at(*date, self.start)?;
+                let end_date = if self.end <= self.start {
+                    date.succ_opt().unwrap()
+                } else {
+                    *date
+                };
+                let end = self.at(end_date, self.end)?;
+                Some((start.max(from), end.min(to)))
+            })
+            .filter(|(start, end)| start < end)
+            .collect()
+    }
+}
+
+/// Pairwise intersection of two sorted lists of disjoint intervals.
+fn intersect(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    let mut result: Vec<Interval> = a
+        .iter()
+        .flat_map(|(a_start, a_end)| {
+            b.iter()
+                .map(move |(b_start, b_end)| (*a_start.max(b_start), *a_end.min(b_end)))
+        })
+        .filter(|(start, end)| start < end)
+        .collect();
+    result.sort();
+    result
+}
+
+/// The windows of the UTC `date` when everyone is at work.
+pub fn overlap(date: NaiveDate, participants: &[Hours]) -> Vec<Interval> {
+    let from = DateTime::<Utc>::from_naive_utc_and_offset(date.and_hms_opt(0, 0, 0).unwrap(), Utc);
+    let day = (from, from + Duration::days(1));
+    participants.iter().fold(vec![day], |windows, hours| {
+        intersect(&windows, &hours.intervals(day))
+    })
+}
+
+fn local_time(instant: DateTime<Utc>, tz: Tz) -> String {
+    instant
+        .with_timezone(&FixedOffset::east_opt(offset_at(tz, instant)).unwrap())
+        .to_rfc3339()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Participant {
+    tz: String,
+    /// Start of the working hours, as `HH:MM`.
+    work_start: String,
+    work_end: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OverlapRequest {
+    /// The UTC day to plan in, now by default.
+    date: Option<String>,
+    participants: Vec<Participant>,
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, AppError> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| AppError::BadRequest(format!("Invalid time {}", time)))
+}
+
+pub async fn overlap_handler(Json(request): Json<OverlapRequest>) -> Result<Json<Value>, AppError> {
+    if request.participants.is_empty() {
+        return Err(AppError::BadRequest(
+            "At least one participant is required".to_string(),
+        ));
+    }
+    let date = match request.date.as_deref() {
+        Some(date) => parse_date(date)?,
+        None => Utc::now(),
+    }
+    .naive_utc()
+    .date();
+    let participants = request
+        .participants
+        .iter()
+        .map(|participant| {
+            let hours = Hours {
+                tz: parse_tz(Some(participant.tz.as_str()))?,
+                start: parse_time(&participant.work_start)?,
+                end: parse_time(&participant.work_end)?,
+            };
+            if hours.start == hours.end {
+                return Err(AppError::BadRequest(
+                    "Working hours can't start and end at the same time".to_string(),
+                ));
+            }
+            Ok(hours)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let windows: Vec<Value> = overlap(date, &participants)
+        .iter()
+        .map(|(start, end)| {
+            let local: Vec<Value> = participants
+                .iter()
+                .map(|hours| {
+                    json!({
+                        "tz": hours.tz.name(),
+                        "start": local_time(*start, hours.tz),
+                        "end": local_time(*end, hours.tz),
+                    })
+                })
+                .collect();
+            json!({
+                "start": start.to_rfc3339(),
+                "end": end.to_rfc3339(),
+                "minutes": (*end - *start).num_minutes(),
+                "local": local,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "date": date.to_string(),
+        "windows": windows,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn hours(tz: &str, start: &str, end: &str) -> Hours {
+        Hours {
+            tz: tz.parse().unwrap(),
+            start: parse_time(start).unwrap(),
+            end: parse_time(end).unwrap(),
+        }
+    }
+
+    #[test]
+    fn working_hours_overlap() {
+        let date = NaiveDate::from_ymd_opt(2016, 12, 19).unwrap();
+        let rome = hours("Europe/Rome", "09:00", "17:00");
+        let new_york = hours("America/New_York", "09:00", "17:00");
+        assert_eq!(
+            overlap(date, &[rome, new_york]),
+            vec![(
+                Utc.with_ymd_and_hms(2016, 12, 19, 14, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2016, 12, 19, 16, 0, 0).unwrap()
+            )]
+        );
+
+        let tokyo = hours("Asia/Tokyo", "09:00", "17:00");
+        assert!(overlap(date, &[rome, new_york, tokyo]).is_empty());
+    }
+
+    #[test]
+    fn windows_past_midnight() {
+        let date = NaiveDate::from_ymd_opt(2016, 12, 19).unwrap();
+        let night = hours("UTC", "22:00", "06:00");
+        let tokyo = hours("Asia/Tokyo", "09:00", "17:00");
+        // Tokyo works from midnight to 08:00 UTC
+        assert_eq!(
+            overlap(date, &[night, tokyo]),
+            vec![(
+                Utc.with_ymd_and_hms(2016, 12, 19, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2016, 12, 19, 6, 0, 0).unwrap()
+            )]
+        );
+        assert_eq!(
+            overlap(date, &[night]),
+            vec![
+                (
+                    Utc.with_ymd_and_hms(2016, 12, 19, 0, 0, 0).unwrap(),
+                    Utc.with_ymd_and_hms(2016, 12, 19, 6, 0, 0).unwrap()
+                ),
+                (
+                    Utc.with_ymd_and_hms(2016, 12, 19, 22, 0, 0).unwrap(),
+                    Utc.with_ymd_and_hms(2016, 12, 20, 0, 0, 0).unwrap()
+                )
+            ]
+        );
+    }
+}
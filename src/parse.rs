@@ -0,0 +1,594 @@
+//! Parsing of the absolute dates accepted by the `/api/:date` family of routes.
+//!
+//! The input is classified once by its shape, then parsed by the matching parser only:
+//! unix timestamps are converted directly, keeping their time of day and their fraction
+//! of a second, down to nanoseconds.
+//!
+//! Timestamps are read in seconds, milliseconds, microseconds or nanoseconds depending on
+//! their magnitude, the current time having 10, 13, 16 and 19 digits respectively. Callers
+//! can force the unit when that guess is wrong, such as for dates before 1973 in ms, or
+//! when timestamps count minutes or days, which are never guessed.
+//!
+//! Regional dates put the month first when separated by slashes, as in the US, and the day
+//! first when separated by dashes or dots. Either order is used when only it gives a valid
+//! date, and dates valid both ways are flagged as ambiguous unless the caller said which
+//! order it uses.
+//!
+//! The basic format of iCalendar (RFC 5545) is read too, as `20161225T000000Z`, times
+//! without `Z` being taken as UTC like dates are. Bare dates such as `20161225` are read
+//! as timestamps from 1970 to 1973 unless the caller asks for the basic format, as eight
+//! digits always were.
+
+use chrono::format::ParseErrorKind;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::calendar;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputKind {
+    /// Seconds since the epoch, such as `1482624000` or `1482624000.25`.
+    Unix,
+    /// An ISO 8601 week date, such as `2016-W51-7`.
+    WeekDate,
+    /// A calendar date, such as `2016-12-25`.
+    Date,
+    /// A date in a regional format, such as `12/25/2016`, `25-12-2016` or `25.12.2016`.
+    Regional,
+    /// An iCalendar basic-format date or date-time, such as `20161225` or `20161225T000000Z`.
+    Basic,
+}
+
+impl InputKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            InputKind::Unix => "unix",
+            InputKind::WeekDate => "week_date",
+            InputKind::Date => "date",
+            InputKind::Regional => "regional_date",
+            InputKind::Basic => "basic_format",
+        }
+    }
+}
+
+/// Unit of a unix timestamp.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum TimeUnit {
+    #[serde(rename = "s")]
+    Seconds,
+    #[serde(rename = "ms")]
+    Millis,
+    #[serde(rename = "us")]
+    Micros,
+    #[serde(rename = "ns")]
+    Nanos,
+    #[serde(rename = "min")]
+    Minutes,
+    #[serde(rename = "days")]
+    Days,
+}
+
+impl TimeUnit {
+    pub fn name(self) -> &'static str {
+        match self {
+            TimeUnit::Seconds => "s",
+            TimeUnit::Millis => "ms",
+            TimeUnit::Micros => "us",
+            TimeUnit::Nanos => "ns",
+            TimeUnit::Minutes => "min",
+            TimeUnit::Days => "days",
+        }
+    }
+
+    pub fn nanos(self) -> i64 {
+        match self {
+            TimeUnit::Seconds => 1_000_000_000,
+            TimeUnit::Millis => 1_000_000,
+            TimeUnit::Micros => 1_000,
+            TimeUnit::Nanos => 1,
+            TimeUnit::Minutes => 60_000_000_000,
+            TimeUnit::Days => 86_400_000_000_000,
+        }
+    }
+
+    /// Guesses the unit of a timestamp from the number of digits of its integer part.
+    /// Seconds go up to 11 digits, which reaches year 5138.
+    pub fn detect(digits: usize) -> TimeUnit {
+        match digits {
+            0..=11 => TimeUnit::Seconds,
+            12..=14 => TimeUnit::Millis,
+            15..=17 => TimeUnit::Micros,
+            _ => TimeUnit::Nanos,
+        }
+    }
+}
+
+/// Why an input was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    /// The input doesn't have the shape of any accepted format.
+    Malformed,
+    /// The input has the shape of a date that doesn't exist, such as `2023-02-30`.
+    Impossible,
+    /// The input is a timestamp too far from the epoch to be represented.
+    OutOfRange,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputError {
+    pub rejection: Rejection,
+    /// What exactly is wrong with the input, for humans.
+    pub reason: String,
+}
+
+impl InputError {
+    fn new(rejection: Rejection, reason: String) -> InputError {
+        InputError { rejection, reason }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.reason)
+    }
+}
+
+/// Caller conventions resolving inputs that can be read several ways.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Hints {
+    /// Unit of timestamps, guessed from their magnitude when `None`.
+    pub unit: Option<TimeUnit>,
+    /// Whether regional dates put the day before the month, which otherwise depends on
+    /// their separator.
+    pub day_first: Option<bool>,
+    /// Whether eight digits are a basic-format date rather than a timestamp.
+    pub basic: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParsedInstant {
+    pub instant: DateTime<Utc>,
+    pub kind: InputKind,
+    /// The input also reads as another valid date, and no hint said which one was meant.
+    pub ambiguous: bool,
+}
+
+/// Whether dates are written day first in a language tag's region, `en-US` aside.
+pub fn day_first_in(locale: &str) -> bool {
+    !locale.eq_ignore_ascii_case("en") && !locale.replace('_', "-").eq_ignore_ascii_case("en-US")
+}
+
+/// Tells which parser an input is meant for, without validating it.
+pub fn classify(input: &str) -> InputKind {
+    let number = input.strip_prefix('-').unwrap_or(input);
+    let (seconds, fraction) = number.split_once('.').unwrap_or((number, "0"));
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if let Some((_, _, _, Some(_))) = basic_fields(input) {
+        return InputKind::Basic;
+    }
+    if is_digits(seconds) && is_digits(fraction) {
+        InputKind::Unix
+    } else if regional_fields(input).is_some() {
+        InputKind::Regional
+    } else if input.contains("-W") {
+        InputKind::WeekDate
+    } else {
+        InputKind::Date
+    }
+}
+
+/// Parses an absolute date, dates without a time standing for their UTC midnight.
+/// Errors describe why the input was rejected.
+pub fn parse_input(input: &str) -> Result<ParsedInstant, InputError> {
+    parse_input_with(input, Hints::default())
+}
+
+/// Same as [`parse_input`], reading inputs the way `hints` say.
+pub fn parse_input_with(input: &str, hints: Hints) -> Result<ParsedInstant, InputError> {
+    let kind = match classify(input) {
+        InputKind::Unix if hints.basic && basic_fields(input).is_some() => InputKind::Basic,
+        kind => kind,
+    };
+    let mut ambiguous = false;
+    let instant = match kind {
+        InputKind::Unix => parse_unix(input, hints.unit)?,
+        InputKind::Regional => {
+            let (date, other) = parse_regional(input, hints.day_first)?;
+            ambiguous = other;
+            midnight(date)
+        }
+        InputKind::WeekDate => {
+            let date = calendar::parse_week_date(input).ok_or_else(|| {
+                InputError::new(
+                    Rejection::Malformed,
+                    format!("{} is not a YYYY-Www-D week date", input),
+                )
+            })?;
+            midnight(date)
+        }
+        InputKind::Date => {
+            let date = match iso_fields(input) {
+                Some((year, month, day)) => calendar_date(year, month, day)?,
+                None => input.parse::<NaiveDate>().map_err(|e| {
+                    let rejection = match e.kind() {
+                        ParseErrorKind::OutOfRange | ParseErrorKind::Impossible => {
+                            Rejection::Impossible
+                        }
+                        _ => Rejection::Malformed,
+                    };
+                    InputError::new(rejection, format!("{}: {}", input, e))
+                })?,
+            };
+            midnight(date)
+        }
+        InputKind::Basic => {
+            let (year, month, day, time) = basic_fields(input).ok_or_else(|| {
+                InputError::new(
+                    Rejection::Malformed,
+                    format!("{} is not a basic-format date", input),
+                )
+            })?;
+            let date = calendar_date(year, month, day)?;
+            match time {
+                Some((hour, minute, second)) => {
+                    let time = date.and_hms_opt(hour, minute, second).ok_or_else(|| {
+                        InputError::new(
+                            Rejection::Impossible,
+                            format!(
+                                "{:02}:{:02}:{:02} is not a valid time",
+                                hour, minute, second
+                            ),
+                        )
+                    })?;
+                    DateTime::<Utc>::from_naive_utc_and_offset(time, Utc)
+                }
+                None => midnight(date),
+            }
+        }
+    };
+    Ok(ParsedInstant {
+        instant,
+        kind,
+        ambiguous,
+    })
+}
+
+/// Splits a `YYYY-MM-DD` date into its fields, without checking their ranges.
+fn iso_fields(input: &str) -> Option<(i32, u32, u32)> {
+    let fields: Vec<&str> = input.split('-').collect();
+    let is_digits = |s: &str, lengths: &[usize]| {
+        lengths.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_digit())
+    };
+    match fields[..] {
+        [year, month, day]
+            if is_digits(year, &[4]) && is_digits(month, &[1, 2]) && is_digits(day, &[1, 2]) =>
+        {
+            Some((year.parse().ok()?, month.parse().ok()?, day.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// Year, month, day and optional hour, minute and second of a basic-format input.
+type BasicFields = (i32, u32, u32, Option<(u32, u32, u32)>);
+
+/// Splits a `YYYYMMDD` or `YYYYMMDDTHHMMSS[Z]` input into its fields, without checking
+/// their ranges.
+fn basic_fields(input: &str) -> Option<BasicFields> {
+    let is_digits =
+        |s: &str, length: usize| s.len() == length && s.bytes().all(|b| b.is_ascii_digit());
+    let (date, time) = match input.split_once('T') {
+        Some((date, time)) => (date, Some(time.strip_suffix('Z').unwrap_or(time))),
+        None => (input, None),
+    };
+    if !is_digits(date, 8) {
+        return None;
+    }
+    let time = match time {
+        Some(time) if is_digits(time, 6) => Some((
+            time[..2].parse().ok()?,
+            time[2..4].parse().ok()?,
+            time[4..].parse().ok()?,
+        )),
+        Some(_) => return None,
+        None => None,
+    };
+    Some((
+        date[..4].parse().ok()?,
+        date[4..6].parse().ok()?,
+        date[6..].parse().ok()?,
+        time,
+    ))
+}
+
+/// Builds a date, explaining which field is out of range when it isn't valid.
+fn calendar_date(year: i32, month: u32, day: u32) -> Result<NaiveDate, InputError> {
+    if !(1..=12).contains(&month) {
+        return Err(InputError::new(
+            Rejection::Impossible,
+            format!("month {} out of range", month),
+        ));
+    }
+    NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| {
+        InputError::new(
+            Rejection::Impossible,
+            format!("day {} out of range for month {}", day, month),
+        )
+    })
+}
+
+/// Splits a regional date into its two leading fields, its year and its separator.
+fn regional_fields(input: &str) -> Option<(u32, u32, i32, char)> {
+    let is_digits = |s: &str, lengths: &[usize]| {
+        lengths.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_digit())
+    };
+    ['/', '-', '.'].iter().find_map(|&separator| {
+        let fields: Vec<&str> = input.split(separator).collect();
+        match fields[..] {
+            [first, second, year]
+                if is_digits(first, &[1, 2])
+                    && is_digits(second, &[1, 2])
+                    && is_digits(year, &[4]) =>
+            {
+                Some((
+                    first.parse().ok()?,
+                    second.parse().ok()?,
+                    year.parse().ok()?,
+                    separator,
+                ))
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Parses a regional date, also telling whether it was ambiguous.
+fn parse_regional(input: &str, day_first: Option<bool>) -> Result<(NaiveDate, bool), InputError> {
+    let (first, second, year, separator) = regional_fields(input).ok_or_else(|| {
+        InputError::new(
+            Rejection::Malformed,
+            format!("{} is not a regional date", input),
+        )
+    })?;
+    let ((day, month), (other_day, other_month)) = if day_first.unwrap_or(separator != '/') {
+        ((first, second), (second, first))
+    } else {
+        ((second, first), (first, second))
+    };
+    let other = NaiveDate::from_ymd_opt(year, other_month, other_day);
+    match (calendar_date(year, month, day), other) {
+        (Ok(date), other) => {
+            let ambiguous = day_first.is_none() && other.is_some_and(|other| other != date);
+            Ok((date, ambiguous))
+        }
+        (Err(_), Some(date)) => Ok((date, false)),
+        // the reason given is the one of the expected order
+        (Err(reason), None) => Err(reason),
+    }
+}
+
+/// Parses a timestamp with an optional fraction. Digits finer than nanoseconds are dropped.
+fn parse_unix(input: &str, unit: Option<TimeUnit>) -> Result<DateTime<Utc>, InputError> {
+    let (negative, magnitude) = match input.strip_prefix('-') {
+        Some(magnitude) => (true, magnitude),
+        None => (false, input),
+    };
+    let (whole, fraction) = magnitude.split_once('.').unwrap_or((magnitude, ""));
+    let unit = unit.unwrap_or_else(|| TimeUnit::detect(whole.trim_start_matches('0').len()));
+    let out_of_range =
+        || InputError::new(Rejection::OutOfRange, format!("{} is out of range", input));
+
+    let per_unit = unit.nanos() as i128;
+    let digits = &fraction[..fraction.len().min(9)];
+    let fraction = match digits {
+        "" => 0,
+        digits => digits.parse::<i128>().unwrap() * per_unit / 10i128.pow(digits.len() as u32),
+    };
+    let nanos = whole
+        .parse::<i128>()
+        .ok()
+        .and_then(|whole| whole.checked_mul(per_unit))
+        .and_then(|nanos| nanos.checked_add(fraction))
+        .ok_or_else(out_of_range)?;
+    // the fraction extends the timestamp away from the epoch, also before 1970
+    let nanos = if negative { -nanos } else { nanos };
+
+    let seconds = i64::try_from(nanos.div_euclid(1_000_000_000)).map_err(|_| out_of_range())?;
+    DateTime::from_timestamp(seconds, nanos.rem_euclid(1_000_000_000) as u32)
+        .ok_or_else(out_of_range)
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    DateTime::<Utc>::from_naive_utc_and_offset(date.and_hms_opt(0, 0, 0).unwrap(), Utc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn classification() {
+        assert_eq!(classify("1482624000"), InputKind::Unix);
+        assert_eq!(classify("-86400"), InputKind::Unix);
+        assert_eq!(classify("2016-W51-7"), InputKind::WeekDate);
+        assert_eq!(classify("2016-12-25"), InputKind::Date);
+        assert_eq!(classify("1451001600.123"), InputKind::Unix);
+        assert_eq!(classify("-"), InputKind::Date);
+        assert_eq!(classify("1451001600."), InputKind::Date);
+        assert_eq!(classify("20161225"), InputKind::Unix);
+        assert_eq!(classify("20161225T000000Z"), InputKind::Basic);
+    }
+
+    #[test]
+    fn timestamps_keep_their_time() {
+        let parsed = parse_input("1482661845").unwrap();
+        assert_eq!(parsed.kind, InputKind::Unix);
+        assert_eq!(parsed.instant.timestamp(), 1482661845);
+
+        let parsed = parse_input("2016-W51-7").unwrap();
+        assert_eq!(parsed.instant.timestamp(), 1482624000);
+        let seconds = Hints {
+            unit: Some(TimeUnit::Seconds),
+            ..Hints::default()
+        };
+        assert!(parse_input_with("99999999999999999", seconds).is_err());
+        assert!(parse_input("2016-13-01").is_err());
+    }
+
+    #[test]
+    fn fractional_timestamps() {
+        let instant = parse_input("1451001600.123").unwrap().instant;
+        assert_eq!(instant.timestamp(), 1451001600);
+        assert_eq!(instant.timestamp_subsec_nanos(), 123_000_000);
+
+        let instant = parse_input("1451001600.000001").unwrap().instant;
+        assert_eq!(instant.timestamp_subsec_nanos(), 1_000);
+
+        let instant = parse_input("-1.5").unwrap().instant;
+        assert_eq!(instant.timestamp_millis(), -1500);
+        let instant = parse_input("0.1234567891").unwrap().instant;
+        assert_eq!(instant.timestamp_subsec_nanos(), 123_456_789);
+    }
+
+    #[test]
+    fn timestamp_units() {
+        for input in &[
+            "1451001600",
+            "1451001600000",
+            "1451001600000000",
+            "1451001600000000000",
+        ] {
+            let instant = parse_input(input).unwrap().instant;
+            assert_eq!(instant.timestamp(), 1451001600, "{}", input);
+        }
+
+        let instant = parse_input("1451001600123.5").unwrap().instant;
+        assert_eq!(instant.timestamp_nanos_opt().unwrap(), 1451001600123500000);
+
+        // a day after the epoch in ms looks like seconds
+        let hints = Hints {
+            unit: Some(TimeUnit::Millis),
+            ..Hints::default()
+        };
+        let instant = parse_input_with("86400000", hints).unwrap().instant;
+        assert_eq!(instant.timestamp(), 86400);
+
+        let days = |input: &str| {
+            let hints = Hints {
+                unit: Some(TimeUnit::Days),
+                ..Hints::default()
+            };
+            parse_input_with(input, hints).unwrap().instant
+        };
+        assert_eq!(
+            days("19700"),
+            Utc.with_ymd_and_hms(2023, 12, 9, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            days("-1.5"),
+            Utc.with_ymd_and_hms(1969, 12, 30, 12, 0, 0).unwrap()
+        );
+        let hints = Hints {
+            unit: Some(TimeUnit::Minutes),
+            ..Hints::default()
+        };
+        let instant = parse_input_with("20161225", hints).unwrap().instant;
+        assert_eq!(instant.timestamp(), 20161225 * 60);
+    }
+
+    #[test]
+    fn out_of_range_fields() {
+        let error = parse_input("2023-01-32").unwrap_err();
+        assert_eq!(error.reason, "day 32 out of range for month 1");
+        assert_eq!(error.rejection, Rejection::Impossible);
+        assert_eq!(
+            parse_input("2023-13-01").unwrap_err().reason,
+            "month 13 out of range"
+        );
+        assert_eq!(
+            parse_input("2023-02-29").unwrap_err().reason,
+            "day 29 out of range for month 2"
+        );
+        assert_eq!(
+            parse_input("2023-02-2x").unwrap_err().rejection,
+            Rejection::Malformed
+        );
+        assert_eq!(
+            parse_input("99999999999999999999999")
+                .unwrap_err()
+                .rejection,
+            Rejection::OutOfRange
+        );
+    }
+
+    #[test]
+    fn regional_dates() {
+        let christmas = 1482624000;
+        for input in &["12/25/2016", "25-12-2016", "25.12.2016", "25/12/2016"] {
+            let parsed = parse_input(input).unwrap();
+            assert_eq!(parsed.kind, InputKind::Regional, "{}", input);
+            assert_eq!(parsed.instant.timestamp(), christmas, "{}", input);
+            assert!(!parsed.ambiguous, "{}", input);
+        }
+
+        let parsed = parse_input("01/02/2023").unwrap();
+        assert_eq!(
+            parsed.instant.date_naive(),
+            NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()
+        );
+        assert!(parsed.ambiguous);
+
+        let hints = Hints {
+            day_first: Some(true),
+            ..Hints::default()
+        };
+        let parsed = parse_input_with("01/02/2023", hints).unwrap();
+        assert_eq!(
+            parsed.instant.date_naive(),
+            NaiveDate::from_ymd_opt(2023, 2, 1).unwrap()
+        );
+        assert!(!parsed.ambiguous);
+
+        assert!(parse_input("13/13/2023").is_err());
+        assert!(parse_input("29.02.2023").is_err());
+        assert!(day_first_in("fr"));
+        assert!(!day_first_in("en-us"));
+    }
+
+    #[test]
+    fn basic_format() {
+        let christmas = 1482624000;
+        let hints = Hints {
+            basic: true,
+            ..Hints::default()
+        };
+        for input in &["20161225", "20161225T000000Z", "20161225T000000"] {
+            let parsed = parse_input_with(input, hints).unwrap();
+            assert_eq!(parsed.kind, InputKind::Basic, "{}", input);
+            assert_eq!(parsed.instant.timestamp(), christmas, "{}", input);
+            assert!(!parsed.ambiguous, "{}", input);
+        }
+        assert_eq!(
+            parse_input("20161225T183015Z").unwrap().instant.timestamp(),
+            christmas + 18 * 3600 + 30 * 60 + 15
+        );
+        assert_eq!(
+            parse_input_with("20161325", hints).unwrap_err().rejection,
+            Rejection::Impossible
+        );
+
+        // eight digits are seconds unless the basic format is asked for
+        let parsed = parse_input("20161225").unwrap();
+        assert_eq!(parsed.kind, InputKind::Unix);
+        assert_eq!(parsed.instant.timestamp(), 20161225);
+
+        assert_eq!(
+            parse_input("20161232T000000Z").unwrap_err().reason,
+            "day 32 out of range for month 12"
+        );
+        assert!(parse_input("20161225T250000Z").is_err());
+    }
+}
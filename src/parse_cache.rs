@@ -0,0 +1,200 @@
+//! Bounded LRU cache of parsed absolute dates.
+//!
+//! Only parses that don't depend on the current time are cached: natural-language inputs
+//! are always resolved again. Failures are cached too, with the reason they were rejected
+//! for, as clients retrying the same malformed input are as common as those repeating
+//! valid ones.
+//!
+//! Replicas of the service can share a second level, such as Redis, looked up on local
+//! misses before parsing, see [`ParseCache::with_shared`].
+
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::parse::Rejection;
+use crate::profile::Profile;
+
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+type Key = (String, Option<Profile>);
+pub type Parsed = Result<DateTime<Utc>, Rejection>;
+
+/// A cache level shared by the replicas of the service, keyed by [`shared_key`].
+pub trait SharedLevel: Send + Sync {
+    fn get(&self, key: &str) -> Option<Parsed>;
+    fn put(&self, key: &str, parsed: Parsed);
+}
+
+/// The key of an input in the shared level.
+pub fn shared_key(input: &str, profile: Option<Profile>) -> String {
+    format!("{}:{}", profile.map_or("any", Profile::name), input)
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<Key, (Parsed, u64)>,
+    /// Keys by the tick they were last used at, the oldest first.
+    recency: BTreeMap<u64, Key>,
+    tick: u64,
+}
+
+#[derive(Clone)]
+pub struct ParseCache {
+    lru: Arc<Mutex<Lru>>,
+    capacity: usize,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+    shared: Option<Arc<dyn SharedLevel>>,
+}
+
+impl ParseCache {
+    pub fn new(capacity: usize) -> ParseCache {
+        ParseCache {
+            lru: Arc::default(),
+            capacity,
+            hits: Arc::default(),
+            misses: Arc::default(),
+            shared: None,
+        }
+    }
+
+    /// Looks up inputs in `shared` before parsing them, and stores them there after.
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    pub fn with_shared(self, shared: Arc<dyn SharedLevel>) -> ParseCache {
+        ParseCache {
+            shared: Some(shared),
+            ..self
+        }
+    }
+
+    /// Returns the cached result for `input`, or computes and stores it with `parse`.
+    pub fn get_or_parse<F>(&self, input: &str, profile: Option<Profile>, parse: F) -> Parsed
+    where
+        F: FnOnce() -> Parsed,
+    {
+        if self.capacity == 0 {
+            return parse();
+        }
+        let key = (input.to_string(), profile);
+        {
+            let mut guard = self.lru.lock().unwrap();
+            let lru = &mut *guard;
+            lru.tick += 1;
+            let tick = lru.tick;
+            if let Some((date, used)) = lru.entries.get_mut(&key) {
+                let (date, previous) = (*date, *used);
+                *used = tick;
+                lru.recency.remove(&previous);
+                lru.recency.insert(tick, key);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return date;
+            }
+        }
+
+        // look up the shared level and parse without holding the lock
+        let shared = self
+            .shared
+            .as_ref()
+            .map(|shared| (shared, shared_key(input, profile)));
+        let date = match shared.as_ref().and_then(|(shared, key)| shared.get(key)) {
+            Some(date) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                date
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                let date = parse();
+                if let Some((shared, key)) = &shared {
+                    shared.put(key, date);
+                }
+                date
+            }
+        };
+
+        let mut guard = self.lru.lock().unwrap();
+        let lru = &mut *guard;
+        lru.tick += 1;
+        let tick = lru.tick;
+        if let Some((_, previous)) = lru.entries.insert(key.clone(), (date, tick)) {
+            lru.recency.remove(&previous);
+        }
+        lru.recency.insert(tick, key);
+        while lru.entries.len() > self.capacity {
+            let oldest = *lru.recency.keys().next().unwrap();
+            let key = lru.recency.remove(&oldest).unwrap();
+            lru.entries.remove(&key);
+        }
+        date
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lru.lock().unwrap().entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = ParseCache::new(2);
+        let date = Ok(Utc.timestamp_opt(0, 0).unwrap());
+        let failed = Err(Rejection::Malformed);
+        let _ = cache.get_or_parse("a", None, || date);
+        let _ = cache.get_or_parse("b", None, || date);
+        // touching "a" makes "b" the oldest entry
+        let _ = cache.get_or_parse("a", None, || unreachable!());
+        let _ = cache.get_or_parse("c", None, || failed);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get_or_parse("b", None, || failed), failed);
+        assert_eq!(cache.get_or_parse("c", None, || date), failed);
+        assert_eq!((cache.hits(), cache.misses()), (2, 4));
+    }
+
+    #[test]
+    fn keyed_by_profile() {
+        let cache = ParseCache::new(8);
+        let _ = cache.get_or_parse("2016-12-25", None, || Ok(Utc.timestamp_opt(0, 0).unwrap()));
+        let strict = cache.get_or_parse("2016-12-25", Some(Profile::Rfc3339), || {
+            Err(Rejection::Malformed)
+        });
+        assert_eq!(strict, Err(Rejection::Malformed));
+    }
+
+    #[derive(Default)]
+    struct SharedMap(Mutex<HashMap<String, Parsed>>);
+
+    impl SharedLevel for SharedMap {
+        fn get(&self, key: &str) -> Option<Parsed> {
+            self.0.lock().unwrap().get(key).copied()
+        }
+
+        fn put(&self, key: &str, parsed: Parsed) {
+            self.0.lock().unwrap().insert(key.to_string(), parsed);
+        }
+    }
+
+    #[test]
+    fn shared_between_replicas() {
+        let shared: Arc<SharedMap> = Arc::default();
+        let first = ParseCache::new(8).with_shared(shared.clone());
+        let second = ParseCache::new(8).with_shared(shared.clone());
+        let date = Ok(Utc.timestamp_opt(0, 0).unwrap());
+        let _ = first.get_or_parse("0", None, || date);
+        assert_eq!(second.get_or_parse("0", None, || unreachable!()), date);
+        assert_eq!((second.hits(), second.misses()), (1, 0));
+        assert!(shared.0.lock().unwrap().contains_key("any:0"));
+    }
+}
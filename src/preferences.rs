@@ -0,0 +1,287 @@
+//! Default query parameters of API keys, set with `PUT /api/preferences`.
+//!
+//! API keys are enabled by listing them in `api_keys`, and sent in the `X-API-Key` header.
+//! The `tz`, `locale` and `template` preferences of a key are added to the query string of
+//! its requests, unless a request gives them itself, so that integrations don't have to
+//! repeat them on every call. Requests with an unknown key are answered with a 401, those
+//! without one are served as usual. Preferences are kept in memory, and lost on restart.
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, Request, Response, Uri};
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tower::{BoxError, Layer, Service};
+
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::{locale, template, timezone};
+
+pub const API_KEY: &str = "x-api-key";
+
+/// Route of the preferences themselves, which are left out of them: a template would
+/// otherwise reshape their own response.
+const ROUTE: &str = "/api/preferences";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Defaults {
+    /// Timezone of requests that don't name one.
+    tz: Option<String>,
+    /// Language of localized output, overriding `Accept-Language`.
+    locale: Option<String>,
+    /// Response template, either JSON or the name of a configured one.
+    template: Option<String>,
+}
+
+impl Defaults {
+    fn validate(&self) -> Result<(), AppError> {
+        if let Some(tz) = &self.tz {
+            timezone::parse_tz(Some(tz))?;
+        }
+        if let Some(code) = &self.locale {
+            locale::find(code)
+                .ok_or_else(|| AppError::BadRequest(format!("Unknown locale {}", code)))?;
+        }
+        match &self.template {
+            Some(template) if template.starts_with(['{', '[']) => {
+                template::parse(template).map_err(AppError::BadRequest)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn pairs(&self) -> [(&'static str, Option<&String>); 3] {
+        [
+            ("tz", self.tz.as_ref()),
+            ("locale", self.locale.as_ref()),
+            ("template", self.template.as_ref()),
+        ]
+    }
+}
+
+/// The known API keys and the preferences they saved.
+#[derive(Clone, Debug, Default)]
+pub struct PreferenceStore {
+    keys: Arc<[Arc<str>]>,
+    saved: Arc<Mutex<HashMap<Arc<str>, Defaults>>>,
+}
+
+impl PreferenceStore {
+    pub fn new(keys: &[String]) -> PreferenceStore {
+        PreferenceStore {
+            keys: keys
+                .iter()
+                .filter(|key| !key.is_empty())
+                .map(|key| Arc::from(key.as_str()))
+                .collect(),
+            saved: Arc::default(),
+        }
+    }
+
+    /// The key of a request, `None` when it sent none. Keys are compared in constant time.
+    fn authenticate(&self, headers: &HeaderMap) -> Result<Option<Arc<str>>, AppError> {
+        let given = match headers.get(API_KEY) {
+            Some(value) if !self.keys.is_empty() => value.as_bytes(),
+            _ => return Ok(None),
+        };
+        let matches = |key: &str| {
+            key.len() == given.len()
+                && key
+                    .bytes()
+                    .zip(given.iter())
+                    .fold(0, |diff, (a, b)| diff | (a ^ b))
+                    == 0
+        };
+        self.keys
+            .iter()
+            .find(|key| matches(key))
+            .cloned()
+            .map(Some)
+            .ok_or(AppError::Unauthorized)
+    }
+
+    fn get(&self, key: &str) -> Defaults {
+        self.saved
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// The query string of a request, completed with the preferences it doesn't override.
+fn with_defaults(query: Option<&str>, defaults: &Defaults) -> String {
+    let pairs: Vec<(String, String)> =
+        serde_urlencoded::from_str(query.unwrap_or_default()).unwrap_or_default();
+    let missing: Vec<(&str, &String)> = defaults
+        .pairs()
+        .iter()
+        .filter_map(|(name, value)| Some((*name, (*value)?)))
+        .filter(|(name, _)| pairs.iter().all(|(given, _)| given != name))
+        .collect();
+    let added = serde_urlencoded::to_string(missing).unwrap();
+    match query.filter(|query| !query.is_empty()) {
+        Some(query) if !added.is_empty() => format!("{}&{}", query, added),
+        Some(query) => query.to_string(),
+        None => added,
+    }
+}
+
+fn key_of(store: &PreferenceStore, headers: &HeaderMap) -> Result<Arc<str>, AppError> {
+    if store.keys.is_empty() {
+        return Err(AppError::NotFound("API keys are disabled".to_string()));
+    }
+    store.authenticate(headers)?.ok_or(AppError::Unauthorized)
+}
+
+pub async fn get_handler(
+    State(store): State<PreferenceStore>,
+    headers: HeaderMap,
+) -> Result<Json<Defaults>, AppError> {
+    let key = key_of(&store, &headers)?;
+    Ok(Json(store.get(&key)))
+}
+
+/// Replaces the preferences of the key of the request.
+pub async fn put_handler(
+    State(store): State<PreferenceStore>,
+    headers: HeaderMap,
+    Json(defaults): Json<Defaults>,
+) -> Result<Json<Defaults>, AppError> {
+    let key = key_of(&store, &headers)?;
+    defaults.validate()?;
+    store.saved.lock().unwrap().insert(key, defaults.clone());
+    Ok(Json(defaults))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PreferencesLayer {
+    store: PreferenceStore,
+}
+
+impl PreferencesLayer {
+    pub fn new(store: PreferenceStore) -> PreferencesLayer {
+        PreferencesLayer { store }
+    }
+}
+
+impl<S> Layer<S> for PreferencesLayer {
+    type Service = Preferences<S>;
+
+    fn layer(&self, inner: S) -> Preferences<S> {
+        Preferences {
+            inner,
+            store: self.store.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Preferences<S> {
+    inner: S,
+    store: PreferenceStore,
+}
+
+impl<S> Preferences<S> {
+    /// Adds the preferences of the key of a request to its query string.
+    fn apply<B>(&self, request: &mut Request<B>) -> Result<(), AppError> {
+        let key = match self.store.authenticate(request.headers())? {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let defaults = self.store.get(&key);
+        if defaults == Defaults::default() || request.uri().path() == ROUTE {
+            return Ok(());
+        }
+        let query = with_defaults(request.uri().query(), &defaults);
+        let uri = format!("{}?{}", request.uri().path(), query);
+        *request.uri_mut() = uri
+            .parse::<Uri>()
+            .map_err(|_| AppError::BadRequest("Invalid URI".to_string()))?;
+        Ok(())
+    }
+}
+
+impl<S, B, ResBody> Service<Request<B>> for Preferences<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        if let Err(error) = self.apply(&mut request) {
+            let response = error.into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+        // responses depend on the preferences of the key, if any
+        let vary = !self.store.keys.is_empty();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = response.await?.map(Body::new);
+            if vary {
+                response
+                    .headers_mut()
+                    .append(header::VARY, HeaderValue::from_static(API_KEY));
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_keep_their_parameters() {
+        let defaults = Defaults {
+            tz: Some("Europe/Rome".to_string()),
+            locale: Some("it".to_string()),
+            template: None,
+        };
+        assert_eq!(with_defaults(None, &defaults), "tz=Europe%2FRome&locale=it");
+        assert_eq!(
+            with_defaults(Some("locale=de&unit=ms"), &defaults),
+            "locale=de&unit=ms&tz=Europe%2FRome"
+        );
+        assert_eq!(
+            with_defaults(Some("tz=UTC&locale=en"), &defaults),
+            "tz=UTC&locale=en"
+        );
+    }
+
+    #[test]
+    fn keys() {
+        let mut headers = HeaderMap::new();
+        let disabled = PreferenceStore::default();
+        headers.insert(API_KEY, "k1".parse().unwrap());
+        assert_eq!(disabled.authenticate(&headers).unwrap(), None);
+
+        let store = PreferenceStore::new(&["k1".to_string(), "k2".to_string()]);
+        assert_eq!(store.authenticate(&headers).unwrap().as_deref(), Some("k1"));
+        assert_eq!(store.authenticate(&HeaderMap::new()).unwrap(), None);
+        headers.insert(API_KEY, "k3".parse().unwrap());
+        assert!(matches!(
+            store.authenticate(&headers),
+            Err(AppError::Unauthorized)
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+//! Strict parsers for the RFC 3339 and ISO 8601 date-time grammars.
+//!
+//! RFC 3339 is essentially a profile of ISO 8601: it always requires a full date, a
+//! time and an offset, in the extended format. ISO 8601 also allows the basic format
+//! (`20161225T000000Z`), reduced precision (`2016-12-25T10:30`), bare dates and local
+//! times without an offset, which are interpreted as UTC here. Neither accepts a space
+//! between date and time.
+
+use axum::Json;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::timezone::parse_offset;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    Rfc3339,
+    Iso8601,
+}
+
+impl Profile {
+    pub const ALL: [Profile; 2] = [Profile::Rfc3339, Profile::Iso8601];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Rfc3339 => "rfc3339",
+            Profile::Iso8601 => "iso8601",
+        }
+    }
+
+    pub fn parse(self, input: &str) -> Option<DateTime<Utc>> {
+        match self {
+            Profile::Rfc3339 => parse_rfc3339(input),
+            Profile::Iso8601 => parse_iso8601(input),
+        }
+        .map(|date| date.with_timezone(&Utc))
+    }
+}
+
+pub fn parse_rfc3339(input: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(input).ok()
+}
+
+pub fn parse_iso8601(input: &str) -> Option<DateTime<FixedOffset>> {
+    let (local, offset) = parse_iso8601_local(input)?;
+    offset
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap())
+        .from_local_datetime(&local)
+        .single()
+}
+
+/// Parses an ISO 8601 date or date-time, keeping apart the local time and its offset,
+/// if any. Dates alone are at midnight.
+pub fn parse_iso8601_local(input: &str) -> Option<(NaiveDateTime, Option<FixedOffset>)> {
+    const DATES: [&str; 2] = ["%Y-%m-%d", "%Y%m%d"];
+    const DATE_TIMES: [&str; 4] = [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y%m%dT%H%M%S%.f",
+        "%Y%m%dT%H%M",
+    ];
+
+    let (date, time) = match input.find(['T', 't']) {
+        Some(index) => (&input[..index], &input[index + 1..]),
+        None => {
+            let date = DATES
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(input, format).ok())?;
+            return Some((date.and_hms_opt(0, 0, 0).unwrap(), None));
+        }
+    };
+
+    let (time, offset) = match time.rfind(['Z', 'z', '+', '-']) {
+        Some(index) => (&time[..index], Some(parse_offset(&time[index..])?)),
+        None => (time, None),
+    };
+    let local = format!("{}T{}", date, time);
+    let local = DATE_TIMES
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(&local, format).ok())?;
+    Some((local, offset))
+}
+
+/// The UTC offset written in a date-time, `Z` being `+00:00`, if it has one.
+pub fn offset_of(input: &str) -> Option<FixedOffset> {
+    match parse_rfc3339(input) {
+        Some(date) => Some(*date.offset()),
+        None => parse_iso8601_local(input)?.1,
+    }
+}
+
+/// Lists the profiles whose grammar `input` satisfies.
+pub fn profiles(input: &str) -> Vec<Profile> {
+    Profile::ALL
+        .iter()
+        .copied()
+        .filter(|profile| profile.parse(input).is_some())
+        .collect()
+}
+
+/// Reports which date-time grammars an input conforms to.
+pub async fn detect_handler(Path(input): Path<String>) -> Result<Json<Value>, AppError> {
+    let profiles = profiles(&input);
+    let date = profiles.first().and_then(|profile| profile.parse(&input));
+    let names: Vec<&str> = profiles.iter().map(|profile| profile.name()).collect();
+
+    Ok(Json(json!({
+        "input": input,
+        "profiles": names,
+        "unix": date.map(|date| date.timestamp()),
+        "utc": date.map(|date| date.to_rfc2822()),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc3339_is_strict() {
+        assert!(parse_rfc3339("2016-12-25T00:00:00Z").is_some());
+        assert!(parse_rfc3339("2016-12-25T00:00:00.5+01:00").is_some());
+        assert!(parse_rfc3339("2016-12-25 00:00:00").is_none());
+        assert!(parse_rfc3339("20161225T000000Z").is_none());
+        assert!(parse_rfc3339("2016-12-25").is_none());
+    }
+
+    #[test]
+    fn iso8601_forms() {
+        let expected = Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap();
+        for input in &[
+            "20161225T000000Z",
+            "2016-12-25T01:00+01:00",
+            "20161225T010000+0100",
+            "2016-12-25",
+            "20161225",
+        ] {
+            assert_eq!(Profile::Iso8601.parse(input), Some(expected), "{}", input);
+        }
+        assert!(parse_iso8601("2016-12-25 00:00:00").is_none());
+        assert!(parse_iso8601("2016-12-25T25:00").is_none());
+    }
+
+    #[test]
+    fn detection() {
+        assert_eq!(
+            profiles("2016-12-25T00:00:00Z"),
+            vec![Profile::Rfc3339, Profile::Iso8601]
+        );
+        assert_eq!(profiles("20161225T000000Z"), vec![Profile::Iso8601]);
+        assert!(profiles("2016-12-25 00:00:00").is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+//! Protocol Buffers types generated from `proto/timestamp.proto`.
+
+use serde_json::Value;
+
+include!(concat!(env!("OUT_DIR"), "/timestamp.rs"));
+
+impl Timestamp {
+    /// Reads the standard JSON response, returning `None` for any other body. Fields outside
+    /// the schema, such as `localized`, are left out.
+    pub fn from_json(body: &Value) -> Option<Timestamp> {
+        let string = |name: &str| body[name].as_str().map(str::to_string);
+        Some(Timestamp {
+            unix: body["unix"].as_i64()?,
+            unix_ms: body["unix_ms"].as_i64().unwrap_or(0),
+            unix_us: body["unix_us"].as_i64().unwrap_or(0),
+            unix_ns: body["unix_ns"].as_i64().unwrap_or(0),
+            utc: string("utc")?,
+            iso8601: string("iso8601")?,
+            iso_week_date: string("iso_week_date")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use serde_json::json;
+
+    #[test]
+    fn round_trip() {
+        let body = json!({
+            "unix": 1482624000,
+            "unix_ms": 1482624000000i64,
+            "unix_us": 1482624000000000i64,
+            "unix_ns": 1482624000000000000i64,
+            "utc": "Sun, 25 Dec 2016 00:00:00 +0000",
+            "iso8601": "2016-12-25T00:00:00Z",
+            "iso_week_date": "2016-W51-7",
+        });
+        let timestamp = Timestamp::from_json(&body).unwrap();
+        let decoded = Timestamp::decode(&timestamp.encode_to_vec()[..]).unwrap();
+        assert_eq!(decoded, timestamp);
+        assert_eq!(decoded.unix, 1482624000);
+
+        assert_eq!(
+            Timestamp::from_json(&json!({ "error": "Invalid Date" })),
+            None
+        );
+    }
+}
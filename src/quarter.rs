@@ -0,0 +1,145 @@
+use axum::Json;
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::calendar::add_months;
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+use crate::query::Query;
+use crate::timezone::{parse_tz, start_of_day};
+
+/// A period of whole months, `end` being the first day after it.
+#[derive(Debug, PartialEq)]
+pub struct Period {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl Period {
+    fn months(start: NaiveDate, months: i32) -> Option<Period> {
+        Some(Period {
+            start,
+            end: add_months(start, months)?,
+        })
+    }
+
+    fn describe(&self, tz: Tz) -> Value {
+        let start = start_of_day(tz, self.start);
+        let end = start_of_day(tz, self.end);
+        json!({
+            "start": { "date": self.start.to_string(), "unix": start.timestamp(), "utc": start.to_rfc2822() },
+            "end": { "date": self.end.to_string(), "unix": end.timestamp(), "utc": end.to_rfc2822() },
+        })
+    }
+}
+
+/// Quarter (1 to 4) of a year starting on the first day of `start_month`, and its bounds.
+pub fn quarter(date: NaiveDate, start_month: u32) -> Option<(u32, Period)> {
+    let months = (date.month() + 12 - start_month) % 12;
+    let quarter = months / 3 + 1;
+    let first = add_months(date.with_day(1)?, -((months % 3) as i32))?;
+    Some((quarter, Period::months(first, 3)?))
+}
+
+/// Fiscal year of a date, named after the calendar year it ends in, and its bounds.
+pub fn fiscal_year(date: NaiveDate, start_month: u32) -> Option<(i32, Period)> {
+    let months = (date.month() + 12 - start_month) % 12;
+    let first = add_months(date.with_day(1)?, -(months as i32))?;
+    let year = Period::months(first, 12)?;
+    let name = if start_month == 1 {
+        first.year()
+    } else {
+        first.year() + 1
+    };
+    Some((name, year))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuarterParams {
+    /// First month of the fiscal year, January by default.
+    fiscal_start: Option<String>,
+    tz: Option<String>,
+}
+
+/// Calendar and fiscal quarters of a date. Periods are reported as the local midnights of
+/// their first day and of the day after their last one.
+pub async fn quarter_handler(
+    Path(date): Path<String>,
+    Query(params): Query<QuarterParams>,
+) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?.date_naive();
+    let tz = parse_tz(params.tz.as_deref())?;
+    let fiscal_start = match params.fiscal_start.as_deref() {
+        Some(month) => month
+            .parse::<u32>()
+            .ok()
+            .filter(|month| (1..=12).contains(month))
+            .ok_or_else(|| AppError::BadRequest(format!("Invalid fiscal start month {}", month)))?,
+        None => 1,
+    };
+
+    let (calendar_quarter, calendar_period) = quarter(date, 1).ok_or(AppError::InvalidDate)?;
+    let (fiscal_quarter, fiscal_period) =
+        quarter(date, fiscal_start).ok_or(AppError::InvalidDate)?;
+    let (year, year_period) = fiscal_year(date, fiscal_start).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(json!({
+        "date": date.to_string(),
+        "tz": tz.name(),
+        "quarter": calendar_quarter,
+        "quarter_period": calendar_period.describe(tz),
+        "fiscal_start": fiscal_start,
+        "fiscal_year": year,
+        "fiscal_year_period": year_period.describe(tz),
+        "fiscal_quarter": fiscal_quarter,
+        "fiscal_quarter_period": fiscal_period.describe(tz),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn calendar_quarters() {
+        assert_eq!(
+            quarter(day(2016, 12, 25), 1),
+            Some((
+                4,
+                Period {
+                    start: day(2016, 10, 1),
+                    end: day(2017, 1, 1)
+                }
+            ))
+        );
+        assert_eq!(quarter(day(2016, 4, 1), 1).unwrap().0, 2);
+    }
+
+    #[test]
+    fn fiscal_quarters() {
+        // April start, as in the UK and Japan
+        assert_eq!(quarter(day(2017, 3, 31), 4).unwrap().0, 4);
+        assert_eq!(quarter(day(2016, 12, 25), 4).unwrap().0, 3);
+        assert_eq!(
+            fiscal_year(day(2017, 3, 31), 4),
+            Some((
+                2017,
+                Period {
+                    start: day(2016, 4, 1),
+                    end: day(2017, 4, 1)
+                }
+            ))
+        );
+        // October start, as the US federal government
+        assert_eq!(quarter(day(2021, 10, 1), 10).unwrap().0, 1);
+        assert_eq!(fiscal_year(day(2021, 10, 1), 10).unwrap().0, 2022);
+        assert_eq!(fiscal_year(day(2021, 10, 1), 1).unwrap().0, 2021);
+    }
+}
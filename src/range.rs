@@ -0,0 +1,112 @@
+//! Sequences of instants between two dates at a fixed step, one page at a time.
+//!
+//! The n-th instant is `from` plus n steps, rather than the previous instant plus one step,
+//! so that monthly ranges starting on the 31st don't drift after a short month. Calendar
+//! steps follow the wall clock of the requested timezone, and instants falling in a DST
+//! gap are left out.
+
+use axum::Json;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::duration::IsoDuration;
+use crate::error::AppError;
+use crate::query::Query;
+use crate::timezone::{parse_in_zone, parse_tz};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+/// The instants of `[from, to]` with indices from `start`, at most `limit` of them, and
+/// the index the next page starts at if the range goes on.
+fn page(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    step: IsoDuration,
+    tz: Tz,
+    start: usize,
+    limit: usize,
+) -> (Vec<DateTime<Utc>>, Option<usize>) {
+    let mut instants = Vec::with_capacity(limit);
+    let end = start.saturating_add(limit);
+    for index in start..end {
+        let instant = match step.times(index as i64).and_then(|s| s.add_to(from, tz)) {
+            Some(instant) => instant,
+            None => continue,
+        };
+        if instant > to {
+            return (instants, None);
+        }
+        instants.push(instant);
+    }
+    (instants, Some(end))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RangeParams {
+    from: String,
+    to: String,
+    step: String,
+    tz: Option<String>,
+    /// Index of the first instant of the page, as returned in `next`.
+    cursor: Option<usize>,
+    limit: Option<usize>,
+}
+
+pub async fn range_handler(Query(params): Query<RangeParams>) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(params.tz.as_deref())?;
+    let from = parse_in_zone(&params.from, tz)?;
+    let to = parse_in_zone(&params.to, tz)?;
+    let step = IsoDuration::parse_shorthand(&params.step)
+        .filter(|step| step.add_to(from, tz).is_some_and(|next| next > from))
+        .ok_or_else(|| AppError::BadRequest("The step must be a positive duration".to_string()))?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let (instants, next) = page(from, to, step, tz, params.cursor.unwrap_or(0), limit);
+    let instants: Vec<Value> = instants
+        .into_iter()
+        .map(|instant| json!({ "unix": instant.timestamp(), "utc": instant.to_rfc2822() }))
+        .collect();
+
+    Ok(Json(json!({
+        "tz": tz.name(),
+        "instants": instants,
+        "next": next,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn monthly_pages() {
+        let from = Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let step = IsoDuration::parse_shorthand("1mo").unwrap();
+
+        let (instants, next) = page(from, to, step, Tz::UTC, 0, 3);
+        assert_eq!(
+            instants,
+            vec![
+                from,
+                Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 3, 31, 0, 0, 0).unwrap(),
+            ]
+        );
+        assert_eq!(next, Some(3));
+
+        let (instants, next) = page(from, to, step, Tz::UTC, 3, 3);
+        assert_eq!(
+            instants,
+            vec![
+                Utc.with_ymd_and_hms(2024, 4, 30, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 5, 31, 0, 0, 0).unwrap(),
+            ]
+        );
+        assert_eq!(next, None);
+    }
+}
@@ -0,0 +1,335 @@
+//! Per-client rate limiting with token buckets.
+//!
+//! Clients are told apart by their IP address: the peer address, unless the peer is one
+//! of the configured `trusted_proxies`. `X-Forwarded-For` is then read from the right,
+//! the client being the first address that isn't a trusted proxy, since entries to its
+//! left could be forged by the client itself. Requests over the limit are answered with
+//! a 429. The limit is read from the runtime settings on every request, so that reloads
+//! apply to existing buckets too.
+//!
+//! Every response tells the client the capacity of its bucket in `X-RateLimit-Limit` and
+//! the requests it has left in `X-RateLimit-Remaining`. Once none is left, `Retry-After`
+//! gives the seconds until the next one, on the response that emptied the bucket too.
+//!
+//! Buckets are kept in memory, each replica of the service limiting on its own, unless
+//! they are moved to a shared store with [`RateLimitLayer::with_buckets`].
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::ConnectInfo;
+use axum::http::{header, Extensions, HeaderMap, HeaderValue, Request, Response};
+use axum::response::IntoResponse;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{BoxError, Layer, Service};
+
+use crate::config::{RateLimitConfig, Settings};
+use crate::error::AppError;
+
+/// Number of tracked clients above which full buckets are forgotten.
+const MAX_CLIENTS: usize = 10_000;
+
+/// Proxies whose `X-Forwarded-For` is believed, set once at startup.
+static TRUSTED_PROXIES: OnceLock<Vec<IpAddr>> = OnceLock::new();
+
+/// Sets the proxies whose `X-Forwarded-For` is believed, none unless called.
+pub fn trust_proxies(proxies: Vec<IpAddr>) {
+    if TRUSTED_PROXIES.set(proxies).is_err() {
+        tracing::warn!("The trusted proxies are already set");
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The state of a client's bucket, as reported in response headers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quota {
+    limit: u64,
+    remaining: u64,
+    /// Seconds until a token is available, when none is.
+    retry_after: Option<u64>,
+}
+
+/// Tokens added per second and capacity of the buckets.
+pub fn rates(config: RateLimitConfig) -> (f64, f64) {
+    let per_second = config.requests_per_minute as f64 / 60.0;
+    let burst = match config.burst {
+        0 => config.requests_per_minute,
+        burst => burst,
+    } as f64;
+    (per_second, burst)
+}
+
+impl Quota {
+    /// The quota of a bucket left with `tokens`.
+    pub fn of(tokens: f64, per_second: f64, burst: f64) -> Quota {
+        Quota {
+            limit: burst as u64,
+            remaining: tokens.floor() as u64,
+            retry_after: Some(((1.0 - tokens) / per_second).ceil() as u64).filter(|_| tokens < 1.0),
+        }
+    }
+
+    fn write(self, headers: &mut HeaderMap) {
+        headers.insert("x-ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(self.remaining));
+        if let Some(wait) = self.retry_after {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(wait));
+        }
+    }
+}
+
+/// Where the buckets of clients are kept.
+pub trait Buckets: Send + Sync + std::fmt::Debug {
+    /// Takes a token from the client's bucket, failing when there is none. Either way the
+    /// state of the bucket is returned.
+    fn take(&self, config: RateLimitConfig, client: Option<IpAddr>) -> Result<Quota, Quota>;
+}
+
+/// Buckets of the clients of this process.
+#[derive(Debug, Default)]
+struct Limiter {
+    /// `None` for clients whose address is unknown, which share a bucket.
+    buckets: Mutex<HashMap<Option<IpAddr>, Bucket>>,
+}
+
+impl Limiter {
+    /// Takes a token from the client's bucket, failing when there is none. Either way the
+    /// state of the bucket is returned.
+    fn acquire(
+        &self,
+        config: RateLimitConfig,
+        client: Option<IpAddr>,
+        now: Instant,
+    ) -> Result<Quota, Quota> {
+        let (per_second, burst) = rates(config);
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_CLIENTS {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens + elapsed * per_second < burst
+            });
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(burst);
+        bucket.updated = now;
+        let acquired = bucket.tokens >= 1.0;
+        if acquired {
+            bucket.tokens -= 1.0;
+        }
+        let quota = Quota::of(bucket.tokens, per_second, burst);
+        if acquired {
+            Ok(quota)
+        } else {
+            Err(quota)
+        }
+    }
+}
+
+impl Buckets for Limiter {
+    fn take(&self, config: RateLimitConfig, client: Option<IpAddr>) -> Result<Quota, Quota> {
+        self.acquire(config, client, Instant::now())
+    }
+}
+
+/// Limits requests per client, or lets everything through when the configured rate is 0.
+#[derive(Clone, Debug)]
+pub struct RateLimitLayer {
+    buckets: Arc<dyn Buckets>,
+    settings: Settings,
+}
+
+impl RateLimitLayer {
+    pub fn new(settings: Settings) -> RateLimitLayer {
+        RateLimitLayer {
+            buckets: Arc::new(Limiter::default()),
+            settings,
+        }
+    }
+
+    /// Keeps the buckets in `buckets` instead of memory.
+    pub fn with_buckets(self, buckets: Arc<dyn Buckets>) -> RateLimitLayer {
+        RateLimitLayer { buckets, ..self }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> RateLimit<S> {
+        RateLimit {
+            inner,
+            buckets: self.buckets.clone(),
+            settings: self.settings.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimit<S> {
+    inner: S,
+    buckets: Arc<dyn Buckets>,
+    settings: Settings,
+}
+
+/// The client connected from `peer`, the right-most address of `X-Forwarded-For` that
+/// isn't a trusted proxy when `peer` is one. An unreadable entry ends the search at the
+/// proxy that added it.
+fn resolve(headers: &HeaderMap, peer: Option<IpAddr>, trusted: &[IpAddr]) -> Option<IpAddr> {
+    let mut client = peer?;
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for address in forwarded.into_iter().rev() {
+        if !trusted.contains(&client) {
+            break;
+        }
+        match address.trim().parse() {
+            Ok(address) => client = address,
+            Err(_) => break,
+        }
+    }
+    Some(client)
+}
+
+/// The address of a client, as connected or forwarded by a trusted proxy.
+pub fn client_address(headers: &HeaderMap, extensions: &Extensions) -> Option<IpAddr> {
+    let peer = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip());
+    let trusted = TRUSTED_PROXIES.get().map_or(&[][..], Vec::as_slice);
+    resolve(headers, peer, trusted)
+}
+
+/// Same as [`client_address`], for a whole request.
+pub fn client<B>(request: &Request<B>) -> Option<IpAddr> {
+    client_address(request.headers(), request.extensions())
+}
+
+impl<S, B, ResBody> Service<Request<B>> for RateLimit<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let config = self.settings.borrow().rate_limit;
+        let mut quota = None;
+        if config.requests_per_minute > 0 {
+            match self.buckets.take(config, client(&request)) {
+                Ok(acquired) => quota = Some(acquired),
+                Err(exhausted) => {
+                    let mut response = AppError::TooManyRequests.into_response();
+                    exhausted.write(response.headers_mut());
+                    return Box::pin(async move { Ok(response) });
+                }
+            }
+        }
+
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = response.await?.map(Body::new);
+            if let Some(quota) = quota {
+                quota.write(response.headers_mut());
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn buckets_refill() {
+        let limiter = Limiter::default();
+        let config = RateLimitConfig {
+            requests_per_minute: 60,
+            burst: 2,
+        };
+        let client = Some(IpAddr::from([10, 0, 0, 1]));
+        let now = Instant::now();
+
+        let quota = |remaining, retry_after| Quota {
+            limit: 2,
+            remaining,
+            retry_after,
+        };
+        assert_eq!(limiter.acquire(config, client, now), Ok(quota(1, None)));
+        assert_eq!(limiter.acquire(config, client, now), Ok(quota(0, Some(1))));
+        assert_eq!(limiter.acquire(config, client, now), Err(quota(0, Some(1))));
+        assert_eq!(limiter.acquire(config, None, now), Ok(quota(1, None)));
+        assert_eq!(
+            limiter.acquire(config, client, now + Duration::from_secs(1)),
+            Ok(quota(0, Some(1)))
+        );
+    }
+
+    #[test]
+    fn forwarded_client() {
+        let proxy = IpAddr::from([10, 0, 0, 1]);
+        let balancer = IpAddr::from([10, 0, 0, 2]);
+        let trusted = [proxy, balancer];
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("198.51.100.1, 203.0.113.7, 10.0.0.1"),
+        );
+        assert_eq!(
+            resolve(&headers, Some(balancer), &trusted),
+            Some(IpAddr::from([203, 0, 113, 7]))
+        );
+        assert_eq!(resolve(&headers, None, &trusted), None);
+
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("10.0.0.1, nope"),
+        );
+        assert_eq!(resolve(&headers, Some(proxy), &trusted), Some(proxy));
+    }
+
+    #[test]
+    fn forged_forwarding() {
+        let peer = IpAddr::from([203, 0, 113, 7]);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("198.51.100.1"));
+        assert_eq!(resolve(&headers, Some(peer), &[]), Some(peer));
+        let proxy = IpAddr::from([10, 0, 0, 1]);
+        assert_eq!(resolve(&headers, Some(peer), &[proxy]), Some(peer));
+
+        let request = Request::builder()
+            .header("x-forwarded-for", "198.51.100.1")
+            .extension(ConnectInfo(SocketAddr::from((peer, 443))))
+            .body(())
+            .unwrap();
+        assert_eq!(client(&request), Some(peer));
+    }
+}
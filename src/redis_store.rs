@@ -0,0 +1,332 @@
+//! Redis backend for the state replicas of the service share, built with the `redis`
+//! feature and enabled by setting `redis_url`.
+//!
+//! It holds the rate limiting buckets, a second level of the parse cache and the named
+//! timestamps, under keys prefixed with `timestamp:`. Calls are synchronous like the
+//! in-memory stores they replace, over a single connection with short timeouts, opened
+//! again after a failure. When Redis can't be reached requests are let through rather
+//! than limited, dates are parsed again, and marks are reported missing.
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use redis::{Client, Connection, RedisResult, Script};
+use serde_json::{json, Value};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::RateLimitConfig;
+use crate::marks::{Mark, MarkStorage};
+use crate::parse::Rejection;
+use crate::parse_cache::{Parsed, SharedLevel};
+use crate::rate_limit::{self, Buckets, Quota};
+
+const TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How long parsed dates are shared for.
+const PARSE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Refills a bucket and takes a token from it if it has one, returning whether it did and
+/// the tokens left. Times come from the Redis server, so that replicas agree on them, and
+/// idle buckets expire once they would be full again.
+const TAKE_TOKEN: &str = r#"
+local per_second = tonumber(ARGV[1])
+local burst = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) + tonumber(time[2]) / 1000000
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'updated')
+local tokens = tonumber(bucket[1]) or burst
+local updated = tonumber(bucket[2]) or now
+tokens = math.min(burst, tokens + math.max(0, now - updated) * per_second)
+local taken = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    taken = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated', tostring(now))
+redis.call('EXPIRE', KEYS[1], math.ceil(burst / per_second) + 1)
+return {taken, tostring(tokens)}
+"#;
+
+pub struct Redis {
+    client: Client,
+    connection: Mutex<Option<Connection>>,
+    take_token: Script,
+}
+
+impl fmt::Debug for Redis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Redis")
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+impl Redis {
+    /// Connects to the server at `url`, failing when it doesn't answer.
+    pub fn connect(url: &str) -> Result<Redis, String> {
+        let client = Client::open(url).map_err(|e| format!("Invalid Redis URL: {}", e))?;
+        let redis = Redis {
+            client,
+            connection: Mutex::default(),
+            take_token: Script::new(TAKE_TOKEN),
+        };
+        redis
+            .run(|connection| redis::cmd("PING").query::<String>(connection))
+            .map_err(|e| format!("Can't reach Redis: {}", e))?;
+        Ok(redis)
+    }
+
+    /// Runs commands on the connection, opening it first if needed. A connection that
+    /// failed is dropped, its state being unknown.
+    fn run<T>(&self, commands: impl FnOnce(&mut Connection) -> RedisResult<T>) -> RedisResult<T> {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.is_none() {
+            let opened = self.client.get_connection_with_timeout(TIMEOUT)?;
+            opened.set_read_timeout(Some(TIMEOUT))?;
+            opened.set_write_timeout(Some(TIMEOUT))?;
+            *connection = Some(opened);
+        }
+        let result = commands(connection.as_mut().unwrap());
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+}
+
+impl Buckets for Redis {
+    fn take(&self, config: RateLimitConfig, client: Option<IpAddr>) -> Result<Quota, Quota> {
+        let (per_second, burst) = rate_limit::rates(config);
+        let key = match client {
+            Some(client) => format!("timestamp:ratelimit:{}", client),
+            None => "timestamp:ratelimit:unknown".to_string(),
+        };
+        let taken = self.run(|connection| {
+            self.take_token
+                .key(&key)
+                .arg(per_second)
+                .arg(burst)
+                .invoke::<(bool, String)>(connection)
+        });
+        match taken {
+            Ok((taken, tokens)) => {
+                let quota = Quota::of(tokens.parse().unwrap_or(0.0), per_second, burst);
+                if taken {
+                    Ok(quota)
+                } else {
+                    Err(quota)
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Not rate limiting, Redis failed: {}", e);
+                Ok(Quota::of(burst, per_second, burst))
+            }
+        }
+    }
+}
+
+/// A parse result as stored in Redis: seconds and nanoseconds, or why it failed.
+fn encode_parsed(parsed: Parsed) -> String {
+    match parsed {
+        Ok(date) => format!("{} {}", date.timestamp(), date.timestamp_subsec_nanos()),
+        Err(Rejection::Malformed) => "malformed".to_string(),
+        Err(Rejection::Impossible) => "impossible".to_string(),
+        Err(Rejection::OutOfRange) => "out_of_range".to_string(),
+    }
+}
+
+fn decode_parsed(value: &str) -> Option<Parsed> {
+    match value {
+        "malformed" => Some(Err(Rejection::Malformed)),
+        "impossible" => Some(Err(Rejection::Impossible)),
+        "out_of_range" => Some(Err(Rejection::OutOfRange)),
+        value => {
+            let (seconds, nanos) = value.split_once(' ')?;
+            let date = Utc
+                .timestamp_opt(seconds.parse().ok()?, nanos.parse().ok()?)
+                .single()?;
+            Some(Ok(date))
+        }
+    }
+}
+
+impl SharedLevel for Redis {
+    fn get(&self, key: &str) -> Option<Parsed> {
+        let key = format!("timestamp:parse:{}", key);
+        match self.run(|connection| {
+            redis::cmd("GET")
+                .arg(&key)
+                .query::<Option<String>>(connection)
+        }) {
+            Ok(value) => value.as_deref().and_then(decode_parsed),
+            Err(e) => {
+                tracing::warn!("Can't read the shared parse cache: {}", e);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, parsed: Parsed) {
+        let key = format!("timestamp:parse:{}", key);
+        let stored = self.run(|connection| {
+            redis::cmd("SET")
+                .arg(&key)
+                .arg(encode_parsed(parsed))
+                .arg("EX")
+                .arg(PARSE_TTL_SECS)
+                .query::<()>(connection)
+        });
+        if let Err(e) = stored {
+            tracing::warn!("Can't write the shared parse cache: {}", e);
+        }
+    }
+}
+
+const MARK_PREFIX: &str = "timestamp:mark:";
+
+fn encode_mark(mark: &Mark) -> String {
+    let date = |date: DateTime<Utc>| date.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+    json!({
+        "at": date(mark.at),
+        "expires_at": mark.expires_at.map(date),
+    })
+    .to_string()
+}
+
+fn decode_mark(value: &str) -> Option<Mark> {
+    let value: Value = serde_json::from_str(value).ok()?;
+    let date = |date: &Value| {
+        let date = DateTime::parse_from_rfc3339(date.as_str()?).ok()?;
+        Some(date.with_timezone(&Utc))
+    };
+    Some(Mark {
+        at: date(&value["at"])?,
+        expires_at: match &value["expires_at"] {
+            Value::Null => None,
+            expires_at => Some(date(expires_at)?),
+        },
+    })
+}
+
+/// Marks expire in Redis too, and are filtered on reads in case Redis hasn't removed them
+/// yet.
+impl MarkStorage for Redis {
+    fn put(&self, name: String, mark: Mark) {
+        let key = format!("{}{}", MARK_PREFIX, name);
+        let mut command = redis::cmd("SET");
+        command.arg(&key).arg(encode_mark(&mark));
+        if let Some(expires_at) = mark.expires_at {
+            let ttl = (expires_at - Utc::now()).num_milliseconds().max(1);
+            command.arg("PX").arg(ttl);
+        }
+        if let Err(e) = self.run(|connection| command.query::<()>(connection)) {
+            tracing::error!("Can't store the mark {}: {}", name, e);
+        }
+    }
+
+    fn get(&self, name: &str, now: DateTime<Utc>) -> Option<Mark> {
+        let key = format!("{}{}", MARK_PREFIX, name);
+        let value = self.run(|connection| {
+            redis::cmd("GET")
+                .arg(&key)
+                .query::<Option<String>>(connection)
+        });
+        match value {
+            Ok(value) => value
+                .as_deref()
+                .and_then(decode_mark)
+                .filter(|mark| mark.is_live(now)),
+            Err(e) => {
+                tracing::error!("Can't read the mark {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    fn remove(&self, name: &str, now: DateTime<Utc>) -> Option<Mark> {
+        let key = format!("{}{}", MARK_PREFIX, name);
+        let removed = self.run(|connection| {
+            redis::pipe()
+                .atomic()
+                .cmd("GET")
+                .arg(&key)
+                .cmd("DEL")
+                .arg(&key)
+                .ignore()
+                .query::<(Option<String>,)>(connection)
+        });
+        match removed {
+            Ok((value,)) => value
+                .as_deref()
+                .and_then(decode_mark)
+                .filter(|mark| mark.is_live(now)),
+            Err(e) => {
+                tracing::error!("Can't remove the mark {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    fn list(&self, now: DateTime<Utc>) -> Vec<(String, Mark)> {
+        let listed = self.run(|connection| {
+            let keys: Vec<String> = redis::cmd("SCAN")
+                .cursor_arg(0)
+                .arg("MATCH")
+                .arg(format!("{}*", MARK_PREFIX))
+                .clone()
+                .iter(connection)?
+                .collect();
+            if keys.is_empty() {
+                return Ok(Vec::new());
+            }
+            let values: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query(connection)?;
+            Ok(keys.into_iter().zip(values).collect::<Vec<_>>())
+        });
+        match listed {
+            Ok(listed) => listed
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    let mark = decode_mark(&value?)?;
+                    let name = key.strip_prefix(MARK_PREFIX)?.to_string();
+                    Some((name, mark))
+                })
+                .filter(|(_, mark)| mark.is_live(now))
+                .collect(),
+            Err(e) => {
+                tracing::error!("Can't list the marks: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsed_values() {
+        let date = Ok(Utc.timestamp_opt(-1, 500).unwrap());
+        assert_eq!(encode_parsed(date), "-1 500");
+        assert_eq!(decode_parsed("-1 500"), Some(date));
+        let failed = Err(Rejection::Impossible);
+        assert_eq!(decode_parsed(&encode_parsed(failed)), Some(failed));
+        assert_eq!(decode_parsed("garbage"), None);
+    }
+
+    #[test]
+    fn mark_values() {
+        let mark = Mark {
+            at: Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap()
+                + chrono::Duration::nanoseconds(1),
+            expires_at: None,
+        };
+        assert_eq!(decode_mark(&encode_mark(&mark)), Some(mark.clone()));
+        let expiring = Mark {
+            expires_at: Some(Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap()),
+            ..mark
+        };
+        assert_eq!(decode_mark(&encode_mark(&expiring)), Some(expiring));
+    }
+}
@@ -0,0 +1,399 @@
+use axum::http::HeaderMap;
+use axum::Json;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::duration::IsoDuration;
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::locale::{self, Locale};
+use crate::profile;
+use crate::query::Query;
+use crate::{parse_base, parse_date_at};
+
+/// Units used when describing a distance in time, from the finest to the coarsest.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Unit {
+    pub const ALL: [Unit; 7] = [
+        Unit::Second,
+        Unit::Minute,
+        Unit::Hour,
+        Unit::Day,
+        Unit::Week,
+        Unit::Month,
+        Unit::Year,
+    ];
+
+    /// Length of the unit in seconds. Months and years use their average
+    /// lengths, which is precise enough for a humanized output.
+    pub fn seconds(self) -> i64 {
+        match self {
+            Unit::Second => 1,
+            Unit::Minute => 60,
+            Unit::Hour => 60 * 60,
+            Unit::Day => 24 * 60 * 60,
+            Unit::Week => 7 * 24 * 60 * 60,
+            Unit::Month => 30 * 24 * 60 * 60,
+            Unit::Year => 365 * 24 * 60 * 60,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Unit::Second => "second",
+            Unit::Minute => "minute",
+            Unit::Hour => "hour",
+            Unit::Day => "day",
+            Unit::Week => "week",
+            Unit::Month => "month",
+            Unit::Year => "year",
+        }
+    }
+}
+
+/// Describes `delta` seconds as "3 days ago" or "in 2 hours".
+///
+/// The largest unit fitting in `delta` is used, but never one finer than `granularity`:
+/// distances shorter than a single `granularity` unit are reported as "just now".
+pub fn humanize(delta: i64, granularity: Unit) -> String {
+    humanize_in(&locale::EN, delta, granularity)
+}
+
+/// Same as [`humanize`], using the vocabulary of the given locale.
+pub fn humanize_in(locale: &Locale, delta: i64, granularity: Unit) -> String {
+    if delta.abs() < granularity.seconds() {
+        return locale.just_now.to_string();
+    }
+
+    let amount = amount_in(locale, delta.abs(), granularity);
+    if delta < 0 {
+        locale.past.replace("{}", &amount)
+    } else {
+        locale.future.replace("{}", &amount)
+    }
+}
+
+/// Describes a non-negative number of seconds as "3 days", in the largest unit fitting
+/// in it but never one finer than `granularity`.
+pub fn amount_in(locale: &Locale, distance: i64, granularity: Unit) -> String {
+    let unit = Unit::ALL
+        .iter()
+        .rev()
+        .find(|unit| **unit >= granularity && distance >= unit.seconds())
+        .copied()
+        .unwrap_or(granularity);
+    let count = distance / unit.seconds();
+    format!("{} {}", count, locale.unit_name(unit, count))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelativeParams {
+    granularity: Option<Unit>,
+    /// Reference instant, defaults to now.
+    base: Option<String>,
+    /// Language of the description, overriding `Accept-Language`.
+    locale: Option<String>,
+}
+
+pub async fn relative_handler(
+    Path(date): Path<String>,
+    Query(params): Query<RelativeParams>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let locale = locale::negotiate(&headers, params.locale.as_deref())?.unwrap_or(&locale::EN);
+    let now = parse_base(params.base.as_deref())?;
+    let date = parse_date_at(&date, now)?;
+    let granularity = params.granularity.unwrap_or(Unit::Second);
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "relative": humanize_in(locale, (date - now).num_seconds(), granularity),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BaseParams {
+    /// Reference instant, defaults to now.
+    base: Option<String>,
+}
+
+/// Describes a signed duration in several units, truncating towards zero.
+fn delta(duration: Duration) -> Value {
+    json!({
+        "seconds": duration.num_seconds(),
+        "minutes": duration.num_minutes(),
+        "hours": duration.num_hours(),
+        "days": duration.num_days(),
+    })
+}
+
+/// Splits the absolute value of a duration into whole days, hours, minutes and seconds,
+/// as shown by a countdown.
+pub fn breakdown(duration: Duration) -> Value {
+    let total = duration.num_seconds().abs();
+    json!({
+        "days": total / 86400,
+        "hours": total % 86400 / 3600,
+        "minutes": total % 3600 / 60,
+        "seconds": total % 60,
+    })
+}
+
+/// Time left from now (or `?base=`) until the given date; negative when the date is in the past.
+pub async fn until_handler(
+    Path(date): Path<String>,
+    Query(params): Query<BaseParams>,
+) -> Result<Json<Value>, AppError> {
+    let now = parse_base(params.base.as_deref())?;
+    let date = parse_date_at(&date, now)?;
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "until": delta(date - now),
+        "total_seconds": (date - now).num_seconds(),
+        "remaining": breakdown(date - now),
+        "past": date < now,
+        "relative": humanize((date - now).num_seconds(), Unit::Second),
+    })))
+}
+
+/// Time elapsed from the given date until now (or `?base=`); negative when the date is in the future.
+pub async fn since_handler(
+    Path(date): Path<String>,
+    Query(params): Query<BaseParams>,
+) -> Result<Json<Value>, AppError> {
+    let now = parse_base(params.base.as_deref())?;
+    let date = parse_date_at(&date, now)?;
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "since": delta(now - date),
+        "relative": humanize((date - now).num_seconds(), Unit::Second),
+    })))
+}
+
+/// Units a difference between two dates can be expressed in.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffUnit {
+    Seconds,
+    Hours,
+    Days,
+}
+
+impl DiffUnit {
+    fn name(self) -> &'static str {
+        match self {
+            DiffUnit::Seconds => "seconds",
+            DiffUnit::Hours => "hours",
+            DiffUnit::Days => "days",
+        }
+    }
+
+    /// Whole units in `duration`, truncating towards zero.
+    fn count(self, duration: Duration) -> i64 {
+        match self {
+            DiffUnit::Seconds => duration.num_seconds(),
+            DiffUnit::Hours => duration.num_hours(),
+            DiffUnit::Days => duration.num_days(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffUnitParams {
+    unit: Option<DiffUnit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffParams {
+    from: String,
+    to: String,
+    unit: Option<DiffUnit>,
+}
+
+/// Reads an end of a difference, also taking RFC 3339 date-times with an offset.
+fn parse_endpoint(date: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, AppError> {
+    match profile::parse_rfc3339(date) {
+        Some(date) => Ok(date.with_timezone(&Utc)),
+        None => parse_date_at(date, now),
+    }
+}
+
+/// Time from `from` to `to`, or to `to` after `from` when it's a duration; negative when
+/// `to` comes first.
+fn diff(from: &str, to: &str, unit: Option<DiffUnit>) -> Result<Value, AppError> {
+    let now = Utc::now();
+    let from = parse_endpoint(from, now)?;
+    // the end may also be a duration from the start, such as `P1M`
+    let to = match IsoDuration::parse(to) {
+        Some(duration) => duration.add_to(from, Tz::UTC).ok_or_else(|| {
+            AppError::Unprocessable("The end of the difference overflows".to_string())
+        })?,
+        None => parse_endpoint(to, now)?,
+    };
+    let unit = unit.unwrap_or(DiffUnit::Seconds);
+
+    Ok(json!({
+        "from": from.timestamp(),
+        "to": to.timestamp(),
+        "unit": unit.name(),
+        "difference": unit.count(to - from),
+        "delta": delta(to - from),
+        "iso8601": IsoDuration::from_seconds((to - from).num_seconds()).to_string(),
+    }))
+}
+
+pub async fn diff_path_handler(
+    Path((from, to)): Path<(String, String)>,
+    Query(params): Query<DiffUnitParams>,
+) -> Result<Json<Value>, AppError> {
+    diff(&from, &to, params.unit).map(Json)
+}
+
+/// Same as [`diff_path_handler`] with the dates in the query string, sparing clients from
+/// escaping the colons and plus signs of RFC 3339 dates in path segments.
+pub async fn diff_handler(Query(params): Query<DiffParams>) -> Result<Json<Value>, AppError> {
+    diff(&params.from, &params.to, params.unit).map(Json)
+}
+
+/// Describes a number of seconds as "1 day, 2 hours", using at most `max_units` units
+/// from the coarsest one fitting in it and dropping what's left.
+pub fn humanize_duration(locale: &Locale, seconds: u64, max_units: usize) -> String {
+    let mut left = seconds;
+    let mut parts = Vec::new();
+    for unit in Unit::ALL.iter().rev() {
+        if parts.len() == max_units {
+            break;
+        }
+        let length = unit.seconds() as u64;
+        // years come first, leaving less than a year for finer units
+        let count = (left / length) as i64;
+        if count > 0 {
+            parts.push(format!("{} {}", count, locale.unit_name(*unit, count)));
+            left %= length;
+        }
+    }
+    if parts.is_empty() {
+        return format!("0 {}", locale.unit_name(Unit::Second, 0));
+    }
+    parts.join(", ")
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HumanizeParams {
+    /// Most units to describe the duration with, 2 by default.
+    max_units: Option<usize>,
+    /// Language of the description, overriding `Accept-Language`.
+    locale: Option<String>,
+}
+
+pub async fn humanize_handler(
+    Path(seconds): Path<i64>,
+    Query(params): Query<HumanizeParams>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let locale = locale::negotiate(&headers, params.locale.as_deref())?.unwrap_or(&locale::EN);
+    let max_units = params.max_units.unwrap_or(2);
+    if max_units == 0 {
+        return Err(AppError::BadRequest(
+            "max_units must be at least 1".to_string(),
+        ));
+    }
+    // a negative duration would read the same as a positive one
+    let length = u64::try_from(seconds)
+        .map_err(|_| AppError::BadRequest("seconds can't be negative".to_string()))?;
+
+    Ok(Json(json!({
+        "seconds": seconds,
+        "locale": locale.code,
+        "humanized": humanize_duration(locale, length, max_units),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn past_and_future() {
+        assert_eq!(humanize(-3 * 24 * 60 * 60, Unit::Second), "3 days ago");
+        assert_eq!(humanize(2 * 60 * 60 + 59, Unit::Second), "in 2 hours");
+        assert_eq!(humanize(-60, Unit::Second), "1 minute ago");
+    }
+
+    #[test]
+    fn granularity() {
+        assert_eq!(humanize(30, Unit::Minute), "just now");
+        assert_eq!(humanize(-2 * 60 * 60, Unit::Day), "just now");
+        assert_eq!(humanize(-90 * 24 * 60 * 60, Unit::Day), "3 months ago");
+    }
+
+    #[test]
+    fn localized() {
+        assert_eq!(humanize_in(&locale::IT, -60, Unit::Second), "1 minuto fa");
+        assert_eq!(
+            humanize_in(&locale::DE, 2 * 24 * 60 * 60, Unit::Second),
+            "in 2 Tagen"
+        );
+    }
+
+    #[test]
+    fn signed_delta() {
+        let value = delta(Duration::seconds(-(26 * 60 * 60 + 30)));
+        assert_eq!(
+            value,
+            json!({ "seconds": -93630, "minutes": -1560, "hours": -26, "days": -1 })
+        );
+    }
+
+    #[test]
+    fn countdown_breakdown() {
+        let value = breakdown(-Duration::seconds(2 * 86400 + 3 * 3600 + 4 * 60 + 5));
+        assert_eq!(
+            value,
+            json!({ "days": 2, "hours": 3, "minutes": 4, "seconds": 5 })
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(humanize_duration(&locale::EN, 93784, 2), "1 day, 2 hours");
+        assert_eq!(
+            humanize_duration(&locale::EN, 93784, 4),
+            "1 day, 2 hours, 3 minutes, 4 seconds"
+        );
+        assert_eq!(humanize_duration(&locale::EN, 3601, 2), "1 hour, 1 second");
+        assert_eq!(humanize_duration(&locale::IT, 7200, 2), "2 ore");
+        assert_eq!(humanize_duration(&locale::EN, 0, 2), "0 seconds");
+        assert_eq!(
+            humanize_duration(&locale::EN, u64::MAX, 2),
+            "584942417355 years, 3 weeks"
+        );
+    }
+
+    #[test]
+    fn diff_to_a_duration() {
+        let value = diff("2016-01-31", "P1M", Some(DiffUnit::Days)).unwrap();
+        assert_eq!(value["to"], json!(1456704000));
+        assert_eq!(value["difference"], json!(29));
+        assert_eq!(value["iso8601"], json!("PT696H"));
+    }
+}
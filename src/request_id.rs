@@ -0,0 +1,175 @@
+//! `X-Request-Id` propagation.
+//!
+//! Requests without a usable id get a fresh one, a UUIDv7 so that ids sort by time. The
+//! id is set on the request before tracing spans are created, returned in the response
+//! headers, and added as `request_id` to JSON error bodies so users can quote it.
+
+use axum::body::{to_bytes, Body, Bytes, HttpBody};
+use axum::http::{header, HeaderValue, Request, Response};
+use chrono::Utc;
+use serde_json::Value;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{BoxError, Layer, Service};
+
+pub const REQUEST_ID: &str = "x-request-id";
+/// Longer ids sent by clients are replaced rather than logged.
+const MAX_LENGTH: usize = 128;
+
+/// Source of request ids, unique within the process and unpredictable across restarts.
+#[derive(Clone, Default)]
+pub struct Generator {
+    counter: Arc<AtomicU64>,
+    random: RandomState,
+}
+
+impl Generator {
+    fn random_bits(&self, count: u64) -> u64 {
+        self.random.hash_one(count)
+    }
+
+    pub fn next(&self) -> String {
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        let ms = Utc::now().timestamp_millis() as u64 & 0xffff_ffff_ffff;
+        // the counter makes ids unique, the hash makes them hard to guess
+        let random = self.random_bits(count);
+        let high = (ms << 16) | 0x7000 | (random >> 52);
+        let low = (0b10 << 62) | ((count & 0xffff_ffff) << 30) | (random & 0x3fff_ffff);
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xffff,
+            high & 0xffff,
+            low >> 48,
+            low & 0xffff_ffff_ffff
+        )
+    }
+}
+
+/// Whether a client supplied id can be logged and echoed as is.
+fn is_valid(id: &HeaderValue) -> bool {
+    let id = id.as_bytes();
+    !id.is_empty() && id.len() <= MAX_LENGTH && id.iter().all(|b| b.is_ascii_graphic())
+}
+
+#[derive(Clone, Default)]
+pub struct RequestIdLayer {
+    generator: Generator,
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestId<S>;
+
+    fn layer(&self, inner: S) -> RequestId<S> {
+        RequestId {
+            inner,
+            generator: self.generator.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestId<S> {
+    inner: S,
+    generator: Generator,
+}
+
+/// Adds the id to a JSON object body, leaving any other body untouched.
+fn with_request_id(body: Bytes, id: &str) -> Bytes {
+    match serde_json::from_slice::<Value>(&body) {
+        Ok(Value::Object(mut object)) => {
+            object.insert("request_id".to_string(), Value::from(id));
+            Bytes::from(serde_json::to_vec(&object).unwrap())
+        }
+        _ => body,
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RequestId<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        let id = match request.headers().get(REQUEST_ID) {
+            Some(id) if is_valid(id) => id.clone(),
+            _ => {
+                let id = HeaderValue::from_str(&self.generator.next()).unwrap();
+                request.headers_mut().insert(REQUEST_ID, id.clone());
+                id
+            }
+        };
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await?;
+            let is_json = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .is_some_and(|kind| kind.as_bytes().starts_with(b"application/json"));
+
+            let mut response = if response.status().is_success() || !is_json {
+                response.map(Body::new)
+            } else {
+                let (mut parts, body) = response.into_parts();
+                let body = match to_bytes(Body::new(body), usize::MAX).await {
+                    Ok(body) => with_request_id(body, id.to_str().unwrap_or_default()),
+                    Err(_) => Bytes::new(),
+                };
+                parts.headers.remove(header::CONTENT_LENGTH);
+                Response::from_parts(parts, Body::from(body))
+            };
+            response.headers_mut().insert(REQUEST_ID, id);
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_ids() {
+        let generator = Generator::default();
+        let first = generator.next();
+        let second = generator.next();
+        assert_ne!(first, second);
+        assert_eq!(first.len(), 36);
+        // version 7, RFC 4122 variant
+        assert_eq!(&first[14..15], "7");
+        assert!("89ab".contains(&first[19..20]));
+    }
+
+    #[test]
+    fn client_ids() {
+        assert!(is_valid(&HeaderValue::from_static("req-42")));
+        assert!(!is_valid(&HeaderValue::from_static("")));
+        assert!(!is_valid(&HeaderValue::from_static("with space")));
+        assert!(!is_valid(&HeaderValue::from_str(&"x".repeat(200)).unwrap()));
+    }
+
+    #[test]
+    fn error_bodies() {
+        let body = with_request_id(Bytes::from(r#"{"error":"Invalid Date"}"#), "req-42");
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["request_id"], "req-42");
+        assert_eq!(with_request_id(Bytes::from("oops"), "req-42"), "oops");
+    }
+}
@@ -0,0 +1,401 @@
+//! Expansion of a subset of RFC 5545 recurrence rules.
+//!
+//! Supported parts are `FREQ` (`DAILY`, `WEEKLY`, `MONTHLY`, `YEARLY`), `INTERVAL`, `COUNT`,
+//! `UNTIL`, `BYMONTH`, `BYMONTHDAY` and `BYDAY`. Ordinal weekdays such as `1MO` or `-1FR`
+//! are resolved within the month, weeks always start on Monday.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::calendar::{add_months, checked_days, days_in_month};
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::timezone::parse_tz;
+
+/// Upper bound on the number of periods walked, so that rules which never match stop.
+const MAX_PERIODS: u32 = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<NaiveDateTime>,
+    months: Vec<u32>,
+    month_days: Vec<i32>,
+    /// Weekdays, with an optional ordinal within the month.
+    weekdays: Vec<(Option<i32>, Weekday)>,
+}
+
+impl Rule {
+    pub fn parse(rule: &str) -> Option<Rule> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+
+        let mut frequency = None;
+        let mut parsed = Rule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            months: Vec::new(),
+            month_days: Vec::new(),
+            weekdays: Vec::new(),
+        };
+
+        for part in rule.split(';') {
+            let (name, value) = part.split_once('=')?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => parsed.interval = value.parse().ok().filter(|i| *i > 0)?,
+                "COUNT" => parsed.count = Some(value.parse().ok()?),
+                "UNTIL" => parsed.until = Some(parse_datetime(value)?),
+                "BYMONTH" => {
+                    parsed.months = parse_list(value, |month| (1..=12).contains(month))?
+                        .into_iter()
+                        .map(|month| month as u32)
+                        .collect()
+                }
+                "BYMONTHDAY" => {
+                    parsed.month_days =
+                        parse_list(value, |day| *day != 0 && (-31..=31).contains(day))?
+                }
+                "BYDAY" => {
+                    parsed.weekdays = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Option<Vec<_>>>()?
+                }
+                // week start is always Monday
+                "WKST" => {}
+                _ => return None,
+            }
+        }
+
+        parsed.frequency = frequency?;
+        Some(parsed)
+    }
+
+    /// Expands all occurrences of the rule starting at `start`, stopping once `end` is passed.
+    pub fn occurrences(&self, start: NaiveDateTime, end: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let mut occurrences = Vec::new();
+        let time = start.time();
+
+        for period in 0..MAX_PERIODS {
+            let dates = i64::from(period)
+                .checked_mul(i64::from(self.interval))
+                .and_then(|step| self.candidates(start.date(), step));
+            let mut dates = match dates {
+                Some(dates) => dates,
+                // the period is past the supported range, and so are the next ones
+                None => break,
+            };
+            dates.sort();
+            dates.dedup();
+
+            for date in dates {
+                if !self.matches(date) {
+                    continue;
+                }
+                let occurrence = date.and_time(time);
+                if occurrence < start {
+                    continue;
+                }
+                let done = occurrence > end
+                    || self.until.is_some_and(|until| occurrence > until)
+                    || self.count.is_some_and(|count| occurrences.len() >= count);
+                if done {
+                    return occurrences;
+                }
+                occurrences.push(occurrence);
+            }
+        }
+
+        occurrences
+    }
+
+    /// Candidate days of the period `step` units of the frequency after `start`, or `None`
+    /// when it is out of the supported range.
+    fn candidates(&self, start: NaiveDate, step: i64) -> Option<Vec<NaiveDate>> {
+        let dates = match self.frequency {
+            Frequency::Daily => vec![start.checked_add_signed(checked_days(step)?)?],
+            Frequency::Weekly => {
+                let weekday = i64::from(start.weekday().num_days_from_monday());
+                let monday = start.checked_sub_signed(Duration::days(weekday))?;
+                let week = monday.checked_add_signed(checked_days(step.checked_mul(7)?)?)?;
+                if self.weekdays.is_empty() {
+                    vec![week.checked_add_signed(Duration::days(weekday))?]
+                } else {
+                    self.weekdays
+                        .iter()
+                        .filter_map(|(_, day)| {
+                            let day = i64::from(day.num_days_from_monday());
+                            week.checked_add_signed(Duration::days(day))
+                        })
+                        .collect()
+                }
+            }
+            Frequency::Monthly => {
+                let month = add_months(start.with_day(1).unwrap(), i32::try_from(step).ok()?)?;
+                self.expand_month(start, month.year(), month.month())
+            }
+            Frequency::Yearly => {
+                let year = start.year().checked_add(i32::try_from(step).ok()?)?;
+                NaiveDate::from_ymd_opt(year, 1, 1)?;
+                let months = if self.months.is_empty() {
+                    vec![start.month()]
+                } else {
+                    self.months.clone()
+                };
+                months
+                    .iter()
+                    .flat_map(|month| self.expand_month(start, year, *month))
+                    .collect()
+            }
+        };
+        Some(dates)
+    }
+
+    /// Candidate days of a month, as selected by `BYMONTHDAY` and `BYDAY`.
+    fn expand_month(&self, start: NaiveDate, year: i32, month: u32) -> Vec<NaiveDate> {
+        let length = days_in_month(year, month) as i32;
+        let days: Vec<i32> = if !self.month_days.is_empty() {
+            self.month_days
+                .iter()
+                .map(|day| if *day < 0 { length + day + 1 } else { *day })
+                .collect()
+        } else if !self.weekdays.is_empty() {
+            (1..=length).collect()
+        } else {
+            vec![start.day() as i32]
+        };
+
+        days.into_iter()
+            .filter(|day| (1..=length).contains(day))
+            .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day as u32))
+            .filter(|date| self.weekdays.is_empty() || self.matches_weekday(*date))
+            .collect()
+    }
+
+    fn matches_weekday(&self, date: NaiveDate) -> bool {
+        let length = days_in_month(date.year(), date.month());
+        let nth = (date.day() as i32 - 1) / 7 + 1;
+        let nth_last = -((length - date.day()) as i32 / 7 + 1);
+
+        self.weekdays.iter().any(|(ordinal, weekday)| {
+            *weekday == date.weekday()
+                && ordinal.is_none_or(|ordinal| ordinal == nth || ordinal == nth_last)
+        })
+    }
+
+    /// Filters applied to every frequency once the candidates have been expanded.
+    fn matches(&self, date: NaiveDate) -> bool {
+        let month = self.months.is_empty() || self.months.contains(&date.month());
+        let weekday = match self.frequency {
+            // monthly and yearly candidates are already filtered by ordinal weekdays
+            Frequency::Daily => self.weekdays.is_empty() || self.matches_weekday(date),
+            _ => true,
+        };
+        let month_day = match self.frequency {
+            Frequency::Daily | Frequency::Weekly => {
+                let length = days_in_month(date.year(), date.month()) as i32;
+                self.month_days.is_empty()
+                    || self.month_days.iter().any(|day| {
+                        let day = if *day < 0 { length + day + 1 } else { *day };
+                        day == date.day() as i32
+                    })
+            }
+            _ => true,
+        };
+        month && weekday && month_day
+    }
+}
+
+fn parse_list<F: Fn(&i32) -> bool>(value: &str, valid: F) -> Option<Vec<i32>> {
+    value
+        .split(',')
+        .map(|item| item.parse().ok().filter(&valid))
+        .collect()
+}
+
+fn parse_weekday(value: &str) -> Option<(Option<i32>, Weekday)> {
+    let split = value.len().checked_sub(2)?;
+    let (ordinal, day) = value.split_at(split);
+    let day = match day.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let ordinal = match ordinal {
+        "" => None,
+        ordinal => Some(
+            ordinal
+                .trim_start_matches('+')
+                .parse()
+                .ok()
+                .filter(|o: &i32| *o != 0)?,
+        ),
+    };
+    Some((ordinal, day))
+}
+
+/// Parses the basic (`20161225T000000Z`) or extended (`2016-12-25T00:00:00`) form of a
+/// local date-time, or a bare date. A trailing `Z` is accepted and ignored.
+fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim_end_matches('Z');
+    ["%Y%m%dT%H%M%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            ["%Y%m%d", "%Y-%m-%d"]
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+        })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RruleRequest {
+    rrule: String,
+    dtstart: String,
+    /// Window of interest; defaults to starting at `dtstart`.
+    from: Option<String>,
+    to: String,
+    /// Timezone the local times are expressed in, UTC by default.
+    tz: Option<String>,
+}
+
+pub async fn rrule_handler(Json(request): Json<RruleRequest>) -> Result<Json<Value>, AppError> {
+    let rule = Rule::parse(&request.rrule)
+        .ok_or_else(|| AppError::BadRequest("Invalid RRULE".to_string()))?;
+    let tz = parse_tz(request.tz.as_deref())?;
+    let start = parse_datetime(&request.dtstart).ok_or(AppError::InvalidDate)?;
+    let from = match request.from.as_deref() {
+        Some(from) => parse_datetime(from).ok_or(AppError::InvalidDate)?,
+        None => start,
+    };
+    let to = parse_datetime(&request.to).ok_or(AppError::InvalidDate)?;
+
+    let occurrences: Vec<Value> = rule
+        .occurrences(start, to)
+        .into_iter()
+        .filter(|occurrence| *occurrence >= from)
+        // wall-clock times skipped by DST have no instant
+        .filter_map(|occurrence| tz.from_local_datetime(&occurrence).earliest())
+        .map(|occurrence| {
+            let occurrence = occurrence.with_timezone(&Utc);
+            json!({
+                "unix": occurrence.timestamp(),
+                "utc": occurrence.to_rfc2822(),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "rrule": request.rrule,
+        "tz": tz.name(),
+        "occurrences": occurrences,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap()
+    }
+
+    fn expand(rule: &str, start: NaiveDateTime) -> Vec<NaiveDateTime> {
+        Rule::parse(rule)
+            .unwrap()
+            .occurrences(start, at(2030, 1, 1))
+    }
+
+    #[test]
+    fn huge_intervals() {
+        for frequency in &["DAILY", "WEEKLY", "MONTHLY", "YEARLY"] {
+            for interval in &[100_000_000u32, 300_000, u32::MAX] {
+                let rule = format!("FREQ={};INTERVAL={}", frequency, interval);
+                let start = at(2021, 8, 18);
+                let occurrences = Rule::parse(&rule)
+                    .unwrap()
+                    .occurrences(start, NaiveDate::MAX.and_hms_opt(0, 0, 0).unwrap());
+                assert_eq!(occurrences[0], start, "{}", rule);
+            }
+        }
+    }
+
+    #[test]
+    fn weekly_by_day() {
+        assert_eq!(
+            expand("FREQ=WEEKLY;BYDAY=MO,WE;COUNT=4", at(2021, 8, 18)),
+            vec![
+                at(2021, 8, 18),
+                at(2021, 8, 23),
+                at(2021, 8, 25),
+                at(2021, 8, 30)
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_last_friday() {
+        assert_eq!(
+            expand("RRULE:FREQ=MONTHLY;BYDAY=-1FR;COUNT=3", at(2021, 8, 1)),
+            vec![at(2021, 8, 27), at(2021, 9, 24), at(2021, 10, 29)]
+        );
+    }
+
+    #[test]
+    fn monthly_skips_short_months() {
+        assert_eq!(
+            expand("FREQ=MONTHLY;COUNT=3", at(2021, 1, 31)),
+            vec![at(2021, 1, 31), at(2021, 3, 31), at(2021, 5, 31)]
+        );
+    }
+
+    #[test]
+    fn yearly_until() {
+        assert_eq!(
+            expand(
+                "FREQ=YEARLY;INTERVAL=2;UNTIL=20250101T000000Z",
+                at(2020, 2, 29)
+            ),
+            vec![at(2020, 2, 29), at(2024, 2, 29)]
+        );
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert!(Rule::parse("INTERVAL=2").is_none());
+        assert!(Rule::parse("FREQ=SECONDLY").is_none());
+        assert!(Rule::parse("FREQ=DAILY;BYDAY=XX").is_none());
+    }
+}
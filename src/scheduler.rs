@@ -0,0 +1,592 @@
+//! Webhooks fired at a requested instant.
+//!
+//! Jobs are kept in memory and delivered by a background worker, which sleeps until the
+//! earliest pending job is due. Failed deliveries are retried with an exponential
+//! backoff. Finished jobs are kept for a day after they were due, so that their outcome
+//! can be checked, then forgotten. Every change to a job goes through a [`Persistence`]
+//! hook, so that a durable backend can restore the queue after a restart.
+//!
+//! Callbacks can't target the host itself or private networks: hosts are checked when
+//! resolved at delivery, so that a name can't be pointed at an internal service after
+//! the job was created.
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{Method, Request, Uri};
+use chrono::{DateTime, Duration, Utc};
+use http_body_util::Full;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::dns::{GaiResolver, Name};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::sync::Notify;
+use tower::Service;
+
+use crate::error::AppError;
+use crate::extract::{Json, Path};
+use crate::parse_date_at;
+
+/// Upper bound on the number of jobs waiting to be delivered.
+const MAX_PENDING: usize = 10_000;
+/// Upper bound on the number of finished jobs kept, the oldest being forgotten first.
+const MAX_FINISHED: usize = 10_000;
+/// How long finished jobs are kept after they were due.
+const RETENTION_HOURS: i64 = 24;
+/// How long a callback may take before the attempt counts as failed.
+const DELIVERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pending,
+    Delivering,
+    Delivered,
+    Failed,
+}
+
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: String,
+    pub at: DateTime<Utc>,
+    pub url: String,
+    pub payload: Value,
+    pub status: Status,
+    pub attempts: u32,
+    /// When the job is next delivered, for pending jobs only.
+    pub next_attempt: Option<DateTime<Utc>>,
+}
+
+/// How failed deliveries are retried: up to `max_attempts` attempts in total, waiting
+/// `initial_backoff` after the first failure and doubling the wait after each one.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::seconds(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before the next attempt, after `attempts` failed ones.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        self.initial_backoff * 2i32.saturating_pow(attempts.saturating_sub(1))
+    }
+}
+
+/// Hook called on every change to a job. The default keeps nothing.
+pub trait Persistence: Send + Sync {
+    fn save(&self, _job: &Job) {}
+    fn remove(&self, _id: &str) {}
+    /// Jobs to restore when the scheduler starts.
+    fn load(&self) -> Vec<Job> {
+        Vec::new()
+    }
+}
+
+pub struct NoPersistence;
+
+impl Persistence for NoPersistence {}
+
+struct Inner {
+    jobs: Mutex<HashMap<String, Job>>,
+    next_id: AtomicU64,
+    wake: Notify,
+    policy: RetryPolicy,
+    persistence: Box<dyn Persistence>,
+    client: Client<HttpsConnector<HttpConnector<PublicResolver>>, Full<Bytes>>,
+}
+
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<Inner>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new(RetryPolicy::default(), Box::new(NoPersistence))
+    }
+}
+
+impl Scheduler {
+    pub fn new(policy: RetryPolicy, persistence: Box<dyn Persistence>) -> Self {
+        let mut jobs = HashMap::new();
+        for mut job in persistence.load() {
+            // A delivery interrupted by the restart is attempted again
+            if job.status == Status::Delivering {
+                job.status = Status::Pending;
+                job.next_attempt = Some(Utc::now());
+            }
+            jobs.insert(job.id.clone(), job);
+        }
+        let next_id = jobs
+            .keys()
+            .filter_map(|id| id.parse::<u64>().ok())
+            .max()
+            .map_or(0, |id| id + 1);
+        let mut http = HttpConnector::new_with_resolver(PublicResolver(GaiResolver::new()));
+        http.enforce_http(false);
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(http);
+
+        Scheduler {
+            inner: Arc::new(Inner {
+                jobs: Mutex::new(jobs),
+                next_id: AtomicU64::new(next_id),
+                wake: Notify::new(),
+                policy,
+                persistence,
+                client: Client::builder(TokioExecutor::new()).build(connector),
+            }),
+        }
+    }
+
+    /// Delivers jobs as they become due, for as long as the process runs.
+    pub fn spawn_worker(&self) {
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            loop {
+                for job in scheduler.take_due(Utc::now()) {
+                    let scheduler = scheduler.clone();
+                    tokio::spawn(async move { scheduler.deliver(job).await });
+                }
+
+                let next = scheduler.next_due();
+                match next.map(|next| (next - Utc::now()).to_std()) {
+                    Some(Ok(wait)) => {
+                        tokio::select! {
+                            _ = tokio::time::sleep(wait) => {}
+                            _ = scheduler.inner.wake.notified() => {}
+                        }
+                    }
+                    // Already due
+                    Some(Err(_)) => {}
+                    None => scheduler.inner.wake.notified().await,
+                }
+            }
+        });
+    }
+
+    fn next_due(&self) -> Option<DateTime<Utc>> {
+        let jobs = self.inner.jobs.lock().unwrap();
+        jobs.values().filter_map(|job| job.next_attempt).min()
+    }
+
+    /// Marks the jobs due at `now` as being delivered and returns them.
+    fn take_due(&self, now: DateTime<Utc>) -> Vec<Job> {
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        let mut due = Vec::new();
+        for job in jobs.values_mut() {
+            if job.next_attempt.is_some_and(|next| next <= now) {
+                job.status = Status::Delivering;
+                job.next_attempt = None;
+                self.inner.persistence.save(job);
+                due.push(job.clone());
+            }
+        }
+        due
+    }
+
+    async fn deliver(&self, job: Job) {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(&job.url)
+            .header("content-type", "application/json")
+            .header("x-schedule-id", &job.id)
+            .body(Full::from(job.payload.to_string()))
+            .expect("The callback URL was validated on creation");
+        let delivered = match tokio::time::timeout(
+            DELIVERY_TIMEOUT,
+            self.inner.client.request(request),
+        )
+        .await
+        {
+            Ok(Ok(response)) if response.status().is_success() => true,
+            Ok(Ok(response)) => {
+                tracing::warn!("Webhook {} answered {}", job.id, response.status());
+                false
+            }
+            Ok(Err(e)) => {
+                tracing::warn!("Webhook {} failed: {}", job.id, e);
+                false
+            }
+            Err(_) => {
+                tracing::warn!("Webhook {} timed out", job.id);
+                false
+            }
+        };
+        self.record_attempt(&job.id, delivered, Utc::now());
+    }
+
+    fn record_attempt(&self, id: &str, delivered: bool, now: DateTime<Utc>) {
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        // The job may have been cancelled meanwhile
+        let job = match jobs.get_mut(id) {
+            Some(job) => job,
+            None => return,
+        };
+        job.attempts += 1;
+        if delivered {
+            job.status = Status::Delivered;
+        } else if job.attempts >= self.inner.policy.max_attempts {
+            job.status = Status::Failed;
+        } else {
+            job.status = Status::Pending;
+            job.next_attempt = Some(now + self.inner.policy.backoff(job.attempts));
+        }
+        self.inner.persistence.save(job);
+        self.prune(&mut jobs, now);
+        drop(jobs);
+        self.inner.wake.notify_one();
+    }
+
+    /// Forgets the finished jobs past their retention, and the oldest ones over
+    /// `MAX_FINISHED`.
+    fn prune(&self, jobs: &mut HashMap<String, Job>, now: DateTime<Utc>) {
+        let mut finished: Vec<(DateTime<Utc>, String)> = jobs
+            .values()
+            .filter(|job| matches!(job.status, Status::Delivered | Status::Failed))
+            .map(|job| (job.at, job.id.clone()))
+            .collect();
+        finished.sort();
+        let retained_after = now - Duration::hours(RETENTION_HOURS);
+        let expired = finished
+            .iter()
+            .take_while(|(at, _)| *at < retained_after)
+            .count();
+        let excess = finished.len().saturating_sub(MAX_FINISHED);
+        for (_, id) in finished.iter().take(expired.max(excess)) {
+            jobs.remove(id);
+            self.inner.persistence.remove(id);
+        }
+    }
+
+    fn schedule(&self, at: DateTime<Utc>, url: String, payload: Value) -> Result<Job, AppError> {
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        self.prune(&mut jobs, Utc::now());
+        let pending = jobs
+            .values()
+            .filter(|job| job.status == Status::Pending)
+            .count();
+        if pending >= MAX_PENDING {
+            return Err(AppError::Unprocessable(
+                "Too many pending webhooks".to_string(),
+            ));
+        }
+        let job = Job {
+            id: self
+                .inner
+                .next_id
+                .fetch_add(1, Ordering::Relaxed)
+                .to_string(),
+            at,
+            url,
+            payload,
+            status: Status::Pending,
+            attempts: 0,
+            next_attempt: Some(at),
+        };
+        self.inner.persistence.save(&job);
+        jobs.insert(job.id.clone(), job.clone());
+        drop(jobs);
+        self.inner.wake.notify_one();
+        Ok(job)
+    }
+}
+
+/// Whether `ip` belongs to the public internet, rather than to the host itself, a private
+/// or link-local network (cloud metadata services included) or a reserved range.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) if ip.is_loopback() || ip.is_unspecified() => false,
+        IpAddr::V6(ip) => match ip.to_ipv4().or_else(|| nat64(ip)) {
+            Some(ip) => is_public_v4(ip),
+            // unique local fc00::/7 and link-local fe80::/10
+            None => ip.segments()[0] & 0xfe00 != 0xfc00 && ip.segments()[0] & 0xffc0 != 0xfe80,
+        },
+    }
+}
+
+/// The IPv4 address translated by the well-known NAT64 prefix, 64:ff9b::/96.
+fn nat64(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    match ip.segments() {
+        [0x64, 0xff9b, 0, 0, 0, 0, high, low] => {
+            Some(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)))
+        }
+        _ => None,
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [first, second, third, _] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || first == 0
+        // shared address space of carrier-grade NAT, 100.64.0.0/10
+        || (first == 100 && second & 0xc0 == 64)
+        // IETF protocol assignments, 192.0.0.0/24
+        || (first == 192 && second == 0 && third == 0)
+        // benchmarking, 198.18.0.0/15
+        || (first == 198 && second & 0xfe == 18)
+        // reserved for future use, 240.0.0.0/4
+        || first >= 240)
+}
+
+/// Resolves callback hosts with the system resolver, keeping public addresses only.
+#[derive(Clone)]
+struct PublicResolver(GaiResolver);
+
+impl Service<Name> for PublicResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolving = self.0.call(name);
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = resolving
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "The callback host has no public address",
+                ));
+            }
+            Ok(addrs.into_iter())
+        })
+    }
+}
+
+/// Checks that `url` is an absolute http or https URL. Hosts given as IP addresses, which
+/// are never resolved, must be public.
+fn validate_url(url: &str) -> Result<(), AppError> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|_| AppError::BadRequest(format!("Invalid callback URL {}", url)))?;
+    let host = match (uri.scheme_str(), uri.host()) {
+        (Some("http"), Some(host)) | (Some("https"), Some(host)) => host,
+        _ => {
+            return Err(AppError::BadRequest(
+                "The callback URL must be an absolute http or https URL".to_string(),
+            ))
+        }
+    };
+    let ip = host.trim_start_matches('[').trim_end_matches(']').parse();
+    if ip.is_ok_and(|ip| !is_public(ip)) {
+        return Err(AppError::BadRequest(
+            "The callback URL must not target a private address".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn render(job: &Job) -> Value {
+    json!({
+        "id": job.id,
+        "at": {
+            "unix": job.at.timestamp(),
+            "utc": job.at.to_rfc2822(),
+        },
+        "url": job.url,
+        "payload": job.payload,
+        "status": job.status,
+        "attempts": job.attempts,
+        "next_attempt": job.next_attempt.map(|next| next.timestamp()),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleRequest {
+    at: String,
+    url: String,
+    #[serde(default)]
+    payload: Value,
+}
+
+pub async fn create_handler(
+    State(scheduler): State<Scheduler>,
+    Json(request): Json<ScheduleRequest>,
+) -> Result<Json<Value>, AppError> {
+    let now = Utc::now();
+    let at = parse_date_at(&request.at, now)?;
+    if at < now {
+        return Err(AppError::BadRequest("The date is in the past".to_string()));
+    }
+    validate_url(&request.url)?;
+    let job = scheduler.schedule(at, request.url, request.payload)?;
+
+    Ok(Json(render(&job)))
+}
+
+pub async fn get_handler(
+    Path(id): Path<String>,
+    State(scheduler): State<Scheduler>,
+) -> Result<Json<Value>, AppError> {
+    let jobs = scheduler.inner.jobs.lock().unwrap();
+    let job = jobs
+        .get(&id)
+        .ok_or_else(|| AppError::NotFound("Unknown webhook".to_string()))?;
+
+    Ok(Json(render(job)))
+}
+
+/// All known jobs, finished ones within their retention, in the order they are due.
+pub async fn list_handler(State(scheduler): State<Scheduler>) -> Json<Value> {
+    let jobs = scheduler.inner.jobs.lock().unwrap();
+    let mut jobs: Vec<&Job> = jobs.values().collect();
+    jobs.sort_by_key(|job| job.at);
+    let jobs: Vec<Value> = jobs.into_iter().map(render).collect();
+
+    Json(json!({ "webhooks": jobs }))
+}
+
+/// Cancels a pending job. Jobs being delivered, or already done, can't be cancelled.
+pub async fn cancel_handler(
+    Path(id): Path<String>,
+    State(scheduler): State<Scheduler>,
+) -> Result<Json<Value>, AppError> {
+    let mut jobs = scheduler.inner.jobs.lock().unwrap();
+    match jobs.get(&id).map(|job| job.status) {
+        None => return Err(AppError::NotFound("Unknown webhook".to_string())),
+        Some(Status::Pending) => {}
+        Some(_) => {
+            return Err(AppError::Unprocessable(
+                "Only pending webhooks can be cancelled".to_string(),
+            ))
+        }
+    }
+    let job = jobs.remove(&id).unwrap();
+    scheduler.inner.persistence.remove(&id);
+
+    Ok(Json(render(&job)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::seconds(10));
+        assert_eq!(policy.backoff(3), Duration::seconds(40));
+    }
+
+    #[test]
+    fn callback_urls() {
+        assert!(validate_url("https://example.com/hook").is_ok());
+        assert!(validate_url("ftp://example.com/hook").is_err());
+        assert!(validate_url("/hook").is_err());
+        assert!(validate_url("http://8.8.8.8/hook").is_ok());
+        assert!(validate_url("http://[2001:4860:4860::8888]/hook").is_ok());
+        for private in &[
+            "http://127.0.0.1/hook",
+            "http://10.1.2.3/hook",
+            "http://192.168.0.1:8080/hook",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://100.64.0.1/hook",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[fe80::1]/hook",
+        ] {
+            assert!(validate_url(private).is_err(), "{}", private);
+        }
+    }
+
+    #[test]
+    fn reserved_ranges() {
+        for public in &["8.8.8.8", "192.0.1.1", "198.20.0.1", "223.255.255.1"] {
+            assert!(is_public(public.parse().unwrap()), "{}", public);
+        }
+        for reserved in &[
+            "192.0.0.8",
+            "192.0.0.255",
+            "198.18.0.1",
+            "198.19.255.255",
+            "240.0.0.1",
+            "255.255.255.254",
+        ] {
+            assert!(!is_public(reserved.parse().unwrap()), "{}", reserved);
+        }
+
+        assert!(is_public("64:ff9b::808:808".parse().unwrap()));
+        for translated in &["64:ff9b::a00:1", "64:ff9b::7f00:1", "64:ff9b::a9fe:a9fe"] {
+            assert!(!is_public(translated.parse().unwrap()), "{}", translated);
+        }
+    }
+
+    #[test]
+    fn retries_until_exhausted() {
+        let scheduler = Scheduler::new(
+            RetryPolicy {
+                max_attempts: 2,
+                initial_backoff: Duration::seconds(5),
+            },
+            Box::new(NoPersistence),
+        );
+        let now = Utc::now();
+        let job = scheduler
+            .schedule(now, "http://localhost/hook".to_string(), Value::Null)
+            .unwrap();
+
+        assert_eq!(scheduler.take_due(now).len(), 1);
+        assert!(scheduler.take_due(now).is_empty());
+        scheduler.record_attempt(&job.id, false, now);
+        assert_eq!(scheduler.next_due(), Some(now + Duration::seconds(5)));
+
+        scheduler.take_due(now + Duration::seconds(5));
+        scheduler.record_attempt(&job.id, false, now);
+        let jobs = scheduler.inner.jobs.lock().unwrap();
+        assert_eq!(jobs[&job.id].status, Status::Failed);
+        assert_eq!(jobs[&job.id].next_attempt, None);
+    }
+
+    #[test]
+    fn finished_jobs_are_forgotten() {
+        let scheduler = Scheduler::default();
+        let now = Utc::now();
+        let job = scheduler
+            .schedule(now, "http://localhost/hook".to_string(), Value::Null)
+            .unwrap();
+        scheduler.take_due(now);
+        scheduler.record_attempt(&job.id, true, now);
+        assert!(scheduler.inner.jobs.lock().unwrap().contains_key(&job.id));
+
+        let mut jobs = scheduler.inner.jobs.lock().unwrap();
+        scheduler.prune(&mut jobs, now + Duration::hours(RETENTION_HOURS + 1));
+        assert!(jobs.is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+//! Hardening headers for a service exposed to browsers without a proxy adding them.
+//!
+//! Every response gets `X-Content-Type-Options: nosniff`, the configured
+//! `Referrer-Policy` and, unless its max age is 0, `Strict-Transport-Security`. HTML
+//! responses also get the configured `Content-Security-Policy`, which would be pointless
+//! on JSON. Headers already set by a handler are left alone.
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::{header, HeaderValue, Request, Response};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{BoxError, Layer, Service};
+
+use crate::config::SecurityHeadersConfig;
+
+/// Header values, validated once.
+#[derive(Clone, Debug, Default)]
+struct Values {
+    hsts: Option<HeaderValue>,
+    referrer_policy: Option<HeaderValue>,
+    content_security_policy: Option<HeaderValue>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SecurityHeadersLayer {
+    /// `None` when the headers are disabled.
+    values: Option<Values>,
+}
+
+impl SecurityHeadersLayer {
+    pub fn new(config: &SecurityHeadersConfig) -> Result<SecurityHeadersLayer, String> {
+        if !config.enabled {
+            return Ok(SecurityHeadersLayer::default());
+        }
+        let value = |name: &str, value: &str| {
+            HeaderValue::from_str(value).map_err(|_| format!("Invalid {} {}", name, value))
+        };
+        let hsts = match config.hsts_max_age_secs {
+            0 => None,
+            max_age => Some(value(
+                "hsts_max_age_secs",
+                &format!("max-age={}; includeSubDomains", max_age),
+            )?),
+        };
+        let non_empty = |name: &str, setting: &str| match setting {
+            "" => Ok(None),
+            setting => value(name, setting).map(Some),
+        };
+        Ok(SecurityHeadersLayer {
+            values: Some(Values {
+                hsts,
+                referrer_policy: non_empty("referrer_policy", &config.referrer_policy)?,
+                content_security_policy: non_empty(
+                    "content_security_policy",
+                    &config.content_security_policy,
+                )?,
+            }),
+        })
+    }
+}
+
+impl<S> Layer<S> for SecurityHeadersLayer {
+    type Service = SecurityHeaders<S>;
+
+    fn layer(&self, inner: S) -> SecurityHeaders<S> {
+        SecurityHeaders {
+            inner,
+            values: self.values.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders<S> {
+    inner: S,
+    values: Option<Values>,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for SecurityHeaders<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let values = self.values.clone();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = response.await?.map(Body::new);
+            let values = match values {
+                Some(values) => values,
+                None => return Ok(response),
+            };
+            let is_html = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .is_some_and(|kind| kind.as_bytes().starts_with(b"text/html"));
+            let headers = response.headers_mut();
+            headers
+                .entry(header::X_CONTENT_TYPE_OPTIONS)
+                .or_insert(HeaderValue::from_static("nosniff"));
+            if let Some(hsts) = values.hsts {
+                headers
+                    .entry(header::STRICT_TRANSPORT_SECURITY)
+                    .or_insert(hsts);
+            }
+            if let Some(policy) = values.referrer_policy {
+                headers.entry(header::REFERRER_POLICY).or_insert(policy);
+            }
+            if let (true, Some(policy)) = (is_html, values.content_security_policy) {
+                headers
+                    .entry(header::CONTENT_SECURITY_POLICY)
+                    .or_insert(policy);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_values() {
+        let layer = SecurityHeadersLayer::new(&SecurityHeadersConfig::default()).unwrap();
+        let values = layer.values.unwrap();
+        assert_eq!(values.hsts.unwrap(), "max-age=31536000; includeSubDomains");
+
+        let config = SecurityHeadersConfig {
+            hsts_max_age_secs: 0,
+            referrer_policy: String::new(),
+            ..SecurityHeadersConfig::default()
+        };
+        let values = SecurityHeadersLayer::new(&config).unwrap().values.unwrap();
+        assert!(values.hsts.is_none() && values.referrer_policy.is_none());
+
+        let config = SecurityHeadersConfig {
+            content_security_policy: "default-src\n'self'".to_string(),
+            ..SecurityHeadersConfig::default()
+        };
+        assert!(SecurityHeadersLayer::new(&config).is_err());
+    }
+}
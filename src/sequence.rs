@@ -0,0 +1,73 @@
+use axum::extract::State;
+use axum::Json;
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Bits of the identifier reserved to the logical counter.
+const COUNTER_BITS: u32 = 16;
+const MAX_COUNTER: u32 = (1 << COUNTER_BITS) - 1;
+
+/// Hands out strictly increasing `(milliseconds, counter)` pairs, even when called
+/// several times within the same millisecond or when the wall clock goes backwards.
+#[derive(Clone, Default)]
+pub struct Sequencer {
+    last: Arc<Mutex<(i64, u32)>>,
+}
+
+impl Sequencer {
+    pub fn next(&self, now_ms: i64) -> (i64, u32) {
+        let mut last = self.last.lock().unwrap();
+        let (last_ms, counter) = *last;
+        *last = if now_ms > last_ms {
+            (now_ms, 0)
+        } else if counter < MAX_COUNTER {
+            (last_ms, counter + 1)
+        } else {
+            // the counter is exhausted, borrow the next millisecond
+            (last_ms + 1, 0)
+        };
+        *last
+    }
+}
+
+/// Packs the pair in a single integer, which preserves their ordering.
+pub fn pack(ms: i64, counter: u32) -> i64 {
+    (ms << COUNTER_BITS) | counter as i64
+}
+
+pub async fn monotonic_id_handler(State(sequencer): State<Sequencer>) -> Json<Value> {
+    let (ms, counter) = sequencer.next(Utc::now().timestamp_millis());
+
+    Json(json!({
+        "unix_ms": ms,
+        "counter": counter,
+        "id": pack(ms, counter),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strictly_increasing() {
+        let sequencer = Sequencer::default();
+        assert_eq!(sequencer.next(1000), (1000, 0));
+        assert_eq!(sequencer.next(1000), (1000, 1));
+        // the clock going backwards doesn't break ordering
+        assert_eq!(sequencer.next(990), (1000, 2));
+        assert_eq!(sequencer.next(1001), (1001, 0));
+        assert!(pack(1000, 2) < pack(1001, 0));
+    }
+
+    #[test]
+    fn counter_overflow() {
+        let sequencer = Sequencer::default();
+        sequencer.next(1000);
+        for _ in 0..MAX_COUNTER {
+            sequencer.next(1000);
+        }
+        assert_eq!(sequencer.next(1000), (1001, 0));
+    }
+}
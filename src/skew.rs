@@ -0,0 +1,95 @@
+//! Estimation of the offset of a client clock from the server clock.
+//!
+//! The client time is the one it sent with its request, so the estimate also includes the
+//! time the request took to reach the server, usually negligible next to a wrong clock.
+//! The `Date` header only has whole seconds, making estimates from it up to a second off.
+
+use axum::http::{header, HeaderMap};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::parse_date;
+use crate::query::Query;
+
+#[derive(Debug, Deserialize)]
+pub struct SkewParams {
+    /// Client time, taking precedence over the `Date` header, such as `1482624000123`.
+    client_time: Option<String>,
+}
+
+/// Reads the `Date` header, an RFC 7231 date such as `Sun, 25 Dec 2016 00:00:00 GMT`.
+fn header_date(headers: &HeaderMap) -> Result<Option<DateTime<Utc>>, AppError> {
+    let date = match headers.get(header::DATE) {
+        Some(date) => date,
+        None => return Ok(None),
+    };
+    date.to_str()
+        .ok()
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        .map(|date| Some(date.with_timezone(&Utc)))
+        .ok_or_else(|| AppError::BadRequest("Invalid Date header".to_string()))
+}
+
+/// How far ahead of the server the client clock is, in milliseconds.
+pub fn skew_ms(client: DateTime<Utc>, server: DateTime<Utc>) -> i64 {
+    (client - server).num_milliseconds()
+}
+
+pub async fn skew_handler(
+    Query(params): Query<SkewParams>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let received = Utc::now();
+    let (client, source, resolution_ms) = match params.client_time {
+        Some(client_time) => (parse_date(&client_time)?, "client_time", 1),
+        None => match header_date(&headers)? {
+            Some(date) => (date, "date_header", 1000),
+            None => {
+                return Err(AppError::BadRequest(
+                    "A Date header or a client_time parameter is required".to_string(),
+                ))
+            }
+        },
+    };
+
+    Ok(Json(json!({
+        "client_ms": client.timestamp_millis(),
+        "server_ms": received.timestamp_millis(),
+        "skew_ms": skew_ms(client, received),
+        "source": source,
+        "resolution_ms": resolution_ms,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn date_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(header_date(&headers).unwrap(), None);
+
+        headers.insert(
+            header::DATE,
+            HeaderValue::from_static("Sun, 25 Dec 2016 00:00:00 GMT"),
+        );
+        let client = header_date(&headers).unwrap().unwrap();
+        assert_eq!(client, Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap());
+        assert_eq!(
+            skew_ms(
+                client,
+                Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 1).unwrap() + Duration::milliseconds(500)
+            ),
+            -1500
+        );
+
+        headers.insert(header::DATE, HeaderValue::from_static("yesterday"));
+        assert!(header_date(&headers).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! Decoding of 64-bit snowflake IDs: 41 bits of milliseconds since a platform epoch,
+//! 10 bits of worker and 12 bits of per-worker sequence.
+
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::query::Query;
+
+const TWITTER_EPOCH_MS: i64 = 1_288_834_974_657;
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+#[derive(Debug, PartialEq)]
+pub struct Snowflake {
+    pub timestamp: DateTime<Utc>,
+    pub worker: u64,
+    pub sequence: u64,
+}
+
+pub fn decode(id: u64, epoch_ms: i64) -> Option<Snowflake> {
+    let ms = (id >> 22) as i64 + epoch_ms;
+    let timestamp = DateTime::from_timestamp(
+        ms.div_euclid(1000),
+        (ms.rem_euclid(1000) * 1_000_000) as u32,
+    )?;
+    Some(Snowflake {
+        timestamp,
+        worker: (id >> 12) & 0x3ff,
+        sequence: id & 0xfff,
+    })
+}
+
+/// Resolves the `?epoch=` parameter: `twitter`, `discord` or a custom epoch in unix
+/// milliseconds.
+fn parse_epoch(epoch: &str) -> Option<i64> {
+    match epoch.to_ascii_lowercase().as_str() {
+        "twitter" => Some(TWITTER_EPOCH_MS),
+        "discord" => Some(DISCORD_EPOCH_MS),
+        custom => custom.parse().ok(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnowflakeParams {
+    /// Twitter's epoch by default.
+    epoch: Option<String>,
+}
+
+pub async fn snowflake_handler(
+    Path(id): Path<String>,
+    Query(params): Query<SnowflakeParams>,
+) -> Result<Json<Value>, AppError> {
+    let epoch_ms = match params.epoch.as_deref() {
+        Some(epoch) => parse_epoch(epoch)
+            .ok_or_else(|| AppError::BadRequest(format!("Invalid epoch {}", epoch)))?,
+        None => TWITTER_EPOCH_MS,
+    };
+    let id: u64 = id
+        .parse()
+        .map_err(|_| AppError::BadRequest("Invalid snowflake".to_string()))?;
+    let snowflake = decode(id, epoch_ms).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(json!({
+        "id": id.to_string(),
+        "epoch_ms": epoch_ms,
+        "unix": snowflake.timestamp.timestamp(),
+        "unix_ms": snowflake.timestamp.timestamp_millis(),
+        "utc": snowflake.timestamp.to_rfc2822(),
+        "worker": snowflake.worker,
+        "sequence": snowflake.sequence,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn discord_snowflake() {
+        // example from the Discord API documentation
+        let snowflake = decode(175_928_847_299_117_063, DISCORD_EPOCH_MS).unwrap();
+        assert_eq!(
+            snowflake.timestamp,
+            Utc.timestamp_millis_opt(1_462_015_105_796).unwrap()
+        );
+        // internal worker 1, process 0
+        assert_eq!(snowflake.worker, 32);
+        assert_eq!(snowflake.sequence, 7);
+    }
+
+    #[test]
+    fn epochs() {
+        assert_eq!(parse_epoch("Discord"), Some(DISCORD_EPOCH_MS));
+        assert_eq!(parse_epoch("1420070400000"), Some(DISCORD_EPOCH_MS));
+        assert_eq!(parse_epoch("yesterday"), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Chronological sorting of dates written in mixed formats, as found in logs gathered from
+//! several systems.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::{parse_date, profile};
+
+/// Longer lists are rejected.
+const MAX_DATES: usize = 10_000;
+
+/// Reads a date of any format `/api/:date` accepts without hints, RFC 3339 and RFC 2822
+/// date-times included. Numbers are unix timestamps.
+fn parse_entry(entry: &Value) -> Result<DateTime<Utc>, AppError> {
+    let date = match entry {
+        Value::String(date) => date.trim(),
+        Value::Number(timestamp) => return parse_date(&timestamp.to_string()),
+        _ => {
+            return Err(AppError::BadRequest(
+                "Dates must be strings or numbers".to_string(),
+            ))
+        }
+    };
+    let with_offset = profile::parse_rfc3339(date)
+        .or_else(|| profile::parse_iso8601(date))
+        .or_else(|| DateTime::parse_from_rfc2822(date).ok());
+    match with_offset {
+        Some(date) => Ok(date.with_timezone(&Utc)),
+        None => parse_date(date),
+    }
+}
+
+/// Reads every entry, returning the dates with their index, and error entries for the
+/// others.
+pub fn parse_entries(entries: &[Value]) -> (Vec<(DateTime<Utc>, usize)>, Vec<Value>) {
+    let mut dates = Vec::with_capacity(entries.len());
+    let mut invalid = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        match parse_entry(entry) {
+            Ok(date) => dates.push((date, index)),
+            Err(error) => invalid.push(json!({
+                "index": index,
+                "input": entry,
+                "error": error.to_string(),
+                "code": error.code(),
+            })),
+        }
+    }
+    (dates, invalid)
+}
+
+/// Sorts `entries` chronologically, keeping the input order of equal instants. Entries
+/// that aren't dates are listed apart.
+fn sort(entries: &[Value]) -> (Vec<Value>, Vec<Value>) {
+    let (mut dates, invalid) = parse_entries(entries);
+    dates.sort();
+
+    let sorted = dates
+        .into_iter()
+        .map(|(date, index)| {
+            json!({
+                "index": index,
+                "input": entries[index],
+                "unix": date.timestamp(),
+                "utc": date.to_rfc2822(),
+            })
+        })
+        .collect();
+    (sorted, invalid)
+}
+
+/// Sorts a JSON array of dates and timestamps, normalizing each of them.
+pub async fn sort_handler(Json(entries): Json<Vec<Value>>) -> Result<Json<Value>, AppError> {
+    if entries.len() > MAX_DATES {
+        return Err(AppError::BadRequest(format!(
+            "At most {} dates can be sorted at once",
+            MAX_DATES
+        )));
+    }
+    let (sorted, invalid) = sort(&entries);
+
+    Ok(Json(json!({
+        "sorted": sorted,
+        "invalid": invalid,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixed_formats() {
+        let (sorted, invalid) = sort(&[
+            json!("2016-12-25T01:00:00+02:00"),
+            json!(1482624000),
+            json!("not a date"),
+            json!("Sat, 24 Dec 2016 12:00:00 +0000"),
+            json!("2016-12-24"),
+        ]);
+        let order: Vec<&Value> = sorted.iter().map(|entry| &entry["index"]).collect();
+        assert_eq!(order, vec![4, 3, 0, 1]);
+        assert_eq!(sorted[2]["unix"], 1482620400);
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0]["index"], 2);
+    }
+}
@@ -0,0 +1,390 @@
+//! SQLite backend keeping timers, marks and scheduled webhooks across restarts, built with
+//! the `sqlite` feature and enabled by setting `database_url`.
+//!
+//! The migrations in `migrations/` run when the database is opened, then every row is
+//! loaded and the stores keep working in memory as they do without a database. Changes are
+//! written behind by a single task, in the order they were made, so that requests never
+//! wait on the disk. A change that can't be written is logged and lost.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+use crate::marks::{self, Mark, MarkStorage, MemoryStorage};
+use crate::scheduler::{self, Job, Status};
+use crate::timers::{self, SavedTimer};
+
+#[derive(Debug)]
+enum Write {
+    PutMark(String, Mark),
+    RemoveMark(String),
+    SaveTimer(String, SavedTimer),
+    RemoveTimer(String),
+    SaveJob(Job),
+    RemoveJob(String),
+}
+
+#[derive(Clone)]
+struct Writes(mpsc::UnboundedSender<Write>);
+
+impl Writes {
+    fn send(&self, write: Write) {
+        if self.0.send(write).is_err() {
+            tracing::error!("The database writer stopped, changes are no longer saved");
+        }
+    }
+}
+
+/// Every row of the database, as loaded at startup.
+#[derive(Debug, Default)]
+struct Rows {
+    marks: Vec<(String, Mark)>,
+    timers: Vec<(String, SavedTimer)>,
+    jobs: Vec<Job>,
+}
+
+pub struct Database {
+    rows: Rows,
+    writes: Writes,
+}
+
+impl Database {
+    /// Opens the database at `url`, creating it if needed, and loads it. This blocks the
+    /// current thread, which must belong to a multi-threaded runtime.
+    pub fn open(url: &str) -> Result<Database, String> {
+        tokio::task::block_in_place(|| {
+            Handle::current().block_on(async {
+                let pool = connect(url).await?;
+                let rows = Rows::load(&pool).await?;
+                let (writes, pending) = mpsc::unbounded_channel();
+                tokio::spawn(write_behind(pool, pending));
+                Ok(Database {
+                    rows,
+                    writes: Writes(writes),
+                })
+            })
+        })
+        .map_err(|e: sqlx::Error| format!("Can't open the database {}: {}", url, e))
+    }
+
+    pub fn marks(&mut self) -> marks::Marks {
+        let memory = MemoryStorage::default();
+        for (name, mark) in self.rows.marks.drain(..) {
+            memory.put(name, mark);
+        }
+        Arc::new(Marks {
+            memory,
+            writes: self.writes.clone(),
+        })
+    }
+
+    pub fn timers(&mut self) -> Box<dyn timers::Persistence> {
+        Box::new(Timers {
+            loaded: Mutex::new(std::mem::take(&mut self.rows.timers)),
+            writes: self.writes.clone(),
+        })
+    }
+
+    pub fn jobs(&mut self) -> Box<dyn scheduler::Persistence> {
+        Box::new(Jobs {
+            loaded: Mutex::new(std::mem::take(&mut self.rows.jobs)),
+            writes: self.writes.clone(),
+        })
+    }
+}
+
+/// A single connection is enough, changes being written one at a time.
+async fn connect(url: &str) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await?;
+    sqlx::migrate!().run(&pool).await?;
+    Ok(pool)
+}
+
+fn decode_error(e: serde_json::Error) -> sqlx::Error {
+    sqlx::Error::Decode(Box::new(e))
+}
+
+impl Rows {
+    /// Loads every row, except the marks that expired while the service was down.
+    async fn load(pool: &SqlitePool) -> Result<Rows, sqlx::Error> {
+        let now = Utc::now();
+        let marks = sqlx::query_as::<_, (String, DateTime<Utc>, Option<DateTime<Utc>>)>(
+            "SELECT name, at, expires_at FROM marks",
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|(name, at, expires_at)| (name, Mark { at, expires_at }))
+        .filter(|(_, mark)| mark.is_live(now))
+        .collect();
+
+        let timers = sqlx::query_as::<
+            _,
+            (String, Option<String>, DateTime<Utc>, String, Option<i64>),
+        >("SELECT id, name, started_at, laps_ms, stopped_ms FROM timers")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|(id, name, started_at, laps_ms, stopped_ms)| {
+            let laps: Vec<u64> = serde_json::from_str(&laps_ms).map_err(decode_error)?;
+            let timer = SavedTimer {
+                name,
+                started_at,
+                laps: laps.into_iter().map(Duration::from_millis).collect(),
+                stopped: stopped_ms.map(|ms| Duration::from_millis(ms as u64)),
+            };
+            Ok((id, timer))
+        })
+        .collect::<Result<_, sqlx::Error>>()?;
+
+        let jobs =
+            sqlx::query_as::<
+                _,
+                (
+                    String,
+                    DateTime<Utc>,
+                    String,
+                    String,
+                    String,
+                    i64,
+                    Option<DateTime<Utc>>,
+                ),
+            >("SELECT id, at, url, payload, status, attempts, next_attempt FROM jobs")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|(id, at, url, payload, status, attempts, next_attempt)| {
+                Ok(Job {
+                    id,
+                    at,
+                    url,
+                    payload: serde_json::from_str(&payload).map_err(decode_error)?,
+                    status: serde_json::from_value(Value::String(status)).map_err(decode_error)?,
+                    attempts: attempts as u32,
+                    next_attempt,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()?;
+
+        Ok(Rows {
+            marks,
+            timers,
+            jobs,
+        })
+    }
+}
+
+fn millis(duration: Duration) -> i64 {
+    duration.as_millis() as i64
+}
+
+fn status_name(status: Status) -> String {
+    json!(status).as_str().unwrap_or_default().to_string()
+}
+
+async fn apply(pool: &SqlitePool, write: Write) -> Result<(), sqlx::Error> {
+    match write {
+        Write::PutMark(name, mark) => {
+            sqlx::query("DELETE FROM marks WHERE expires_at <= ?")
+                .bind(Utc::now())
+                .execute(pool)
+                .await?;
+            sqlx::query("INSERT OR REPLACE INTO marks (name, at, expires_at) VALUES (?, ?, ?)")
+                .bind(name)
+                .bind(mark.at)
+                .bind(mark.expires_at)
+                .execute(pool)
+                .await?;
+        }
+        Write::RemoveMark(name) => {
+            sqlx::query("DELETE FROM marks WHERE name = ?")
+                .bind(name)
+                .execute(pool)
+                .await?;
+        }
+        Write::SaveTimer(id, timer) => {
+            let laps: Vec<i64> = timer.laps.into_iter().map(millis).collect();
+            sqlx::query(
+                "INSERT OR REPLACE INTO timers (id, name, started_at, laps_ms, stopped_ms) \
+                 VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(id)
+            .bind(timer.name)
+            .bind(timer.started_at)
+            .bind(json!(laps).to_string())
+            .bind(timer.stopped.map(millis))
+            .execute(pool)
+            .await?;
+        }
+        Write::RemoveTimer(id) => {
+            sqlx::query("DELETE FROM timers WHERE id = ?")
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
+        Write::SaveJob(job) => {
+            sqlx::query(
+                "INSERT OR REPLACE INTO jobs \
+                 (id, at, url, payload, status, attempts, next_attempt) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(job.id)
+            .bind(job.at)
+            .bind(job.url)
+            .bind(job.payload.to_string())
+            .bind(status_name(job.status))
+            .bind(i64::from(job.attempts))
+            .bind(job.next_attempt)
+            .execute(pool)
+            .await?;
+        }
+        Write::RemoveJob(id) => {
+            sqlx::query("DELETE FROM jobs WHERE id = ?")
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn write_behind(pool: SqlitePool, mut pending: mpsc::UnboundedReceiver<Write>) {
+    while let Some(write) = pending.recv().await {
+        if let Err(e) = apply(&pool, write).await {
+            tracing::error!("Can't write to the database: {}", e);
+        }
+    }
+}
+
+/// Marks are read from memory and written through to the database.
+struct Marks {
+    memory: MemoryStorage,
+    writes: Writes,
+}
+
+impl MarkStorage for Marks {
+    fn put(&self, name: String, mark: Mark) {
+        self.writes.send(Write::PutMark(name.clone(), mark.clone()));
+        self.memory.put(name, mark);
+    }
+
+    fn get(&self, name: &str, now: DateTime<Utc>) -> Option<Mark> {
+        self.memory.get(name, now)
+    }
+
+    fn remove(&self, name: &str, now: DateTime<Utc>) -> Option<Mark> {
+        self.writes.send(Write::RemoveMark(name.to_string()));
+        self.memory.remove(name, now)
+    }
+
+    fn list(&self, now: DateTime<Utc>) -> Vec<(String, Mark)> {
+        self.memory.list(now)
+    }
+}
+
+struct Timers {
+    loaded: Mutex<Vec<(String, SavedTimer)>>,
+    writes: Writes,
+}
+
+impl timers::Persistence for Timers {
+    fn save(&self, id: &str, timer: &SavedTimer) {
+        self.writes
+            .send(Write::SaveTimer(id.to_string(), timer.clone()));
+    }
+
+    fn remove(&self, id: &str) {
+        self.writes.send(Write::RemoveTimer(id.to_string()));
+    }
+
+    fn load(&self) -> Vec<(String, SavedTimer)> {
+        std::mem::take(&mut self.loaded.lock().unwrap())
+    }
+}
+
+struct Jobs {
+    loaded: Mutex<Vec<Job>>,
+    writes: Writes,
+}
+
+impl scheduler::Persistence for Jobs {
+    fn save(&self, job: &Job) {
+        self.writes.send(Write::SaveJob(job.clone()));
+    }
+
+    fn remove(&self, id: &str) {
+        self.writes.send(Write::RemoveJob(id.to_string()));
+    }
+
+    fn load(&self) -> Vec<Job> {
+        std::mem::take(&mut self.loaded.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn rows_round_trip() {
+        let pool = connect("sqlite::memory:").await.unwrap();
+        let at = Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap();
+        let mark = Mark {
+            at,
+            expires_at: None,
+        };
+        let expired = Mark {
+            at,
+            expires_at: Some(at),
+        };
+        let timer = SavedTimer {
+            name: Some("build".to_string()),
+            started_at: at,
+            laps: vec![Duration::from_millis(1500)],
+            stopped: Some(Duration::from_millis(2000)),
+        };
+        let job = Job {
+            id: "3".to_string(),
+            at,
+            url: "https://example.com/hook".to_string(),
+            payload: json!({"hello": "world"}),
+            status: Status::Failed,
+            attempts: 5,
+            next_attempt: None,
+        };
+        for write in [
+            Write::PutMark("christmas".to_string(), mark.clone()),
+            Write::PutMark("expired".to_string(), expired),
+            Write::PutMark("removed".to_string(), mark.clone()),
+            Write::RemoveMark("removed".to_string()),
+            Write::SaveTimer("0".to_string(), timer.clone()),
+            Write::SaveJob(job.clone()),
+        ] {
+            apply(&pool, write).await.unwrap();
+        }
+
+        let rows = Rows::load(&pool).await.unwrap();
+        assert_eq!(rows.marks, vec![("christmas".to_string(), mark)]);
+        assert_eq!(rows.timers, vec![("0".to_string(), timer)]);
+        assert_eq!(rows.jobs.len(), 1);
+        assert_eq!(rows.jobs[0].id, job.id);
+        assert_eq!(rows.jobs[0].payload, job.payload);
+        assert_eq!(rows.jobs[0].status, Status::Failed);
+        assert_eq!(rows.jobs[0].attempts, 5);
+
+        apply(&pool, Write::RemoveJob("3".to_string()))
+            .await
+            .unwrap();
+        assert!(Rows::load(&pool).await.unwrap().jobs.is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+//! Summary statistics over posted timestamps, for one-off checks from monitoring scripts.
+
+use axum::http::HeaderMap;
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::duration::IsoDuration;
+use crate::error::AppError;
+use crate::extract::Json;
+use crate::locale;
+use crate::relative::humanize_duration;
+use crate::sort::parse_entries;
+
+/// Longer lists of timestamps are rejected.
+const MAX_TIMESTAMPS: usize = 100_000;
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+
+fn nanos(instant: DateTime<Utc>) -> i128 {
+    i128::from(instant.timestamp()) * NANOS_PER_SECOND
+        + i128::from(instant.timestamp_subsec_nanos())
+}
+
+fn from_nanos(nanos: i128) -> Option<DateTime<Utc>> {
+    let seconds = i64::try_from(nanos.div_euclid(NANOS_PER_SECOND)).ok()?;
+    let subsec = nanos.rem_euclid(NANOS_PER_SECOND) as u32;
+    Utc.timestamp_opt(seconds, subsec).single()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub min: DateTime<Utc>,
+    pub max: DateTime<Utc>,
+    pub mean: DateTime<Utc>,
+    /// The middle instant, or the midpoint of the two middle ones.
+    pub median: DateTime<Utc>,
+}
+
+/// Summarizes a list of instants, `None` when it's empty.
+pub fn summarize(instants: &mut [DateTime<Utc>]) -> Option<Summary> {
+    instants.sort();
+    let (min, max) = (*instants.first()?, *instants.last()?);
+    let count = instants.len();
+    let total: i128 = instants.iter().map(|instant| nanos(*instant)).sum();
+    let mean = from_nanos(total.div_euclid(count as i128))?;
+    let median = if count % 2 == 1 {
+        instants[count / 2]
+    } else {
+        let (low, high) = (nanos(instants[count / 2 - 1]), nanos(instants[count / 2]));
+        from_nanos(low + (high - low) / 2)?
+    };
+    Some(Summary {
+        min,
+        max,
+        mean,
+        median,
+    })
+}
+
+fn describe(instant: DateTime<Utc>) -> Value {
+    json!({
+        "unix": instant.timestamp(),
+        "utc": instant.to_rfc2822(),
+        "iso8601": instant.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    })
+}
+
+/// Minimum, maximum, mean, median and span of a JSON array of dates and timestamps.
+pub async fn stats_handler(
+    headers: HeaderMap,
+    Json(entries): Json<Vec<Value>>,
+) -> Result<Json<Value>, AppError> {
+    let locale = locale::negotiate(&headers, None)?.unwrap_or(&locale::EN);
+    if entries.len() > MAX_TIMESTAMPS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} timestamps can be summarized at once",
+            MAX_TIMESTAMPS
+        )));
+    }
+    let (dates, invalid) = parse_entries(&entries);
+    let mut instants: Vec<DateTime<Utc>> = dates.into_iter().map(|(date, _)| date).collect();
+    let summary = summarize(&mut instants)
+        .ok_or_else(|| AppError::BadRequest("No timestamp could be read".to_string()))?;
+    let span = (summary.max - summary.min).num_seconds();
+
+    Ok(Json(json!({
+        "count": instants.len(),
+        "min": describe(summary.min),
+        "max": describe(summary.max),
+        "mean": describe(summary.mean),
+        "median": describe(summary.median),
+        "span": {
+            "seconds": span,
+            "iso8601": IsoDuration::from_seconds(span).to_string(),
+            "humanized": humanize_duration(locale, span.unsigned_abs(), 2),
+        },
+        "invalid": invalid,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let at = |hour| Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap();
+        let summary = summarize(&mut [at(12), at(0), at(3), at(1)]).unwrap();
+        assert_eq!(summary.min, at(0));
+        assert_eq!(summary.max, at(12));
+        assert_eq!(summary.mean, at(4));
+        assert_eq!(
+            summary.median,
+            Utc.with_ymd_and_hms(2024, 5, 1, 2, 0, 0).unwrap()
+        );
+
+        let summary = summarize(&mut [at(5)]).unwrap();
+        assert_eq!(summary.median, at(5));
+        assert!(summarize(&mut []).is_none());
+    }
+}
@@ -0,0 +1,170 @@
+//! Sunrise and sunset times from the sunrise equation, accurate to about a minute
+//! outside of the polar regions.
+
+use axum::Json;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::parse_date;
+use crate::query::Query;
+use crate::timezone::parse_tz;
+
+/// Julian date of the unix epoch.
+const UNIX_EPOCH_JULIAN: f64 = 2_440_587.5;
+/// Julian date of the J2000 epoch, 2000-01-01T12:00:00 TT.
+const J2000: f64 = 2_451_545.0;
+/// Altitude of the sun's centre at sunrise, accounting for refraction and the solar disc.
+const SUNRISE_ALTITUDE: f64 = -0.833;
+const OBLIQUITY: f64 = 23.4397;
+
+#[derive(Debug, PartialEq)]
+pub enum Daylight {
+    /// The sun rises and sets.
+    Normal {
+        sunrise: DateTime<Utc>,
+        sunset: DateTime<Utc>,
+    },
+    /// The sun doesn't set all day.
+    PolarDay,
+    /// The sun doesn't rise all day.
+    PolarNight,
+}
+
+fn from_julian(julian: f64) -> DateTime<Utc> {
+    let seconds = (julian - UNIX_EPOCH_JULIAN) * 86400.0;
+    DateTime::from_timestamp(seconds.round() as i64, 0).unwrap()
+}
+
+/// Solar noon and daylight of a day at the given latitude and longitude (east positive),
+/// both in degrees.
+pub fn sun_times(date: NaiveDate, lat: f64, lon: f64) -> (DateTime<Utc>, Daylight) {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() as f64 / 86400.0
+        + UNIX_EPOCH_JULIAN;
+    let day = (midnight - J2000 + 0.0008).ceil();
+    let mean_noon = day - lon / 360.0;
+
+    let anomaly = (357.5291 + 0.985_600_28 * mean_noon)
+        .rem_euclid(360.0)
+        .to_radians();
+    let center =
+        1.9148 * anomaly.sin() + 0.0200 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let longitude = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit = J2000 + mean_noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * longitude).sin();
+
+    let declination = (longitude.sin() * OBLIQUITY.to_radians().sin()).asin();
+    let lat = lat.to_radians();
+    let hour_angle = (SUNRISE_ALTITUDE.to_radians().sin() - lat.sin() * declination.sin())
+        / (lat.cos() * declination.cos());
+
+    let daylight = if hour_angle < -1.0 {
+        Daylight::PolarDay
+    } else if hour_angle > 1.0 {
+        Daylight::PolarNight
+    } else {
+        let half_day = hour_angle.acos().to_degrees() / 360.0;
+        Daylight::Normal {
+            sunrise: from_julian(transit - half_day),
+            sunset: from_julian(transit + half_day),
+        }
+    };
+    (from_julian(transit), daylight)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SunParams {
+    /// Defaults to today.
+    date: Option<String>,
+    lat: f64,
+    lon: f64,
+    /// Timezone of the local times, UTC by default.
+    tz: Option<String>,
+}
+
+fn describe(instant: DateTime<Utc>, tz: Tz) -> Value {
+    json!({
+        "unix": instant.timestamp(),
+        "utc": instant.to_rfc2822(),
+        "local": instant.with_timezone(&tz).to_rfc3339(),
+    })
+}
+
+pub async fn sun_handler(Query(params): Query<SunParams>) -> Result<Json<Value>, AppError> {
+    if !(-90.0..=90.0).contains(&params.lat) || !(-180.0..=180.0).contains(&params.lon) {
+        return Err(AppError::BadRequest("Invalid coordinates".to_string()));
+    }
+    let tz = parse_tz(params.tz.as_deref())?;
+    let date = match params.date.as_deref() {
+        Some(date) => parse_date(date)?,
+        None => Utc::now(),
+    };
+    let date = date.date_naive();
+
+    let (noon, daylight) = sun_times(date, params.lat, params.lon);
+    let (sunrise, sunset, day_length, polar) = match daylight {
+        Daylight::Normal { sunrise, sunset } => (
+            describe(sunrise, tz),
+            describe(sunset, tz),
+            (sunset - sunrise).num_seconds(),
+            Value::Null,
+        ),
+        Daylight::PolarDay => (Value::Null, Value::Null, 86400, json!("day")),
+        Daylight::PolarNight => (Value::Null, Value::Null, 0, json!("night")),
+    };
+
+    Ok(Json(json!({
+        "date": date.to_string(),
+        "lat": params.lat,
+        "lon": params.lon,
+        "tz": tz.name(),
+        "sunrise": sunrise,
+        "sunset": sunset,
+        "solar_noon": describe(noon, tz),
+        "day_length_seconds": day_length,
+        "polar": polar,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn close(actual: DateTime<Utc>, expected: DateTime<Utc>) -> bool {
+        (actual - expected).num_seconds().abs() <= 120
+    }
+
+    #[test]
+    fn milan_summer_solstice() {
+        let (noon, daylight) =
+            sun_times(NaiveDate::from_ymd_opt(2024, 6, 21).unwrap(), 45.46, 9.19);
+        assert!(close(
+            noon,
+            Utc.with_ymd_and_hms(2024, 6, 21, 11, 25, 0).unwrap()
+        ));
+        match daylight {
+            Daylight::Normal { sunrise, sunset } => {
+                assert!(close(
+                    sunrise,
+                    Utc.with_ymd_and_hms(2024, 6, 21, 3, 35, 0).unwrap()
+                ));
+                assert!(close(
+                    sunset,
+                    Utc.with_ymd_and_hms(2024, 6, 21, 19, 15, 0).unwrap()
+                ));
+            }
+            _ => panic!("expected a sunrise in Milan"),
+        }
+    }
+
+    #[test]
+    fn polar_regions() {
+        let solstice = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        assert_eq!(sun_times(solstice, 78.22, 15.65).1, Daylight::PolarDay);
+        assert_eq!(sun_times(solstice, -78.22, 15.65).1, Daylight::PolarNight);
+    }
+}
@@ -0,0 +1,255 @@
+//! User-defined shapes of successful JSON responses, for integrations expecting a fixed
+//! format.
+//!
+//! `?template=` is either a JSON template, such as `{"epoch":"{unix}","pretty":"{utc}"}`,
+//! or the name of one configured in the `[templates]` section. Strings of the template
+//! holding `{field}` placeholders are filled from the response, `{from.unix}` reaching into
+//! nested objects: a string that is a single placeholder takes the field's value as is,
+//! numbers included, while placeholders mixed with text are replaced by its text. Lists of
+//! results get the template applied to each of them, except for error entries. Fields
+//! missing from the response are answered with a 400, as are malformed templates.
+
+use axum::body::{to_bytes, Body, Bytes, HttpBody};
+use axum::http::{header, Request, Response};
+use axum::response::IntoResponse;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{BoxError, Layer, Service};
+
+use crate::error::AppError;
+
+/// Parses a template, which must be a JSON object or array.
+pub fn parse(template: &str) -> Result<Value, String> {
+    match serde_json::from_str(template) {
+        Ok(template @ Value::Object(_)) | Ok(template @ Value::Array(_)) => Ok(template),
+        Ok(_) => Err("A template must be a JSON object or array".to_string()),
+        Err(e) => Err(format!("Invalid template: {}", e)),
+    }
+}
+
+/// The value at a dotted path such as `from.unix`, list indexes included.
+fn lookup<'a>(data: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(data, |value, key| match value {
+        Value::Object(object) => object.get(key),
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Fills the placeholders of a template string.
+fn fill(template: &str, data: &Value) -> Result<Value, String> {
+    let field =
+        |path: &str| lookup(data, path).ok_or_else(|| format!("Unknown template field {}", path));
+    if let Some(path) = template
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .filter(|path| !path.contains(['{', '}']))
+    {
+        return field(path).cloned();
+    }
+
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        filled.push_str(&rest[..start]);
+        filled.push_str(&text(field(&rest[start + 1..end])?));
+        rest = &rest[end + 1..];
+    }
+    filled.push_str(rest);
+    Ok(Value::String(filled))
+}
+
+/// Shapes `data` after `template`.
+pub fn render(template: &Value, data: &Value) -> Result<Value, String> {
+    match template {
+        Value::String(template) => fill(template, data),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| render(item, data))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        Value::Object(object) => object
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), render(value, data)?)))
+            .collect::<Result<_, String>>()
+            .map(Value::Object),
+        other => Ok(other.clone()),
+    }
+}
+
+/// Applies a template to a response body, or to each result of a list.
+fn apply(template: &Value, body: &[u8]) -> Result<Bytes, String> {
+    let data: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let rendered = match &data {
+        // entries of dates that failed to convert are left as they are
+        Value::Array(results) => results
+            .iter()
+            .map(|result| match result.get("error") {
+                Some(_) => Ok(result.clone()),
+                None => render(template, result),
+            })
+            .collect::<Result<_, _>>()
+            .map(Value::Array)?,
+        data => render(template, data)?,
+    };
+    Ok(Bytes::from(serde_json::to_vec(&rendered).unwrap()))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TemplateLayer {
+    named: Arc<BTreeMap<String, Value>>,
+}
+
+impl TemplateLayer {
+    pub fn new(templates: &BTreeMap<String, String>) -> Result<TemplateLayer, String> {
+        let named = templates
+            .iter()
+            .map(|(name, template)| {
+                parse(template)
+                    .map(|template| (name.clone(), template))
+                    .map_err(|e| format!("templates.{}: {}", name, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(TemplateLayer {
+            named: Arc::new(named),
+        })
+    }
+
+    /// The template asked for in a query string, if any.
+    fn requested(&self, query: Option<&str>) -> Result<Option<Value>, AppError> {
+        let pairs: Vec<(String, String)> =
+            serde_urlencoded::from_str(query.unwrap_or_default()).unwrap_or_default();
+        let template = match pairs.into_iter().find(|(key, _)| key == "template") {
+            Some((_, template)) => template,
+            None => return Ok(None),
+        };
+        if template.starts_with(['{', '[']) {
+            return parse(&template).map(Some).map_err(AppError::BadRequest);
+        }
+        self.named
+            .get(&template)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown template {}", template)))
+    }
+}
+
+impl<S> Layer<S> for TemplateLayer {
+    type Service = Template<S>;
+
+    fn layer(&self, inner: S) -> Template<S> {
+        Template {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Template<S> {
+    inner: S,
+    layer: TemplateLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for Template<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let template = match self.layer.requested(request.uri().query()) {
+            Ok(template) => template,
+            Err(error) => {
+                let response = error.into_response();
+                return Box::pin(async move { Ok(response) });
+            }
+        };
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await?;
+            let is_json = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .is_some_and(|kind| kind.as_bytes().starts_with(b"application/json"));
+            let template = match template {
+                Some(template) if is_json && response.status().is_success() => template,
+                _ => return Ok(response.map(Body::new)),
+            };
+
+            let (mut parts, body) = response.into_parts();
+            let body = match to_bytes(Body::new(body), usize::MAX).await {
+                Ok(body) => body,
+                Err(_) => Bytes::new(),
+            };
+            match apply(&template, &body) {
+                Ok(body) => {
+                    parts.headers.remove(header::CONTENT_LENGTH);
+                    Ok(Response::from_parts(parts, Body::from(body)))
+                }
+                Err(e) => Ok(AppError::BadRequest(e).into_response()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn placeholders() {
+        let data = json!({"unix": 1482624000, "utc": "Sun, 25 Dec 2016 00:00:00 +0000"});
+        let template =
+            parse(r#"{"epoch": "{unix}", "pretty": "{utc}", "text": "at {unix}s"}"#).unwrap();
+        assert_eq!(
+            render(&template, &data).unwrap(),
+            json!({
+                "epoch": 1482624000,
+                "pretty": "Sun, 25 Dec 2016 00:00:00 +0000",
+                "text": "at 1482624000s"
+            })
+        );
+        assert!(render(&json!({"x": "{missing}"}), &data).is_err());
+    }
+
+    #[test]
+    fn nested_fields_and_lists() {
+        let data = json!({"from": {"tz": "Europe/Rome"}, "list": [1, 2]});
+        assert_eq!(
+            render(&json!(["{from.tz}", "{list.1}", "{open"]), &data).unwrap(),
+            json!(["Europe/Rome", 2, "{open"])
+        );
+
+        let body = apply(&json!({"t": "{unix}"}), br#"[{"unix": 1}, {"unix": 2}]"#).unwrap();
+        assert_eq!(body, Bytes::from(r#"[{"t":1},{"t":2}]"#));
+        assert!(parse("42").is_err());
+    }
+}
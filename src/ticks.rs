@@ -0,0 +1,79 @@
+//! .NET `DateTime.Ticks`: 100-nanosecond intervals since 0001-01-01T00:00:00.
+
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::parse_date;
+
+const TICKS_PER_SECOND: i64 = 10_000_000;
+/// Ticks at the unix epoch.
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+/// Ticks at 9999-12-31T23:59:59.9999999, the largest `DateTime` value.
+const MAX_TICKS: i64 = 3_155_378_975_999_999_999;
+
+pub fn from_ticks(ticks: i64) -> Option<DateTime<Utc>> {
+    if !(0..=MAX_TICKS).contains(&ticks) {
+        return None;
+    }
+    let since_epoch = ticks - UNIX_EPOCH_TICKS;
+    let seconds = since_epoch.div_euclid(TICKS_PER_SECOND);
+    let nanos = since_epoch.rem_euclid(TICKS_PER_SECOND) * 100;
+    DateTime::from_timestamp(seconds, nanos as u32)
+}
+
+pub fn to_ticks(date: DateTime<Utc>) -> Option<i64> {
+    let ticks = date
+        .timestamp()
+        .checked_mul(TICKS_PER_SECOND)?
+        .checked_add(UNIX_EPOCH_TICKS)?
+        .checked_add(date.timestamp_subsec_nanos() as i64 / 100)?;
+    Some(ticks).filter(|ticks| (0..=MAX_TICKS).contains(ticks))
+}
+
+pub async fn from_ticks_handler(Path(ticks): Path<String>) -> Result<Json<Value>, AppError> {
+    let ticks: i64 = ticks.parse().map_err(|_| AppError::InvalidDate)?;
+    let date = from_ticks(ticks).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "ticks": ticks,
+    })))
+}
+
+pub async fn to_ticks_handler(Path(date): Path<String>) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?;
+    let ticks = to_ticks(date).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "ticks": ticks,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn conversions() {
+        let christmas = Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap();
+        assert_eq!(to_ticks(christmas), Some(636_182_208_000_000_000));
+        assert_eq!(from_ticks(636_182_208_000_000_000), Some(christmas));
+        assert_eq!(
+            from_ticks(0),
+            Some(Utc.with_ymd_and_hms(1, 1, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            from_ticks(UNIX_EPOCH_TICKS + 1),
+            Some(Utc.timestamp_opt(0, 100).unwrap())
+        );
+        assert_eq!(from_ticks(-1), None);
+        assert_eq!(from_ticks(MAX_TICKS + 1), None);
+    }
+}
@@ -0,0 +1,300 @@
+//! Remote stopwatches, such as for timing the stages of a CI pipeline.
+//!
+//! Elapsed times are measured with the monotonic clock, so they aren't affected by
+//! adjustments of the wall clock while a timer runs. Timers restored after a restart
+//! resume from their wall-clock start though, the monotonic clock starting over.
+
+use axum::extract::State;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::extract::{Json, Path};
+
+/// Upper bound on the number of timers kept, stopped ones included.
+const MAX_TIMERS: usize = 10_000;
+
+#[derive(Clone, Debug)]
+struct Timer {
+    name: Option<String>,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    /// Elapsed time at each lap.
+    laps: Vec<Duration>,
+    stopped: Option<Duration>,
+}
+
+impl Timer {
+    fn elapsed(&self) -> Duration {
+        self.stopped.unwrap_or_else(|| self.started.elapsed())
+    }
+
+    fn saved(&self) -> SavedTimer {
+        SavedTimer {
+            name: self.name.clone(),
+            started_at: self.started_at,
+            laps: self.laps.clone(),
+            stopped: self.stopped,
+        }
+    }
+}
+
+/// A timer as persisted, without its monotonic start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedTimer {
+    pub name: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub laps: Vec<Duration>,
+    pub stopped: Option<Duration>,
+}
+
+impl SavedTimer {
+    fn restore(self) -> Timer {
+        let elapsed = (Utc::now() - self.started_at).to_std().unwrap_or_default();
+        Timer {
+            name: self.name,
+            started_at: self.started_at,
+            started: Instant::now()
+                .checked_sub(elapsed)
+                .unwrap_or_else(Instant::now),
+            laps: self.laps,
+            stopped: self.stopped,
+        }
+    }
+}
+
+/// Hook called on every change to a timer. The default keeps nothing.
+pub trait Persistence: Send + Sync {
+    fn save(&self, _id: &str, _timer: &SavedTimer) {}
+    fn remove(&self, _id: &str) {}
+    /// Timers to restore when the store is created.
+    fn load(&self) -> Vec<(String, SavedTimer)> {
+        Vec::new()
+    }
+}
+
+pub struct NoPersistence;
+
+impl Persistence for NoPersistence {}
+
+#[derive(Clone)]
+pub struct TimerStore {
+    timers: Arc<Mutex<HashMap<String, Timer>>>,
+    next_id: Arc<AtomicU64>,
+    persistence: Arc<dyn Persistence>,
+}
+
+impl Default for TimerStore {
+    fn default() -> Self {
+        TimerStore::new(Box::new(NoPersistence))
+    }
+}
+
+impl TimerStore {
+    pub fn new(persistence: Box<dyn Persistence>) -> Self {
+        let timers: HashMap<String, Timer> = persistence
+            .load()
+            .into_iter()
+            .map(|(id, timer)| (id, timer.restore()))
+            .collect();
+        let next_id = timers
+            .keys()
+            .filter_map(|id| id.parse::<u64>().ok())
+            .max()
+            .map_or(0, |id| id + 1);
+
+        TimerStore {
+            timers: Arc::new(Mutex::new(timers)),
+            next_id: Arc::new(AtomicU64::new(next_id)),
+            persistence: Arc::from(persistence),
+        }
+    }
+
+    /// Applies `update` to a timer and renders it.
+    fn with_timer<F>(&self, id: &str, update: F) -> Result<Value, AppError>
+    where
+        F: FnOnce(&mut Timer) -> Result<(), AppError>,
+    {
+        let mut timers = self.timers.lock().unwrap();
+        let timer = timers
+            .get_mut(id)
+            .ok_or_else(|| AppError::NotFound("Unknown timer".to_string()))?;
+        update(timer)?;
+        self.persistence.save(id, &timer.saved());
+        Ok(render(id, timer))
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+fn render(id: &str, timer: &Timer) -> Value {
+    let laps: Vec<Value> = timer
+        .laps
+        .iter()
+        .enumerate()
+        .map(|(index, elapsed)| {
+            let previous = index
+                .checked_sub(1)
+                .map_or(Duration::default(), |i| timer.laps[i]);
+            json!({
+                "elapsed_ms": millis(*elapsed),
+                "split_ms": millis(*elapsed - previous),
+            })
+        })
+        .collect();
+
+    json!({
+        "id": id,
+        "name": timer.name,
+        "started_at": {
+            "unix": timer.started_at.timestamp(),
+            "utc": timer.started_at.to_rfc2822(),
+        },
+        "running": timer.stopped.is_none(),
+        "elapsed_ms": millis(timer.elapsed()),
+        "laps": laps,
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TimerRequest {
+    name: Option<String>,
+}
+
+pub async fn start_handler(
+    State(store): State<TimerStore>,
+    request: Option<Json<TimerRequest>>,
+) -> Result<Json<Value>, AppError> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let timer = Timer {
+        name: request.name,
+        started_at: Utc::now(),
+        started: Instant::now(),
+        laps: Vec::new(),
+        stopped: None,
+    };
+
+    let mut timers = store.timers.lock().unwrap();
+    if timers.len() >= MAX_TIMERS {
+        return Err(AppError::Unprocessable(
+            "Too many timers, delete some first".to_string(),
+        ));
+    }
+    let id = store.next_id.fetch_add(1, Ordering::Relaxed).to_string();
+    let body = render(&id, &timer);
+    store.persistence.save(&id, &timer.saved());
+    timers.insert(id, timer);
+
+    Ok(Json(body))
+}
+
+pub async fn get_handler(
+    Path(id): Path<String>,
+    State(store): State<TimerStore>,
+) -> Result<Json<Value>, AppError> {
+    let timers = store.timers.lock().unwrap();
+    let timer = timers
+        .get(&id)
+        .ok_or_else(|| AppError::NotFound("Unknown timer".to_string()))?;
+
+    Ok(Json(render(&id, timer)))
+}
+
+pub async fn lap_handler(
+    Path(id): Path<String>,
+    State(store): State<TimerStore>,
+) -> Result<Json<Value>, AppError> {
+    let body = store.with_timer(&id, |timer| {
+        if timer.stopped.is_some() {
+            return Err(AppError::Unprocessable("The timer is stopped".to_string()));
+        }
+        timer.laps.push(timer.elapsed());
+        Ok(())
+    })?;
+
+    Ok(Json(body))
+}
+
+/// Stops a timer, freezing its elapsed time. Stopping it again has no effect.
+pub async fn stop_handler(
+    Path(id): Path<String>,
+    State(store): State<TimerStore>,
+) -> Result<Json<Value>, AppError> {
+    let body = store.with_timer(&id, |timer| {
+        timer.stopped = Some(timer.elapsed());
+        Ok(())
+    })?;
+
+    Ok(Json(body))
+}
+
+pub async fn delete_handler(
+    Path(id): Path<String>,
+    State(store): State<TimerStore>,
+) -> Result<Json<Value>, AppError> {
+    let timer = store
+        .timers
+        .lock()
+        .unwrap()
+        .remove(&id)
+        .ok_or_else(|| AppError::NotFound("Unknown timer".to_string()))?;
+    store.persistence.remove(&id);
+
+    Ok(Json(render(&id, &timer)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn laps_and_stop() {
+        let store = TimerStore::default();
+        let timer = Timer {
+            name: None,
+            started_at: Utc::now(),
+            started: Instant::now(),
+            laps: vec![Duration::from_millis(1500), Duration::from_millis(4000)],
+            stopped: Some(Duration::from_millis(5000)),
+        };
+        store.timers.lock().unwrap().insert("0".to_string(), timer);
+
+        let body = store.with_timer("0", |_| Ok(())).unwrap();
+        assert_eq!(body["running"], false);
+        assert_eq!(body["elapsed_ms"], 5000);
+        assert_eq!(body["laps"][1]["split_ms"], 2500);
+        assert!(store.with_timer("1", |_| Ok(())).is_err());
+    }
+
+    struct Saved(Vec<(String, SavedTimer)>);
+
+    impl Persistence for Saved {
+        fn load(&self) -> Vec<(String, SavedTimer)> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn restored_timers() {
+        let started_at = Utc::now() - chrono::Duration::seconds(60);
+        let saved = SavedTimer {
+            name: Some("build".to_string()),
+            started_at,
+            laps: vec![Duration::from_secs(30)],
+            stopped: None,
+        };
+        let store = TimerStore::new(Box::new(Saved(vec![("4".to_string(), saved)])));
+
+        let body = store.with_timer("4", |_| Ok(())).unwrap();
+        assert_eq!(body["running"], true);
+        assert!(body["elapsed_ms"].as_u64().unwrap() >= 60_000);
+        assert_eq!(store.next_id.load(Ordering::Relaxed), 5);
+    }
+}
@@ -0,0 +1,727 @@
+use axum::http::HeaderMap;
+use axum::Json;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Timelike, Utc,
+};
+use chrono_tz::{OffsetComponents, Tz};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::locale::{self, Clock};
+use crate::parse_date;
+use crate::profile::parse_iso8601_local;
+use crate::query::Query;
+use crate::tzdata;
+
+/// Timezone of requests that don't name one, set once at startup.
+static DEFAULT_TZ: OnceLock<Tz> = OnceLock::new();
+
+/// Sets the timezone returned by [`parse_tz`] when none is given, UTC unless called.
+pub fn set_default(tz: Tz) {
+    if DEFAULT_TZ.set(tz).is_err() {
+        tracing::warn!("The default timezone is already set");
+    }
+}
+
+/// Parses an IANA timezone name such as `Europe/Rome`, defaulting to the configured
+/// timezone when none is given.
+pub fn parse_tz(name: Option<&str>) -> Result<Tz, AppError> {
+    match name {
+        Some(name) => name
+            .parse()
+            .map_err(|_| AppError::BadRequest(format!("Unknown timezone {}", name))),
+        None => Ok(*DEFAULT_TZ.get().unwrap_or(&Tz::UTC)),
+    }
+}
+
+/// Parses a timezone given as a path segment, where the `/` in `Europe/Rome` has to be
+/// sent percent-encoded.
+pub fn parse_zone_path(zone: &str) -> Result<Tz, AppError> {
+    zone.replace("%2F", "/")
+        .replace("%2f", "/")
+        .parse()
+        .map_err(|_| AppError::NotFound(format!("Unknown timezone {}", zone)))
+}
+
+/// First instant of `date` in `tz`, which is not always midnight: some zones skip it
+/// when switching to daylight saving time.
+pub fn start_of_day(tz: Tz, date: NaiveDate) -> DateTime<Tz> {
+    let mut time = date.and_hms_opt(0, 0, 0).unwrap();
+    loop {
+        if let Some(instant) = tz.from_local_datetime(&time).earliest() {
+            return instant;
+        }
+        time += Duration::minutes(15);
+    }
+}
+
+/// Resolves a wall-clock time to an instant, using its own offset when it has one and
+/// `tz` otherwise. Times repeated by DST resolve to their first occurrence, skipped ones
+/// to `None`.
+pub fn resolve_local(
+    local: NaiveDateTime,
+    offset: Option<FixedOffset>,
+    tz: Tz,
+) -> Option<DateTime<Utc>> {
+    match offset {
+        Some(offset) => offset
+            .from_local_datetime(&local)
+            .single()
+            .map(|instant| instant.with_timezone(&Utc)),
+        None => tz
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|instant| instant.with_timezone(&Utc)),
+    }
+}
+
+/// Parses a unix timestamp, or an ISO 8601 date-time read in `tz` unless it has an offset.
+pub fn parse_in_zone(date: &str, tz: Tz) -> Result<DateTime<Utc>, AppError> {
+    match parse_iso8601_local(date) {
+        Some((local, offset)) => resolve_local(local, offset, tz).ok_or(AppError::InvalidDate),
+        None => parse_date(date),
+    }
+}
+
+/// UTC offset of `tz` at the given instant, in seconds.
+pub fn offset_at(tz: Tz, instant: DateTime<Utc>) -> i32 {
+    match tzdata::local_time_type(tz, instant) {
+        Some(loaded) => loaded.ut_offset(),
+        None => tz
+            .offset_from_utc_datetime(&instant.naive_utc())
+            .fix()
+            .local_minus_utc(),
+    }
+}
+
+/// Abbreviation of the offset of `tz` at the given instant, such as `CEST`.
+pub fn abbreviation_at(tz: Tz, instant: DateTime<Utc>) -> String {
+    match tzdata::local_time_type(tz, instant) {
+        Some(loaded) => loaded.time_zone_designation().to_string(),
+        None => instant.with_timezone(&tz).format("%Z").to_string(),
+    }
+}
+
+/// Whether daylight saving time is in effect in `tz` at the given instant.
+pub fn is_dst(tz: Tz, instant: DateTime<Utc>) -> bool {
+    match tzdata::local_time_type(tz, instant) {
+        Some(loaded) => loaded.is_dst(),
+        None => {
+            tz.offset_from_utc_datetime(&instant.naive_utc())
+                .dst_offset()
+                != Duration::zero()
+        }
+    }
+}
+
+/// A change of UTC offset in a timezone.
+#[derive(Debug, PartialEq)]
+pub struct Transition {
+    /// First instant using the new offset.
+    pub at: DateTime<Utc>,
+    pub before: i32,
+    pub after: i32,
+}
+
+impl Transition {
+    /// Wall-clock times that are skipped (when moving forward) or repeated (when moving
+    /// backward) by the transition, as a `[start, end)` range.
+    pub fn wall_clock_range(&self) -> (NaiveDateTime, NaiveDateTime) {
+        let start = self.at.naive_utc() + Duration::seconds(self.before.min(self.after) as i64);
+        let end = self.at.naive_utc() + Duration::seconds(self.before.max(self.after) as i64);
+        (start, end)
+    }
+
+    pub fn is_gap(&self) -> bool {
+        self.after > self.before
+    }
+}
+
+/// Lists the offset transitions of `tz` in `[from, to)`.
+///
+/// Offsets are sampled every day and each change is then narrowed down to the second,
+/// which assumes no zone changes offset twice within the same day.
+pub fn transitions(tz: Tz, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Transition> {
+    let mut transitions = Vec::new();
+    let mut current = from;
+    let mut offset = offset_at(tz, current);
+
+    while current < to {
+        let next = (current + Duration::days(1)).min(to);
+        let next_offset = offset_at(tz, next);
+        if next_offset != offset {
+            let (mut low, mut high) = (current, next);
+            while high - low > Duration::seconds(1) {
+                let middle = low + Duration::seconds((high - low).num_seconds() / 2);
+                if offset_at(tz, middle) == offset {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            transitions.push(Transition {
+                at: high,
+                before: offset,
+                after: next_offset,
+            });
+            offset = next_offset;
+        }
+        current = next;
+    }
+
+    transitions
+}
+
+/// Parses a fixed UTC offset such as `+05:30`, `-0800`, `+02` or `Z`.
+pub fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    if offset.eq_ignore_ascii_case("z") {
+        return FixedOffset::east_opt(0);
+    }
+
+    let sign = match offset.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let digits = offset[1..].replace(':', "");
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Shows an instant in a fixed UTC offset, as found in offset-only data sources.
+pub async fn offset_handler(
+    Path((date, offset)): Path<(String, String)>,
+) -> Result<Json<Value>, AppError> {
+    let date = parse_date(&date)?;
+    let offset = parse_offset(&offset)
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid offset {}", offset)))?;
+    let local = date.with_timezone(&offset);
+
+    Ok(Json(json!({
+        "unix": date.timestamp(),
+        "utc": date.to_rfc2822(),
+        "offset": offset.to_string(),
+        "local": local.to_rfc3339(),
+        "local_rfc2822": local.to_rfc2822(),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogueParams {
+    /// Case-insensitive substring of the zone name.
+    q: Option<String>,
+    /// Only zones currently at this UTC offset.
+    offset: Option<String>,
+}
+
+/// Lists the supported IANA zones with their current offset.
+pub async fn catalogue_handler(
+    Query(params): Query<CatalogueParams>,
+) -> Result<Json<Value>, AppError> {
+    let query = params.q.map(|q| q.to_lowercase());
+    let offset = match params.offset.as_deref() {
+        Some(offset) => Some(
+            parse_offset(offset)
+                .ok_or_else(|| AppError::BadRequest(format!("Invalid offset {}", offset)))?
+                .local_minus_utc(),
+        ),
+        None => None,
+    };
+
+    let now = Utc::now();
+    let zones: Vec<Value> = chrono_tz::TZ_VARIANTS
+        .iter()
+        .filter(|tz| {
+            query
+                .as_ref()
+                .is_none_or(|query| tz.name().to_lowercase().contains(query))
+        })
+        .map(|tz| (tz, offset_at(*tz, now)))
+        .filter(|(_, current)| offset.is_none_or(|offset| offset == *current))
+        .map(|(tz, current)| {
+            json!({
+                "name": tz.name(),
+                "offset": FixedOffset::east_opt(current).unwrap().to_string(),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "count": zones.len(),
+        "zones": zones,
+    })))
+}
+
+/// Zones using an abbreviation during the given year, grouped by the offset it stands for.
+pub fn resolve_abbreviation(abbreviation: &str, year: i32) -> BTreeMap<i32, Vec<Tz>> {
+    // winter and summer, on both hemispheres
+    let samples = [
+        Utc.with_ymd_and_hms(year, 1, 15, 12, 0, 0).unwrap(),
+        Utc.with_ymd_and_hms(year, 7, 15, 12, 0, 0).unwrap(),
+    ];
+
+    let mut offsets: BTreeMap<i32, Vec<Tz>> = BTreeMap::new();
+    for tz in chrono_tz::TZ_VARIANTS.iter() {
+        for sample in &samples {
+            let local = sample.with_timezone(tz);
+            if local
+                .format("%Z")
+                .to_string()
+                .eq_ignore_ascii_case(abbreviation)
+            {
+                let zones = offsets.entry(offset_at(*tz, *sample)).or_default();
+                if !zones.contains(tz) {
+                    zones.push(*tz);
+                }
+            }
+        }
+    }
+    offsets
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AbbreviationParams {
+    /// Preferred region, such as `America`, restricting the candidate zones.
+    region: Option<String>,
+}
+
+/// Lists the zones and offsets an abbreviation like `CST` may refer to. Abbreviations
+/// standing for more than one offset are reported as ambiguous rather than guessed.
+pub async fn abbreviation_handler(
+    Path(abbreviation): Path<String>,
+    Query(params): Query<AbbreviationParams>,
+) -> Result<Json<Value>, AppError> {
+    let mut offsets = resolve_abbreviation(&abbreviation, Utc::now().year());
+    if let Some(region) = &params.region {
+        let prefix = format!("{}/", region.to_lowercase());
+        for zones in offsets.values_mut() {
+            zones.retain(|tz| tz.name().to_lowercase().starts_with(&prefix));
+        }
+        offsets.retain(|_, zones| !zones.is_empty());
+    }
+    if offsets.is_empty() {
+        return Err(AppError::NotFound(format!(
+            "Unknown abbreviation {}",
+            abbreviation
+        )));
+    }
+
+    let candidates: Vec<Value> = offsets
+        .iter()
+        .map(|(offset, zones)| {
+            let zones: Vec<&str> = zones.iter().map(|tz| tz.name()).collect();
+            json!({
+                "offset": FixedOffset::east_opt(*offset).unwrap().to_string(),
+                "zones": zones,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "abbreviation": abbreviation.to_uppercase(),
+        "ambiguous": offsets.len() > 1,
+        "candidates": candidates,
+    })))
+}
+
+/// Local representation of an instant in a zone.
+pub fn describe_local(instant: DateTime<Utc>, tz: Tz) -> Value {
+    let offset = FixedOffset::east_opt(offset_at(tz, instant)).unwrap();
+    json!({
+        "tz": tz.name(),
+        "local": instant.with_timezone(&offset).to_rfc3339(),
+        "offset": offset.to_string(),
+        "abbreviation": abbreviation_at(tz, instant),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConvertParams {
+    /// A unix timestamp, or an ISO 8601 date-time read in `from` unless it has an offset.
+    date: String,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// Converts an instant between two timezones.
+pub async fn convert_handler(Query(params): Query<ConvertParams>) -> Result<Json<Value>, AppError> {
+    let from = parse_tz(params.from.as_deref())?;
+    let to = parse_tz(params.to.as_deref())?;
+    let instant = parse_in_zone(&params.date, from)?;
+
+    Ok(Json(json!({
+        "unix": instant.timestamp(),
+        "utc": instant.to_rfc2822(),
+        "from": describe_local(instant, from),
+        "to": describe_local(instant, to),
+    })))
+}
+
+/// Most zones a single world clock request can ask for.
+const MAX_WORLD_CLOCK_ZONES: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct WorldClockParams {
+    /// Comma-separated zone names such as `Europe/Rome,Asia/Tokyo`.
+    zones: String,
+    /// Adds the wall-clock time of each zone, as does a supported `Accept-Language`.
+    clock: Option<Clock>,
+}
+
+/// Local time, offset and DST status of each zone at `now`, with its wall-clock time when
+/// a `clock` is given.
+pub fn world_clock(zones: &[Tz], now: DateTime<Utc>, clock: Option<Clock>) -> Vec<Value> {
+    zones
+        .iter()
+        .map(|tz| {
+            let mut described = describe_local(now, *tz);
+            described["dst"] = json!(is_dst(*tz, now));
+            if let Some(clock) = clock {
+                described["time"] = json!(clock.format_time(now.with_timezone(tz).time()));
+            }
+            described
+        })
+        .collect()
+}
+
+/// The current time in several zones at once.
+pub async fn world_clock_handler(
+    Query(params): Query<WorldClockParams>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let clock = match params.clock {
+        Some(clock) => Some(clock),
+        None => locale::negotiate(&headers, None)?.map(|locale| locale.clock),
+    };
+    let zones = params
+        .zones
+        .split(',')
+        .map(str::trim)
+        .filter(|zone| !zone.is_empty())
+        .map(|zone| parse_tz(Some(zone)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if zones.is_empty() || zones.len() > MAX_WORLD_CLOCK_ZONES {
+        return Err(AppError::BadRequest(format!(
+            "Between 1 and {} zones are required",
+            MAX_WORLD_CLOCK_ZONES
+        )));
+    }
+    let now = Utc::now();
+
+    Ok(Json(json!({
+        "unix": now.timestamp(),
+        "utc": now.to_rfc2822(),
+        "zones": world_clock(&zones, now, clock),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ZoneOffsetParams {
+    /// A unix timestamp, or an ISO 8601 date-time read in the zone. Defaults to now.
+    at: Option<String>,
+}
+
+/// UTC offset, abbreviation and DST status of a zone at an instant.
+pub async fn zone_offset_handler(
+    Path(zone): Path<String>,
+    Query(params): Query<ZoneOffsetParams>,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_zone_path(&zone)?;
+    let at = match params.at.as_deref() {
+        Some(at) => parse_in_zone(at, tz)?,
+        None => Utc::now(),
+    };
+    let offset = offset_at(tz, at);
+
+    Ok(Json(json!({
+        "tz": tz.name(),
+        "unix": at.timestamp(),
+        "utc": at.to_rfc2822(),
+        "offset": FixedOffset::east_opt(offset).unwrap().to_string(),
+        "offset_seconds": offset,
+        "abbreviation": abbreviation_at(tz, at),
+        "dst": is_dst(tz, at),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransitionsParams {
+    /// Defaults to the current year.
+    year: Option<i32>,
+}
+
+/// Lists the DST transitions of a zone in a year, with the wall-clock gap or overlap
+/// each one creates.
+pub async fn transitions_handler(
+    Path(zone): Path<String>,
+    Query(params): Query<TransitionsParams>,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_zone_path(&zone)?;
+    let year = params.year.unwrap_or_else(|| Utc::now().year());
+    let from = Utc
+        .with_ymd_and_hms(year, 1, 1, 0, 0, 0)
+        .single()
+        .ok_or(AppError::InvalidDate)?;
+    let to = Utc
+        .with_ymd_and_hms(year + 1, 1, 1, 0, 0, 0)
+        .single()
+        .ok_or(AppError::InvalidDate)?;
+
+    let transitions: Vec<Value> = transitions(tz, from, to)
+        .iter()
+        .map(|transition| {
+            let (start, end) = transition.wall_clock_range();
+            json!({
+                "unix": transition.at.timestamp(),
+                "utc": transition.at.to_rfc2822(),
+                "offset_before": FixedOffset::east_opt(transition.before).unwrap().to_string(),
+                "offset_after": FixedOffset::east_opt(transition.after).unwrap().to_string(),
+                "kind": if transition.is_gap() { "gap" } else { "overlap" },
+                "local_start": start.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                "local_end": end.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "tz": tz.name(),
+        "year": year,
+        "transitions": transitions,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SafeTimesParams {
+    /// Wall-clock window to inspect, as `HH:MM-HH:MM`. Defaults to the whole day.
+    window: Option<String>,
+    /// Number of years to inspect starting from the current one.
+    years: Option<i32>,
+}
+
+fn parse_window(window: &str) -> Option<(u32, u32)> {
+    let (start, end) = window.split_once('-')?;
+    let minutes = |time: &str| {
+        NaiveTime::parse_from_str(time, "%H:%M")
+            .ok()
+            .map(|time| time.hour() * 60 + time.minute())
+    };
+    Some((minutes(start)?, minutes(end)?))
+}
+
+/// Collapses a sorted list of minutes of the day into `HH:MM-HH:MM` ranges, end excluded.
+fn minute_ranges(minutes: &[u32]) -> Vec<String> {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for minute in minutes {
+        match ranges.last_mut() {
+            Some((_, end)) if *end == *minute => *end += 1,
+            _ => ranges.push((*minute, minute + 1)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            format!(
+                "{:02}:{:02}-{:02}:{:02}",
+                start / 60,
+                start % 60,
+                end / 60,
+                end % 60
+            )
+        })
+        .collect()
+}
+
+/// Reports which wall-clock times in a window are skipped or repeated by DST transitions,
+/// so that jobs can be scheduled at times that always fire exactly once.
+pub async fn safe_times_handler(
+    Path(zone): Path<String>,
+    Query(params): Query<SafeTimesParams>,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_zone_path(&zone)?;
+    let (start, end) = match params.window.as_deref() {
+        Some(window) => parse_window(window)
+            .ok_or_else(|| AppError::BadRequest("Invalid window".to_string()))?,
+        None => (0, 24 * 60),
+    };
+    let years = params.years.unwrap_or(10).clamp(1, 100);
+
+    let year = Utc::now().year();
+    let from = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+    let to = Utc.with_ymd_and_hms(year + years, 1, 1, 0, 0, 0).unwrap();
+
+    let mut skipped = [false; 24 * 60];
+    let mut repeated = [false; 24 * 60];
+    for transition in transitions(tz, from, to) {
+        let (mut time, end) = transition.wall_clock_range();
+        while time < end {
+            let minute = (time.hour() * 60 + time.minute()) as usize;
+            if transition.is_gap() {
+                skipped[minute] = true;
+            } else {
+                repeated[minute] = true;
+            }
+            time += Duration::minutes(1);
+        }
+    }
+
+    // a window ending before it starts wraps around midnight
+    let window: Vec<u32> = if start < end {
+        (start..end).collect()
+    } else {
+        (start..24 * 60).chain(0..end).collect()
+    };
+    let filter = |flags: &[bool]| -> Vec<u32> {
+        let mut minutes: Vec<u32> = window
+            .iter()
+            .copied()
+            .filter(|minute| flags[*minute as usize])
+            .collect();
+        minutes.sort_unstable();
+        minutes
+    };
+    let skipped = filter(&skipped);
+    let repeated = filter(&repeated);
+
+    Ok(Json(json!({
+        "tz": tz.name(),
+        "from_year": year,
+        "to_year": year + years - 1,
+        "safe": skipped.is_empty() && repeated.is_empty(),
+        "skipped": minute_ranges(&skipped),
+        "repeated": minute_ranges(&repeated),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rome_transitions() {
+        let tz: Tz = "Europe/Rome".parse().unwrap();
+        let from = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            transitions(tz, from, to),
+            vec![
+                Transition {
+                    at: Utc.with_ymd_and_hms(2021, 3, 28, 1, 0, 0).unwrap(),
+                    before: 3600,
+                    after: 7200,
+                },
+                Transition {
+                    at: Utc.with_ymd_and_hms(2021, 10, 31, 1, 0, 0).unwrap(),
+                    before: 7200,
+                    after: 3600,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn wall_clock_ranges() {
+        let gap = Transition {
+            at: Utc.with_ymd_and_hms(2021, 3, 28, 1, 0, 0).unwrap(),
+            before: 3600,
+            after: 7200,
+        };
+        assert!(gap.is_gap());
+        assert_eq!(
+            gap.wall_clock_range(),
+            (
+                NaiveDate::from_ymd_opt(2021, 3, 28)
+                    .unwrap()
+                    .and_hms_opt(2, 0, 0)
+                    .unwrap(),
+                NaiveDate::from_ymd_opt(2021, 3, 28)
+                    .unwrap()
+                    .and_hms_opt(3, 0, 0)
+                    .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(parse_offset("+05:30"), FixedOffset::east_opt(19800));
+        assert_eq!(parse_offset("-0800"), FixedOffset::west_opt(28800));
+        assert_eq!(parse_offset("+02"), FixedOffset::east_opt(7200));
+        assert_eq!(parse_offset("Z"), FixedOffset::east_opt(0));
+        assert_eq!(parse_offset("05:30"), None);
+        assert_eq!(parse_offset("+05:75"), None);
+        assert_eq!(parse_offset("+5:30"), None);
+    }
+
+    #[test]
+    fn daylight_saving() {
+        let rome = Tz::Europe__Rome;
+        assert!(is_dst(
+            rome,
+            Utc.with_ymd_and_hms(2021, 7, 1, 12, 0, 0).unwrap()
+        ));
+        assert!(!is_dst(
+            rome,
+            Utc.with_ymd_and_hms(2021, 12, 1, 12, 0, 0).unwrap()
+        ));
+        assert!(!is_dst(
+            Tz::UTC,
+            Utc.with_ymd_and_hms(2021, 7, 1, 12, 0, 0).unwrap()
+        ));
+    }
+
+    #[test]
+    fn abbreviations() {
+        let ist = resolve_abbreviation("IST", 2021);
+        assert!(ist.len() > 1);
+        assert!(ist[&19800].contains(&Tz::Asia__Kolkata));
+
+        let cest = resolve_abbreviation("cest", 2021);
+        assert_eq!(cest.keys().collect::<Vec<_>>(), vec![&7200]);
+        assert!(cest[&7200].contains(&Tz::Europe__Rome));
+    }
+
+    #[test]
+    fn windows() {
+        assert_eq!(parse_window("02:00-03:30"), Some((120, 210)));
+        assert_eq!(parse_window("02:00"), None);
+        assert_eq!(
+            minute_ranges(&[120, 121, 122, 200]),
+            vec!["02:00-02:03", "03:20-03:21"]
+        );
+    }
+
+    #[test]
+    fn world_clock_zones() {
+        let zones = [Tz::Europe__Rome, Tz::Asia__Tokyo, Tz::America__New_York];
+        let now = Utc.with_ymd_and_hms(2021, 7, 1, 12, 0, 0).unwrap();
+        let clocks = world_clock(&zones, now, None);
+        assert_eq!(clocks[0]["local"], json!("2021-07-01T14:00:00+02:00"));
+        assert_eq!(clocks[0]["dst"], json!(true));
+        assert_eq!(clocks[1]["offset"], json!("+09:00"));
+        assert_eq!(clocks[1]["dst"], json!(false));
+        assert_eq!(clocks[2]["abbreviation"], json!("EDT"));
+        assert!(clocks[0].get("time").is_none());
+
+        let clocks = world_clock(&zones, now, Some(Clock::H12));
+        assert_eq!(clocks[0]["time"], json!("2:00:00 PM"));
+        assert_eq!(clocks[2]["time"], json!("8:00:00 AM"));
+    }
+}
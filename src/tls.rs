@@ -0,0 +1,61 @@
+//! HTTPS termination with rustls, for deployments without a reverse proxy.
+//!
+//! Enabled by passing `--tls-cert` and `--tls-key`, or by setting `TIMESTAMP_TLS_CERT`
+//! and `TIMESTAMP_TLS_KEY`, to PEM files. Sending `SIGHUP` reloads both files, so that
+//! renewed certificates are picked up without dropping connections.
+
+use axum_server::tls_rustls::RustlsConfig;
+use std::path::PathBuf;
+
+use crate::config::argument;
+
+#[derive(Clone, Debug)]
+pub struct TlsPaths {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Reads the certificate and key paths, command line arguments taking precedence over
+/// environment variables. Returns `None` when serving plain HTTP.
+pub fn paths(args: &[String]) -> Result<Option<TlsPaths>, String> {
+    let cert = argument(args, "--tls-cert").or_else(|| std::env::var("TIMESTAMP_TLS_CERT").ok());
+    let key = argument(args, "--tls-key").or_else(|| std::env::var("TIMESTAMP_TLS_KEY").ok());
+    match (cert, key) {
+        (Some(cert), Some(key)) => Ok(Some(TlsPaths {
+            cert: cert.into(),
+            key: key.into(),
+        })),
+        (None, None) => Ok(None),
+        _ => Err("Both a TLS certificate and a key are needed".to_string()),
+    }
+}
+
+pub async fn load(paths: &TlsPaths) -> std::io::Result<RustlsConfig> {
+    RustlsConfig::from_pem_file(&paths.cert, &paths.key).await
+}
+
+/// Reloads the certificate and key whenever the process receives `SIGHUP`. A failed
+/// reload is logged and the previous certificate kept.
+#[cfg(unix)]
+pub fn reload_on_sighup(config: RustlsConfig, paths: TlsPaths) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                tracing::error!("Can't listen for SIGHUP, TLS reload disabled: {}", e);
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            match config.reload_from_pem_file(&paths.cert, &paths.key).await {
+                Ok(()) => tracing::info!("Reloaded the TLS certificate"),
+                Err(e) => tracing::error!("Can't reload the TLS certificate: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn reload_on_sighup(_config: RustlsConfig, _paths: TlsPaths) {}
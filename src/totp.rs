@@ -0,0 +1,74 @@
+//! RFC 6238 time steps, to compare the counter of a one-time password with this clock.
+
+use axum::Json;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::parse_date;
+use crate::query::Query;
+
+/// The time step recommended by the RFC, in seconds.
+const DEFAULT_PERIOD: i64 = 30;
+
+/// The counter at `unix`, and the start of its time step. `None` before `t0`, where the
+/// RFC doesn't define one.
+pub fn counter(unix: i64, t0: i64, period: i64) -> Option<(u64, i64)> {
+    let elapsed = unix.checked_sub(t0).filter(|elapsed| *elapsed >= 0)?;
+    let counter = elapsed / period;
+    Some((counter as u64, t0 + counter * period))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CounterParams {
+    /// Length of a time step in seconds.
+    period: Option<i64>,
+    /// Unix time the counting starts at.
+    t0: Option<i64>,
+    /// Instant to count at, defaults to now.
+    at: Option<String>,
+}
+
+pub async fn counter_handler(Query(params): Query<CounterParams>) -> Result<Json<Value>, AppError> {
+    let period = params.period.unwrap_or(DEFAULT_PERIOD);
+    if period <= 0 {
+        return Err(AppError::BadRequest(
+            "The period must be a positive number of seconds".to_string(),
+        ));
+    }
+    let t0 = params.t0.unwrap_or(0);
+    let at = match params.at {
+        Some(at) => parse_date(&at)?,
+        None => Utc::now(),
+    };
+    let unix = at.timestamp();
+    let (counter, start) = counter(unix, t0, period)
+        .ok_or_else(|| AppError::BadRequest("The instant is before t0".to_string()))?;
+
+    Ok(Json(json!({
+        "unix": unix,
+        "period": period,
+        "t0": t0,
+        "counter": counter,
+        // the 8-byte big-endian message that gets signed, as in the RFC test vectors
+        "counter_hex": format!("{:016X}", counter),
+        "window_start": start,
+        "window_end": start + period,
+        "remaining": start + period - unix,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc_test_vectors() {
+        assert_eq!(counter(59, 0, 30), Some((1, 30)));
+        assert_eq!(counter(1111111109, 0, 30), Some((0x023523EC, 1111111080)));
+        assert_eq!(counter(2000000000, 0, 30), Some((0x03F940AA, 1999999980)));
+        assert_eq!(counter(20000000000, 0, 30), Some((0x27BC86AA, 19999999980)));
+        assert_eq!(counter(9, 10, 30), None);
+    }
+}
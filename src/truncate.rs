@@ -0,0 +1,268 @@
+//! Alignment of instants to the hour, day, week, month, quarter or year boundaries of a
+//! timezone, and the start and end of the period an instant falls in.
+//!
+//! Boundaries are wall-clock times: a day starts at local midnight and a week on the
+//! configured `week_start`, Monday by default.
+//! Rounding picks the nearest boundary in elapsed time, ties going up, so a day shortened
+//! by DST still rounds at its real middle.
+
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::calendar::add_months;
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::query::Query;
+use crate::timezone::{parse_in_zone, parse_tz, resolve_local};
+use crate::week::{start_of_week, WeekSettings};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Floor,
+    Ceil,
+    Round,
+}
+
+/// The last boundary at or before `local`, and the one after it.
+fn surrounding(
+    local: NaiveDateTime,
+    to: Boundary,
+    week_start: Weekday,
+) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let date = local.date();
+    Some(match to {
+        Boundary::Hour => {
+            let start = date.and_hms_opt(local.hour(), 0, 0).unwrap();
+            (start, start + Duration::hours(1))
+        }
+        Boundary::Day => {
+            let start = date.and_hms_opt(0, 0, 0).unwrap();
+            (start, start + Duration::days(1))
+        }
+        Boundary::Week => {
+            let start = start_of_week(date, week_start)
+                .and_hms_opt(0, 0, 0)
+                .unwrap();
+            (start, start + Duration::weeks(1))
+        }
+        Boundary::Month => {
+            let first = date.with_day(1)?;
+            (
+                first.and_hms_opt(0, 0, 0).unwrap(),
+                add_months(first, 1)?.and_hms_opt(0, 0, 0).unwrap(),
+            )
+        }
+        Boundary::Quarter => {
+            let first = NaiveDate::from_ymd_opt(date.year(), (date.month0() / 3) * 3 + 1, 1)?;
+            (
+                first.and_hms_opt(0, 0, 0).unwrap(),
+                add_months(first, 3)?.and_hms_opt(0, 0, 0).unwrap(),
+            )
+        }
+        Boundary::Year => (
+            NaiveDate::from_ymd_opt(date.year(), 1, 1)?
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+            NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)?
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        ),
+    })
+}
+
+/// Resolves a boundary, moving those skipped by DST an hour later, the length of most gaps.
+fn resolve(local: NaiveDateTime, tz: Tz) -> Option<DateTime<Utc>> {
+    resolve_local(local, None, tz).or_else(|| resolve_local(local + Duration::hours(1), None, tz))
+}
+
+pub fn align(
+    instant: DateTime<Utc>,
+    tz: Tz,
+    to: Boundary,
+    mode: Mode,
+    week_start: Weekday,
+) -> Option<DateTime<Utc>> {
+    let local = instant.with_timezone(&tz).naive_local();
+    let (floor, ceil) = surrounding(local, to, week_start)?;
+    let floor = resolve(floor, tz)?;
+    if floor == instant {
+        return Some(floor);
+    }
+    let ceil = resolve(ceil, tz)?;
+    Some(match mode {
+        Mode::Floor => floor,
+        Mode::Ceil => ceil,
+        Mode::Round if instant - floor < ceil - instant => floor,
+        Mode::Round => ceil,
+    })
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    Start,
+    End,
+}
+
+/// The start of the period `instant` falls in, or its end: the start of the next one, so
+/// that consecutive periods tile without gaps.
+pub fn period_edge(
+    instant: DateTime<Utc>,
+    tz: Tz,
+    period: Boundary,
+    edge: Edge,
+    week_start: Weekday,
+) -> Option<DateTime<Utc>> {
+    let local = instant.with_timezone(&tz).naive_local();
+    let (start, end) = surrounding(local, period, week_start)?;
+    match edge {
+        Edge::Start => resolve(start, tz),
+        Edge::End => resolve(end, tz),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TruncateParams {
+    to: Boundary,
+    mode: Option<Mode>,
+    tz: Option<String>,
+    /// First day of the week, `week_start` of the configuration by default.
+    week_start: Option<String>,
+}
+
+pub async fn truncate_handler(
+    Path(date): Path<String>,
+    Query(params): Query<TruncateParams>,
+    State(settings): State<WeekSettings>,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(params.tz.as_deref())?;
+    let week_start = settings.week_start(params.week_start.as_deref())?;
+    let date = parse_in_zone(&date, tz)?;
+    let mode = params.mode.unwrap_or(Mode::Floor);
+    let aligned = align(date, tz, params.to, mode, week_start).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(json!({
+        "unix": aligned.timestamp(),
+        "utc": aligned.to_rfc2822(),
+        "local": aligned.with_timezone(&tz).to_rfc3339(),
+        "tz": tz.name(),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoundaryParams {
+    period: Boundary,
+    /// The start by default.
+    edge: Option<Edge>,
+    tz: Option<String>,
+    /// First day of the week, `week_start` of the configuration by default.
+    week_start: Option<String>,
+}
+
+pub async fn boundary_handler(
+    Path(date): Path<String>,
+    Query(params): Query<BoundaryParams>,
+    State(settings): State<WeekSettings>,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(params.tz.as_deref())?;
+    let week_start = settings.week_start(params.week_start.as_deref())?;
+    let date = parse_in_zone(&date, tz)?;
+    let edge = params.edge.unwrap_or(Edge::Start);
+    let boundary =
+        period_edge(date, tz, params.period, edge, week_start).ok_or(AppError::InvalidDate)?;
+
+    Ok(Json(json!({
+        "unix": boundary.timestamp(),
+        "utc": boundary.to_rfc2822(),
+        "local": boundary.with_timezone(&tz).to_rfc3339(),
+        "tz": tz.name(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn alignment() {
+        // Wednesday, 14:40 in Rome
+        let instant = Utc.with_ymd_and_hms(2016, 12, 21, 13, 40, 0).unwrap();
+        let rome: Tz = "Europe/Rome".parse().unwrap();
+        let at = |to, mode| align(instant, rome, to, mode, Weekday::Mon).unwrap();
+
+        assert_eq!(
+            at(Boundary::Hour, Mode::Floor),
+            Utc.with_ymd_and_hms(2016, 12, 21, 13, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at(Boundary::Hour, Mode::Round),
+            Utc.with_ymd_and_hms(2016, 12, 21, 14, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at(Boundary::Day, Mode::Ceil),
+            Utc.with_ymd_and_hms(2016, 12, 21, 23, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at(Boundary::Week, Mode::Floor),
+            Utc.with_ymd_and_hms(2016, 12, 18, 23, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at(Boundary::Month, Mode::Round),
+            Utc.with_ymd_and_hms(2016, 12, 31, 23, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn period_edges() {
+        let sydney: Tz = "Australia/Sydney".parse().unwrap();
+        // May 15th in Sydney, 10 hours ahead of UTC
+        let instant = Utc.with_ymd_and_hms(2024, 5, 15, 3, 0, 0).unwrap();
+        let edge = |period, edge| period_edge(instant, sydney, period, edge, Weekday::Mon).unwrap();
+
+        assert_eq!(
+            edge(Boundary::Month, Edge::Start),
+            Utc.with_ymd_and_hms(2024, 4, 30, 14, 0, 0).unwrap()
+        );
+        assert_eq!(
+            edge(Boundary::Quarter, Edge::Start),
+            Utc.with_ymd_and_hms(2024, 3, 31, 13, 0, 0).unwrap()
+        );
+        // daylight saving time starts again in October
+        assert_eq!(
+            edge(Boundary::Year, Edge::End),
+            Utc.with_ymd_and_hms(2024, 12, 31, 13, 0, 0).unwrap()
+        );
+
+        let midnight = Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap();
+        assert_eq!(
+            period_edge(midnight, Tz::UTC, Boundary::Day, Edge::End, Weekday::Mon),
+            Some(Utc.with_ymd_and_hms(2016, 12, 26, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn boundaries_stay_put() {
+        let midnight = Utc.with_ymd_and_hms(2016, 12, 25, 0, 0, 0).unwrap();
+        assert_eq!(
+            align(midnight, Tz::UTC, Boundary::Day, Mode::Ceil, Weekday::Mon),
+            Some(midnight)
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Zone rules loaded at startup from a zoneinfo directory, such as `/usr/share/zoneinfo`,
+//! for deployments that can't wait for a release built with newer rules.
+//!
+//! The directory is configured with `tzdata_dir`. Its compiled TZif files take precedence
+//! over the rules built into chrono-tz for the UTC offsets, DST status and abbreviations
+//! reported by the zone endpoints, transitions included. Calendar arithmetic in a zone,
+//! such as truncation or ranges, still uses the built-in rules, as do zones missing from
+//! the directory.
+
+use axum::Json;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tz::{LocalTimeType, TimeZone};
+
+static LOADED: OnceLock<ZoneDb> = OnceLock::new();
+
+pub struct ZoneDb {
+    dir: PathBuf,
+    /// The IANA release, such as `2024a`, when the directory records it.
+    version: Option<String>,
+    zones: HashMap<&'static str, TimeZone>,
+}
+
+/// Reads the release from `tzdata.zi`, whose first line is `# version 2024a`, or from the
+/// `+VERSION` file some distributions ship instead.
+fn read_version(dir: &Path) -> Option<String> {
+    let zi = std::fs::read_to_string(dir.join("tzdata.zi")).ok();
+    let from_zi = zi.as_deref().and_then(|zi| {
+        zi.lines()
+            .next()?
+            .strip_prefix("# version ")
+            .map(|version| version.trim().to_string())
+    });
+    from_zi.or_else(|| {
+        std::fs::read_to_string(dir.join("+VERSION"))
+            .ok()
+            .map(|version| version.trim().to_string())
+            .filter(|version| !version.is_empty())
+    })
+}
+
+/// Loads the TZif file of every zone known to chrono-tz. Fails when none of them is found,
+/// which usually means the path is wrong.
+pub fn load(dir: &Path) -> Result<ZoneDb, String> {
+    let mut zones = HashMap::new();
+    for tz in chrono_tz::TZ_VARIANTS.iter() {
+        let path = dir.join(tz.name());
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        let zone =
+            TimeZone::from_tz_data(&data).map_err(|e| format!("{}: {}", path.display(), e))?;
+        zones.insert(tz.name(), zone);
+    }
+    if zones.is_empty() {
+        return Err(format!("{}: no zoneinfo files found", dir.display()));
+    }
+    Ok(ZoneDb {
+        dir: dir.to_path_buf(),
+        version: read_version(dir),
+        zones,
+    })
+}
+
+/// Makes `db` take precedence over the built-in rules, once at startup.
+pub fn install(db: ZoneDb) {
+    tracing::info!(
+        "Loaded {} zones of tzdata {} from {}",
+        db.zones.len(),
+        db.version.as_deref().unwrap_or("of unknown version"),
+        db.dir.display()
+    );
+    if LOADED.set(db).is_err() {
+        tracing::warn!("A zone database is already loaded");
+    }
+}
+
+/// The loaded rules in effect in `tz` at `instant`, if `tz` was loaded.
+pub fn local_time_type(tz: Tz, instant: DateTime<Utc>) -> Option<&'static LocalTimeType> {
+    LOADED
+        .get()?
+        .zones
+        .get(tz.name())?
+        .find_local_time_type(instant.timestamp())
+        .ok()
+}
+
+/// Reports where the zone rules come from. chrono-tz doesn't expose the release it was
+/// built with, so the version is only known for loaded rules.
+pub async fn version_handler() -> Json<Value> {
+    Json(match LOADED.get() {
+        Some(db) => json!({
+            "source": "zoneinfo",
+            "version": db.version,
+            "path": db.dir.display().to_string(),
+            "zones": db.zones.len(),
+        }),
+        None => json!({
+            "source": "bundled",
+            "version": null,
+            "zones": chrono_tz::TZ_VARIANTS.len(),
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions() {
+        let dir = std::env::temp_dir().join(format!("tzdata-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(read_version(&dir), None);
+        std::fs::write(dir.join("+VERSION"), "2023c\n").unwrap();
+        assert_eq!(read_version(&dir).as_deref(), Some("2023c"));
+        std::fs::write(dir.join("tzdata.zi"), "# version 2024a\n# zone rules\n").unwrap();
+        assert_eq!(read_version(&dir).as_deref(), Some("2024a"));
+
+        assert!(load(&dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
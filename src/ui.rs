@@ -0,0 +1,13 @@
+//! Converter page served at the root, calling the API from the browser to show every
+//! representation of a pasted date or epoch.
+//!
+//! Its script and stylesheet are static files of their own, see [`crate::assets`], as
+//! the `Content-Security-Policy` of HTML responses forbids inline ones.
+
+use axum::response::Html;
+
+const PAGE: &str = include_str!("../static/index.html");
+
+pub async fn page_handler() -> Html<&'static str> {
+    Html(PAGE)
+}
@@ -0,0 +1,23 @@
+//! Bounds on the error of the system clock, as estimated by the kernel NTP discipline.
+
+/// `adjtimex` state meaning the clock is not synchronized.
+#[cfg(target_os = "linux")]
+const TIME_ERROR: i32 = 5;
+
+/// Maximum error of the system clock in milliseconds, or `None` when it is unknown
+/// because the clock isn't synchronized or the platform doesn't report it.
+#[cfg(target_os = "linux")]
+pub fn clock_uncertainty_ms() -> Option<f64> {
+    // with `modes` set to zero adjtimex only reads the current state
+    let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+    let state = unsafe { libc::adjtimex(&mut timex) };
+    if state == -1 || state == TIME_ERROR {
+        return None;
+    }
+    Some(timex.maxerror as f64 / 1000.0)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn clock_uncertainty_ms() -> Option<f64> {
+    None
+}
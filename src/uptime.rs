@@ -0,0 +1,43 @@
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+use std::time::Instant;
+
+use crate::locale;
+use crate::relative::{amount_in, breakdown, Unit};
+
+/// When the process started, captured once in `main`.
+#[derive(Clone, Copy, Debug)]
+pub struct Started {
+    pub at: DateTime<Utc>,
+    /// Monotonic counterpart of `at`, so that wall clock adjustments don't skew the uptime.
+    pub instant: Instant,
+}
+
+impl Started {
+    pub fn now() -> Started {
+        Started {
+            at: Utc::now(),
+            instant: Instant::now(),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        Duration::from_std(self.instant.elapsed()).unwrap_or(Duration::MAX)
+    }
+}
+
+pub async fn handler(State(started): State<Started>) -> Json<Value> {
+    let uptime = started.uptime();
+
+    Json(json!({
+        "started_at": {
+            "unix": started.at.timestamp(),
+            "utc": started.at.to_rfc2822(),
+        },
+        "uptime_seconds": uptime.num_seconds(),
+        "uptime": breakdown(uptime),
+        "humanized": amount_in(&locale::EN, uptime.num_seconds(), Unit::Second),
+    }))
+}
@@ -0,0 +1,141 @@
+//! Timestamps embedded in time-based UUIDs (RFC 4122 version 1, and the newer versions 6
+//! and 7).
+
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+
+/// 100-nanosecond intervals between the Gregorian reform (1582-10-15) and the unix epoch.
+const GREGORIAN_OFFSET: i64 = 122_192_928_000_000_000;
+
+#[derive(Debug, PartialEq)]
+pub struct TimeUuid {
+    pub version: u8,
+    pub timestamp: DateTime<Utc>,
+    /// Clock sequence and node of versions 1 and 6.
+    pub clock_seq: Option<u16>,
+    pub node: Option<u64>,
+}
+
+pub fn parse_hex(uuid: &str) -> Option<u128> {
+    let hex: String = uuid
+        .trim_start_matches("urn:uuid:")
+        .trim_matches(|c| c == '{' || c == '}')
+        .chars()
+        .filter(|c| *c != '-')
+        .collect();
+    if hex.len() != 32 {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16).ok()
+}
+
+/// Converts a count of 100ns intervals since 1582-10-15.
+fn from_gregorian(ticks: u64) -> Option<DateTime<Utc>> {
+    let since_epoch = ticks as i64 - GREGORIAN_OFFSET;
+    let seconds = since_epoch.div_euclid(10_000_000);
+    let nanos = since_epoch.rem_euclid(10_000_000) * 100;
+    DateTime::from_timestamp(seconds, nanos as u32)
+}
+
+/// Extracts the timestamp of a UUID, failing with its version when it doesn't have one.
+pub fn extract(value: u128) -> Result<TimeUuid, u8> {
+    let version = ((value >> 76) & 0xf) as u8;
+    let clock_seq = ((value >> 48) & 0x3fff) as u16;
+    let node = (value & 0xffff_ffff_ffff) as u64;
+
+    let (timestamp, clock_seq, node) = match version {
+        1 => {
+            let time_low = (value >> 96) as u64 & 0xffff_ffff;
+            let time_mid = (value >> 80) as u64 & 0xffff;
+            let time_high = (value >> 64) as u64 & 0x0fff;
+            let ticks = time_high << 48 | time_mid << 32 | time_low;
+            (from_gregorian(ticks), Some(clock_seq), Some(node))
+        }
+        6 => {
+            let time_high = (value >> 96) as u64 & 0xffff_ffff;
+            let time_mid = (value >> 80) as u64 & 0xffff;
+            let time_low = (value >> 64) as u64 & 0x0fff;
+            let ticks = time_high << 28 | time_mid << 12 | time_low;
+            (from_gregorian(ticks), Some(clock_seq), Some(node))
+        }
+        7 => {
+            let ms = (value >> 80) as i64;
+            let date = DateTime::from_timestamp(ms / 1000, (ms % 1000) as u32 * 1_000_000);
+            (date, None, None)
+        }
+        _ => return Err(version),
+    };
+
+    Ok(TimeUuid {
+        version,
+        timestamp: timestamp.ok_or(version)?,
+        clock_seq,
+        node,
+    })
+}
+
+pub async fn uuid_handler(Path(uuid): Path<String>) -> Result<Json<Value>, AppError> {
+    let value = parse_hex(&uuid).ok_or_else(|| AppError::BadRequest("Invalid UUID".to_string()))?;
+    let uuid = extract(value).map_err(|version| {
+        AppError::Unprocessable(format!("UUID version {} has no timestamp", version))
+    })?;
+
+    Ok(Json(json!({
+        "version": uuid.version,
+        "unix": uuid.timestamp.timestamp(),
+        "unix_ms": uuid.timestamp.timestamp_millis(),
+        "utc": uuid.timestamp.to_rfc2822(),
+        "clock_seq": uuid.clock_seq,
+        "node": uuid.node.map(|node| format!("{:012x}", node)),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn version_1() {
+        // example from RFC 9562, 2022-02-22T19:22:22Z
+        let uuid = extract(parse_hex("C232AB00-9414-11EC-B3C8-9F6BDECED846").unwrap()).unwrap();
+        assert_eq!(uuid.version, 1);
+        assert_eq!(
+            uuid.timestamp,
+            Utc.with_ymd_and_hms(2022, 2, 22, 19, 22, 22).unwrap()
+        );
+        assert_eq!(uuid.clock_seq, Some(0x33c8));
+        assert_eq!(uuid.node, Some(0x9f6b_dece_d846));
+    }
+
+    #[test]
+    fn version_6() {
+        let uuid = extract(parse_hex("1EC9414C-232A-6B00-B3C8-9F6BDECED846").unwrap()).unwrap();
+        assert_eq!(uuid.version, 6);
+        assert_eq!(
+            uuid.timestamp,
+            Utc.with_ymd_and_hms(2022, 2, 22, 19, 22, 22).unwrap()
+        );
+    }
+
+    #[test]
+    fn version_7() {
+        let uuid = extract(parse_hex("017F22E2-79B0-7CC3-98C4-DC0C0C07398F").unwrap()).unwrap();
+        assert_eq!(uuid.version, 7);
+        assert_eq!(
+            uuid.timestamp,
+            Utc.with_ymd_and_hms(2022, 2, 22, 19, 22, 22).unwrap()
+        );
+    }
+
+    #[test]
+    fn random_uuids_have_no_timestamp() {
+        let value = parse_hex("{919108f7-52d1-4320-9bac-f847db4148a8}").unwrap();
+        assert_eq!(extract(value), Err(4));
+        assert_eq!(parse_hex("919108f7-52d1"), None);
+    }
+}
@@ -0,0 +1,61 @@
+//! Checks whether an input would be accepted by `/api/:date`, without converting it.
+
+use axum::Json;
+use chrono::Utc;
+use serde_json::{json, Value};
+
+use crate::extract::Path;
+use crate::{natural, parse};
+
+/// Reports whether `input` is a valid date, the format it was read as, and why it was
+/// rejected if it was. The format is the one the input looks like, even when invalid.
+pub fn validate(input: &str) -> Value {
+    if natural::parse(input, Utc::now()).is_some() {
+        return json!({
+            "input": input,
+            "valid": true,
+            "detected_format": "natural",
+            "reason": null,
+        });
+    }
+
+    let kind = parse::classify(input);
+    let (valid, reason, ambiguous) = match parse::parse_input(input) {
+        Ok(parsed) => (true, None, parsed.ambiguous),
+        Err(error) => (false, Some(error.reason), false),
+    };
+    let mut body = json!({
+        "input": input,
+        "valid": valid,
+        "detected_format": kind.name(),
+        "reason": reason,
+    });
+    if ambiguous {
+        body["ambiguous"] = json!(true);
+    }
+    body
+}
+
+pub async fn validate_handler(Path(input): Path<String>) -> Json<Value> {
+    Json(validate(&input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnostics() {
+        let body = validate("2023-01-32");
+        assert_eq!(body["valid"], false);
+        assert_eq!(body["detected_format"], "date");
+        assert_eq!(body["reason"], "day 32 out of range for month 1");
+
+        let body = validate("1482624000");
+        assert_eq!(body["valid"], true);
+        assert_eq!(body["detected_format"], "unix");
+        assert_eq!(body["reason"], Value::Null);
+
+        assert_eq!(validate("tomorrow")["detected_format"], "natural");
+    }
+}
@@ -0,0 +1,210 @@
+//! API versioning.
+//!
+//! Routes are served under the `/v1` and `/v2` prefixes, `/v2/api/2016-12-25` being the
+//! v2 flavour of `/api/2016-12-25`. Unprefixed routes are served with the version asked
+//! for in the `Accept-Version` header, v1 by default. Handlers whose response shape
+//! changed between versions read the negotiated one with `Extension<ApiVersion>`: v1
+//! converts dates to `unix` and `utc` only, v2 adds the finer epoch units, RFC 3339 and
+//! ISO week dates. Protobuf encodes the v2 shape, so v1 responses stay JSON.
+//!
+//! Responses served with a version older than the latest carry a `Deprecation` header and,
+//! once `v1_sunset` in the configuration sets the date it will be removed, a `Sunset` header.
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::{HeaderValue, Request, Response};
+use axum::response::IntoResponse;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{BoxError, Layer, Service};
+
+use crate::error::AppError;
+use crate::parse_date;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+
+    /// Parses `2` or `v2`.
+    fn parse(value: &str) -> Option<ApiVersion> {
+        let value = value.trim();
+        match value
+            .strip_prefix(|c| c == 'v' || c == 'V')
+            .unwrap_or(value)
+        {
+            "1" => Some(ApiVersion::V1),
+            "2" => Some(ApiVersion::V2),
+            _ => None,
+        }
+    }
+
+    pub fn number(self) -> u16 {
+        match self {
+            ApiVersion::V1 => 1,
+            ApiVersion::V2 => 2,
+        }
+    }
+}
+
+/// Splits `/v2/api/now` into its version and the route path, `/api/now`.
+fn split_prefix(path: &str) -> Option<(ApiVersion, &str)> {
+    let rest = path.strip_prefix("/v")?;
+    let (version, route) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    Some((ApiVersion::parse(version)?, route))
+}
+
+/// Picks the version of a request, stripping the version prefix from its URI if any.
+fn negotiate<B>(request: &mut Request<B>) -> Result<ApiVersion, AppError> {
+    let prefixed = split_prefix(request.uri().path()).map(|(version, route)| {
+        let uri = match request.uri().query() {
+            Some(query) => format!("{}?{}", route, query),
+            None => route.to_string(),
+        };
+        (version, uri)
+    });
+    if let Some((version, uri)) = prefixed {
+        *request.uri_mut() = uri
+            .parse()
+            .map_err(|_| AppError::BadRequest("Invalid URI".to_string()))?;
+        return Ok(version);
+    }
+
+    match request.headers().get("accept-version") {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(ApiVersion::parse)
+            .ok_or_else(|| AppError::BadRequest("Unsupported API version".to_string())),
+        None => Ok(ApiVersion::V1),
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VersionLayer {
+    sunset: Option<HeaderValue>,
+}
+
+impl VersionLayer {
+    pub fn new(sunset: Option<&str>) -> Result<VersionLayer, String> {
+        let sunset = match sunset {
+            Some(value) => {
+                let date = parse_date(value).map_err(|_| format!("Invalid v1_sunset {}", value))?;
+                let date = date.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+                Some(HeaderValue::from_str(&date).map_err(|e| e.to_string())?)
+            }
+            None => None,
+        };
+        Ok(VersionLayer { sunset })
+    }
+}
+
+impl<S> Layer<S> for VersionLayer {
+    type Service = Versioned<S>;
+
+    fn layer(&self, inner: S) -> Versioned<S> {
+        Versioned {
+            inner,
+            sunset: self.sunset.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Versioned<S> {
+    inner: S,
+    sunset: Option<HeaderValue>,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for Versioned<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let version = match negotiate(&mut request) {
+            Ok(version) => version,
+            Err(error) => {
+                let response = error.into_response();
+                return Box::pin(async move { Ok(response) });
+            }
+        };
+        request.extensions_mut().insert(version);
+        let sunset = self.sunset.clone();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = response.await?.map(Body::new);
+            let headers = response.headers_mut();
+            headers.insert("api-version", HeaderValue::from(version.number()));
+            if version < ApiVersion::LATEST {
+                headers.insert("deprecation", HeaderValue::from_static("true"));
+                if let Some(sunset) = sunset {
+                    headers.insert("sunset", sunset);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(
+            split_prefix("/v2/api/now"),
+            Some((ApiVersion::V2, "/api/now"))
+        );
+        assert_eq!(split_prefix("/v1"), Some((ApiVersion::V1, "/")));
+        assert_eq!(split_prefix("/v3/api"), None);
+        assert_eq!(split_prefix("/api/v2"), None);
+    }
+
+    #[test]
+    fn negotiation() {
+        let mut request = Request::builder()
+            .uri("/v2/api/2016-12-25?base=now")
+            .body(())
+            .unwrap();
+        assert_eq!(negotiate(&mut request).unwrap(), ApiVersion::V2);
+        assert_eq!(request.uri(), "/api/2016-12-25?base=now");
+
+        let mut request = Request::builder()
+            .uri("/api")
+            .header("accept-version", "v2")
+            .body(())
+            .unwrap();
+        assert_eq!(negotiate(&mut request).unwrap(), ApiVersion::V2);
+
+        let mut request = Request::builder().uri("/api").body(()).unwrap();
+        assert_eq!(negotiate(&mut request).unwrap(), ApiVersion::V1);
+
+        let mut request = Request::builder()
+            .uri("/api")
+            .header("accept-version", "3")
+            .body(())
+            .unwrap();
+        assert!(negotiate(&mut request).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! Weeks starting on a configurable day, since what a week is depends on the region: Monday
+//! in most of Europe, Sunday in the US, Saturday in much of the Middle East.
+//!
+//! The `week_start` of the configuration applies to week numbers, calendar grids and
+//! alignment to weeks, each of them taking a `?week_start=` overriding it.
+
+use axum::extract::State;
+use axum::Json;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::extract::Path;
+use crate::query::Query;
+use crate::timezone::{parse_in_zone, parse_tz};
+
+/// Week defaults from the configuration.
+#[derive(Clone, Copy, Debug)]
+pub struct WeekSettings {
+    pub week_start: Weekday,
+}
+
+impl WeekSettings {
+    /// The first day of the week asked for in a request, the configured one by default.
+    pub fn week_start(&self, requested: Option<&str>) -> Result<Weekday, AppError> {
+        match requested {
+            Some(day) => day
+                .parse::<Weekday>()
+                .map_err(|_| AppError::BadRequest(format!("Invalid weekday {}", day))),
+            None => Ok(self.week_start),
+        }
+    }
+}
+
+/// The first day of the week `date` falls in.
+pub fn start_of_week(date: NaiveDate, week_start: Weekday) -> NaiveDate {
+    let days = (7 + date.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+    date - Duration::days(days as i64)
+}
+
+/// Number of the week `date` falls in within its year, the first week being the one
+/// holding January 1st, as in the US and Middle-Eastern conventions. The last days of
+/// December can fall in a week 53 or 54 that ends in the next year.
+pub fn week_of_year(date: NaiveDate, week_start: Weekday) -> u32 {
+    let first = start_of_week(date.with_ordinal(1).unwrap(), week_start);
+    ((date - first).num_days() / 7) as u32 + 1
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WeekParams {
+    tz: Option<String>,
+    /// First day of the week, `week_start` of the configuration by default.
+    week_start: Option<String>,
+}
+
+/// The week a date falls in: its number, first and last days, and its ISO 8601 week,
+/// which always starts on Monday.
+pub async fn week_handler(
+    Path(date): Path<String>,
+    Query(params): Query<WeekParams>,
+    State(settings): State<WeekSettings>,
+) -> Result<Json<Value>, AppError> {
+    let tz = parse_tz(params.tz.as_deref())?;
+    let week_start = settings.week_start(params.week_start.as_deref())?;
+    let date = parse_in_zone(&date, tz)?.with_timezone(&tz).date_naive();
+    let start = start_of_week(date, week_start);
+    let iso = date.iso_week();
+
+    Ok(Json(json!({
+        "date": date.to_string(),
+        "week_start": week_start.to_string(),
+        "week": week_of_year(date, week_start),
+        "year": date.year(),
+        "start": start.to_string(),
+        "end": (start + Duration::days(6)).to_string(),
+        "iso_week": iso.week(),
+        "iso_year": iso.year(),
+        "tz": tz.name(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn week_numbers() {
+        // January 1st, 2022 was a Saturday
+        let date = NaiveDate::from_ymd_opt(2022, 1, 2).unwrap();
+        assert_eq!(week_of_year(date, Weekday::Sun), 2);
+        assert_eq!(week_of_year(date, Weekday::Mon), 1);
+        assert_eq!(week_of_year(date, Weekday::Sat), 1);
+        assert_eq!(
+            start_of_week(date, Weekday::Sat),
+            NaiveDate::from_ymd_opt(2022, 1, 1).unwrap()
+        );
+        assert_eq!(
+            week_of_year(NaiveDate::from_ymd_opt(2022, 12, 31).unwrap(), Weekday::Sun),
+            53
+        );
+    }
+}
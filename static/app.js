@@ -0,0 +1,95 @@
+"use strict";
+
+// Fields of /api/<date> worth showing, in order, with their labels.
+const FIELDS = [
+  ["unix", "Unix seconds"],
+  ["unix_ms", "Unix milliseconds"],
+  ["unix_us", "Unix microseconds"],
+  ["unix_ns", "Unix nanoseconds"],
+  ["iso8601", "ISO 8601"],
+  ["utc", "RFC 2822"],
+  ["iso_week_date", "ISO week date"],
+  ["local", "Local time"],
+  ["offset", "Offset"],
+];
+
+const form = document.getElementById("convert");
+const input = document.getElementById("date");
+const error = document.getElementById("error");
+const result = document.getElementById("result");
+
+async function getJson(path) {
+  const response = await fetch(path, { headers: { Accept: "application/json" } });
+  const body = await response.json();
+  if (!response.ok) {
+    throw new Error(body.error || response.statusText);
+  }
+  return body;
+}
+
+function show(rows) {
+  const body = result.tBodies[0];
+  body.replaceChildren();
+  for (const [label, value] of rows) {
+    const row = body.insertRow();
+    const header = document.createElement("th");
+    header.textContent = label;
+    row.appendChild(header);
+    row.insertCell().textContent = value;
+  }
+  error.hidden = true;
+  result.hidden = false;
+}
+
+function fail(message) {
+  error.textContent = message;
+  error.hidden = false;
+  result.hidden = true;
+}
+
+async function convert(date) {
+  const path = encodeURIComponent(date);
+  try {
+    const [converted, relative] = await Promise.all([
+      getJson("/api/" + path),
+      getJson("/api/relative/" + path).catch(() => null),
+    ]);
+    const rows = FIELDS.filter(([field]) => converted[field] != null).map(
+      ([field, label]) => [label, String(converted[field])]
+    );
+    if (relative) {
+      rows.push(["Relative", relative.relative]);
+    }
+    if (converted.ambiguous) {
+      rows.push(["Note", "Day and month order is ambiguous"]);
+    }
+    show(rows);
+    history.replaceState(null, "", "?date=" + path);
+  } catch (e) {
+    fail(e.message);
+  }
+}
+
+form.addEventListener("submit", (event) => {
+  event.preventDefault();
+  const date = input.value.trim();
+  if (date) {
+    convert(date);
+  }
+});
+
+document.getElementById("now").addEventListener("click", async () => {
+  try {
+    const now = await getJson("/api");
+    input.value = String(now.unix);
+    convert(input.value);
+  } catch (e) {
+    fail(e.message);
+  }
+});
+
+const shared = new URLSearchParams(location.search).get("date");
+if (shared) {
+  input.value = shared;
+  convert(shared);
+}